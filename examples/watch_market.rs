@@ -1,33 +1,25 @@
-use dotenv::dotenv;
 use ftx::ws::Result;
-use ftx::ws::{Channel, Data, Orderbook, Ws};
-use std::env::var;
+use ftx::ws::{Channel, Data, Orderbook, Symbol, Ws};
 use std::io;
 use std::io::Write;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    dotenv().ok();
+    // Trades and orderbooks are public channels, no API key is required
+    let mut websocket = Ws::connect_public().await?;
 
-    let mut websocket = Ws::connect(
-        var("API_KEY").expect("API Key is not defined."),
-        var("API_SECRET").expect("API Secret is not defined."),
-        var("SUBACCOUNT").ok(),
-    )
-    .await?;
-
-    let market = String::from("BTC-PERP");
-    let mut orderbook = Orderbook::new(market.to_owned());
+    let market = Symbol::from("BTC-PERP");
+    let mut orderbook = Orderbook::new(market.clone());
 
     websocket
         .subscribe(vec![
-            Channel::Trades(market.to_owned()),
-            Channel::Orderbook(market.to_owned()),
+            Channel::Trades(market.clone()),
+            Channel::Orderbook(market.clone()),
         ])
         .await?;
 
     loop {
-        let data = websocket.next().await?.expect("No data received");
+        let (_, data) = websocket.next().await?.expect("No data received");
 
         match data {
             Data::Trade(trade) => {
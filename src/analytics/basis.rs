@@ -0,0 +1,134 @@
+use crate::rest::{self, Future, FutureType, Market, Rest, Symbol};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+const SECONDS_PER_DAY: i64 = 86400;
+const DAYS_PER_YEAR: i64 = 365;
+
+/// Premium of a dated future over its reference price
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FutureBasis {
+    pub name: Symbol,
+    pub expiry: DateTime<Utc>,
+    /// Fractional days until expiry
+    pub days_to_expiry: Decimal,
+    pub mark: Decimal,
+    /// Mark price minus the reference price
+    pub basis: Decimal,
+    /// Basis relative to the reference price, extrapolated to a year
+    pub annualized_basis: Option<Decimal>,
+}
+
+/// MOVE contract, whose price is the expected absolute move of the
+/// underlying, so it has no basis
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MoveContract {
+    pub name: Symbol,
+    pub expiry: DateTime<Utc>,
+    pub days_to_expiry: Decimal,
+    pub mark: Option<Decimal>,
+}
+
+/// Live dated futures and MOVE contracts of an underlying, e.g. BTC
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasisReport {
+    pub underlying: Symbol,
+    pub index: Option<Decimal>,
+    /// Price of the `<underlying>/USD` spot market, if listed
+    pub spot: Option<Decimal>,
+    /// Dated futures sorted by expiry, the basis is relative to the spot
+    /// price if listed, otherwise to the index
+    pub futures: Vec<FutureBasis>,
+    /// MOVE contracts sorted by expiry
+    pub moves: Vec<MoveContract>,
+}
+
+impl BasisReport {
+    /// Fetches futures and markets and reports on every underlying with
+    /// live dated futures or MOVE contracts
+    pub async fn fetch(rest: &Rest) -> rest::Result<Vec<BasisReport>> {
+        let futures = rest.get_futures().await?;
+        let markets = rest.get_markets().await?;
+        Ok(Self::from_futures(&futures, &markets, Utc::now()))
+    }
+
+    /// Reports on every underlying with live dated futures or MOVE contracts
+    /// as of `now`, sorted by underlying
+    pub fn from_futures(
+        futures: &[Future],
+        markets: &[Market],
+        now: DateTime<Utc>,
+    ) -> Vec<BasisReport> {
+        let mut reports: BTreeMap<&Symbol, BasisReport> = BTreeMap::new();
+
+        for future in futures {
+            let expiry = match future.expiry {
+                Some(expiry) if future.enabled && !future.expired && expiry > now => expiry,
+                _ => continue,
+            };
+            let days_to_expiry =
+                Decimal::from((expiry - now).num_seconds()) / Decimal::from(SECONDS_PER_DAY);
+
+            let report = reports
+                .entry(&future.underlying)
+                .or_insert_with(|| BasisReport {
+                    underlying: future.underlying.clone(),
+                    index: None,
+                    spot: spot_price(markets, &future.underlying),
+                    futures: Vec::new(),
+                    moves: Vec::new(),
+                });
+            report.index = report.index.or(future.index);
+
+            match future.market_type {
+                FutureType::Future => {
+                    let (mark, reference) = match (future.mark, report.spot.or(future.index)) {
+                        (Some(mark), Some(reference)) if !reference.is_zero() => (mark, reference),
+                        _ => continue,
+                    };
+                    let annualized_basis =
+                        Some(days_to_expiry)
+                            .filter(|days| !days.is_zero())
+                            .map(|days| {
+                                (mark / reference - Decimal::ONE) * Decimal::from(DAYS_PER_YEAR)
+                                    / days
+                            });
+                    report.futures.push(FutureBasis {
+                        name: future.name.clone(),
+                        expiry,
+                        days_to_expiry,
+                        mark,
+                        basis: mark - reference,
+                        annualized_basis,
+                    });
+                }
+                FutureType::Move => report.moves.push(MoveContract {
+                    name: future.name.clone(),
+                    expiry,
+                    days_to_expiry,
+                    mark: future.mark,
+                }),
+                FutureType::Perpetual | FutureType::Prediction => {}
+            }
+        }
+
+        reports
+            .into_values()
+            .map(|mut report| {
+                report.futures.sort_by_key(|future| future.expiry);
+                report.moves.sort_by_key(|contract| contract.expiry);
+                report
+            })
+            .filter(|report| !report.futures.is_empty() || !report.moves.is_empty())
+            .collect()
+    }
+}
+
+fn spot_price(markets: &[Market], underlying: &str) -> Option<Decimal> {
+    let name = format!("{}/USD", underlying);
+    markets
+        .iter()
+        .find(|market| market.name == name.as_str())
+        .map(|market| market.price)
+}
@@ -0,0 +1,166 @@
+//! Rolling statistics over trade streams, e.g. to benchmark executions, and
+//! the basis of dated futures.
+//!
+//! ```no_run
+//! # async fn example() -> ftx::ws::Result<()> {
+//! use ftx::analytics::{Twap, Vwap, Window};
+//! use ftx::ws::{Channel, Data, Ws};
+//! use std::time::Duration;
+//!
+//! let mut ws = Ws::connect_public().await?;
+//! ws.subscribe(vec![Channel::Trades("BTC-PERP".into())]).await?;
+//!
+//! let mut vwap = Vwap::new(Window::Time(Duration::from_secs(300)));
+//! let mut twap = Twap::new(Window::Time(Duration::from_secs(300)));
+//! while let Some((_, data)) = ws.next().await? {
+//!     if let Data::Trade(trade) = data {
+//!         vwap.update(&trade);
+//!         twap.update(&trade);
+//!         println!("VWAP {:?}, TWAP {:?}", vwap.value(), twap.value());
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod basis;
+#[cfg(test)]
+mod tests;
+mod twap;
+mod vwap;
+
+pub use basis::*;
+pub use twap::*;
+pub use vwap::*;
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Trades a rolling statistic is calculated over
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Window {
+    /// Trades within the given duration of the latest trade
+    Time(Duration),
+    /// The latest trades adding up to the given size, in the base currency
+    Volume(Decimal),
+}
+
+/// A trade as seen by the calculators, created from REST or websocket trades
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub time: DateTime<Utc>,
+    pub price: Decimal,
+    pub size: Decimal,
+}
+
+impl From<&crate::rest::Trade> for Sample {
+    fn from(trade: &crate::rest::Trade) -> Self {
+        Self {
+            time: trade.time,
+            price: trade.price,
+            size: trade.size,
+        }
+    }
+}
+
+#[cfg(feature = "ws")]
+impl From<&crate::ws::Trade> for Sample {
+    fn from(trade: &crate::ws::Trade) -> Self {
+        Self {
+            time: trade.time,
+            price: trade.price,
+            size: trade.size,
+        }
+    }
+}
+
+/// Samples within a window, oldest first. With `Window::Volume`, the oldest
+/// sample may only partially be within the window.
+#[derive(Clone, Debug)]
+struct Samples {
+    window: Window,
+    samples: VecDeque<Sample>,
+    /// Sum of the sizes of all samples
+    volume: Decimal,
+    /// Sum of price times size of all samples
+    notional: Decimal,
+}
+
+impl Samples {
+    fn new(window: Window) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+            volume: Decimal::ZERO,
+            notional: Decimal::ZERO,
+        }
+    }
+
+    /// Adds a sample and drops the ones no longer needed for the window.
+    /// With `Window::Time`, `keep_previous` keeps the last sample before the
+    /// window, which determines the price at the start of the window.
+    fn push(&mut self, sample: Sample, keep_previous: bool) {
+        self.volume += sample.size;
+        self.notional += sample.price * sample.size;
+        self.samples.push_back(sample);
+
+        match self.window {
+            Window::Time(_) => {
+                let start = self.start_time().unwrap();
+                loop {
+                    let outside = if keep_previous {
+                        self.samples.get(1).is_some_and(|next| next.time <= start)
+                    } else {
+                        self.samples
+                            .front()
+                            .is_some_and(|oldest| oldest.time <= start)
+                    };
+                    if !outside {
+                        break;
+                    }
+                    self.pop_front();
+                }
+            }
+            Window::Volume(volume) => {
+                while let Some(oldest) = self.samples.front() {
+                    if self.volume - oldest.size < volume {
+                        break;
+                    }
+                    self.pop_front();
+                }
+            }
+        }
+    }
+
+    fn pop_front(&mut self) {
+        if let Some(oldest) = self.samples.pop_front() {
+            self.volume -= oldest.size;
+            self.notional -= oldest.price * oldest.size;
+        }
+    }
+
+    /// Start of the window, the time of the oldest sample for
+    /// `Window::Volume`
+    fn start_time(&self) -> Option<DateTime<Utc>> {
+        match self.window {
+            Window::Time(duration) => {
+                let latest = self.samples.back()?.time;
+                let start = chrono::Duration::from_std(duration)
+                    .ok()
+                    .and_then(|duration| latest.checked_sub_signed(duration));
+                Some(start.unwrap_or(DateTime::<Utc>::MIN_UTC))
+            }
+            Window::Volume(_) => Some(self.samples.front()?.time),
+        }
+    }
+
+    /// Size of the oldest sample outside of a volume window
+    fn excess(&self) -> Decimal {
+        match self.window {
+            Window::Volume(volume) if self.volume > volume => self.volume - volume,
+            _ => Decimal::ZERO,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use super::*;
+use chrono::TimeZone;
+use rust_decimal_macros::dec;
+
+fn sample(seconds: i64, price: Decimal, size: Decimal) -> Sample {
+    Sample {
+        time: Utc.timestamp_opt(seconds, 0).unwrap(),
+        price,
+        size,
+    }
+}
+
+#[test]
+fn vwap_time_window() {
+    let mut vwap = Vwap::new(Window::Time(Duration::from_secs(60)));
+    assert_eq!(vwap.value(), None);
+
+    vwap.update(sample(0, dec!(100), dec!(1)));
+    vwap.update(sample(30, dec!(110), dec!(3)));
+    assert_eq!(vwap.value(), Some(dec!(107.5)));
+    assert_eq!(vwap.volume(), dec!(4));
+
+    // The first trade leaves the window
+    vwap.update(sample(70, dec!(120), dec!(1)));
+    assert_eq!(vwap.value(), Some(dec!(112.5)));
+    assert_eq!(vwap.volume(), dec!(4));
+}
+
+#[test]
+fn vwap_volume_window() {
+    let mut vwap = Vwap::new(Window::Volume(dec!(2)));
+
+    vwap.update(sample(0, dec!(100), dec!(1)));
+    assert_eq!(vwap.value(), Some(dec!(100)));
+    assert_eq!(vwap.volume(), dec!(1));
+
+    // Only half of the first trade is within the window
+    vwap.update(sample(1, dec!(110), dec!(1.5)));
+    assert_eq!(vwap.value(), Some(dec!(107.5)));
+    assert_eq!(vwap.volume(), dec!(2));
+
+    vwap.update(sample(2, dec!(120), dec!(2)));
+    assert_eq!(vwap.value(), Some(dec!(120)));
+}
+
+#[test]
+fn twap() {
+    let mut twap = Twap::new(Window::Time(Duration::from_secs(60)));
+    assert_eq!(twap.value(), None);
+
+    twap.update(sample(0, dec!(100), dec!(5)));
+    assert_eq!(twap.value(), Some(dec!(100)));
+
+    // 100 for 30s, then 110 for 10s
+    twap.update(sample(30, dec!(110), dec!(1)));
+    twap.update(sample(40, dec!(90), dec!(1)));
+    assert_eq!(twap.value(), Some(dec!(102.5)));
+
+    // Window from 20s to 80s: 100 for 10s, 110 for 10s, 90 for 40s
+    twap.update(sample(80, dec!(120), dec!(1)));
+    assert_eq!(twap.value(), Some(dec!(95)));
+
+    let mut twap = Twap::new(Window::Volume(dec!(2)));
+    twap.update(sample(0, dec!(100), dec!(1)));
+    twap.update(sample(10, dec!(110), dec!(1)));
+    twap.update(sample(20, dec!(130), dec!(1)));
+    twap.update(sample(40, dec!(90), dec!(1)));
+    // Only the last two trades are within the window
+    assert_eq!(twap.value(), Some(dec!(130)));
+}
+
+#[test]
+fn basis_report() {
+    use crate::rest::{Future, Market};
+    use serde_json::json;
+
+    let future = |name: &str, r#type: &str, expiry: Option<&str>, mark: f64| -> Future {
+        serde_json::from_value(json!({"ask": null, "bid": null, "change1h": null, "change24h": null, "changeBod": null, "volumeUsd24h": null, "volume": null, "description": name, "enabled": true, "expired": false, "expiry": expiry, "index": 40000.0, "imfFactor": 0.002, "last": null, "lowerBound": 0, "mark": mark, "name": name, "perpetual": r#type == "perpetual", "positionLimitWeight": 1.0, "postOnly": false, "priceIncrement": 1.0, "sizeIncrement": 0.0001, "underlying": "BTC", "upperBound": 100000, "type": r#type})).unwrap()
+    };
+    let futures = vec![
+        future("BTC-PERP", "perpetual", None, 40010.0),
+        future(
+            "BTC-0924",
+            "future",
+            Some("2021-09-24T03:00:00+00:00"),
+            40800.0,
+        ),
+        future(
+            "BTC-0625",
+            "future",
+            Some("2021-06-25T03:00:00+00:00"),
+            40400.0,
+        ),
+        future(
+            "BTC-MOVE-0524",
+            "move",
+            Some("2021-05-24T00:00:00+00:00"),
+            1500.0,
+        ),
+    ];
+    let market: Market = serde_json::from_value(json!({"type": "spot", "name": "BTC/USD", "underlying": null, "baseCurrency": "BTC", "quoteCurrency": "USD", "enabled": true, "ask": 40001.0, "bid": 39999.0, "last": 40000.0, "postOnly": false, "priceIncrement": 1.0, "sizeIncrement": 0.0001, "restricted": false, "minProvideSize": 0.0001, "price": 40000.0, "highLeverageFeeExempt": true, "change1h": 0.0, "change24h": 0.0, "changeBod": 0.0, "quoteVolume24h": 0.0, "volumeUsd24h": 0.0})).unwrap();
+
+    // 36.5 days before the June expiry
+    let now = Utc.with_ymd_and_hms(2021, 5, 19, 15, 0, 0).unwrap();
+    let reports = BasisReport::from_futures(&futures, &[market], now);
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(report.underlying, "BTC");
+    assert_eq!(report.spot, Some(dec!(40000)));
+
+    let names: Vec<&str> = report.futures.iter().map(|f| &*f.name).collect();
+    assert_eq!(names, ["BTC-0625", "BTC-0924"]);
+    let june = &report.futures[0];
+    assert_eq!(june.days_to_expiry, dec!(36.5));
+    assert_eq!(june.basis, dec!(400));
+    // 1% over 36.5 days
+    assert_eq!(june.annualized_basis, Some(dec!(0.1)));
+    assert_eq!(report.moves[0].name, "BTC-MOVE-0524");
+
+    // Expired contracts are skipped
+    let later = Utc.with_ymd_and_hms(2021, 7, 1, 0, 0, 0).unwrap();
+    let reports = BasisReport::from_futures(&futures, &[], later);
+    assert_eq!(reports[0].futures.len(), 1);
+    assert!(reports[0].moves.is_empty());
+    assert_eq!(reports[0].futures[0].basis, dec!(800));
+}
@@ -0,0 +1,50 @@
+use super::{Sample, Samples, Window};
+use rust_decimal::Decimal;
+
+/// Rolling time-weighted average price. Each trade's price holds until the
+/// next trade, the latest price is weighted up to the time of the latest
+/// trade.
+#[derive(Clone, Debug)]
+pub struct Twap {
+    samples: Samples,
+}
+
+impl Twap {
+    pub fn new(window: Window) -> Self {
+        Self {
+            samples: Samples::new(window),
+        }
+    }
+
+    /// Adds a trade, which must not be older than the previous one
+    pub fn update<S: Into<Sample>>(&mut self, trade: S) {
+        self.samples.push(trade.into(), true);
+    }
+
+    /// Time-weighted average price within the window, `None` before the
+    /// first trade. The latest price while all trades happened at once.
+    pub fn value(&self) -> Option<Decimal> {
+        let latest = self.samples.samples.back()?;
+        let start = self.samples.start_time()?;
+
+        let mut weighted = Decimal::ZERO;
+        let mut duration = 0;
+        let mut samples = self.samples.samples.iter().peekable();
+        while let Some(sample) = samples.next() {
+            let next = match samples.peek() {
+                Some(next) => next,
+                None => break,
+            };
+            let millis = (next.time - sample.time.max(start))
+                .num_milliseconds()
+                .max(0);
+            weighted += sample.price * Decimal::from(millis);
+            duration += millis;
+        }
+
+        if duration == 0 {
+            return Some(latest.price);
+        }
+        Some(weighted / Decimal::from(duration))
+    }
+}
@@ -0,0 +1,40 @@
+use super::{Sample, Samples, Window};
+use rust_decimal::Decimal;
+
+/// Rolling volume-weighted average price
+#[derive(Clone, Debug)]
+pub struct Vwap {
+    samples: Samples,
+}
+
+impl Vwap {
+    pub fn new(window: Window) -> Self {
+        Self {
+            samples: Samples::new(window),
+        }
+    }
+
+    /// Adds a trade, which must not be older than the previous one
+    pub fn update<S: Into<Sample>>(&mut self, trade: S) {
+        self.samples.push(trade.into(), false);
+    }
+
+    /// Volume-weighted average price within the window, `None` before the
+    /// first trade
+    pub fn value(&self) -> Option<Decimal> {
+        let oldest = self.samples.samples.front()?;
+        let excess = self.samples.excess();
+        let volume = self.volume();
+        if volume.is_zero() {
+            return Some(self.samples.samples.back()?.price);
+        }
+
+        // Only part of the oldest trade is within a volume window
+        Some((self.samples.notional - oldest.price * excess) / volume)
+    }
+
+    /// Traded size within the window, in the base currency
+    pub fn volume(&self) -> Decimal {
+        self.samples.volume - self.samples.excess()
+    }
+}
@@ -0,0 +1,166 @@
+//! Traits abstracting the REST and websocket APIs, so that strategies can be
+//! written generically and run against `Rest` and `Ws`, against simulated
+//! order entry with `paper::PaperRest`, or against recorded data with
+//! `ws::replay::Replay`.
+//!
+//! ```no_run
+//! # async fn example() -> ftx::ws::Result<()> {
+//! use ftx::api::{MarketDataSource, OrderEntry};
+//! use ftx::rest::{OrderRequest, OrderSide, Rest};
+//! use ftx::ws::{Channel, Data, Ws};
+//! use rust_decimal_macros::dec;
+//!
+//! async fn buy_below(
+//!     source: &mut impl MarketDataSource,
+//!     orders: &impl OrderEntry,
+//! ) -> ftx::ws::Result<()> {
+//!     source.subscribe(vec![Channel::Trades("BTC/USD".into())]).await?;
+//!     while let Some((_, data)) = source.next().await? {
+//!         if let Data::Trade(trade) = data {
+//!             if trade.price < dec!(30000) {
+//!                 let order = OrderRequest::market("BTC/USD", OrderSide::Buy, dec!(0.01));
+//!                 orders.submit_order(&order).await.unwrap();
+//!                 break;
+//!             }
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//!
+//! let mut ws = Ws::connect_public().await?;
+//! let api = Rest::new("<api key>".into(), "<api secret>".into(), None);
+//! buy_below(&mut ws, &api).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(all(test, feature = "ws"))]
+mod tests;
+
+use crate::rest::{self, Id, OrderInfo, OrderRequest, Rest, WalletBalance};
+#[cfg(feature = "ws")]
+use crate::ws::{self, replay::Replay, Channel, Data, Symbol, Ws, WsPool};
+use std::future::Future;
+#[cfg(feature = "ws")]
+use std::io::BufRead;
+use std::pin::Pin;
+
+/// Future returned by the methods of the traits of this module
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Data returned by `MarketDataSource::next` along with its market, if any
+#[cfg(feature = "ws")]
+pub type MarketData = (Option<Symbol>, Data);
+
+/// Order entry and the account state needed for it, implemented by `Rest`
+/// and `paper::PaperRest`
+pub trait OrderEntry {
+    /// See `Rest::submit_order`
+    fn submit_order<'a>(
+        &'a self,
+        order: &'a OrderRequest,
+    ) -> BoxFuture<'a, rest::Result<OrderInfo>>;
+
+    /// See `Rest::cancel_order`
+    fn cancel_order(&self, order_id: Id) -> BoxFuture<'_, rest::Result<String>>;
+
+    /// See `Rest::get_order`
+    fn get_order(&self, order_id: Id) -> BoxFuture<'_, rest::Result<OrderInfo>>;
+
+    /// See `Rest::get_open_orders`
+    fn get_open_orders<'a>(
+        &'a self,
+        market: &'a str,
+    ) -> BoxFuture<'a, rest::Result<Vec<OrderInfo>>>;
+
+    /// See `Rest::get_wallet_balances`
+    fn get_wallet_balances(&self) -> BoxFuture<'_, rest::Result<Vec<WalletBalance>>>;
+}
+
+impl OrderEntry for Rest {
+    fn submit_order<'a>(
+        &'a self,
+        order: &'a OrderRequest,
+    ) -> BoxFuture<'a, rest::Result<OrderInfo>> {
+        Box::pin(Rest::submit_order(self, order))
+    }
+
+    fn cancel_order(&self, order_id: Id) -> BoxFuture<'_, rest::Result<String>> {
+        Box::pin(Rest::cancel_order(self, order_id))
+    }
+
+    fn get_order(&self, order_id: Id) -> BoxFuture<'_, rest::Result<OrderInfo>> {
+        Box::pin(Rest::get_order(self, order_id))
+    }
+
+    fn get_open_orders<'a>(
+        &'a self,
+        market: &'a str,
+    ) -> BoxFuture<'a, rest::Result<Vec<OrderInfo>>> {
+        Box::pin(Rest::get_open_orders(self, market))
+    }
+
+    fn get_wallet_balances(&self) -> BoxFuture<'_, rest::Result<Vec<WalletBalance>>> {
+        Box::pin(Rest::get_wallet_balances(self))
+    }
+}
+
+/// Stream of market and account data, implemented by `Ws`, `WsPool`, and
+/// `ws::replay::Replay`
+#[cfg(feature = "ws")]
+pub trait MarketDataSource {
+    /// See `Ws::subscribe`
+    fn subscribe(&mut self, channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>>;
+
+    /// See `Ws::unsubscribe`
+    fn unsubscribe(&mut self, channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>>;
+
+    /// See `Ws::next`, `None` when the source has ended
+    fn next(&mut self) -> BoxFuture<'_, ws::Result<Option<MarketData>>>;
+}
+
+#[cfg(feature = "ws")]
+impl MarketDataSource for Ws {
+    fn subscribe(&mut self, channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>> {
+        Box::pin(Ws::subscribe(self, channels))
+    }
+
+    fn unsubscribe(&mut self, channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>> {
+        Box::pin(Ws::unsubscribe(self, channels))
+    }
+
+    fn next(&mut self) -> BoxFuture<'_, ws::Result<Option<MarketData>>> {
+        Box::pin(Ws::next(self))
+    }
+}
+
+#[cfg(feature = "ws")]
+impl MarketDataSource for WsPool {
+    fn subscribe(&mut self, channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>> {
+        Box::pin(WsPool::subscribe(self, channels))
+    }
+
+    fn unsubscribe(&mut self, channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>> {
+        Box::pin(WsPool::unsubscribe(self, channels))
+    }
+
+    fn next(&mut self) -> BoxFuture<'_, ws::Result<Option<MarketData>>> {
+        Box::pin(WsPool::next(self))
+    }
+}
+
+/// A replay returns the recorded data regardless of subscriptions
+#[cfg(feature = "ws")]
+impl<R: BufRead + Send> MarketDataSource for Replay<R> {
+    fn subscribe(&mut self, _channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>> {
+        Box::pin(std::future::ready(Ok(())))
+    }
+
+    fn unsubscribe(&mut self, _channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>> {
+        Box::pin(std::future::ready(Ok(())))
+    }
+
+    fn next(&mut self) -> BoxFuture<'_, ws::Result<Option<MarketData>>> {
+        Box::pin(Replay::next(self))
+    }
+}
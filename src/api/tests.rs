@@ -0,0 +1,121 @@
+use super::*;
+use crate::paper::PaperRest;
+use crate::rest::{OrderSide, OrderStatus};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::io::Cursor;
+
+/// Passes the data of `source` to `paper` before returning it
+struct PaperFeed<'a, S> {
+    source: S,
+    paper: &'a PaperRest,
+}
+
+impl<S: MarketDataSource + Send> MarketDataSource for PaperFeed<'_, S> {
+    fn subscribe(&mut self, channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>> {
+        self.source.subscribe(channels)
+    }
+
+    fn unsubscribe(&mut self, channels: Vec<Channel>) -> BoxFuture<'_, ws::Result<()>> {
+        self.source.unsubscribe(channels)
+    }
+
+    fn next(&mut self) -> BoxFuture<'_, ws::Result<Option<MarketData>>> {
+        Box::pin(async move {
+            let data = self.source.next().await?;
+            if let Some((market, data)) = &data {
+                self.paper.update(market.as_deref(), data);
+            }
+            Ok(data)
+        })
+    }
+}
+
+/// Bids below the first trade and returns the order once it is filled
+async fn bid_below_first_trade(
+    source: &mut impl MarketDataSource,
+    orders: &impl OrderEntry,
+) -> Option<OrderInfo> {
+    source
+        .subscribe(vec![Channel::Trades("BTC/USD".into())])
+        .await
+        .unwrap();
+
+    let mut bid = None;
+    while let Some((_, data)) = source.next().await.unwrap() {
+        match (&bid, data) {
+            (None, Data::Trade(trade)) => {
+                let order = OrderRequest::limit(
+                    "BTC/USD",
+                    OrderSide::Buy,
+                    trade.price - Decimal::ONE,
+                    dec!(1),
+                );
+                bid = Some(orders.submit_order(&order).await.unwrap().id);
+            }
+            (Some(id), _) => {
+                let order = orders.get_order(*id).await.unwrap();
+                if order.status == OrderStatus::Closed {
+                    return Some(order);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn record(time: i64, price: &str, size: &str) -> String {
+    format!(
+        r#"{{"time": {}, "message": "{{\"channel\": \"trades\", \"market\": \"BTC/USD\", \"type\": \"update\", \"data\": [{{\"id\": {}, \"price\": {}, \"size\": {}, \"side\": \"sell\", \"liquidation\": false, \"time\": \"2021-05-23T05:24:24.315884+00:00\"}}]}}"}}"#,
+        time, time, price, size
+    )
+}
+
+#[tokio::test]
+async fn generic_strategy() {
+    let session = [
+        record(1, "100", "1"),
+        record(2, "99.5", "1"),
+        record(3, "98.5", "2"),
+        record(4, "98", "1"),
+    ]
+    .join("\n");
+    let paper = PaperRest::new()
+        .balance("USD", dec!(1000))
+        .fees(dec!(0), dec!(0));
+    let mut replay = Replay::new(Cursor::new(session));
+    replay.set_speed(f64::INFINITY);
+    let mut feed = PaperFeed {
+        source: replay,
+        paper: &paper,
+    };
+
+    let order = bid_below_first_trade(&mut feed, &paper).await.unwrap();
+    assert_eq!(order.filled_size, dec!(1));
+    assert_eq!(order.avg_fill_price, Some(dec!(99)));
+    let balances = paper.get_wallet_balances().await.unwrap();
+    let balances: Vec<_> = balances
+        .iter()
+        .map(|balance| (balance.coin.as_str(), balance.total))
+        .collect();
+    assert_eq!(balances, vec![("BTC", dec!(1)), ("USD", dec!(901))]);
+
+    // The trade filling the order was the third one
+    assert!(feed.next().await.unwrap().is_some());
+    assert!(feed.next().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn exchange_api() {
+    use crate::rest::{ApiFuture, ExchangeApi, WalletBalance};
+
+    // Code written against the former names still compiles
+    fn balances(api: &impl ExchangeApi) -> ApiFuture<'_, Vec<WalletBalance>> {
+        api.get_wallet_balances()
+    }
+
+    let paper = PaperRest::new().balance("USD", dec!(1000));
+    let balances = balances(&paper).await.unwrap();
+    assert_eq!(balances[0].total, dec!(1000));
+}
@@ -0,0 +1,26 @@
+use crate::{rest, ws};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Rest(rest::Error),
+    Ws(ws::Error),
+    /// `Client::panic_close` was called without the confirmation
+    PanicCloseNotConfirmed,
+    /// The websocket was closed, e.g. with `Ws::close`, while waiting for
+    /// an update
+    Closed,
+}
+
+impl From<rest::Error> for Error {
+    fn from(err: rest::Error) -> Error {
+        Error::Rest(err)
+    }
+}
+
+impl From<ws::Error> for Error {
+    fn from(err: ws::Error) -> Error {
+        Error::Ws(err)
+    }
+}
@@ -0,0 +1,90 @@
+use super::{Client, Error, Result};
+use crate::rest::{self, CancelAllFilter, OrderInfo, OrderRequest, OrderSide, Rest};
+
+/// Accounts processed by `Client::panic_close` and the orders placed to
+/// close their positions
+#[derive(Debug, Default)]
+pub struct PanicCloseReport {
+    /// Accounts whose orders were cancelled, `None` for the main account
+    pub accounts: Vec<Option<String>>,
+    /// Reduce-only market orders closing positions
+    pub closing_orders: Vec<OrderInfo>,
+    /// Failed requests by account. The other accounts and positions were
+    /// still processed.
+    pub errors: Vec<(Option<String>, rest::Error)>,
+}
+
+impl Client {
+    /// Confirmation to pass to `Client::panic_close`
+    pub const PANIC_CLOSE_CONFIRMATION: &'static str = "cancel all orders";
+
+    /// Kill switch for emergencies: cancels all open orders, including
+    /// conditional orders, of the account and of all its subaccounts if the
+    /// API key is allowed to list them. With `flatten`, positions are then
+    /// closed with reduce-only market orders. Spot balances are left as they
+    /// are.
+    ///
+    /// `confirmation` must be `Client::PANIC_CLOSE_CONFIRMATION`, otherwise
+    /// `Error::PanicCloseNotConfirmed` is returned without sending any
+    /// request. Failures are collected in the report instead of stopping at
+    /// the first one.
+    pub async fn panic_close(&self, confirmation: &str, flatten: bool) -> Result<PanicCloseReport> {
+        if confirmation != Self::PANIC_CLOSE_CONFIRMATION {
+            return Err(Error::PanicCloseNotConfirmed);
+        }
+
+        let mut report = PanicCloseReport::default();
+        let mut accounts = vec![self.rest.with_subaccount(self.rest.subaccount())];
+        if self.rest.subaccount().is_none() {
+            // Keys of subaccounts can't list the other subaccounts
+            match self.rest.get_subaccounts().await {
+                Ok(subaccounts) => accounts.extend(
+                    subaccounts
+                        .iter()
+                        .map(|subaccount| self.rest.with_subaccount(Some(&subaccount.nickname))),
+                ),
+                Err(err) => report.errors.push((None, err)),
+            }
+        }
+
+        for rest in &accounts {
+            let account = rest.subaccount().map(str::to_string);
+            if let Err(err) = rest.cancel_all_orders(&CancelAllFilter::new()).await {
+                report.errors.push((account.clone(), err));
+            }
+            report.accounts.push(account.clone());
+
+            if flatten {
+                if let Err(err) = close_positions(rest, &mut report).await {
+                    report.errors.push((account, err));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// Closes the positions of the account of `rest` with reduce-only market
+/// orders
+async fn close_positions(rest: &Rest, report: &mut PanicCloseReport) -> rest::Result<()> {
+    for position in rest.get_positions().await? {
+        if position.net_size.is_zero() {
+            continue;
+        }
+        let side = if position.net_size.is_sign_positive() {
+            OrderSide::Sell
+        } else {
+            OrderSide::Buy
+        };
+        let order =
+            OrderRequest::market(&position.future, side, position.net_size.abs()).reduce_only(true);
+        match rest.submit_order(&order).await {
+            Ok(info) => report.closing_orders.push(info),
+            Err(err) => report
+                .errors
+                .push((rest.subaccount().map(str::to_string), err)),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,292 @@
+//! This module combines the REST and Websocket APIs into a single client.
+
+mod error;
+mod kill_switch;
+mod sync;
+#[cfg(test)]
+mod tests;
+mod tracker;
+
+pub use error::*;
+pub use kill_switch::*;
+pub use sync::*;
+pub use tracker::*;
+
+use crate::options::{Exchange, Options};
+use crate::rest::{Id, OrderInfo, OrderRequest, Rest};
+use crate::ws::{self, Channel, Data, SpotMarginRates, Symbol, Ws};
+use chrono::Utc;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+
+/// Client sharing one set of credentials between a `Rest` handle for order
+/// entry and a `Ws` connection subscribed to the orders channel, so that
+/// orders placed with a client ID can be followed through their updates.
+pub struct Client {
+    rest: Rest,
+    ws: Ws,
+    /// Data received while waiting for an order update
+    buf: VecDeque<(Option<Symbol>, Data)>,
+    /// State of orders placed or updated since connecting
+    orders: OrderTracker,
+    /// Polls the rates while subscribed to `Channel::SpotMarginRates`
+    spot_margin_rates: Option<Interval>,
+    spot_margin_rate_interval: Duration,
+}
+
+impl Client {
+    /// Interval between REST polls of an order awaited by
+    /// `Client::wait_for_fill`, in case its updates are missed
+    const FILL_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// Default interval between polls of the spot margin rates
+    pub const SPOT_MARGIN_RATE_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Connections of the REST handle are kept alive, the first one is
+    /// opened right away so that orders are sent without delay
+    pub async fn connect(key: String, secret: String, subaccount: Option<String>) -> Result<Self> {
+        Self::from_options(&Options {
+            key: Some(key),
+            secret: Some(secret),
+            subaccount,
+            exchange: Exchange::Com,
+        })
+        .await
+    }
+
+    pub async fn connect_us(
+        key: String,
+        secret: String,
+        subaccount: Option<String>,
+    ) -> Result<Self> {
+        Self::from_options(&Options {
+            key: Some(key),
+            secret: Some(secret),
+            subaccount,
+            exchange: Exchange::Us,
+        })
+        .await
+    }
+
+    /// Connects to the exchange of `options` with its credentials, see
+    /// `Options::from_env`
+    pub async fn from_options(options: &Options) -> Result<Self> {
+        let rest = Rest::from_options(options);
+        rest.warm_up().await?;
+        let ws = Ws::builder().options(options).connect().await?;
+        Self::from_parts(rest, ws).await
+    }
+
+    /// Combines a REST handle and a websocket connection configured
+    /// separately, e.g. connected to custom endpoints with
+    /// `Rest::new_with_endpoint` and `Ws::builder`. Both should use the same
+    /// credentials and subaccount.
+    pub async fn from_parts(rest: Rest, mut ws: Ws) -> Result<Self> {
+        ws.subscribe(vec![Channel::Orders]).await?;
+
+        Ok(Self {
+            rest,
+            ws,
+            buf: VecDeque::new(),
+            orders: OrderTracker::new(),
+            spot_margin_rates: None,
+            spot_margin_rate_interval: Self::SPOT_MARGIN_RATE_INTERVAL,
+        })
+    }
+
+    pub fn rest(&self) -> &Rest {
+        &self.rest
+    }
+
+    pub fn rest_mut(&mut self) -> &mut Rest {
+        &mut self.rest
+    }
+
+    /// Use to configure the connection. Data should be read with
+    /// `Client::next` so that order updates are tracked.
+    pub fn ws_mut(&mut self) -> &mut Ws {
+        &mut self.ws
+    }
+
+    /// Subscribe to specified `Channel`s, including those polled from the
+    /// REST API like `Channel::SpotMarginRates`. Their data is returned by
+    /// `Client::next` along with the data of the websocket channels.
+    pub async fn subscribe(&mut self, channels: Vec<Channel>) -> Result<()> {
+        let (polled, channels): (Vec<_>, Vec<_>) = channels
+            .into_iter()
+            .partition(|channel| *channel == Channel::SpotMarginRates);
+        if !channels.is_empty() {
+            self.ws.subscribe(channels).await?;
+        }
+        if !polled.is_empty() && self.spot_margin_rates.is_none() {
+            // The first tick completes right away
+            let mut interval = time::interval(self.spot_margin_rate_interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            self.spot_margin_rates = Some(interval);
+        }
+
+        Ok(())
+    }
+
+    /// Unsubscribe from specified `Channel`s, see `Client::subscribe`
+    pub async fn unsubscribe(&mut self, channels: Vec<Channel>) -> Result<()> {
+        let (polled, channels): (Vec<_>, Vec<_>) = channels
+            .into_iter()
+            .partition(|channel| *channel == Channel::SpotMarginRates);
+        if !polled.is_empty() {
+            if self.spot_margin_rates.is_none() {
+                let err = ws::Error::NotSubscribedToThisChannel(Channel::SpotMarginRates);
+                return Err(err.into());
+            }
+            self.spot_margin_rates = None;
+        }
+        if !channels.is_empty() {
+            self.ws.unsubscribe(channels).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Set the interval between polls of the spot margin rates, applied to
+    /// subsequent subscriptions. FTX updates the estimated rates
+    /// continuously and the previous rates hourly.
+    pub fn set_spot_margin_rate_interval(&mut self, interval: Duration) {
+        self.spot_margin_rate_interval = interval;
+    }
+
+    /// Places an order through the REST API. Orders with a client ID can be
+    /// followed with `Client::order` and `Client::next_order_update`.
+    pub async fn place_order(&mut self, order: &OrderRequest) -> Result<OrderInfo> {
+        let info = self.rest.submit_order(order).await?;
+        // Ignored if updates already arrived on the orders channel
+        self.orders.on_order(&info);
+
+        Ok(info)
+    }
+
+    /// Returns the latest known state of the order placed with `client_id`
+    pub fn order(&self, client_id: &str) -> Option<&OrderInfo> {
+        Some(&self.orders.by_client_id(client_id)?.info)
+    }
+
+    /// State of all orders placed or updated since connecting. Subscribe to
+    /// `Channel::Fills` to also track fills.
+    pub fn orders(&self) -> &OrderTracker {
+        &self.orders
+    }
+
+    /// Waits for the next update of the order placed with `client_id`. Data
+    /// received in the meantime is returned by subsequent calls to `next`.
+    pub async fn next_order_update(&mut self, client_id: &str) -> Result<OrderInfo> {
+        loop {
+            let data = match self.ws.next().await? {
+                Some(data) => data,
+                None => return Err(Error::Closed),
+            };
+            self.track(&data.1);
+
+            if let (_, Data::Order(order)) = &data {
+                if order.client_id.as_deref() == Some(client_id) {
+                    return Ok(order.clone());
+                }
+            }
+
+            self.buf.push_back(data);
+        }
+    }
+
+    /// Waits until the order is filled or cancelled, following the `orders`
+    /// channel and polling `Rest::get_order` in case updates are missed.
+    /// After `timeout`, the order is fetched once more and returned even if
+    /// it is still open, so check its `TrackedOrder::state`. Subscribe to
+    /// `Channel::Fills` to get its fills as well. Data received in the
+    /// meantime is returned by subsequent calls to `next`. Fails with
+    /// `Error::Closed` once the websocket is closed.
+    pub async fn wait_for_fill(&mut self, order_id: Id, timeout: Duration) -> Result<TrackedOrder> {
+        let deadline = time::sleep_until(Instant::now() + timeout);
+        tokio::pin!(deadline);
+        let mut poll = time::interval_at(
+            Instant::now() + Self::FILL_POLL_INTERVAL,
+            Self::FILL_POLL_INTERVAL,
+        );
+        poll.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            if let Some(order) = self.orders.get(order_id) {
+                if order.state.is_closed() {
+                    return Ok(order.clone());
+                }
+            }
+
+            tokio::select! {
+                data = self.ws.next() => {
+                    let data = data?.ok_or(Error::Closed)?;
+                    self.track(&data.1);
+                    self.buf.push_back(data);
+                }
+                _ = poll.tick() => {
+                    self.poll_order(order_id).await?;
+                }
+                _ = &mut deadline => {
+                    self.poll_order(order_id).await?;
+                    if let Some(order) = self.orders.get(order_id) {
+                        return Ok(order.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the next data received on any subscribed channel, see
+    /// `Ws::next`, or polled for `Channel::SpotMarginRates`
+    pub async fn next(&mut self) -> Result<Option<(Option<Symbol>, Data)>> {
+        if let Some(data) = self.buf.pop_front() {
+            return Ok(Some(data));
+        }
+
+        let data = match &mut self.spot_margin_rates {
+            Some(interval) => tokio::select! {
+                data = self.ws.next() => Some(data?),
+                _ = interval.tick() => None,
+            },
+            None => Some(self.ws.next().await?),
+        };
+        let data = match data {
+            Some(data) => data,
+            None => {
+                let rates = self.poll_spot_margin_rates().await?;
+                return Ok(Some((None, Data::SpotMarginRates(rates))));
+            }
+        };
+        if let Some((_, data)) = &data {
+            self.track(data);
+        }
+
+        Ok(data)
+    }
+
+    /// Fetches the borrow and lending rates of all coins
+    async fn poll_spot_margin_rates(&self) -> Result<SpotMarginRates> {
+        let (borrow, lending) =
+            tokio::try_join!(self.rest.get_borrow_rates(), self.rest.get_lending_rates())?;
+
+        Ok(SpotMarginRates {
+            borrow,
+            lending,
+            time: Utc::now() + self.rest.clock_offset(),
+        })
+    }
+
+    /// Fetches the state of an order, which is tracked from then on
+    async fn poll_order(&mut self, order_id: Id) -> Result<()> {
+        let info = self.rest.get_order(order_id).await?;
+        self.orders.on_order(&info);
+        Ok(())
+    }
+
+    /// Records order updates and fills
+    fn track(&mut self, data: &Data) {
+        self.orders.update(data);
+    }
+}
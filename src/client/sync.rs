@@ -0,0 +1,281 @@
+use super::{OrderState, OrderTracker};
+use crate::portfolio::{PositionTracker, TrackedPosition};
+use crate::rest::{self, Coin, Fill, Id, OrderInfo, Position, Rest, Side, Symbol};
+use crate::ws::Data;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+/// Difference between locally tracked state and the state returned by the
+/// REST API, usually caused by missed websocket messages. The local state is
+/// replaced by the remote one when reported.
+#[derive(Clone, Debug)]
+pub enum Divergence {
+    /// The order is unknown locally or its status or filled size differs
+    Order {
+        local: Option<OrderState>,
+        remote: OrderInfo,
+    },
+    /// The net size of a futures position differs
+    Position {
+        market: Symbol,
+        local: Decimal,
+        remote: Decimal,
+    },
+    /// The total balance of a coin differs
+    Balance {
+        coin: Coin,
+        local: Decimal,
+        remote: Decimal,
+    },
+}
+
+/// Tracks orders, positions, and balances from websocket data and
+/// periodically reconciles them with the REST API, so that bots can detect
+/// missed messages and heal their state.
+///
+/// ```no_run
+/// # async fn example() -> ftx::client::Result<()> {
+/// use ftx::client::AccountSync;
+/// use ftx::rest::Rest;
+/// use ftx::ws::{Channel, Ws};
+/// use std::time::Duration;
+///
+/// let api = Rest::new("<api key>".into(), "<api secret>".into(), None);
+/// let mut ws = Ws::connect("<api key>".into(), "<api secret>".into(), None).await?;
+/// ws.subscribe(vec![Channel::Orders, Channel::Fills]).await?;
+///
+/// let mut sync = AccountSync::new(Duration::from_secs(60));
+/// loop {
+///     tokio::select! {
+///         data = ws.next() => {
+///             if let Some((_, data)) = data? {
+///                 sync.update(&data);
+///             }
+///         }
+///         _ = sync.tick() => {
+///             for divergence in sync.sync(&api).await? {
+///                 println!("Healed {:?}", divergence);
+///             }
+///         }
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AccountSync {
+    orders: OrderTracker,
+    positions: PositionTracker,
+    /// Total balance per coin
+    balances: HashMap<Coin, Decimal>,
+    interval: Interval,
+    /// Whether the local state was seeded by a first sync
+    synced: bool,
+}
+
+impl AccountSync {
+    /// Syncs every `period`, the first sync happening right away
+    pub fn new(period: Duration) -> Self {
+        let mut interval = time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Self {
+            orders: OrderTracker::new(),
+            positions: PositionTracker::new(),
+            balances: HashMap::new(),
+            interval,
+            synced: false,
+        }
+    }
+
+    /// Applies order updates and fills, ignoring other data. Fills update
+    /// positions and balances as well.
+    pub fn update(&mut self, data: &Data) {
+        self.orders.update(data);
+        if let Data::Fill(fill) = data {
+            self.apply_fill(fill);
+        }
+    }
+
+    pub fn orders(&self) -> &OrderTracker {
+        &self.orders
+    }
+
+    pub fn positions(&self) -> &PositionTracker {
+        &self.positions
+    }
+
+    /// Total balance per coin
+    pub fn balances(&self) -> &HashMap<Coin, Decimal> {
+        &self.balances
+    }
+
+    /// Waits until the next sync is due. Cancel safe, so it can be used in
+    /// `tokio::select!` along with `Ws::next`.
+    pub async fn tick(&mut self) {
+        self.interval.tick().await;
+    }
+
+    /// Fetches open orders, positions, and balances, and replaces the local
+    /// state where it differs. The first sync seeds the local state and
+    /// reports no divergences.
+    pub async fn sync(&mut self, rest: &Rest) -> rest::Result<Vec<Divergence>> {
+        let mut divergences = Vec::new();
+
+        let mut remote_orders = rest.get_all_open_orders().await?;
+        let open_ids: Vec<_> = self.orders.open_orders().map(|o| o.info.id).collect();
+        for id in open_ids {
+            if !remote_orders.iter().any(|order| order.id == id) {
+                // Closed since the last update
+                remote_orders.push(rest.get_order(id).await?);
+            }
+        }
+        let positions = rest.get_positions().await?;
+        let balances = rest.get_wallet_balances().await?;
+
+        for remote in remote_orders {
+            let local = self.orders.get(remote.id).map(|order| &order.info);
+            let diverged = match local {
+                Some(local) => {
+                    local.status != remote.status || local.filled_size != remote.filled_size
+                }
+                None => true,
+            };
+            if diverged {
+                let local = self.orders.get(remote.id).map(|order| order.state);
+                self.orders.replace(&remote);
+                divergences.push(Divergence::Order { local, remote });
+            }
+        }
+
+        let remote_positions: HashMap<&Symbol, &Position> = positions
+            .iter()
+            .map(|position| (&position.future, position))
+            .collect();
+        let mut markets: Vec<Symbol> = self
+            .positions
+            .positions()
+            .map(|(market, _)| market.clone())
+            .chain(remote_positions.keys().map(|&market| market.clone()))
+            .collect();
+        markets.sort();
+        markets.dedup();
+        for market in markets {
+            let local = self.positions.get(&market).cloned().unwrap_or_default();
+            let remote = remote_positions.get(&market).copied();
+            let remote_size = remote.map(|position| position.net_size).unwrap_or_default();
+            if local.net_size == remote_size {
+                continue;
+            }
+            match remote {
+                Some(remote) => self.positions.seed(std::slice::from_ref(remote)),
+                // Closed positions may be missing from the response
+                None => self.positions.set(
+                    market.clone(),
+                    TrackedPosition {
+                        net_size: Decimal::ZERO,
+                        entry_price: None,
+                        ..local.clone()
+                    },
+                ),
+            }
+            divergences.push(Divergence::Position {
+                market,
+                local: local.net_size,
+                remote: remote_size,
+            });
+        }
+
+        let remote_balances: HashMap<Coin, Decimal> = balances
+            .into_iter()
+            .map(|balance| (balance.coin, balance.total))
+            .collect();
+        let mut coins: Vec<_> = self.balances.keys().cloned().collect();
+        coins.extend(remote_balances.keys().cloned());
+        coins.sort();
+        coins.dedup();
+        for coin in coins {
+            let local = self.balances.get(&coin).copied().unwrap_or_default();
+            let remote = remote_balances.get(&coin).copied().unwrap_or_default();
+            if local != remote {
+                divergences.push(Divergence::Balance {
+                    coin,
+                    local,
+                    remote,
+                });
+            }
+        }
+        self.balances = remote_balances;
+
+        if !self.synced {
+            self.synced = true;
+            divergences.clear();
+        }
+        Ok(divergences)
+    }
+
+    fn apply_fill(&mut self, fill: &Fill) {
+        *self.balances.entry(fill.fee_currency.clone()).or_default() -= fill.fee;
+
+        match (&fill.base_currency, &fill.quote_currency) {
+            (Some(base), Some(quote)) => {
+                let notional = fill.price * fill.size;
+                let (base_change, quote_change) = match fill.side {
+                    Side::Buy => (fill.size, -notional),
+                    Side::Sell => (-fill.size, notional),
+                };
+                *self.balances.entry(base.clone()).or_default() += base_change;
+                *self.balances.entry(quote.clone()).or_default() += quote_change;
+            }
+            _ => {
+                self.positions.apply(fill);
+            }
+        }
+    }
+}
+
+/// Returns the fills of the last `window` listed by the REST API that are
+/// missing from `ws_fills`, oldest first. Useful after reconnecting, as fills
+/// sent while the websocket was disconnected are not sent again. The missed
+/// fills can be applied like those received on the websocket, e.g. with
+/// `AccountSync::update`.
+///
+/// ```no_run
+/// # async fn example() -> ftx::rest::Result<()> {
+/// use ftx::client::reconcile_fills;
+/// use ftx::rest::Rest;
+/// use std::time::Duration;
+///
+/// let api = Rest::new("<api key>".into(), "<api secret>".into(), None);
+/// let received = Vec::new(); // fills received on the websocket
+/// for fill in reconcile_fills(&received, &api, Duration::from_secs(600)).await? {
+///     println!("Missed fill {} of order {}", fill.id, fill.order_id);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn reconcile_fills(
+    ws_fills: &[Fill],
+    rest: &Rest,
+    window: Duration,
+) -> rest::Result<Vec<Fill>> {
+    let now = Utc::now() + rest.clock_offset();
+    let start_time = chrono::Duration::from_std(window)
+        .ok()
+        .and_then(|window| now.checked_sub_signed(window))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let received: HashSet<Id> = ws_fills.iter().map(|fill| fill.id).collect();
+
+    let mut missed: Vec<Fill> = rest
+        .paginate_fills(None, Some(start_time), None)
+        .try_collect()
+        .await?
+        .into_iter()
+        .filter(|fill| fill.time >= start_time && !received.contains(&fill.id))
+        .collect();
+    missed.sort_by_key(|fill| (fill.time, fill.id));
+
+    Ok(missed)
+}
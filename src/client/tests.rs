@@ -0,0 +1,478 @@
+use super::*;
+use crate::rest::OrderStatus;
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+const SUBSCRIBED_ORDERS: &str = r#"{"type": "subscribed", "channel": "orders"}"#;
+const TRADES_UPDATE: &str = r#"{"channel": "trades", "market": "BTC-PERP", "type": "update", "data": [{"id": 1, "price": 40000.0, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"}]}"#;
+const ORDER_UPDATE: &str = r#"{"channel": "orders", "type": "update", "data": {"id": 24852229, "clientId": "my-order", "market": "BTC-PERP", "type": "limit", "side": "buy", "size": 0.1, "price": 40000.0, "reduceOnly": false, "ioc": false, "postOnly": false, "status": "closed", "filledSize": 0.1, "remainingSize": 0.0, "avgFillPrice": 40000.0, "createdAt": "2021-05-23T05:24:24.315884+00:00"}}"#;
+
+#[tokio::test]
+async fn next_order_update() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut stream = tokio_tungstenite::accept_async(socket).await.unwrap();
+        stream.next().await.unwrap().unwrap(); // login
+        stream.next().await.unwrap().unwrap(); // ping
+        stream
+            .send(Message::Text(r#"{"type": "pong"}"#.to_string()))
+            .await
+            .unwrap();
+        stream.next().await.unwrap().unwrap(); // subscribe
+        for message in [SUBSCRIBED_ORDERS, TRADES_UPDATE, ORDER_UPDATE] {
+            stream
+                .send(Message::Text(message.to_string()))
+                .await
+                .unwrap();
+        }
+        stream
+    });
+
+    let rest = Rest::new("key".to_string(), "secret".to_string(), None);
+    let ws = Ws::builder()
+        .endpoint(&endpoint)
+        .credentials("key", "secret")
+        .connect()
+        .await
+        .expect("Connection failed.");
+    let mut client = Client::from_parts(rest, ws)
+        .await
+        .expect("Subscription failed.");
+
+    let order = client.next_order_update("my-order").await.unwrap();
+    assert_eq!(order.status, OrderStatus::Closed);
+    assert_eq!(
+        client.order("my-order").map(|order| order.id),
+        Some(24852229)
+    );
+
+    // Data received while waiting is not lost
+    match client.next().await.unwrap() {
+        Some((_, Data::Trade(trade))) => assert_eq!(trade.id, 1),
+        _ => panic!("Trade data expected."),
+    }
+
+    // Waiting fails instead of hanging once the websocket is closed
+    client.ws_mut().close().await.unwrap();
+    assert!(matches!(
+        client.next_order_update("my-order").await,
+        Err(Error::Closed)
+    ));
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn custom_endpoints() {
+    use crate::rest::OrderSide;
+    use crate::testing::{MockRest, MockWsServer};
+    use rust_decimal_macros::dec;
+    use serde_json::{json, Value};
+
+    let order = json!({"id": 9596912, "clientId": "my-order", "market": "BTC-PERP", "type": "limit", "side": "buy", "size": 0.001, "price": 40000.0, "reduceOnly": false, "ioc": false, "postOnly": false, "status": "new", "filledSize": 0.0, "remainingSize": 0.001, "avgFillPrice": null, "createdAt": "2021-05-23T05:24:24.315884+00:00"});
+    let rest_server = MockRest::bind().await.unwrap();
+    rest_server.respond_ok("POST", "/orders", order.clone());
+    let ws_server = MockWsServer::bind().await.unwrap();
+
+    let rest = Rest::new_with_endpoint(
+        &rest_server.endpoint(),
+        "FTX",
+        MockRest::KEY.to_string(),
+        MockRest::SECRET.to_string(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(rest.endpoint(), rest_server.endpoint());
+    let ws = Ws::builder()
+        .endpoint(&ws_server.endpoint())
+        .credentials(MockRest::KEY, MockRest::SECRET)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    let mut client = Client::from_parts(rest, ws)
+        .await
+        .expect("Subscription failed.");
+
+    let info = client
+        .place_order(
+            &OrderRequest::limit("BTC-PERP", OrderSide::Buy, dec!(40000), dec!(0.001))
+                .client_id("my-order"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(info.id, 9596912);
+    assert_eq!(rest_server.requests()[0].json()["clientId"], "my-order");
+
+    let mut closed = order;
+    closed["status"] = Value::from("closed");
+    ws_server.send_order(closed);
+    let update = client.next_order_update("my-order").await.unwrap();
+    assert_eq!(update.status, OrderStatus::Closed);
+}
+
+#[test]
+fn order_tracker() {
+    use crate::rest::{Fill, OrderInfo};
+    use rust_decimal_macros::dec;
+    use serde_json::json;
+
+    let order = |status: &str, filled: f64| -> OrderInfo {
+        serde_json::from_value(json!({"id": 1, "clientId": "my-order", "market": "BTC-PERP", "type": "limit", "side": "buy", "size": 0.2, "price": 40000.0, "reduceOnly": false, "ioc": false, "postOnly": false, "status": status, "filledSize": filled, "remainingSize": 0.2 - filled, "avgFillPrice": null, "createdAt": "2021-05-23T05:24:24.315884+00:00"})).unwrap()
+    };
+    let fill = |id: u64, price: f64| -> Fill {
+        serde_json::from_value(json!({"id": id, "market": "BTC-PERP", "future": "BTC-PERP", "baseCurrency": null, "quoteCurrency": null, "type": "order", "side": "buy", "price": price, "size": 0.1, "orderId": 1, "tradeId": id, "time": "2021-05-23T05:24:24.315884+00:00", "fee": 0.0, "feeRate": 0.0, "feeCurrency": "USD", "liquidity": "maker"})).unwrap()
+    };
+
+    let mut tracker = OrderTracker::new();
+
+    // Fills of unknown orders are applied once the order is seen
+    assert_eq!(tracker.on_fill(&fill(10, 40000.0)), None);
+    let event = tracker.on_order(&order("new", 0.0)).unwrap();
+    assert_eq!(event.from, None);
+    assert_eq!(event.to, OrderState::PartiallyFilled);
+    assert_eq!(tracker.open_orders().count(), 1);
+
+    // Stale updates and duplicate fills are ignored
+    assert_eq!(tracker.on_order(&order("open", 0.0)), None);
+    assert_eq!(tracker.on_fill(&fill(10, 40000.0)), None);
+    let tracked = tracker.by_client_id("my-order").unwrap();
+    assert_eq!(tracked.state, OrderState::PartiallyFilled);
+    assert_eq!(tracked.info.filled_size, dec!(0.1));
+
+    let event = tracker.update(&Data::Fill(fill(11, 40010.0))).unwrap();
+    assert_eq!(event.from, Some(OrderState::PartiallyFilled));
+    assert_eq!(event.to, OrderState::Filled);
+    let tracked = tracker.get(1).unwrap();
+    assert_eq!(tracked.info.avg_fill_price, Some(dec!(40005)));
+    assert_eq!(tracked.fills.len(), 2);
+
+    // Closed orders don't change anymore
+    assert_eq!(tracker.on_order(&order("closed", 0.1)), None);
+    assert_eq!(tracker.open_orders().count(), 0);
+    tracker.remove_closed();
+    assert!(tracker.by_client_id("my-order").is_none());
+}
+
+#[tokio::test]
+async fn account_sync() {
+    use crate::rest::Fill;
+    use crate::testing::MockRest;
+    use rust_decimal_macros::dec;
+    use serde_json::json;
+    use std::time::Duration;
+
+    let order = |status: &str, filled: f64| json!({"id": 1, "clientId": null, "market": "BTC-PERP", "type": "limit", "side": "buy", "size": 0.2, "price": 40000.0, "reduceOnly": false, "ioc": false, "postOnly": false, "status": status, "filledSize": filled, "remainingSize": 0.2 - filled, "avgFillPrice": null, "createdAt": "2021-05-23T05:24:24.315884+00:00"});
+    let position = |net_size: f64| json!({"cost": 0.0, "entryPrice": 40000.0, "estimatedLiquidationPrice": null, "future": "BTC-PERP", "initialMarginRequirement": 0.1, "longOrderSize": 0.0, "maintenanceMarginRequirement": 0.03, "netSize": net_size, "openSize": net_size, "realizedPnl": 0.0, "shortOrderSize": 0.0, "side": "buy", "size": net_size, "unrealizedPnl": 0.0, "collateralUsed": 0.0});
+    let balance = |coin: &str, total: f64| json!({"coin": coin, "free": total, "total": total, "spotBorrow": 0.0, "availableWithoutBorrow": total, "usdValue": null});
+    let fill: Fill = serde_json::from_value(json!({"id": 10, "market": "BTC-PERP", "future": "BTC-PERP", "baseCurrency": null, "quoteCurrency": null, "type": "order", "side": "buy", "price": 40000.0, "size": 0.1, "orderId": 1, "tradeId": 10, "time": "2021-05-23T05:24:24.315884+00:00", "fee": 1.0, "feeRate": 0.0, "feeCurrency": "USD", "liquidity": "maker"})).unwrap();
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok("GET", "/orders", json!([order("open", 0.0)]));
+    server.respond_ok("GET", "/orders", json!([]));
+    server.respond_ok("GET", "/orders/1", order("closed", 0.2));
+    server.respond_ok("GET", "/positions", json!([position(0.1)]));
+    server.respond_ok("GET", "/positions", json!([position(0.3)]));
+    server.respond_ok("GET", "/wallet/balances", json!([balance("USD", 1000.0)]));
+    server.respond_ok(
+        "GET",
+        "/wallet/balances",
+        json!([balance("USD", 999.0), balance("BTC", 1.0)]),
+    );
+    let rest = server.rest();
+
+    // The first sync seeds the local state
+    let mut sync = AccountSync::new(Duration::from_secs(60));
+    sync.tick().await;
+    assert!(sync.sync(&rest).await.unwrap().is_empty());
+    assert_eq!(sync.orders().open_orders().count(), 1);
+    assert_eq!(
+        sync.positions().get("BTC-PERP").unwrap().net_size,
+        dec!(0.1)
+    );
+
+    sync.update(&Data::Fill(fill));
+    assert_eq!(
+        sync.positions().get("BTC-PERP").unwrap().net_size,
+        dec!(0.2)
+    );
+    assert_eq!(sync.balances()["USD"], dec!(999));
+
+    // Missed order update, fill, and balance change
+    let divergences = sync.sync(&rest).await.unwrap();
+    assert_eq!(divergences.len(), 3);
+    match &divergences[0] {
+        Divergence::Order { local, remote } => {
+            assert_eq!(*local, Some(OrderState::PartiallyFilled));
+            assert_eq!(remote.status, OrderStatus::Closed);
+        }
+        divergence => panic!("Order divergence expected, got {:?}", divergence),
+    }
+    match &divergences[1] {
+        Divergence::Position { local, remote, .. } => {
+            assert_eq!((*local, *remote), (dec!(0.2), dec!(0.3)));
+        }
+        divergence => panic!("Position divergence expected, got {:?}", divergence),
+    }
+    match &divergences[2] {
+        Divergence::Balance {
+            coin,
+            local,
+            remote,
+        } => {
+            assert_eq!((coin.as_str(), *local, *remote), ("BTC", dec!(0), dec!(1)));
+        }
+        divergence => panic!("Balance divergence expected, got {:?}", divergence),
+    }
+
+    // The local state was healed
+    assert_eq!(sync.orders().get(1).unwrap().state, OrderState::Filled);
+    assert_eq!(
+        sync.positions().get("BTC-PERP").unwrap().net_size,
+        dec!(0.3)
+    );
+    assert_eq!(sync.balances()["BTC"], dec!(1));
+}
+
+#[tokio::test]
+async fn reconcile_fills() {
+    use crate::rest::Fill;
+    use crate::testing::MockRest;
+    use chrono::Utc;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    let now = Utc::now();
+    let fill = |id: u64, seconds_ago: i64| -> Value {
+        let time = now - chrono::Duration::seconds(seconds_ago);
+        json!({"id": id, "market": "BTC-PERP", "future": "BTC-PERP", "baseCurrency": null, "quoteCurrency": null, "type": "order", "side": "buy", "price": 40000.0, "size": 0.1, "orderId": 1, "tradeId": id, "time": time.to_rfc3339(), "fee": 0.0, "feeRate": 0.0, "feeCurrency": "USD", "liquidity": "maker"})
+    };
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "GET",
+        "/fills",
+        json!([fill(4, 10), fill(2, 20), fill(1, 30), fill(3, 7200)]),
+    );
+    server.respond_ok("GET", "/fills", json!([]));
+    let rest = server.rest();
+
+    // Fill 2 was received on the websocket, fill 3 is outside of the window
+    let received: Fill = serde_json::from_value(fill(2, 20)).unwrap();
+    let missed = super::reconcile_fills(&[received], &rest, Duration::from_secs(600))
+        .await
+        .unwrap();
+    let ids: Vec<_> = missed.iter().map(|fill| fill.id).collect();
+    assert_eq!(ids, vec![1, 4]);
+
+    // Fills are requested from the start of the window
+    let path = &server.requests()[0].path;
+    let start_time: i64 = path.split("start_time=").nth(1).unwrap()[..10]
+        .parse()
+        .unwrap();
+    assert!((start_time - (now.timestamp() - 600)).abs() <= 1);
+}
+
+#[tokio::test]
+async fn wait_for_fill() {
+    use crate::testing::{MockRest, MockWsServer};
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    let order = |id: u64, status: &str, filled: f64| -> Value {
+        json!({"id": id, "clientId": null, "market": "BTC-PERP", "type": "limit", "side": "buy", "size": 0.2, "price": 40000.0, "reduceOnly": false, "ioc": false, "postOnly": false, "status": status, "filledSize": filled, "remainingSize": 0.2 - filled, "avgFillPrice": null, "createdAt": "2021-05-23T05:24:24.315884+00:00"})
+    };
+    let rest_server = MockRest::bind().await.unwrap();
+    rest_server.respond_ok("GET", "/orders/2", order(2, "open", 0.0));
+    rest_server.respond_ok("GET", "/orders/2", order(2, "closed", 0.1));
+    let ws_server = MockWsServer::bind().await.unwrap();
+    let ws = Ws::builder()
+        .endpoint(&ws_server.endpoint())
+        .credentials(MockRest::KEY, MockRest::SECRET)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    let mut client = Client::from_parts(rest_server.rest(), ws)
+        .await
+        .expect("Subscription failed.");
+
+    // Filled through the orders channel
+    ws_server.send_order(order(3, "open", 0.0));
+    ws_server.send_order(order(1, "open", 0.1));
+    ws_server.send_order(order(1, "closed", 0.2));
+    let filled = client
+        .wait_for_fill(1, Duration::from_secs(10))
+        .await
+        .unwrap();
+    assert_eq!(filled.state, OrderState::Filled);
+    assert!(rest_server.requests().is_empty());
+
+    // Data received while waiting is not lost
+    match client.next().await.unwrap() {
+        Some((_, Data::Order(order))) => assert_eq!(order.id, 3),
+        _ => panic!("Order update expected."),
+    }
+
+    // Without updates, the order is fetched once the timeout expires
+    let open = client
+        .wait_for_fill(2, Duration::from_millis(50))
+        .await
+        .unwrap();
+    assert_eq!(open.state, OrderState::Open);
+    let cancelled = client
+        .wait_for_fill(2, Duration::from_millis(50))
+        .await
+        .unwrap();
+    assert_eq!(cancelled.state, OrderState::Cancelled);
+    assert_eq!(rest_server.requests().len(), 2);
+
+    // Waiting fails right away once the websocket is closed
+    client.ws_mut().close().await.unwrap();
+    assert!(matches!(
+        client.wait_for_fill(4, Duration::from_secs(3600)).await,
+        Err(Error::Closed)
+    ));
+    assert_eq!(rest_server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn panic_close() {
+    use crate::testing::{MockRest, MockWsServer};
+    use serde_json::json;
+
+    let position = |future: &str, net_size: f64| json!({"cost": 0.0, "entryPrice": 40000.0, "estimatedLiquidationPrice": null, "future": future, "initialMarginRequirement": 0.1, "longOrderSize": 0.0, "maintenanceMarginRequirement": 0.03, "netSize": net_size, "openSize": net_size.abs(), "realizedPnl": 0.0, "shortOrderSize": 0.0, "side": "buy", "size": net_size.abs(), "unrealizedPnl": 0.0, "collateralUsed": 0.0});
+    let rest_server = MockRest::with_fixtures().await.unwrap();
+    rest_server.respond_ok(
+        "GET",
+        "/subaccounts",
+        json!([{"nickname": "sub1", "deletable": true, "editable": true, "competition": false}]),
+    );
+    rest_server.respond_ok("DELETE", "/orders", json!("Orders queued for cancelation"));
+    rest_server.respond_ok(
+        "GET",
+        "/positions",
+        json!([position("BTC-PERP", -0.5), position("ETH-PERP", 0.0)]),
+    );
+    let ws_server = MockWsServer::bind().await.unwrap();
+    let ws = Ws::builder()
+        .endpoint(&ws_server.endpoint())
+        .credentials(MockRest::KEY, MockRest::SECRET)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    let client = Client::from_parts(rest_server.rest(), ws)
+        .await
+        .expect("Subscription failed.");
+
+    // Nothing is sent without the confirmation
+    assert!(matches!(
+        client.panic_close("yes", true).await,
+        Err(Error::PanicCloseNotConfirmed)
+    ));
+    assert!(rest_server.requests().is_empty());
+
+    let report = client
+        .panic_close(Client::PANIC_CLOSE_CONFIRMATION, true)
+        .await
+        .unwrap();
+    assert_eq!(report.accounts, vec![None, Some("sub1".to_string())]);
+    assert_eq!(report.closing_orders.len(), 2);
+    assert!(report.errors.is_empty());
+
+    let requests = rest_server.requests();
+    let cancels: Vec<_> = requests
+        .iter()
+        .filter(|request| request.method == "DELETE")
+        .map(|request| request.header("FTX-SUBACCOUNT"))
+        .collect();
+    assert_eq!(cancels, vec![None, Some("sub1")]);
+    let orders: Vec<_> = requests
+        .iter()
+        .filter(|request| request.method == "POST")
+        .map(|request| request.json())
+        .collect();
+    assert_eq!(orders.len(), 2);
+    assert_eq!(orders[0]["market"], "BTC-PERP");
+    assert_eq!(orders[0]["side"], "buy");
+    assert_eq!(orders[0]["size"], "0.5");
+    assert_eq!(orders[0]["reduceOnly"], true);
+}
+
+#[tokio::test]
+async fn spot_margin_rates() {
+    use crate::testing::{MockRest, MockWsServer};
+    use rust_decimal_macros::dec;
+    use serde_json::json;
+    use std::time::Duration;
+
+    let rest_server = MockRest::bind().await.unwrap();
+    rest_server.respond_ok(
+        "GET",
+        "/spot_margin/borrow_rates",
+        json!([{"coin": "BTC", "estimate": 0.000004, "previous": 0.000003}]),
+    );
+    rest_server.respond_ok(
+        "GET",
+        "/spot_margin/lending_rates",
+        json!([{"coin": "BTC", "estimate": 0.000002, "previous": 0.000001}]),
+    );
+    let ws_server = MockWsServer::bind().await.unwrap();
+    let ws = Ws::builder()
+        .endpoint(&ws_server.endpoint())
+        .credentials(MockRest::KEY, MockRest::SECRET)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    let mut client = Client::from_parts(rest_server.rest(), ws)
+        .await
+        .expect("Subscription failed.");
+
+    // Only the client polls the synthetic channel
+    assert!(matches!(
+        client
+            .ws_mut()
+            .subscribe(vec![Channel::SpotMarginRates])
+            .await,
+        Err(ws::Error::PolledChannel(Channel::SpotMarginRates))
+    ));
+
+    // Rates are polled right away, then once per interval
+    client.set_spot_margin_rate_interval(Duration::from_millis(200));
+    client
+        .subscribe(vec![Channel::SpotMarginRates])
+        .await
+        .unwrap();
+    for _ in 0..2 {
+        match client.next().await.unwrap() {
+            Some((None, Data::SpotMarginRates(rates))) => {
+                assert_eq!(rates.borrow[0].coin, "BTC");
+                assert_eq!(rates.borrow[0].estimate, dec!(0.000004));
+                assert_eq!(rates.lending[0].previous, dec!(0.000001));
+            }
+            _ => panic!("Spot margin rates expected."),
+        }
+    }
+    assert_eq!(rest_server.requests().len(), 4);
+
+    // Websocket data is returned in between
+    ws_server.send_order(json!({"id": 1, "clientId": null, "market": "BTC-PERP", "type": "limit", "side": "buy", "size": 0.1, "price": 40000.0, "reduceOnly": false, "ioc": false, "postOnly": false, "status": "new", "filledSize": 0.0, "remainingSize": 0.1, "avgFillPrice": null, "createdAt": "2021-05-23T05:24:24.315884+00:00"}));
+    loop {
+        match client.next().await.unwrap() {
+            Some((_, Data::Order(order))) => break assert_eq!(order.id, 1),
+            Some((_, Data::SpotMarginRates(_))) => continue,
+            _ => panic!("Order update expected."),
+        }
+    }
+
+    client
+        .unsubscribe(vec![Channel::SpotMarginRates])
+        .await
+        .unwrap();
+    assert!(client
+        .unsubscribe(vec![Channel::SpotMarginRates])
+        .await
+        .is_err());
+}
@@ -0,0 +1,247 @@
+use crate::rest::{Fill, Id, OrderInfo, OrderStatus};
+use crate::ws::Data;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Lifecycle state of a tracked order
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OrderState {
+    /// Accepted but not processed yet
+    New,
+    /// Resting on the book without fills
+    Open,
+    PartiallyFilled,
+    Filled,
+    /// Closed before being completely filled, possibly after partial fills
+    Cancelled,
+}
+
+impl OrderState {
+    fn of(info: &OrderInfo) -> Self {
+        match info.status {
+            OrderStatus::New => OrderState::New,
+            OrderStatus::Open if info.filled_size.is_zero() => OrderState::Open,
+            OrderStatus::Open => OrderState::PartiallyFilled,
+            OrderStatus::Closed if info.filled_size >= info.size => OrderState::Filled,
+            OrderStatus::Closed => OrderState::Cancelled,
+        }
+    }
+
+    /// Whether the order is closed and won't change anymore
+    pub fn is_closed(&self) -> bool {
+        matches!(self, OrderState::Filled | OrderState::Cancelled)
+    }
+}
+
+/// Transition of an order to a new state
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OrderEvent {
+    pub id: Id,
+    pub client_id: Option<String>,
+    /// `None` when the order is first seen
+    pub from: Option<OrderState>,
+    pub to: OrderState,
+}
+
+/// Latest known state of an order along with its fills
+#[derive(Clone, Debug)]
+pub struct TrackedOrder {
+    pub info: OrderInfo,
+    pub state: OrderState,
+    pub fills: Vec<Fill>,
+}
+
+/// Maintains the state of orders from REST acknowledgments and the `orders`
+/// and `fills` channels, which may arrive in any order. Updates older than
+/// the known state are ignored, so an order never moves back, e.g. from
+/// partially filled to open.
+///
+/// ```no_run
+/// # async fn example() -> ftx::client::Result<()> {
+/// use ftx::client::OrderTracker;
+/// use ftx::ws::{Channel, Ws};
+///
+/// let mut ws = Ws::connect("<api key>".into(), "<api secret>".into(), None).await?;
+/// ws.subscribe(vec![Channel::Orders, Channel::Fills]).await?;
+///
+/// let mut tracker = OrderTracker::new();
+/// while let Some((_, data)) = ws.next().await? {
+///     if let Some(event) = tracker.update(&data) {
+///         println!("Order {} is now {:?}", event.id, event.to);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OrderTracker {
+    orders: HashMap<Id, TrackedOrder>,
+    client_ids: HashMap<String, Id>,
+    /// Fills received before their order, by order ID
+    pending_fills: HashMap<Id, Vec<Fill>>,
+}
+
+impl OrderTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies order updates and fills, ignoring other data
+    pub fn update(&mut self, data: &Data) -> Option<OrderEvent> {
+        match data {
+            Data::Order(info) => self.on_order(info),
+            Data::Fill(fill) => self.on_fill(fill),
+            _ => None,
+        }
+    }
+
+    /// Applies an order returned by the REST API, e.g. by `Rest::place_order`,
+    /// or received on the `orders` channel
+    pub fn on_order(&mut self, info: &OrderInfo) -> Option<OrderEvent> {
+        if let Some(client_id) = &info.client_id {
+            self.client_ids.insert(client_id.clone(), info.id);
+        }
+
+        let (from, order) = match self.orders.get_mut(&info.id) {
+            Some(order) => {
+                let stale = order.state.is_closed()
+                    || status_rank(info.status) < status_rank(order.info.status)
+                    || info.filled_size < order.info.filled_size;
+                if stale {
+                    return None;
+                }
+                order.info = info.clone();
+                (Some(order.state), order)
+            }
+            None => {
+                let order = TrackedOrder {
+                    info: info.clone(),
+                    state: OrderState::of(info),
+                    fills: Vec::new(),
+                };
+                (None, self.orders.entry(info.id).or_insert(order))
+            }
+        };
+
+        for fill in self.pending_fills.remove(&info.id).unwrap_or_default() {
+            add_fill(order, fill);
+        }
+        apply_fills(order);
+
+        transition(from, order)
+    }
+
+    /// Replaces the state of an order even if it is closed or the update
+    /// looks stale, e.g. with its state fetched from the REST API after
+    /// updates were missed. Fills already received are kept.
+    pub fn replace(&mut self, info: &OrderInfo) -> Option<OrderEvent> {
+        let previous = self.orders.remove(&info.id);
+        self.on_order(info);
+        let order = self.orders.get_mut(&info.id)?;
+        let from = previous.map(|previous| {
+            for fill in previous.fills {
+                add_fill(order, fill);
+            }
+            previous.state
+        });
+        transition(from, order)
+    }
+
+    /// Applies a fill received on the `fills` channel. Fills of unknown
+    /// orders are kept until the order is seen.
+    pub fn on_fill(&mut self, fill: &Fill) -> Option<OrderEvent> {
+        let order = match self.orders.get_mut(&fill.order_id) {
+            Some(order) => order,
+            None => {
+                self.pending_fills
+                    .entry(fill.order_id)
+                    .or_default()
+                    .push(fill.clone());
+                return None;
+            }
+        };
+
+        let from = order.state;
+        if !add_fill(order, fill.clone()) {
+            return None;
+        }
+        apply_fills(order);
+
+        transition(Some(from), order)
+    }
+
+    pub fn get(&self, id: Id) -> Option<&TrackedOrder> {
+        self.orders.get(&id)
+    }
+
+    pub fn by_client_id(&self, client_id: &str) -> Option<&TrackedOrder> {
+        self.get(*self.client_ids.get(client_id)?)
+    }
+
+    /// Orders that are not closed yet
+    pub fn open_orders(&self) -> impl Iterator<Item = &TrackedOrder> {
+        self.orders
+            .values()
+            .filter(|order| !order.state.is_closed())
+    }
+
+    /// Forgets closed orders, e.g. to bound memory in long running bots
+    pub fn remove_closed(&mut self) {
+        self.orders.retain(|_, order| !order.state.is_closed());
+        let orders = &self.orders;
+        self.client_ids.retain(|_, id| orders.contains_key(id));
+    }
+}
+
+fn status_rank(status: OrderStatus) -> u8 {
+    match status {
+        OrderStatus::New => 0,
+        OrderStatus::Open => 1,
+        OrderStatus::Closed => 2,
+    }
+}
+
+/// Adds `fill` to `order` unless it was already added, returns whether it
+/// was added
+fn add_fill(order: &mut TrackedOrder, fill: Fill) -> bool {
+    if order.fills.iter().any(|f| f.id == fill.id) {
+        return false;
+    }
+    order.fills.push(fill);
+    true
+}
+
+/// Updates the filled size of `order` if its fills are ahead of the last
+/// order update
+fn apply_fills(order: &mut TrackedOrder) {
+    let filled: Decimal = order.fills.iter().map(|fill| fill.size).sum();
+    if filled <= order.info.filled_size || order.state.is_closed() {
+        return;
+    }
+
+    let notional: Decimal = order.fills.iter().map(|fill| fill.price * fill.size).sum();
+    let info = &mut order.info;
+    info.filled_size = filled;
+    info.remaining_size = (info.size - filled).max(Decimal::ZERO);
+    info.avg_fill_price = Some(notional / filled);
+    if info.remaining_size.is_zero() {
+        info.status = OrderStatus::Closed;
+    } else if info.status == OrderStatus::New {
+        info.status = OrderStatus::Open;
+    }
+}
+
+fn transition(from: Option<OrderState>, order: &mut TrackedOrder) -> Option<OrderEvent> {
+    let to = OrderState::of(&order.info);
+    order.state = to;
+    if from == Some(to) {
+        return None;
+    }
+
+    Some(OrderEvent {
+        id: order.info.id,
+        client_id: order.info.client_id.clone(),
+        from,
+        to,
+    })
+}
@@ -0,0 +1,177 @@
+use super::{ChildOrders, ExecutionStats};
+use crate::api::OrderEntry;
+use crate::rest::{self, ApiError, Error, Id, OrderInfo, OrderRequest, OrderSide, Symbol};
+use crate::ws::Data;
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use tokio::time::Instant;
+
+/// Iceberg order, which FTX doesn't offer natively: keeps a limit order of
+/// at most `display_size` resting at the current price until `size` is
+/// filled. A new order is placed once the resting one is filled, and the
+/// resting order is replaced when the price is changed with `set_price`,
+/// e.g. to follow the best bid.
+///
+/// `requote` places and cancels orders as needed and should be called after
+/// passing order updates and fills to `update`, or after changing the price.
+///
+/// ```no_run
+/// # async fn example() -> ftx::client::Result<()> {
+/// use ftx::execution::IcebergExecutor;
+/// use ftx::rest::{OrderSide, Rest};
+/// use ftx::ws::{Channel, Data, Ws};
+/// use rust_decimal_macros::dec;
+///
+/// let api = Rest::new("<api key>".into(), "<api secret>".into(), None);
+/// let mut ws = Ws::connect("<api key>".into(), "<api secret>".into(), None).await?;
+/// ws.subscribe(vec![
+///     Channel::Orders,
+///     Channel::Fills,
+///     Channel::Ticker("BTC-PERP".into()),
+/// ])
+/// .await?;
+///
+/// // Buy 10 BTC at the best bid, showing at most 0.5 BTC
+/// let mut iceberg = IcebergExecutor::new("BTC-PERP", OrderSide::Buy, dec!(10), dec!(0.5), dec!(30000))
+///     .post_only(true);
+/// while !iceberg.is_done() {
+///     if let Some((_, data)) = ws.next().await? {
+///         if let Data::Ticker(ticker) = &data {
+///             if let Some(bid) = ticker.bid {
+///                 iceberg.set_price(bid);
+///             }
+///         }
+///         iceberg.update(&data);
+///         iceberg.requote(&api).await?;
+///     }
+/// }
+/// println!("{:?}", iceberg.stats());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct IcebergExecutor {
+    market: Symbol,
+    side: OrderSide,
+    size: Decimal,
+    display_size: Decimal,
+    price: Decimal,
+    post_only: bool,
+    started: Instant,
+    /// Child orders being cancelled, replaced once they are closed
+    cancelling: HashSet<Id>,
+    cancelled: bool,
+    children: ChildOrders,
+}
+
+impl IcebergExecutor {
+    pub fn new(
+        market: &str,
+        side: OrderSide,
+        size: Decimal,
+        display_size: Decimal,
+        price: Decimal,
+    ) -> Self {
+        Self {
+            market: market.into(),
+            side,
+            size,
+            display_size,
+            price,
+            post_only: false,
+            started: Instant::now(),
+            cancelling: HashSet::new(),
+            cancelled: false,
+            children: ChildOrders::default(),
+        }
+    }
+
+    /// Place post-only orders, which are cancelled instead of taking
+    /// liquidity if the price crosses the book
+    pub fn post_only(mut self, post_only: bool) -> Self {
+        self.post_only = post_only;
+        self
+    }
+
+    /// Price of the visible order, which is replaced by the next `requote`
+    /// if its price differs
+    pub fn set_price(&mut self, price: Decimal) {
+        self.price = price;
+    }
+
+    /// Applies order updates and fills of the child orders, ignoring other
+    /// data
+    pub fn update(&mut self, data: &Data) {
+        self.children.update(&self.market, data);
+    }
+
+    /// Cancels the resting order if its price is outdated, or places the
+    /// next visible order if none is resting. Returns the order placed, if
+    /// any. The replacement of a cancelled order is only placed once the
+    /// cancellation is confirmed, so that the size never exceeds `size`.
+    pub async fn requote(&mut self, api: &impl OrderEntry) -> rest::Result<Option<OrderInfo>> {
+        if self.cancelled {
+            return Ok(None);
+        }
+
+        let outdated: Vec<_> = self
+            .children
+            .open()
+            .filter(|order| order.info.price != Some(self.price))
+            .map(|order| order.info.id)
+            .filter(|id| !self.cancelling.contains(id))
+            .collect();
+        for id in outdated {
+            self.cancel_child(api, id).await?;
+        }
+        if self.children.open().next().is_some() {
+            return Ok(None);
+        }
+        self.cancelling.clear();
+
+        let size = (self.size - self.children.committed_size()).min(self.display_size);
+        if size <= Decimal::ZERO {
+            return Ok(None);
+        }
+        let order = OrderRequest::limit(&self.market, self.side, self.price, size)
+            .post_only(self.post_only);
+        let info = api.submit_order(&order).await?;
+        self.children.add(&info);
+
+        Ok(Some(info))
+    }
+
+    /// Stops the execution, cancelling the resting order
+    pub async fn cancel(&mut self, api: &impl OrderEntry) -> rest::Result<()> {
+        self.cancelled = true;
+        let open: Vec<_> = self.children.open().map(|order| order.info.id).collect();
+        for id in open {
+            self.cancel_child(api, id).await?;
+        }
+        Ok(())
+    }
+
+    /// Whether `size` was filled or the execution was cancelled, and no
+    /// child order is open anymore
+    pub fn is_done(&self) -> bool {
+        let filled = self.children.filled_size() >= self.size;
+        (filled || self.cancelled) && self.children.open().next().is_none()
+    }
+
+    pub fn stats(&self) -> ExecutionStats {
+        self.children.stats(self.size, self.started.elapsed())
+    }
+
+    /// Cancels a child order and fetches its state, in case it closed right
+    /// away
+    async fn cancel_child(&mut self, api: &impl OrderEntry, id: Id) -> rest::Result<()> {
+        match api.cancel_order(id).await {
+            Ok(_) | Err(Error::Api(ApiError::OrderAlreadyClosed)) => {}
+            Err(err) => return Err(err),
+        }
+        self.cancelling.insert(id);
+        let info = api.get_order(id).await?;
+        self.children.update(&self.market, &Data::Order(info));
+        Ok(())
+    }
+}
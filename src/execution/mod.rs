@@ -0,0 +1,136 @@
+//! Execution algorithms splitting large orders into smaller child orders:
+//! `TwapExecutor` spreads them over time and `IcebergExecutor` only shows
+//! part of the size on the book.
+//!
+//! ```no_run
+//! # async fn example() -> ftx::client::Result<()> {
+//! use ftx::execution::TwapExecutor;
+//! use ftx::rest::{OrderSide, Rest};
+//! use ftx::ws::{Channel, Ws};
+//! use rust_decimal_macros::dec;
+//! use std::time::Duration;
+//!
+//! let api = Rest::new("<api key>".into(), "<api secret>".into(), None);
+//! let mut ws = Ws::connect("<api key>".into(), "<api secret>".into(), None).await?;
+//! ws.subscribe(vec![Channel::Orders, Channel::Fills]).await?;
+//!
+//! // Buy 1 BTC over an hour in 60 market orders
+//! let mut twap = TwapExecutor::new("BTC-PERP", OrderSide::Buy, dec!(1), Duration::from_secs(3600))
+//!     .slices(60)
+//!     .size_increment(dec!(0.0001));
+//! while !twap.is_done() {
+//!     tokio::select! {
+//!         data = ws.next() => {
+//!             if let Some((_, data)) = data? {
+//!                 twap.update(&data);
+//!             }
+//!         }
+//!         _ = twap.tick(), if !twap.is_scheduled() => {
+//!             twap.place_slice(&api).await?;
+//!         }
+//!     }
+//! }
+//! println!("{:?}", twap.stats());
+//! # Ok(())
+//! # }
+//! ```
+
+mod iceberg;
+#[cfg(test)]
+mod tests;
+mod twap;
+
+pub use iceberg::*;
+pub use twap::*;
+
+use crate::client::{OrderTracker, TrackedOrder};
+use crate::rest::{Id, OrderInfo, Symbol};
+use crate::ws::Data;
+use rust_decimal::Decimal;
+use std::time::Duration;
+
+/// Statistics of an execution, see `TwapExecutor::stats` and
+/// `IcebergExecutor::stats`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecutionStats {
+    pub target_size: Decimal,
+    pub filled_size: Decimal,
+    /// Size neither filled nor part of an open child order
+    pub remaining_size: Decimal,
+    /// Average fill price of the child orders, `None` before the first fill
+    pub avg_fill_price: Option<Decimal>,
+    /// Fees of the fills received on the `fills` channel
+    pub fees: Decimal,
+    pub child_orders: usize,
+    pub elapsed: Duration,
+}
+
+/// Child orders placed by an executor along with their state
+#[derive(Debug, Default)]
+struct ChildOrders {
+    ids: Vec<Id>,
+    orders: OrderTracker,
+}
+
+impl ChildOrders {
+    fn add(&mut self, info: &OrderInfo) {
+        self.ids.push(info.id);
+        self.orders.on_order(info);
+    }
+
+    /// Applies order updates and fills of `market`. Fills may arrive before
+    /// the order is acknowledged, so they aren't filtered by order ID.
+    fn update(&mut self, market: &Symbol, data: &Data) {
+        let data_market = match data {
+            Data::Order(order) => &order.market,
+            Data::Fill(fill) => &fill.market,
+            _ => return,
+        };
+        if data_market == market {
+            self.orders.update(data);
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &TrackedOrder> {
+        self.ids.iter().filter_map(move |id| self.orders.get(*id))
+    }
+
+    /// Child orders not closed yet
+    fn open(&self) -> impl Iterator<Item = &TrackedOrder> {
+        self.iter().filter(|order| !order.state.is_closed())
+    }
+
+    fn open_size(&self) -> Decimal {
+        self.open().map(|order| order.info.remaining_size).sum()
+    }
+
+    fn filled_size(&self) -> Decimal {
+        self.iter().map(|order| order.info.filled_size).sum()
+    }
+
+    /// Size filled or part of open child orders
+    fn committed_size(&self) -> Decimal {
+        self.filled_size() + self.open_size()
+    }
+
+    fn stats(&self, target_size: Decimal, elapsed: Duration) -> ExecutionStats {
+        let mut filled_size = Decimal::ZERO;
+        let mut notional = Decimal::ZERO;
+        let mut fees = Decimal::ZERO;
+        for order in self.iter() {
+            filled_size += order.info.filled_size;
+            notional += order.info.filled_size * order.info.avg_fill_price.unwrap_or_default();
+            fees += order.fills.iter().map(|fill| fill.fee).sum::<Decimal>();
+        }
+
+        ExecutionStats {
+            target_size,
+            filled_size,
+            remaining_size: (target_size - filled_size - self.open_size()).max(Decimal::ZERO),
+            avg_fill_price: (!filled_size.is_zero()).then(|| notional / filled_size),
+            fees,
+            child_orders: self.ids.len(),
+            elapsed,
+        }
+    }
+}
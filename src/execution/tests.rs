@@ -0,0 +1,141 @@
+use super::*;
+use crate::api::OrderEntry;
+use crate::paper::PaperRest;
+use crate::rest::OrderSide;
+use crate::ws::{OrderbookAction, OrderbookData};
+use chrono::Utc;
+use rust_decimal_macros::dec;
+
+#[tokio::test]
+async fn twap_executor() {
+    let paper = PaperRest::new()
+        .balance("USD", dec!(1000))
+        .fees(dec!(0), dec!(0.001));
+    paper.update(
+        Some("BTC/USD"),
+        &Data::OrderbookData(OrderbookData {
+            action: OrderbookAction::Partial,
+            bids: vec![(dec!(99), dec!(10))],
+            asks: vec![(dec!(100), dec!(10))],
+            checksum: 0,
+            time: Utc::now(),
+        }),
+    );
+
+    let mut twap = TwapExecutor::new(
+        "BTC/USD",
+        OrderSide::Buy,
+        dec!(1),
+        Duration::from_millis(30),
+    )
+    .slices(3)
+    .size_increment(dec!(0.1));
+    let mut sizes = Vec::new();
+    while !twap.is_done() {
+        twap.tick().await;
+        if let Some(order) = twap.place_slice(&paper).await.unwrap() {
+            sizes.push(order.size);
+        }
+        for data in paper.take_updates() {
+            twap.update(&data);
+        }
+    }
+
+    // Slices catch up with the schedule, the last one takes the remainder
+    assert_eq!(sizes, vec![dec!(0.3), dec!(0.3), dec!(0.4)]);
+    let stats = twap.stats();
+    assert_eq!(stats.filled_size, dec!(1));
+    assert_eq!(stats.remaining_size, dec!(0));
+    assert_eq!(stats.avg_fill_price, Some(dec!(100)));
+    assert_eq!(stats.fees, dec!(0.1));
+    assert_eq!(stats.child_orders, 3);
+    assert!(stats.elapsed >= Duration::from_millis(20));
+
+    // Slices below the minimum are deferred, unfilled size is carried over
+    let mut twap = TwapExecutor::new(
+        "BTC/USD",
+        OrderSide::Buy,
+        dec!(1),
+        Duration::from_millis(30),
+    )
+    .slices(3)
+    .min_slice_size(dec!(0.5))
+    .limit_price(dec!(99));
+    while !twap.is_scheduled() {
+        twap.tick().await;
+        twap.place_slice(&paper).await.unwrap();
+    }
+    let stats = twap.stats();
+    assert_eq!(stats.child_orders, 2);
+    assert_eq!(stats.filled_size, dec!(0));
+    assert_eq!(stats.remaining_size, dec!(1));
+    assert!(twap.is_done());
+}
+
+#[tokio::test]
+async fn iceberg_executor() {
+    use crate::rest::Side;
+    use crate::ws::Trade;
+
+    let paper = PaperRest::new()
+        .balance("USD", dec!(1000))
+        .fees(dec!(0), dec!(0));
+    let trade = |id: u64, price: Decimal, size: Decimal| {
+        paper.update(
+            Some("BTC/USD"),
+            &Data::Trade(Trade {
+                id,
+                price,
+                size,
+                side: Side::Sell,
+                liquidation: false,
+                time: Utc::now(),
+            }),
+        );
+    };
+
+    let mut iceberg = IcebergExecutor::new("BTC/USD", OrderSide::Buy, dec!(1), dec!(0.4), dec!(99))
+        .post_only(true);
+    let order = iceberg.requote(&paper).await.unwrap().unwrap();
+    assert_eq!((order.price, order.size), (Some(dec!(99)), dec!(0.4)));
+    assert!(iceberg.requote(&paper).await.unwrap().is_none());
+
+    // Partially filled, then re-quoted at a new price with the unfilled size
+    trade(1, dec!(98.5), dec!(0.3));
+    for data in paper.take_updates() {
+        iceberg.update(&data);
+    }
+    assert!(iceberg.requote(&paper).await.unwrap().is_none());
+    iceberg.set_price(dec!(99.5));
+    let order = iceberg.requote(&paper).await.unwrap().unwrap();
+    assert_eq!((order.price, order.size), (Some(dec!(99.5)), dec!(0.4)));
+    assert_eq!(paper.get_open_orders("BTC/USD").await.unwrap().len(), 1);
+
+    // Reloaded once filled until the total size is reached
+    trade(2, dec!(99), dec!(1));
+    for data in paper.take_updates() {
+        iceberg.update(&data);
+    }
+    let order = iceberg.requote(&paper).await.unwrap().unwrap();
+    assert_eq!(order.size, dec!(0.3));
+    trade(3, dec!(99), dec!(1));
+    for data in paper.take_updates() {
+        iceberg.update(&data);
+    }
+    assert!(iceberg.requote(&paper).await.unwrap().is_none());
+    assert!(iceberg.is_done());
+    let stats = iceberg.stats();
+    assert_eq!(stats.filled_size, dec!(1));
+    assert_eq!(stats.avg_fill_price, Some(dec!(99.35)));
+    assert_eq!(stats.child_orders, 3);
+
+    // Cancelling stops the execution
+    let mut iceberg = IcebergExecutor::new("BTC/USD", OrderSide::Buy, dec!(1), dec!(0.4), dec!(99));
+    iceberg.requote(&paper).await.unwrap().unwrap();
+    assert!(!iceberg.is_done());
+    iceberg.cancel(&paper).await.unwrap();
+    assert!(iceberg.is_done());
+    assert!(iceberg.requote(&paper).await.unwrap().is_none());
+    assert!(paper.get_open_orders("BTC/USD").await.unwrap().is_empty());
+    assert_eq!(iceberg.stats().remaining_size, dec!(1));
+}
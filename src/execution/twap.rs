@@ -0,0 +1,146 @@
+use super::{ChildOrders, ExecutionStats};
+use crate::api::OrderEntry;
+use crate::rest::{self, round_to_increment, OrderInfo, OrderRequest, OrderSide, Symbol};
+use crate::ws::Data;
+use rust_decimal::prelude::*;
+use std::time::Duration;
+use tokio::time::{self, Instant, Interval, MissedTickBehavior};
+
+/// Time-weighted average price execution: places `slices` child orders at
+/// regular intervals over `duration`, each catching up with the size that
+/// should have been filled by then. Child orders are market orders, or
+/// immediate-or-cancel limit orders with `TwapExecutor::limit_price`, so
+/// that unfilled size is carried over to the next slice.
+///
+/// Orders are placed with `place_slice` whenever `tick` completes, and
+/// order updates and fills should be passed to `update`.
+#[derive(Debug)]
+pub struct TwapExecutor {
+    market: Symbol,
+    side: OrderSide,
+    size: Decimal,
+    duration: Duration,
+    slices: u32,
+    min_slice_size: Decimal,
+    size_increment: Decimal,
+    limit_price: Option<Decimal>,
+    /// Created on the first tick, so that the schedule starts when polled
+    interval: Option<Interval>,
+    started: Option<Instant>,
+    slices_placed: u32,
+    children: ChildOrders,
+}
+
+impl TwapExecutor {
+    /// Executes `size` over `duration` in one slice per minute
+    pub fn new(market: &str, side: OrderSide, size: Decimal, duration: Duration) -> Self {
+        Self {
+            market: market.into(),
+            side,
+            size,
+            duration,
+            slices: (duration.as_secs() / 60).clamp(1, u32::MAX as u64) as u32,
+            min_slice_size: Decimal::ZERO,
+            size_increment: Decimal::ZERO,
+            limit_price: None,
+            interval: None,
+            started: None,
+            slices_placed: 0,
+            children: ChildOrders::default(),
+        }
+    }
+
+    /// Number of child orders, at least one
+    pub fn slices(mut self, slices: u32) -> Self {
+        self.slices = slices.max(1);
+        self
+    }
+
+    /// Smaller slices are skipped and their size added to the next one,
+    /// except for the last slice
+    pub fn min_slice_size(mut self, min_slice_size: Decimal) -> Self {
+        self.min_slice_size = min_slice_size;
+        self
+    }
+
+    /// Child order sizes are rounded down to the size increment of the
+    /// market, see `Market::round_size`
+    pub fn size_increment(mut self, size_increment: Decimal) -> Self {
+        self.size_increment = size_increment;
+        self
+    }
+
+    /// Place immediate-or-cancel limit orders at `price` instead of market
+    /// orders, so that the execution never pays more (buying) or receives
+    /// less (selling)
+    pub fn limit_price(mut self, price: Decimal) -> Self {
+        self.limit_price = Some(price);
+        self
+    }
+
+    /// Waits until the next slice is due, the first one right away. Cancel
+    /// safe, so it can be used in `tokio::select!` along with `Ws::next`.
+    pub async fn tick(&mut self) {
+        let period = (self.duration / self.slices).max(Duration::from_millis(1));
+        let start = *self.started.get_or_insert_with(Instant::now);
+        let interval = self.interval.get_or_insert_with(|| {
+            let mut interval = time::interval_at(start, period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+        interval.tick().await;
+    }
+
+    /// Places the child order of the next slice, sized to catch up with the
+    /// schedule. Returns `None` if the slice was skipped because it would be
+    /// smaller than the minimum slice size or the size increment.
+    pub async fn place_slice(&mut self, api: &impl OrderEntry) -> rest::Result<Option<OrderInfo>> {
+        if self.is_scheduled() {
+            return Ok(None);
+        }
+        self.started.get_or_insert_with(Instant::now);
+        self.slices_placed += 1;
+
+        let last = self.is_scheduled();
+        let target = self.size * Decimal::from(self.slices_placed) / Decimal::from(self.slices);
+        let size = round_to_increment(
+            target - self.children.committed_size(),
+            self.size_increment,
+            RoundingStrategy::ToZero,
+        );
+        let too_small = size < self.min_slice_size && !last;
+        if size <= Decimal::ZERO || too_small {
+            return Ok(None);
+        }
+
+        let order = match self.limit_price {
+            Some(price) => OrderRequest::limit(&self.market, self.side, price, size).ioc(true),
+            None => OrderRequest::market(&self.market, self.side, size),
+        };
+        let info = api.submit_order(&order).await?;
+        self.children.add(&info);
+
+        Ok(Some(info))
+    }
+
+    /// Applies order updates and fills of the child orders, ignoring other
+    /// data
+    pub fn update(&mut self, data: &Data) {
+        self.children.update(&self.market, data);
+    }
+
+    /// Whether all slices were placed
+    pub fn is_scheduled(&self) -> bool {
+        self.slices_placed >= self.slices
+    }
+
+    /// Whether all slices were placed and all child orders are closed
+    pub fn is_done(&self) -> bool {
+        self.is_scheduled() && self.children.open_size().is_zero()
+    }
+
+    pub fn stats(&self) -> ExecutionStats {
+        let elapsed = self.started.map(|started| started.elapsed());
+        self.children.stats(self.size, elapsed.unwrap_or_default())
+    }
+}
@@ -0,0 +1,442 @@
+use crate::rest::{self, Candle, Fill, Liquidity, Side};
+#[cfg(feature = "ws")]
+use crate::ws::{self, OrderbookAction, OrderbookData};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Column of a record type, see `CsvRecord`
+pub trait CsvColumn: Copy + 'static {
+    /// All columns in their default order
+    const ALL: &'static [Self];
+
+    /// Name of the column in the header row
+    fn name(self) -> &'static str;
+}
+
+/// Type written to CSV by a `CsvWriter`
+pub trait CsvRecord {
+    type Column: CsvColumn;
+
+    /// Number of rows the record is written as, e.g. one per level of an
+    /// orderbook update
+    fn rows(&self) -> usize {
+        1
+    }
+
+    /// Value of `column` in the row `row`. `market` is the market the record
+    /// was received for, used by records that don't include it.
+    fn field(&self, market: Option<&str>, row: usize, column: Self::Column) -> String;
+}
+
+/// Writes records as CSV rows, preceded by a header row with the names of
+/// the columns. Each writer has a configurable set of columns and is
+/// flushed at a fixed interval, so that a file being written can be read
+/// while a stream is running.
+///
+/// ```no_run
+/// # async fn example() -> ftx::ws::Result<()> {
+/// use ftx::export::{CsvWriter, TradeColumn};
+/// use ftx::ws::{Channel, Data, Ws};
+/// use std::time::Duration;
+///
+/// let mut csv = CsvWriter::create("trades.csv")?
+///     .columns(&[
+///         TradeColumn::Time,
+///         TradeColumn::Market,
+///         TradeColumn::Price,
+///         TradeColumn::Size,
+///     ])
+///     .flush_interval(Duration::from_secs(5));
+///
+/// let mut ws = Ws::connect_public().await?;
+/// ws.subscribe(vec![Channel::Trades("BTC-PERP".into())]).await?;
+/// while let Some((market, data)) = ws.next().await? {
+///     if let Data::Trade(trade) = data {
+///         csv.write(market.as_deref(), &trade)?;
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct CsvWriter<R: CsvRecord, W: Write> {
+    writer: W,
+    columns: Vec<R::Column>,
+    header_written: bool,
+    flush_interval: Duration,
+    last_flush: Instant,
+}
+
+impl<R: CsvRecord> CsvWriter<R, BufWriter<File>> {
+    /// Creates or truncates the file at `path`
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<R: CsvRecord, W: Write> CsvWriter<R, W> {
+    /// Default interval between flushes of the underlying writer
+    pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Writes all columns of the record type to `writer`
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            columns: R::Column::ALL.to_vec(),
+            header_written: false,
+            flush_interval: Self::FLUSH_INTERVAL,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Columns to write, in their order in the file
+    pub fn columns(mut self, columns: &[R::Column]) -> Self {
+        self.columns = columns.to_vec();
+        self
+    }
+
+    /// Flushes the underlying writer when a record is written at least
+    /// `interval` after the last flush. Zero flushes after every record.
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Writes the rows of `record`, see `CsvRecord::field` for `market`
+    pub fn write(&mut self, market: Option<&str>, record: &R) -> io::Result<()> {
+        if !self.header_written {
+            let names: Vec<_> = self.columns.iter().map(|column| column.name()).collect();
+            write_row(&mut self.writer, names)?;
+            self.header_written = true;
+        }
+
+        for row in 0..record.rows() {
+            let fields = self
+                .columns
+                .iter()
+                .map(|column| record.field(market, row, *column));
+            write_row(&mut self.writer, fields)?;
+        }
+
+        if self.last_flush.elapsed() >= self.flush_interval {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes every record of `records`
+    pub fn write_all<'a, I>(&mut self, market: Option<&str>, records: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = &'a R>,
+        R: 'a,
+    {
+        for record in records {
+            self.write(market, record)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        self.writer.flush()
+    }
+
+    /// Flushes and returns the underlying writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Writes a row, quoting fields that contain separators, quotes, or line
+/// breaks
+pub(super) fn write_row<W, I, S>(writer: &mut W, fields: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            writer.write_all(b",")?;
+        }
+        let field = field.as_ref();
+        if field.contains(&[',', '"', '\n', '\r'][..]) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            writer.write_all(field.as_bytes())?;
+        }
+    }
+    writer.write_all(b"\r\n")
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn format_side(side: Side) -> String {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    }
+    .into()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TradeColumn {
+    Time,
+    /// Market the trade was received for
+    Market,
+    Id,
+    Side,
+    Price,
+    Size,
+    Liquidation,
+}
+
+impl CsvColumn for TradeColumn {
+    const ALL: &'static [Self] = &[
+        TradeColumn::Time,
+        TradeColumn::Market,
+        TradeColumn::Id,
+        TradeColumn::Side,
+        TradeColumn::Price,
+        TradeColumn::Size,
+        TradeColumn::Liquidation,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            TradeColumn::Time => "time",
+            TradeColumn::Market => "market",
+            TradeColumn::Id => "id",
+            TradeColumn::Side => "side",
+            TradeColumn::Price => "price",
+            TradeColumn::Size => "size",
+            TradeColumn::Liquidation => "liquidation",
+        }
+    }
+}
+
+/// Trades of the REST and websocket APIs have the same fields
+macro_rules! impl_trade_record {
+    ($trade:ty) => {
+        impl CsvRecord for $trade {
+            type Column = TradeColumn;
+
+            fn field(&self, market: Option<&str>, _row: usize, column: TradeColumn) -> String {
+                match column {
+                    TradeColumn::Time => format_time(self.time),
+                    TradeColumn::Market => market.unwrap_or_default().into(),
+                    TradeColumn::Id => self.id.to_string(),
+                    TradeColumn::Side => format_side(self.side),
+                    TradeColumn::Price => self.price.to_string(),
+                    TradeColumn::Size => self.size.to_string(),
+                    TradeColumn::Liquidation => self.liquidation.to_string(),
+                }
+            }
+        }
+    };
+}
+
+impl_trade_record!(rest::Trade);
+#[cfg(feature = "ws")]
+impl_trade_record!(ws::Trade);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FillColumn {
+    Time,
+    Market,
+    Id,
+    OrderId,
+    TradeId,
+    Side,
+    Price,
+    Size,
+    Fee,
+    FeeCurrency,
+    FeeRate,
+    Liquidity,
+}
+
+impl CsvColumn for FillColumn {
+    const ALL: &'static [Self] = &[
+        FillColumn::Time,
+        FillColumn::Market,
+        FillColumn::Id,
+        FillColumn::OrderId,
+        FillColumn::TradeId,
+        FillColumn::Side,
+        FillColumn::Price,
+        FillColumn::Size,
+        FillColumn::Fee,
+        FillColumn::FeeCurrency,
+        FillColumn::FeeRate,
+        FillColumn::Liquidity,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            FillColumn::Time => "time",
+            FillColumn::Market => "market",
+            FillColumn::Id => "id",
+            FillColumn::OrderId => "order_id",
+            FillColumn::TradeId => "trade_id",
+            FillColumn::Side => "side",
+            FillColumn::Price => "price",
+            FillColumn::Size => "size",
+            FillColumn::Fee => "fee",
+            FillColumn::FeeCurrency => "fee_currency",
+            FillColumn::FeeRate => "fee_rate",
+            FillColumn::Liquidity => "liquidity",
+        }
+    }
+}
+
+impl CsvRecord for Fill {
+    type Column = FillColumn;
+
+    /// Fills include their market, so `market` is ignored
+    fn field(&self, _market: Option<&str>, _row: usize, column: FillColumn) -> String {
+        match column {
+            FillColumn::Time => format_time(self.time),
+            FillColumn::Market => self.market.to_string(),
+            FillColumn::Id => self.id.to_string(),
+            FillColumn::OrderId => self.order_id.to_string(),
+            FillColumn::TradeId => self.trade_id.to_string(),
+            FillColumn::Side => format_side(self.side),
+            FillColumn::Price => self.price.to_string(),
+            FillColumn::Size => self.size.to_string(),
+            FillColumn::Fee => self.fee.to_string(),
+            FillColumn::FeeCurrency => self.fee_currency.clone(),
+            FillColumn::FeeRate => self.fee_rate.to_string(),
+            FillColumn::Liquidity => match self.liquidity {
+                Liquidity::Maker => "maker".into(),
+                Liquidity::Taker => "taker".into(),
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CandleColumn {
+    StartTime,
+    /// Market the candles were requested or built for
+    Market,
+    Open,
+    High,
+    Low,
+    Close,
+    Volume,
+}
+
+impl CsvColumn for CandleColumn {
+    const ALL: &'static [Self] = &[
+        CandleColumn::StartTime,
+        CandleColumn::Market,
+        CandleColumn::Open,
+        CandleColumn::High,
+        CandleColumn::Low,
+        CandleColumn::Close,
+        CandleColumn::Volume,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            CandleColumn::StartTime => "start_time",
+            CandleColumn::Market => "market",
+            CandleColumn::Open => "open",
+            CandleColumn::High => "high",
+            CandleColumn::Low => "low",
+            CandleColumn::Close => "close",
+            CandleColumn::Volume => "volume",
+        }
+    }
+}
+
+impl CsvRecord for Candle {
+    type Column = CandleColumn;
+
+    fn field(&self, market: Option<&str>, _row: usize, column: CandleColumn) -> String {
+        match column {
+            CandleColumn::StartTime => format_time(self.start_time),
+            CandleColumn::Market => market.unwrap_or_default().into(),
+            CandleColumn::Open => self.open.to_string(),
+            CandleColumn::High => self.high.to_string(),
+            CandleColumn::Low => self.low.to_string(),
+            CandleColumn::Close => self.close.to_string(),
+            CandleColumn::Volume => self.volume.to_string(),
+        }
+    }
+}
+
+/// Columns of orderbook data, which is written as one row per level with
+/// the bids first
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OrderbookColumn {
+    Time,
+    /// Market the data was received for
+    Market,
+    /// `partial` for snapshots and `update` for updates
+    Action,
+    /// `buy` for bids and `sell` for asks
+    Side,
+    Price,
+    /// Size of the level, zero for removed levels
+    Size,
+    Checksum,
+}
+
+impl CsvColumn for OrderbookColumn {
+    const ALL: &'static [Self] = &[
+        OrderbookColumn::Time,
+        OrderbookColumn::Market,
+        OrderbookColumn::Action,
+        OrderbookColumn::Side,
+        OrderbookColumn::Price,
+        OrderbookColumn::Size,
+        OrderbookColumn::Checksum,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            OrderbookColumn::Time => "time",
+            OrderbookColumn::Market => "market",
+            OrderbookColumn::Action => "action",
+            OrderbookColumn::Side => "side",
+            OrderbookColumn::Price => "price",
+            OrderbookColumn::Size => "size",
+            OrderbookColumn::Checksum => "checksum",
+        }
+    }
+}
+
+#[cfg(feature = "ws")]
+impl CsvRecord for OrderbookData {
+    type Column = OrderbookColumn;
+
+    fn rows(&self) -> usize {
+        self.bids.len() + self.asks.len()
+    }
+
+    fn field(&self, market: Option<&str>, row: usize, column: OrderbookColumn) -> String {
+        let (side, (price, size)) = match self.bids.get(row) {
+            Some(level) => (Side::Buy, level),
+            None => (Side::Sell, &self.asks[row - self.bids.len()]),
+        };
+        match column {
+            OrderbookColumn::Time => format_time(self.time),
+            OrderbookColumn::Market => market.unwrap_or_default().into(),
+            OrderbookColumn::Action => match self.action {
+                OrderbookAction::Partial => "partial".into(),
+                OrderbookAction::Update => "update".into(),
+            },
+            OrderbookColumn::Side => format_side(side),
+            OrderbookColumn::Price => price.to_string(),
+            OrderbookColumn::Size => size.to_string(),
+            OrderbookColumn::Checksum => self.checksum.to_string(),
+        }
+    }
+}
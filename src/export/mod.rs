@@ -0,0 +1,15 @@
+//! Writes trades, fills, candles, and orderbook data to files: to CSV with
+//! a `CsvWriter`, enabled by the `csv` feature, e.g. to load recorded market
+//! data into pandas, and to Parquet with a `parquet::ParquetWriter`, enabled
+//! by the `arrow` feature, for captures over months that are queried with
+//! tools like DuckDB or Spark.
+
+#[cfg(feature = "csv")]
+mod csv;
+#[cfg(feature = "arrow")]
+pub mod parquet;
+#[cfg(test)]
+mod tests;
+
+#[cfg(feature = "csv")]
+pub use csv::*;
@@ -0,0 +1,380 @@
+//! Writes trades and orderbook data to Parquet files partitioned by market
+//! and date, e.g. `trades/market=BTC-PERP/date=2021-05-23/part-1621747464315.parquet`.
+//! Query engines like DuckDB, Spark, or pyarrow read such a directory as
+//! one dataset and skip the partitions a query doesn't need.
+//!
+//! ```no_run
+//! # async fn example() -> ftx::ws::Result<()> {
+//! use ftx::export::parquet::ParquetWriter;
+//! use ftx::ws::{Channel, Data, Ws};
+//!
+//! let mut trades = ParquetWriter::create("data/trades").unwrap().batch_size(50_000);
+//! let mut orderbooks = ParquetWriter::create("data/orderbook").unwrap();
+//!
+//! let mut ws = Ws::connect_public().await?;
+//! ws.subscribe(vec![
+//!     Channel::Trades("BTC-PERP".into()),
+//!     Channel::Orderbook("BTC-PERP".into()),
+//! ])
+//! .await?;
+//! while let Some((market, data)) = ws.next().await? {
+//!     let market = market.as_deref().unwrap_or_default();
+//!     match data {
+//!         Data::Trade(trade) => trades.write(market, &trade).unwrap(),
+//!         Data::OrderbookData(data) => orderbooks.write(market, &data).unwrap(),
+//!         _ => {}
+//!     }
+//! }
+//! trades.close().unwrap();
+//! orderbooks.close().unwrap();
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::rest::{self, Side};
+#[cfg(feature = "ws")]
+use crate::ws::{self, OrderbookAction, OrderbookData};
+#[cfg(feature = "ws")]
+use arrow_array::UInt32Array;
+use arrow_array::{
+    ArrayRef, BooleanArray, Float64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+    UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, NaiveDate, Utc};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::ZstdLevel;
+use parquet::file::properties::WriterProperties;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub use parquet::basic::Compression;
+pub use parquet::errors::ParquetError;
+
+pub type Result<T> = std::result::Result<T, ParquetError>;
+
+/// Type written to Parquet by a `ParquetWriter`
+pub trait ArrowRecord: Clone {
+    /// Schema of the record batches, the same for all markets
+    fn schema() -> SchemaRef;
+
+    /// Time the record is partitioned by
+    fn time(&self) -> DateTime<Utc>;
+
+    /// Converts records received for `market` into a record batch
+    fn record_batch(market: &str, records: &[Self])
+        -> std::result::Result<RecordBatch, ArrowError>;
+}
+
+/// Writes records to Parquet files partitioned by market and date, see the
+/// module documentation.
+///
+/// Records are buffered per market until `batch_size` of them are pending,
+/// and written to the file of their market and date, which is created when
+/// the first batch is written. A file is complete once the records of a
+/// later date arrive for its market or the writer is closed, so `close`
+/// must be called before the writer is dropped. Every writer creates new
+/// files, named after the time they were created, so restarting a capture
+/// doesn't overwrite earlier files.
+pub struct ParquetWriter<R: ArrowRecord> {
+    dir: PathBuf,
+    batch_size: usize,
+    compression: Compression,
+    partitions: HashMap<String, Partition<R>>,
+}
+
+/// Pending records and the open file of a market
+struct Partition<R> {
+    date: NaiveDate,
+    records: Vec<R>,
+    writer: Option<ArrowWriter<File>>,
+}
+
+impl<R: ArrowRecord> ParquetWriter<R> {
+    /// Default number of records buffered per market before they are written
+    pub const BATCH_SIZE: usize = 10_000;
+
+    /// Writes the partitions into `dir`, which is created if it doesn't
+    /// exist
+    pub fn create<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir: dir.as_ref().into(),
+            batch_size: Self::BATCH_SIZE,
+            compression: Compression::ZSTD(ZstdLevel::default()),
+            partitions: HashMap::new(),
+        })
+    }
+
+    /// Number of records buffered per market before they are written. Larger
+    /// batches compress better but keep more records in memory.
+    pub fn batch_size(mut self, records: usize) -> Self {
+        self.batch_size = records.max(1);
+        self
+    }
+
+    /// Compression of the files created from now on, zstd by default
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Directory of the partition of `market` and `date`. Slashes of spot
+    /// markets are replaced with underscores, e.g. `market=BTC_USD`.
+    pub fn partition_dir(&self, market: &str, date: NaiveDate) -> PathBuf {
+        partition_dir(&self.dir, market, date)
+    }
+
+    /// Buffers `record` of `market`, and writes the pending records of the
+    /// market if the batch is full. A record of a later date completes the
+    /// file of the previous date, records of an earlier date are written to
+    /// the current file.
+    pub fn write(&mut self, market: &str, record: &R) -> Result<()> {
+        let date = record.time().date_naive();
+        let partition = self
+            .partitions
+            .entry(market.into())
+            .or_insert_with(|| Partition {
+                date,
+                records: Vec::new(),
+                writer: None,
+            });
+
+        if date > partition.date {
+            partition.write_batch(&self.dir, market, self.compression)?;
+            if let Some(writer) = partition.writer.take() {
+                writer.close()?;
+            }
+            partition.date = date;
+        }
+
+        partition.records.push(record.clone());
+        if partition.records.len() >= self.batch_size {
+            partition.write_batch(&self.dir, market, self.compression)?;
+        }
+        Ok(())
+    }
+
+    /// Writes every record of `records`
+    pub fn write_all<'a, I>(&mut self, market: &str, records: I) -> Result<()>
+    where
+        R: 'a,
+        I: IntoIterator<Item = &'a R>,
+    {
+        for record in records {
+            self.write(market, record)?;
+        }
+        Ok(())
+    }
+
+    /// Number of records that weren't written yet
+    pub fn pending(&self) -> usize {
+        self.partitions
+            .values()
+            .map(|partition| partition.records.len())
+            .sum()
+    }
+
+    /// Writes all pending records. The files stay open, and can only be read
+    /// once they are complete.
+    pub fn flush(&mut self) -> Result<()> {
+        for (market, partition) in &mut self.partitions {
+            partition.write_batch(&self.dir, market, self.compression)?;
+        }
+        Ok(())
+    }
+
+    /// Writes all pending records and completes the files
+    pub fn close(mut self) -> Result<()> {
+        self.flush()?;
+        for (_, partition) in self.partitions.drain() {
+            if let Some(writer) = partition.writer {
+                writer.close()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R: ArrowRecord> Partition<R> {
+    /// Writes the pending records, creating the file of the partition in
+    /// `dir` if needed
+    fn write_batch(&mut self, dir: &Path, market: &str, compression: Compression) -> Result<()> {
+        if self.records.is_empty() {
+            return Ok(());
+        }
+
+        let batch = R::record_batch(market, &self.records)?;
+        let writer = match &mut self.writer {
+            Some(writer) => writer,
+            None => {
+                let dir = partition_dir(dir, market, self.date);
+                fs::create_dir_all(&dir)?;
+                let path = dir.join(format!("part-{}.parquet", Utc::now().timestamp_millis()));
+                let properties = WriterProperties::builder()
+                    .set_compression(compression)
+                    .build();
+                let writer =
+                    ArrowWriter::try_new(File::create(path)?, R::schema(), Some(properties))?;
+                self.writer.insert(writer)
+            }
+        };
+        writer.write(&batch)?;
+        self.records.clear();
+        Ok(())
+    }
+}
+
+fn partition_dir(dir: &Path, market: &str, date: NaiveDate) -> PathBuf {
+    dir.join(format!("market={}", market.replace('/', "_")))
+        .join(format!("date={}", date.format("%Y-%m-%d")))
+}
+
+fn time_field(name: &str) -> Field {
+    Field::new(
+        name,
+        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        false,
+    )
+}
+
+fn time_array<I: IntoIterator<Item = DateTime<Utc>>>(times: I) -> ArrayRef {
+    let micros: Vec<_> = times
+        .into_iter()
+        .map(|time| time.timestamp_micros())
+        .collect();
+    Arc::new(TimestampMicrosecondArray::from(micros).with_timezone("UTC"))
+}
+
+/// Prices and sizes are stored as doubles, which query engines aggregate
+/// much faster than decimals
+fn decimal_array<I: IntoIterator<Item = Decimal>>(values: I) -> ArrayRef {
+    let values: Vec<_> = values
+        .into_iter()
+        .map(|value| value.to_f64().unwrap_or(f64::NAN))
+        .collect();
+    Arc::new(Float64Array::from(values))
+}
+
+fn side_array<I: IntoIterator<Item = Side>>(sides: I) -> ArrayRef {
+    let sides: Vec<_> = sides
+        .into_iter()
+        .map(|side| match side {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        })
+        .collect();
+    Arc::new(StringArray::from(sides))
+}
+
+fn market_array(market: &str, rows: usize) -> ArrayRef {
+    Arc::new(StringArray::from(vec![market; rows]))
+}
+
+/// Trades of the REST and websocket APIs have the same fields
+macro_rules! impl_trade_record {
+    ($trade:ty) => {
+        impl ArrowRecord for $trade {
+            fn schema() -> SchemaRef {
+                Arc::new(Schema::new(vec![
+                    time_field("time"),
+                    Field::new("market", DataType::Utf8, false),
+                    Field::new("id", DataType::UInt64, false),
+                    Field::new("side", DataType::Utf8, false),
+                    Field::new("price", DataType::Float64, false),
+                    Field::new("size", DataType::Float64, false),
+                    Field::new("liquidation", DataType::Boolean, false),
+                ]))
+            }
+
+            fn time(&self) -> DateTime<Utc> {
+                self.time
+            }
+
+            fn record_batch(
+                market: &str,
+                records: &[Self],
+            ) -> std::result::Result<RecordBatch, ArrowError> {
+                let ids: Vec<_> = records.iter().map(|trade| trade.id).collect();
+                let liquidations: Vec<_> = records.iter().map(|trade| trade.liquidation).collect();
+                RecordBatch::try_new(
+                    Self::schema(),
+                    vec![
+                        time_array(records.iter().map(|trade| trade.time)),
+                        market_array(market, records.len()),
+                        Arc::new(UInt64Array::from(ids)),
+                        side_array(records.iter().map(|trade| trade.side)),
+                        decimal_array(records.iter().map(|trade| trade.price)),
+                        decimal_array(records.iter().map(|trade| trade.size)),
+                        Arc::new(BooleanArray::from(liquidations)),
+                    ],
+                )
+            }
+        }
+    };
+}
+
+impl_trade_record!(rest::Trade);
+#[cfg(feature = "ws")]
+impl_trade_record!(ws::Trade);
+
+/// Snapshots and updates are written as one row per level with the bids
+/// first, and an `action` of `partial` or `update`
+#[cfg(feature = "ws")]
+impl ArrowRecord for OrderbookData {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            time_field("time"),
+            Field::new("market", DataType::Utf8, false),
+            Field::new("action", DataType::Utf8, false),
+            Field::new("side", DataType::Utf8, false),
+            Field::new("price", DataType::Float64, false),
+            Field::new("size", DataType::Float64, false),
+            Field::new("checksum", DataType::UInt32, false),
+        ]))
+    }
+
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn record_batch(
+        market: &str,
+        records: &[Self],
+    ) -> std::result::Result<RecordBatch, ArrowError> {
+        let levels: Vec<_> = records
+            .iter()
+            .flat_map(|data| {
+                let bids = data.bids.iter().map(move |level| (data, Side::Buy, level));
+                let asks = data.asks.iter().map(move |level| (data, Side::Sell, level));
+                bids.chain(asks)
+            })
+            .collect();
+        let actions: Vec<_> = levels
+            .iter()
+            .map(|(data, _, _)| match data.action {
+                OrderbookAction::Partial => "partial",
+                OrderbookAction::Update => "update",
+            })
+            .collect();
+        let checksums: Vec<_> = levels.iter().map(|(data, _, _)| data.checksum).collect();
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                time_array(levels.iter().map(|(data, _, _)| data.time)),
+                market_array(market, levels.len()),
+                Arc::new(StringArray::from(actions)),
+                side_array(levels.iter().map(|(_, side, _)| *side)),
+                decimal_array(levels.iter().map(|(_, _, (price, _))| *price)),
+                decimal_array(levels.iter().map(|(_, _, (_, size))| *size)),
+                Arc::new(UInt32Array::from(checksums)),
+            ],
+        )
+    }
+}
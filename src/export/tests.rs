@@ -0,0 +1,206 @@
+#[cfg(feature = "arrow")]
+use super::parquet::*;
+#[cfg(feature = "csv")]
+use super::*;
+use crate::rest;
+#[cfg(feature = "csv")]
+use crate::rest::{Candle, Fill};
+#[cfg(feature = "ws")]
+use crate::ws::OrderbookData;
+#[cfg(feature = "arrow")]
+use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+#[cfg(all(feature = "arrow", feature = "ws"))]
+use arrow_array::UInt32Array;
+#[cfg(feature = "arrow")]
+use arrow_array::{Array, Float64Array, RecordBatch, StringArray, UInt64Array};
+#[cfg(feature = "arrow")]
+use chrono::NaiveDate;
+use serde_json::json;
+#[cfg(feature = "arrow")]
+use std::path::Path;
+#[cfg(feature = "csv")]
+use std::time::Duration;
+
+#[cfg(feature = "csv")]
+fn written<R: CsvRecord>(csv: CsvWriter<R, Vec<u8>>) -> String {
+    String::from_utf8(csv.into_inner().unwrap()).unwrap()
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn trades_and_candles() {
+    let trade: rest::Trade = serde_json::from_value(json!({"id": 1, "price": 40000.5, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"})).unwrap();
+    let mut csv = CsvWriter::new(Vec::new());
+    csv.write_all(Some("BTC-PERP"), &[trade, trade]).unwrap();
+    assert_eq!(
+        written(csv),
+        "time,market,id,side,price,size,liquidation\r\n\
+         2021-05-23T05:24:24.315884Z,BTC-PERP,1,buy,40000.5,0.1,false\r\n\
+         2021-05-23T05:24:24.315884Z,BTC-PERP,1,buy,40000.5,0.1,false\r\n"
+    );
+
+    // Columns are written in the configured order
+    let candle: Candle = serde_json::from_value(json!({"close": 40100.0, "high": 40200.0, "low": 39900.0, "open": 40000.0, "volume": 1000000.0, "startTime": "2021-05-23T05:00:00+00:00"})).unwrap();
+    let mut csv = CsvWriter::new(Vec::new()).columns(&[
+        CandleColumn::Close,
+        CandleColumn::StartTime,
+        CandleColumn::Market,
+    ]);
+    csv.write(None, &candle).unwrap();
+    assert_eq!(
+        written(csv),
+        "close,start_time,market\r\n40100,2021-05-23T05:00:00Z,\r\n"
+    );
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn fills() {
+    let fill: Fill = serde_json::from_value(json!({"id": 1, "market": "BTC-PERP", "future": "BTC-PERP", "baseCurrency": null, "quoteCurrency": null, "type": "order", "side": "sell", "price": 40000.0, "size": 0.1, "orderId": 2, "tradeId": 3, "time": "2021-05-23T05:24:24.315884+00:00", "fee": 0.8, "feeRate": 0.0002, "feeCurrency": "USD", "liquidity": "maker"})).unwrap();
+    let mut csv = CsvWriter::new(Vec::new()).columns(&[
+        FillColumn::Market,
+        FillColumn::OrderId,
+        FillColumn::Side,
+        FillColumn::Fee,
+        FillColumn::FeeCurrency,
+        FillColumn::Liquidity,
+    ]);
+    csv.write(Some("ETH-PERP"), &fill).unwrap();
+    assert_eq!(
+        written(csv),
+        "market,order_id,side,fee,fee_currency,liquidity\r\nBTC-PERP,2,sell,0.8,USD,maker\r\n"
+    );
+}
+
+#[cfg(all(feature = "csv", feature = "ws"))]
+#[test]
+fn orderbook_data() {
+    let data: OrderbookData = serde_json::from_value(json!({"action": "update", "bids": [[40000.0, 1.5], [39999.0, 0.0]], "asks": [[40001.0, 2.0]], "checksum": 1234, "time": 1621747464.5})).unwrap();
+    let mut csv = CsvWriter::new(Vec::new()).columns(&[
+        OrderbookColumn::Time,
+        OrderbookColumn::Action,
+        OrderbookColumn::Side,
+        OrderbookColumn::Price,
+        OrderbookColumn::Size,
+    ]);
+    csv.write(Some("BTC-PERP"), &data).unwrap();
+    assert_eq!(
+        written(csv),
+        "time,action,side,price,size\r\n\
+         2021-05-23T05:24:24.500Z,update,buy,40000,1.5\r\n\
+         2021-05-23T05:24:24.500Z,update,buy,39999,0\r\n\
+         2021-05-23T05:24:24.500Z,update,sell,40001,2\r\n"
+    );
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn quoting_and_flushing() {
+    let mut row = Vec::new();
+    write_row(&mut row, ["plain", "a,b", "say \"hi\""]).unwrap();
+    assert_eq!(row, b"plain,\"a,b\",\"say \"\"hi\"\"\"\r\n");
+
+    // Rows are buffered until the flush interval elapsed
+    let trade: rest::Trade = serde_json::from_value(json!({"id": 1, "price": 1.0, "size": 1.0, "side": "sell", "liquidation": true, "time": "2021-05-23T05:24:24+00:00"})).unwrap();
+    let file = std::env::temp_dir().join(format!("ftx-export-{}.csv", std::process::id()));
+    let mut csv = CsvWriter::create(&file)
+        .unwrap()
+        .columns(&[TradeColumn::Liquidation])
+        .flush_interval(Duration::from_secs(3600));
+    csv.write(None, &trade).unwrap();
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), "");
+    csv.flush().unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "liquidation\r\ntrue\r\n"
+    );
+
+    let mut csv = csv.flush_interval(Duration::ZERO);
+    csv.write(None, &trade).unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&file).unwrap(),
+        "liquidation\r\ntrue\r\ntrue\r\n"
+    );
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[cfg(feature = "arrow")]
+/// Reads the single file of the partition in `dir`
+fn read_partition(dir: &Path) -> RecordBatch {
+    let files: Vec<_> = std::fs::read_dir(dir).unwrap().collect();
+    assert_eq!(files.len(), 1);
+    let file = std::fs::File::open(files[0].as_ref().unwrap().path()).unwrap();
+    let mut reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap();
+    reader.next().unwrap().unwrap()
+}
+
+#[cfg(feature = "arrow")]
+fn column<A: Array + Clone + 'static>(batch: &RecordBatch, name: &str) -> A {
+    let column = batch.column_by_name(name).unwrap();
+    column.as_any().downcast_ref::<A>().unwrap().clone()
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn parquet_trades() {
+    let dir = std::env::temp_dir().join(format!("ftx-parquet-{}", std::process::id()));
+    let trade: rest::Trade = serde_json::from_value(json!({"id": 1, "price": 40000.5, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T23:59:59.5+00:00"})).unwrap();
+    let next_day = rest::Trade {
+        id: 2,
+        side: rest::Side::Sell,
+        time: trade.time + chrono::Duration::seconds(1),
+        ..trade
+    };
+
+    let mut parquet = ParquetWriter::create(&dir).unwrap().batch_size(2);
+    parquet.write_all("BTC-PERP", &[trade, trade]).unwrap();
+    assert_eq!(parquet.pending(), 0);
+    parquet.write("BTC-PERP", &next_day).unwrap();
+    parquet.write("BTC/USD", &trade).unwrap();
+    assert_eq!(parquet.pending(), 2);
+    let partition = parquet.partition_dir("BTC/USD", NaiveDate::from_ymd_opt(2021, 5, 23).unwrap());
+    assert_eq!(
+        partition,
+        dir.join("market=BTC_USD").join("date=2021-05-23")
+    );
+    parquet.close().unwrap();
+
+    let batch = read_partition(&dir.join("market=BTC-PERP/date=2021-05-23"));
+    assert_eq!(batch.num_rows(), 2);
+    assert_eq!(column::<StringArray>(&batch, "market").value(0), "BTC-PERP");
+    assert_eq!(column::<Float64Array>(&batch, "price").value(1), 40000.5);
+
+    let batch = read_partition(&dir.join("market=BTC-PERP/date=2021-05-24"));
+    assert_eq!(batch.num_rows(), 1);
+    assert_eq!(column::<UInt64Array>(&batch, "id").value(0), 2);
+    assert_eq!(column::<StringArray>(&batch, "side").value(0), "sell");
+
+    let batch = read_partition(&partition);
+    assert_eq!(column::<StringArray>(&batch, "market").value(0), "BTC/USD");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(all(feature = "arrow", feature = "ws"))]
+#[test]
+fn parquet_orderbook_data() {
+    let dir = std::env::temp_dir().join(format!("ftx-parquet-orderbook-{}", std::process::id()));
+    let data: OrderbookData = serde_json::from_value(json!({"action": "partial", "bids": [[40000.0, 1.5], [39999.0, 0.5]], "asks": [[40001.0, 2.0]], "checksum": 1234, "time": 1621747464.5})).unwrap();
+
+    let mut parquet = ParquetWriter::create(&dir)
+        .unwrap()
+        .compression(Compression::SNAPPY);
+    parquet.write("BTC-PERP", &data).unwrap();
+    parquet.close().unwrap();
+
+    let batch = read_partition(&dir.join("market=BTC-PERP/date=2021-05-23"));
+    assert_eq!(batch.num_rows(), 3);
+    let sides = column::<StringArray>(&batch, "side");
+    assert_eq!((sides.value(1), sides.value(2)), ("buy", "sell"));
+    assert_eq!(column::<Float64Array>(&batch, "size").value(2), 2.0);
+    assert_eq!(column::<StringArray>(&batch, "action").value(0), "partial");
+    assert_eq!(column::<UInt32Array>(&batch, "checksum").value(0), 1234);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
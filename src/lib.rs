@@ -1,3 +1,23 @@
+#[macro_use]
+mod trace;
+
+pub mod analytics;
+pub mod api;
+#[cfg(feature = "ws")]
+pub mod client;
+#[cfg(feature = "ws")]
+pub mod execution;
+#[cfg(any(feature = "csv", feature = "arrow"))]
+pub mod export;
+pub mod options;
+#[cfg(feature = "ws")]
+pub mod paper;
+pub mod portfolio;
 pub mod rest;
+pub mod signer;
+#[cfg(any(feature = "postgres", feature = "redis"))]
+pub mod sinks;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 #[cfg(feature = "ws")]
 pub mod ws;
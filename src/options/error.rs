@@ -0,0 +1,26 @@
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the configuration file failed
+    Io(std::io::Error),
+    #[cfg(feature = "toml")]
+    Toml(toml_edit::TomlError),
+    /// The endpoint is neither `com` nor `us`
+    InvalidEndpoint(String),
+    /// The value of the named key isn't a string
+    InvalidValue(String),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl From<toml_edit::TomlError> for Error {
+    fn from(err: toml_edit::TomlError) -> Error {
+        Error::Toml(err)
+    }
+}
@@ -0,0 +1,179 @@
+//! Credentials and endpoint selection shared by the REST and websocket
+//! clients, loaded from the environment or a TOML file.
+//!
+//! ```no_run
+//! # async fn example() -> ftx::options::Result<()> {
+//! use ftx::options::Options;
+//! use ftx::rest::Rest;
+//! use ftx::ws::Ws;
+//!
+//! let options = Options::from_env()?;
+//! let api = Rest::from_options(&options);
+//! let ws = Ws::builder()
+//!     .options(&options)
+//!     .connect()
+//!     .await
+//!     .expect("Connection failed");
+//! # Ok(())
+//! # }
+//! ```
+
+mod error;
+#[cfg(test)]
+mod tests;
+
+pub use error::*;
+
+use crate::rest::Rest;
+use std::fmt;
+use std::str::FromStr;
+use zeroize::Zeroize;
+
+/// FTX exchange to connect to. Selects the endpoints of `Rest` and `Ws`,
+/// which reject endpoints and channels the exchange doesn't offer with an
+/// `UnsupportedOnExchange` error.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Exchange {
+    /// ftx.com
+    #[default]
+    Com,
+    /// ftx.us, without futures and options
+    Us,
+}
+
+#[deprecated(note = "renamed to `Exchange`")]
+pub type Endpoint = Exchange;
+
+impl Exchange {
+    pub fn rest(&self) -> &'static str {
+        match self {
+            Exchange::Com => Rest::ENDPOINT,
+            Exchange::Us => Rest::ENDPOINT_US,
+        }
+    }
+
+    #[cfg(feature = "ws")]
+    pub fn ws(&self) -> &'static str {
+        match self {
+            Exchange::Com => crate::ws::Ws::ENDPOINT,
+            Exchange::Us => crate::ws::Ws::ENDPOINT_US,
+        }
+    }
+
+    /// Prefix of the authentication headers of REST requests
+    pub fn header_prefix(&self) -> &'static str {
+        match self {
+            Exchange::Com => "FTX",
+            Exchange::Us => "FTXUS",
+        }
+    }
+
+    /// Whether perpetuals, dated futures, and MOVE contracts are listed
+    pub fn has_futures(&self) -> bool {
+        *self == Exchange::Com
+    }
+
+    /// Whether options can be traded
+    pub fn has_options(&self) -> bool {
+        *self == Exchange::Com
+    }
+}
+
+impl FromStr for Exchange {
+    type Err = Error;
+
+    /// Parses `com` or `ftx.com`, and `us` or `ftx.us`, ignoring case
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().trim_start_matches("ftx.") {
+            "com" => Ok(Exchange::Com),
+            "us" => Ok(Exchange::Us),
+            _ => Err(Error::InvalidEndpoint(s.to_string())),
+        }
+    }
+}
+
+/// Credentials and exchange used to construct `Rest`, `Ws`, and `Client`.
+/// Without a key and secret, only public endpoints and channels can be used.
+/// The secret is zeroized when dropped.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Options {
+    pub key: Option<String>,
+    pub secret: Option<String>,
+    pub subaccount: Option<String>,
+    pub exchange: Exchange,
+}
+
+impl Options {
+    /// Reads `FTX_API_KEY`, `FTX_SECRET`, `FTX_SUBACCOUNT`, and
+    /// `FTX_ENDPOINT` (`com` or `us`), also loading them from a `.env` file
+    /// if present
+    pub fn from_env() -> Result<Self> {
+        dotenv::dotenv().ok();
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        Ok(Self {
+            key: var("FTX_API_KEY"),
+            secret: var("FTX_SECRET"),
+            subaccount: var("FTX_SUBACCOUNT"),
+            exchange: var("FTX_ENDPOINT")
+                .map(|endpoint| endpoint.parse())
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Reads a TOML file with optional `key`, `secret`, `subaccount`, and
+    /// `endpoint` string values, e.g.
+    ///
+    /// ```toml
+    /// key = "..."
+    /// secret = "..."
+    /// subaccount = "bot"
+    /// endpoint = "us"
+    /// ```
+    #[cfg(feature = "toml")]
+    pub fn from_toml<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        Self::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses the contents of a TOML file, see `Options::from_toml`
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(toml: &str) -> Result<Self> {
+        let document: toml_edit::DocumentMut = toml.parse()?;
+        let value = |name: &str| match document.get(name) {
+            Some(item) => item
+                .as_str()
+                .map(|value| Some(value.to_string()))
+                .ok_or_else(|| Error::InvalidValue(name.to_string())),
+            None => Ok(None),
+        };
+
+        Ok(Self {
+            key: value("key")?,
+            secret: value("secret")?,
+            subaccount: value("subaccount")?,
+            exchange: value("endpoint")?
+                .map(|endpoint| endpoint.parse())
+                .transpose()?
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl Drop for Options {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+// Keeps the secret out of logs
+impl fmt::Debug for Options {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Options")
+            .field("key", &self.key)
+            .field("secret", &self.secret.as_ref().map(|_| "<redacted>"))
+            .field("subaccount", &self.subaccount)
+            .field("exchange", &self.exchange)
+            .finish()
+    }
+}
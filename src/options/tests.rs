@@ -0,0 +1,59 @@
+use super::*;
+
+#[test]
+fn exchange() {
+    assert_eq!("us".parse::<Exchange>().unwrap(), Exchange::Us);
+    assert_eq!("FTX.com".parse::<Exchange>().unwrap(), Exchange::Com);
+    assert!("binance.com".parse::<Exchange>().is_err());
+    assert_eq!(Exchange::Us.rest(), Rest::ENDPOINT_US);
+    assert_eq!(Exchange::Us.header_prefix(), "FTXUS");
+    assert!(Exchange::Com.has_futures());
+    assert!(!Exchange::Us.has_options());
+}
+
+#[test]
+fn from_env() {
+    std::env::set_var("FTX_API_KEY", "key");
+    std::env::set_var("FTX_SECRET", "secret");
+    std::env::set_var("FTX_SUBACCOUNT", "");
+    std::env::set_var("FTX_ENDPOINT", "us");
+
+    let options = Options::from_env().unwrap();
+    assert_eq!(options.key.as_deref(), Some("key"));
+    assert_eq!(options.secret.as_deref(), Some("secret"));
+    assert_eq!(options.subaccount, None);
+    assert_eq!(options.exchange, Exchange::Us);
+    assert!(!format!("{:?}", options).contains("secret\""));
+
+    let api = Rest::from_options(&options);
+    assert_eq!(api.endpoint(), Rest::ENDPOINT_US);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn from_toml() {
+    let options = Options::from_toml_str(
+        r#"
+        key = "key"
+        secret = "secret"
+        subaccount = "bot"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(options.subaccount.as_deref(), Some("bot"));
+    assert_eq!(options.exchange, Exchange::Com);
+
+    assert!(matches!(
+        Options::from_toml_str("key = 1"),
+        Err(Error::InvalidValue(_))
+    ));
+    assert!(matches!(
+        Options::from_toml_str(r#"endpoint = "eu""#),
+        Err(Error::InvalidEndpoint(_))
+    ));
+
+    let path = std::env::temp_dir().join("ftx-options-test.toml");
+    std::fs::write(&path, r#"endpoint = "ftx.us""#).unwrap();
+    assert_eq!(Options::from_toml(&path).unwrap().exchange, Exchange::Us);
+    std::fs::remove_file(path).unwrap();
+}
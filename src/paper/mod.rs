@@ -0,0 +1,475 @@
+//! Paper trading, i.e. simulated order entry against live market data, to
+//! test strategies without placing real orders.
+//!
+//! ```no_run
+//! # async fn example() -> ftx::ws::Result<()> {
+//! use ftx::paper::PaperRest;
+//! use ftx::api::OrderEntry;
+//! use ftx::rest::{OrderRequest, OrderSide};
+//! use ftx::ws::{Channel, Ws};
+//! use rust_decimal_macros::dec;
+//!
+//! let paper = PaperRest::new().balance("USD", dec!(10000));
+//! let mut ws = Ws::connect_public().await?;
+//! ws.subscribe(vec![
+//!     Channel::Orderbook("BTC/USD".into()),
+//!     Channel::Trades("BTC/USD".into()),
+//! ])
+//! .await?;
+//!
+//! while let Some((market, data)) = ws.next().await? {
+//!     paper.update(market.as_deref(), &data);
+//!     if paper.get_open_orders("BTC/USD").await.unwrap().is_empty() {
+//!         let order = OrderRequest::limit("BTC/USD", OrderSide::Buy, dec!(30000), dec!(0.01));
+//!         paper.submit_order(&order).await.unwrap();
+//!     }
+//!     for update in paper.take_updates() {
+//!         println!("{:?}", update);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(test)]
+mod tests;
+
+use crate::api::{BoxFuture, OrderEntry};
+use crate::rest::{
+    ApiError, Coin, Error, Fill, Id, Liquidity, OrderInfo, OrderRequest, OrderSide, OrderStatus,
+    OrderType, Result, Symbol, SymbolKind, WalletBalance,
+};
+use crate::ws::{Data, Orderbook, Trade};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::HashMap;
+use std::future::ready;
+use std::sync::Mutex;
+
+/// Implements `OrderEntry` without sending any requests, matching orders
+/// against the orderbooks and trades passed to `PaperRest::update` and
+/// keeping balances locally:
+///
+/// - Orders crossing the local orderbook of their market take its liquidity
+///   at the taker fee, the taken size is removed from the book until the
+///   level is updated. The rest of market and IOC orders is cancelled.
+/// - Post-only orders that would take liquidity are cancelled.
+/// - Resting limit orders are filled at their price by trades through it,
+///   up to the size of the trade, at the maker fee.
+///
+/// Spot orders move the balances of the base and quote coin and are
+/// rejected with `ApiError::NotEnoughMargin` if the free balance doesn't
+/// cover them, fees are charged in the quote coin. Fills of other markets
+/// only charge fees to the USD balance, follow their positions by applying
+/// the fills to a `portfolio::PositionTracker`.
+pub struct PaperRest {
+    state: Mutex<State>,
+}
+
+impl PaperRest {
+    /// Maker fee of the lowest FTX tier
+    pub const MAKER_FEE: Decimal = dec!(0.0002);
+
+    /// Taker fee of the lowest FTX tier
+    pub const TAKER_FEE: Decimal = dec!(0.0007);
+
+    /// Paper account without balances, paying the fees of the lowest tier
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                maker_fee: Self::MAKER_FEE,
+                taker_fee: Self::TAKER_FEE,
+                ..State::default()
+            }),
+        }
+    }
+
+    /// Sets the balance of `coin`
+    pub fn balance(mut self, coin: &str, total: Decimal) -> Self {
+        self.state_mut().balances.insert(coin.to_string(), total);
+        self
+    }
+
+    /// Sets the fee rates of orders adding and taking liquidity
+    pub fn fees(mut self, maker: Decimal, taker: Decimal) -> Self {
+        let state = self.state_mut();
+        state.maker_fee = maker;
+        state.taker_fee = taker;
+        self
+    }
+
+    /// Applies data received from `Ws::next`, using orderbook data to fill
+    /// marketable orders and trades to fill resting orders. Other data is
+    /// ignored.
+    pub fn update(&self, market: Option<&str>, data: &Data) {
+        let market = match market {
+            Some(market) => market,
+            None => return,
+        };
+        let mut state = self.state.lock().unwrap();
+        match data {
+            Data::OrderbookData(data) => state.book(market).update(data),
+            Data::GroupedOrderbook(data) => state.book(market).update_grouped(data),
+            Data::Trade(trade) => state.on_trade(market, trade),
+            _ => {}
+        }
+    }
+
+    /// Takes the simulated updates of the orders and fills channels since
+    /// the last call, e.g. to apply them to a `client::OrderTracker`
+    pub fn take_updates(&self) -> Vec<Data> {
+        std::mem::take(&mut self.state.lock().unwrap().updates)
+    }
+
+    fn state_mut(&mut self) -> &mut State {
+        self.state.get_mut().unwrap()
+    }
+}
+
+impl Default for PaperRest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderEntry for PaperRest {
+    fn submit_order<'a>(&'a self, order: &'a OrderRequest) -> BoxFuture<'a, Result<OrderInfo>> {
+        Box::pin(ready(self.state.lock().unwrap().submit(order)))
+    }
+
+    fn cancel_order(&self, order_id: Id) -> BoxFuture<'_, Result<String>> {
+        Box::pin(ready(self.state.lock().unwrap().cancel(order_id)))
+    }
+
+    fn get_order(&self, order_id: Id) -> BoxFuture<'_, Result<OrderInfo>> {
+        let order = self.state.lock().unwrap().orders.get(&order_id).cloned();
+        Box::pin(ready(order.ok_or_else(order_not_found)))
+    }
+
+    fn get_open_orders<'a>(&'a self, market: &'a str) -> BoxFuture<'a, Result<Vec<OrderInfo>>> {
+        let state = self.state.lock().unwrap();
+        let mut orders: Vec<_> = state
+            .open_orders()
+            .filter(|order| order.market == market)
+            .cloned()
+            .collect();
+        orders.sort_by_key(|order| order.id);
+        Box::pin(ready(Ok(orders)))
+    }
+
+    fn get_wallet_balances(&self) -> BoxFuture<'_, Result<Vec<WalletBalance>>> {
+        let state = self.state.lock().unwrap();
+        let mut balances: Vec<_> = state
+            .balances
+            .iter()
+            .map(|(coin, &total)| {
+                let free = total - state.reserved(coin);
+                WalletBalance {
+                    coin: coin.clone(),
+                    free,
+                    total,
+                    spot_borrow: Decimal::ZERO,
+                    available_without_borrow: free,
+                    usd_value: None,
+                }
+            })
+            .collect();
+        balances.sort_by(|a, b| a.coin.cmp(&b.coin));
+        Box::pin(ready(Ok(balances)))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    maker_fee: Decimal,
+    taker_fee: Decimal,
+    balances: HashMap<Coin, Decimal>,
+    books: HashMap<Symbol, Orderbook>,
+    orders: HashMap<Id, OrderInfo>,
+    /// Last ID given to an order or fill
+    last_id: Id,
+    /// Order updates and fills not taken yet
+    updates: Vec<Data>,
+}
+
+impl State {
+    fn next_id(&mut self) -> Id {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn book(&mut self, market: &str) -> &mut Orderbook {
+        self.books
+            .entry(market.into())
+            .or_insert_with(|| Orderbook::new(market.into()))
+    }
+
+    fn open_orders(&self) -> impl Iterator<Item = &OrderInfo> {
+        self.orders
+            .values()
+            .filter(|order| order.status != OrderStatus::Closed)
+    }
+
+    /// Balance of `coin` locked by open spot orders
+    fn reserved(&self, coin: &str) -> Decimal {
+        self.open_orders()
+            .filter_map(|order| {
+                let (base, quote) = spot_coins(&order.market)?;
+                match order.side {
+                    OrderSide::Buy if quote == coin => {
+                        Some(order.price.unwrap_or_default() * order.remaining_size)
+                    }
+                    OrderSide::Sell if base == coin => Some(order.remaining_size),
+                    _ => None,
+                }
+            })
+            .sum()
+    }
+
+    fn free(&self, coin: &str) -> Decimal {
+        self.balances.get(coin).copied().unwrap_or_default() - self.reserved(coin)
+    }
+
+    fn submit(&mut self, request: &OrderRequest) -> Result<OrderInfo> {
+        let limit = match (request.r#type, request.price) {
+            (OrderType::Limit, None) => return Err(Error::PlacingLimitOrderRequiresPrice),
+            (OrderType::Limit, price) => price,
+            (OrderType::Market, _) => None,
+        };
+        if request.size <= Decimal::ZERO {
+            return Err(Error::Api(ApiError::SizeTooSmall));
+        }
+        if matches!(limit, Some(price) if price <= Decimal::ZERO) {
+            return Err(Error::Api(ApiError::InvalidPrice));
+        }
+
+        let mut takes = self.takeable(&request.market, request.side, limit, request.size);
+        let rejected = request.flags.post_only && !takes.is_empty();
+        if rejected {
+            takes.clear();
+        }
+        let taken: Decimal = takes.iter().map(|(_, size)| size).sum();
+        let resting = match limit {
+            Some(_) if !request.flags.ioc && !rejected => request.size - taken,
+            _ => Decimal::ZERO,
+        };
+
+        if let Some((base, quote)) = spot_coins(&request.market) {
+            let enough = match request.side {
+                OrderSide::Buy => {
+                    let cost: Decimal = takes.iter().map(|(price, size)| price * size).sum();
+                    cost * (Decimal::ONE + self.taker_fee) + resting * limit.unwrap_or_default()
+                        <= self.free(quote)
+                }
+                OrderSide::Sell => taken + resting <= self.free(base),
+            };
+            if !enough {
+                return Err(Error::Api(ApiError::NotEnoughMargin));
+            }
+        }
+
+        let id = self.next_id();
+        let market = Symbol::from(request.market.as_str());
+        self.orders.insert(
+            id,
+            OrderInfo {
+                id,
+                future: (market.kind() != SymbolKind::Spot).then(|| market.clone()),
+                market: market.clone(),
+                r#type: request.r#type,
+                side: request.side,
+                price: limit,
+                size: request.size,
+                reduce_only: request.flags.reduce_only,
+                ioc: request.flags.ioc,
+                post_only: request.flags.post_only,
+                status: OrderStatus::Open,
+                filled_size: Decimal::ZERO,
+                remaining_size: request.size,
+                avg_fill_price: None,
+                liquidation: Some(false),
+                created_at: Utc::now(),
+                client_id: request.client_id.clone(),
+            },
+        );
+
+        for (price, size) in takes {
+            self.take_liquidity(&market, request.side, price, size);
+            self.fill(id, price, size, Liquidity::Taker, None);
+        }
+
+        let order = self.orders.get_mut(&id).unwrap();
+        if resting.is_zero() {
+            order.status = OrderStatus::Closed;
+        }
+        let order = order.clone();
+        self.updates.push(Data::Order(order.clone()));
+        Ok(order)
+    }
+
+    fn cancel(&mut self, order_id: Id) -> Result<String> {
+        let order = self.orders.get_mut(&order_id).ok_or_else(order_not_found)?;
+        if order.status == OrderStatus::Closed {
+            return Err(Error::Api(ApiError::OrderAlreadyClosed));
+        }
+        order.status = OrderStatus::Closed;
+        self.updates.push(Data::Order(order.clone()));
+
+        Ok("Order queued for cancellation".to_string())
+    }
+
+    /// Levels of the local book an order could take, best first
+    fn takeable(
+        &self,
+        market: &str,
+        side: OrderSide,
+        limit: Option<Decimal>,
+        size: Decimal,
+    ) -> Vec<(Decimal, Decimal)> {
+        let book = match self.books.get(market) {
+            Some(book) => book,
+            None => return Vec::new(),
+        };
+        let levels: Box<dyn Iterator<Item = (&Decimal, &Decimal)>> = match side {
+            OrderSide::Buy => Box::new(book.asks.iter()),
+            OrderSide::Sell => Box::new(book.bids.iter().rev()),
+        };
+
+        let mut left = size;
+        let mut takes = Vec::new();
+        for (&price, &level) in levels {
+            let crosses = match (side, limit) {
+                (_, None) => true,
+                (OrderSide::Buy, Some(limit)) => price <= limit,
+                (OrderSide::Sell, Some(limit)) => price >= limit,
+            };
+            if !crosses || left.is_zero() {
+                break;
+            }
+            let take = level.min(left);
+            takes.push((price, take));
+            left -= take;
+        }
+        takes
+    }
+
+    fn take_liquidity(&mut self, market: &str, side: OrderSide, price: Decimal, size: Decimal) {
+        let book = self.book(market);
+        let levels = match side {
+            OrderSide::Buy => &mut book.asks,
+            OrderSide::Sell => &mut book.bids,
+        };
+        if let Some(level) = levels.get_mut(&price) {
+            *level -= size;
+            if *level <= Decimal::ZERO {
+                levels.remove(&price);
+            }
+        }
+    }
+
+    /// Fills resting orders whose price the trade went through, best priced
+    /// and oldest first
+    fn on_trade(&mut self, market: &str, trade: &Trade) {
+        let mut orders: Vec<_> = self
+            .open_orders()
+            .filter(|order| order.market == market)
+            .filter_map(|order| {
+                let price = order.price?;
+                let through = match order.side {
+                    OrderSide::Buy => trade.price < price,
+                    OrderSide::Sell => trade.price > price,
+                };
+                through.then_some((order.side, price, order.id, order.remaining_size))
+            })
+            .collect();
+        orders.sort_by_key(|&(side, price, id, _)| match side {
+            OrderSide::Buy => (-price, id),
+            OrderSide::Sell => (price, id),
+        });
+
+        let mut left = trade.size;
+        for (_, price, id, remaining) in orders {
+            if left.is_zero() {
+                break;
+            }
+            let size = remaining.min(left);
+            left -= size;
+            self.fill(id, price, size, Liquidity::Maker, Some(trade.id));
+
+            let order = self.orders.get_mut(&id).unwrap();
+            if order.remaining_size.is_zero() {
+                order.status = OrderStatus::Closed;
+            }
+            let order = order.clone();
+            self.updates.push(Data::Order(order));
+        }
+    }
+
+    /// Applies a fill to its order and the balances
+    fn fill(
+        &mut self,
+        order_id: Id,
+        price: Decimal,
+        size: Decimal,
+        liquidity: Liquidity,
+        trade_id: Option<Id>,
+    ) {
+        let id = self.next_id();
+        let fee_rate = match liquidity {
+            Liquidity::Maker => self.maker_fee,
+            Liquidity::Taker => self.taker_fee,
+        };
+        let fee = price * size * fee_rate;
+
+        let order = self.orders.get_mut(&order_id).unwrap();
+        let notional = order.avg_fill_price.unwrap_or_default() * order.filled_size + price * size;
+        order.filled_size += size;
+        order.remaining_size -= size;
+        order.avg_fill_price = Some(notional / order.filled_size);
+        let (market, future, side) = (order.market.clone(), order.future.clone(), order.side);
+
+        let coins = spot_coins(&market).map(|(base, quote)| (base.to_string(), quote.to_string()));
+        let fee_currency = coins.as_ref().map_or("USD", |(_, quote)| quote).to_string();
+        let mut add = |coin: &str, amount: Decimal| {
+            *self.balances.entry(coin.to_string()).or_default() += amount;
+        };
+        add(&fee_currency, -fee);
+        if let Some((base, quote)) = &coins {
+            let (base_amount, quote_amount) = match side {
+                OrderSide::Buy => (size, -price * size),
+                OrderSide::Sell => (-size, price * size),
+            };
+            add(base, base_amount);
+            add(quote, quote_amount);
+        }
+
+        self.updates.push(Data::Fill(Fill {
+            id,
+            base_currency: coins.as_ref().map(|(base, _)| base.clone()),
+            quote_currency: coins.map(|(_, quote)| quote),
+            market,
+            future,
+            r#type: "order".to_string(),
+            side: side.into(),
+            price,
+            size,
+            order_id,
+            trade_id: trade_id.unwrap_or(id),
+            time: Utc::now(),
+            fee,
+            fee_rate,
+            fee_currency,
+            liquidity,
+        }));
+    }
+}
+
+/// Base and quote coin of a spot market
+fn spot_coins(market: &str) -> Option<(&str, &str)> {
+    market.split_once('/')
+}
+
+fn order_not_found() -> Error {
+    Error::Api(ApiError::Other("Order not found".to_string()))
+}
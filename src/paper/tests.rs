@@ -0,0 +1,128 @@
+use super::*;
+use crate::rest::Side;
+use crate::ws::{OrderbookAction, OrderbookData};
+use rust_decimal_macros::dec;
+
+fn book(action: OrderbookAction, bids: &[(Decimal, Decimal)], asks: &[(Decimal, Decimal)]) -> Data {
+    Data::OrderbookData(OrderbookData {
+        action,
+        bids: bids.to_vec(),
+        asks: asks.to_vec(),
+        checksum: 0,
+        time: Utc::now(),
+    })
+}
+
+fn trade(id: Id, side: Side, price: Decimal, size: Decimal) -> Data {
+    Data::Trade(Trade {
+        id,
+        price,
+        size,
+        side,
+        liquidation: false,
+        time: Utc::now(),
+    })
+}
+
+async fn balance(paper: &PaperRest, coin: &str) -> (Decimal, Decimal) {
+    let balances = paper.get_wallet_balances().await.unwrap();
+    let balance = balances
+        .iter()
+        .find(|balance| balance.coin == coin)
+        .unwrap();
+    (balance.total, balance.free)
+}
+
+#[tokio::test]
+async fn paper_trading() {
+    let paper = PaperRest::new()
+        .balance("USD", dec!(1000))
+        .fees(dec!(0), dec!(0.001));
+    paper.update(
+        Some("BTC/USD"),
+        &book(
+            OrderbookAction::Partial,
+            &[(dec!(99), dec!(1))],
+            &[(dec!(100), dec!(1)), (dec!(101), dec!(2))],
+        ),
+    );
+
+    // Market orders take the book
+    let order = paper
+        .submit_order(&OrderRequest::market("BTC/USD", OrderSide::Buy, dec!(2)))
+        .await
+        .unwrap();
+    assert_eq!(order.status, OrderStatus::Closed);
+    assert_eq!(order.filled_size, dec!(2));
+    assert_eq!(order.avg_fill_price, Some(dec!(100.5)));
+    assert_eq!(balance(&paper, "BTC").await, (dec!(2), dec!(2)));
+    assert_eq!(
+        balance(&paper, "USD").await.0,
+        dec!(1000) - dec!(201) * dec!(1.001)
+    );
+
+    // Resting orders reserve their balance
+    let ask = paper
+        .submit_order(&OrderRequest::limit(
+            "BTC/USD",
+            OrderSide::Sell,
+            dec!(105),
+            dec!(1.5),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(ask.status, OrderStatus::Open);
+    assert_eq!(balance(&paper, "BTC").await, (dec!(2), dec!(0.5)));
+    let too_large = OrderRequest::limit("BTC/USD", OrderSide::Sell, dec!(110), dec!(1));
+    assert!(matches!(
+        paper.submit_order(&too_large).await,
+        Err(Error::Api(ApiError::NotEnoughMargin))
+    ));
+
+    // Post-only orders crossing the book are cancelled, only one ask is left
+    let post_only =
+        OrderRequest::limit("BTC/USD", OrderSide::Buy, dec!(101), dec!(1)).post_only(true);
+    let order = paper.submit_order(&post_only).await.unwrap();
+    assert_eq!(order.status, OrderStatus::Closed);
+    assert_eq!(order.filled_size, dec!(0));
+    let ioc = OrderRequest::limit("BTC/USD", OrderSide::Buy, dec!(101), dec!(2)).ioc(true);
+    let order = paper.submit_order(&ioc).await.unwrap();
+    assert_eq!(order.filled_size, dec!(1));
+    assert_eq!(order.remaining_size, dec!(1));
+    assert_eq!(order.status, OrderStatus::Closed);
+
+    // Trades through the price fill resting orders at the maker fee
+    paper.take_updates();
+    paper.update(Some("BTC/USD"), &trade(7, Side::Buy, dec!(105), dec!(1)));
+    paper.update(Some("ETH/USD"), &trade(8, Side::Buy, dec!(106), dec!(1)));
+    assert!(paper.take_updates().is_empty());
+    paper.update(Some("BTC/USD"), &trade(9, Side::Buy, dec!(106), dec!(1)));
+    let updates = paper.take_updates();
+    match updates.as_slice() {
+        [Data::Fill(fill), Data::Order(order)] => {
+            assert_eq!(
+                (fill.price, fill.size, fill.trade_id),
+                (dec!(105), dec!(1), 9)
+            );
+            assert_eq!(fill.liquidity, Liquidity::Maker);
+            assert_eq!(order.status, OrderStatus::Open);
+            assert_eq!(order.remaining_size, dec!(0.5));
+        }
+        _ => panic!("Fill and order update expected."),
+    }
+    assert_eq!(balance(&paper, "BTC").await, (dec!(2), dec!(1.5)));
+    assert_eq!(paper.get_open_orders("BTC/USD").await.unwrap().len(), 1);
+
+    // Cancelling
+    paper.cancel_order(ask.id).await.unwrap();
+    assert!(paper.get_open_orders("BTC/USD").await.unwrap().is_empty());
+    assert_eq!(balance(&paper, "BTC").await, (dec!(2), dec!(2)));
+    assert!(matches!(
+        paper.cancel_order(ask.id).await,
+        Err(Error::Api(ApiError::OrderAlreadyClosed))
+    ));
+    assert!(matches!(
+        paper.get_order(1000).await,
+        Err(Error::Api(ApiError::Other(_)))
+    ));
+}
@@ -0,0 +1,279 @@
+use crate::rest::{self, Fill, Rest, Side, Symbol};
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use std::collections::{BTreeMap, HashMap};
+use tokio::time::{self, Interval, MissedTickBehavior};
+
+/// Expected funding of a single perpetual future
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FundingAccrual {
+    /// Positive when long, negative when short
+    pub net_size: Decimal,
+    /// Mark or index price the payments are computed with
+    pub price: Option<Decimal>,
+    /// Hourly funding rate, see `FutureStats::next_funding_rate`
+    pub rate: Option<Decimal>,
+    /// Expected payments not reconciled yet, by the hour they are due.
+    /// Positive when paid, like `FundingPayment::payment`.
+    pub pending: BTreeMap<DateTime<Utc>, Decimal>,
+    /// Time up to which payments were accrued
+    pub accrued_until: Option<DateTime<Utc>>,
+}
+
+impl FundingAccrual {
+    /// Sum of the pending payments
+    pub fn expected(&self) -> Decimal {
+        self.pending.values().sum()
+    }
+
+    /// Accrues `net_size * price * rate` per hour up to `now`, split by the
+    /// hour the payment is due
+    fn accrue(&mut self, now: DateTime<Utc>) {
+        let (price, rate, mut from) = match (self.price, self.rate, self.accrued_until) {
+            (Some(price), Some(rate), Some(from)) if !self.net_size.is_zero() => {
+                (price, rate, from)
+            }
+            _ => {
+                self.accrued_until = Some(now.max(self.accrued_until.unwrap_or(now)));
+                return;
+            }
+        };
+
+        let hourly = self.net_size * price * rate;
+        while from < now {
+            let due = hour_end(from);
+            let to = due.min(now);
+            let share = Decimal::from((to - from).num_milliseconds()) / dec!(3_600_000);
+            *self.pending.entry(due).or_default() += hourly * share;
+            from = to;
+        }
+        self.accrued_until = Some(from);
+    }
+}
+
+/// Funding payments reconciled by `FundingTracker::reconcile`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FundingReconciliation {
+    pub market: Symbol,
+    /// Payments accrued locally, positive when paid
+    pub expected: Decimal,
+    /// Payments returned by `GET /funding_payments`, positive when paid
+    pub actual: Decimal,
+}
+
+impl FundingReconciliation {
+    /// Actual minus expected payments
+    pub fn difference(&self) -> Decimal {
+        self.actual - self.expected
+    }
+}
+
+/// Accrues the funding payments expected from positions in perpetual
+/// futures as time passes, and compares them hourly with the payments
+/// returned by the REST API, e.g. to follow the carry of a basis trade.
+///
+/// Positions follow fills, prices and rates are set as they are received,
+/// and `refresh` fetches all of them from the REST API. Payments are
+/// reconciled from the first update on, so the first hour is usually only
+/// partially expected.
+///
+/// ```no_run
+/// # async fn example() -> ftx::client::Result<()> {
+/// use ftx::portfolio::FundingTracker;
+/// use ftx::rest::Rest;
+/// use ftx::ws::{Channel, Data, Ws};
+///
+/// let api = Rest::new("<api key>".into(), "<api secret>".into(), None);
+/// let mut funding = FundingTracker::new();
+/// funding.refresh(&api).await?;
+///
+/// let mut ws = Ws::connect("<api key>".into(), "<api secret>".into(), None).await?;
+/// ws.subscribe(vec![Channel::Fills, Channel::Ticker("BTC-PERP".into())])
+///     .await?;
+/// loop {
+///     tokio::select! {
+///         data = ws.next() => match data? {
+///             Some((_, Data::Fill(fill))) => funding.apply(&fill),
+///             Some((Some(market), Data::Ticker(ticker))) => {
+///                 if let Some(last) = ticker.last {
+///                     funding.set_price(&market, last, ticker.time);
+///                 }
+///             }
+///             _ => {}
+///         },
+///         _ = funding.tick() => {
+///             for reconciliation in funding.reconcile(&api).await? {
+///                 println!("{:?}", reconciliation);
+///             }
+///             funding.refresh(&api).await?;
+///         }
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FundingTracker {
+    markets: HashMap<Symbol, FundingAccrual>,
+    /// Payments due up to this time were reconciled, starts when the first
+    /// position, price, or rate is set
+    reconciled_until: Option<DateTime<Utc>>,
+    interval: Interval,
+}
+
+impl Default for FundingTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FundingTracker {
+    pub fn new() -> Self {
+        let mut interval = time::interval(std::time::Duration::from_secs(3600));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        Self {
+            markets: HashMap::new(),
+            reconciled_until: None,
+            interval,
+        }
+    }
+
+    /// Applies a fill to the position of its market, accruing up to the
+    /// time of the fill first
+    pub fn apply(&mut self, fill: &Fill) {
+        let signed_size = match fill.side {
+            Side::Buy => fill.size,
+            Side::Sell => -fill.size,
+        };
+        let accrual = self.accrual_at(&fill.market, fill.time);
+        accrual.net_size += signed_size;
+    }
+
+    /// Replaces the position of `market` from `time` on
+    pub fn set_position(&mut self, market: &str, net_size: Decimal, time: DateTime<Utc>) {
+        self.accrual_at(market, time).net_size = net_size;
+    }
+
+    /// Sets the price of `market` from `time` on, e.g. the mark price or the
+    /// last price of the ticker channel
+    pub fn set_price(&mut self, market: &str, price: Decimal, time: DateTime<Utc>) {
+        self.accrual_at(market, time).price = Some(price);
+    }
+
+    /// Sets the hourly funding rate of `market` from `time` on
+    pub fn set_rate(&mut self, market: &str, rate: Decimal, time: DateTime<Utc>) {
+        self.accrual_at(market, time).rate = Some(rate);
+    }
+
+    /// Accrues the expected payments of all markets up to `now`
+    pub fn accrue(&mut self, now: DateTime<Utc>) {
+        for accrual in self.markets.values_mut() {
+            accrual.accrue(now);
+        }
+    }
+
+    pub fn get(&self, market: &str) -> Option<&FundingAccrual> {
+        self.markets.get(market)
+    }
+
+    /// Fetches positions, mark prices, and predicted funding rates
+    pub async fn refresh(&mut self, rest: &Rest) -> rest::Result<()> {
+        let now = Utc::now() + rest.clock_offset();
+        let positions = rest.get_positions().await?;
+        for accrual in self.markets.values_mut() {
+            accrual.accrue(now);
+            accrual.net_size = Decimal::ZERO;
+        }
+
+        for position in positions.iter().filter(|p| !p.net_size.is_zero()) {
+            let future = rest.get_future(&position.future).await?;
+            let stats = rest.get_future_stats(&position.future).await?;
+            let accrual = self.accrual_at(&position.future, now);
+            accrual.net_size = position.net_size;
+            accrual.price = future.mark.or(accrual.price);
+            accrual.rate = stats.next_funding_rate.or(accrual.rate);
+        }
+        Ok(())
+    }
+
+    /// Waits until the next hourly reconciliation is due. Cancel safe, so it
+    /// can be used in `tokio::select!` along with `Ws::next`.
+    pub async fn tick(&mut self) {
+        self.interval.tick().await;
+    }
+
+    /// Compares the payments due since the last reconciliation up to the
+    /// last full hour with `GET /funding_payments`, see `reconcile_until`
+    pub async fn reconcile(&mut self, rest: &Rest) -> rest::Result<Vec<FundingReconciliation>> {
+        let now = Utc::now() + rest.clock_offset();
+        self.reconcile_until(rest, now).await
+    }
+
+    /// Compares the payments due up to `until` with those returned by
+    /// `GET /funding_payments`, for each market with either. Reconciled
+    /// payments are removed from the pending ones.
+    pub async fn reconcile_until(
+        &mut self,
+        rest: &Rest,
+        until: DateTime<Utc>,
+    ) -> rest::Result<Vec<FundingReconciliation>> {
+        let until = until.duration_trunc(Duration::hours(1)).unwrap_or(until);
+        let start = match self.reconciled_until {
+            Some(start) if start < until => start,
+            _ => return Ok(Vec::new()),
+        };
+        self.accrue(until);
+
+        let mut actual: HashMap<Symbol, Decimal> = HashMap::new();
+        let payments = rest
+            .paginate_funding_payments(None, Some(start), Some(until))
+            .try_collect()
+            .await?;
+        for payment in payments {
+            if payment.time > start && payment.time <= until {
+                *actual.entry(payment.future).or_default() += payment.payment;
+            }
+        }
+
+        let mut markets: Vec<Symbol> = self.markets.keys().chain(actual.keys()).cloned().collect();
+        markets.sort();
+        markets.dedup();
+
+        let mut reconciliations = Vec::new();
+        for market in markets {
+            let accrual = self.markets.entry(market.clone()).or_default();
+            let expected: Decimal = accrual
+                .pending
+                .range(..=until)
+                .map(|(_, payment)| payment)
+                .sum();
+            accrual.pending.retain(|due, _| *due > until);
+            let actual = actual.get(&market).copied().unwrap_or_default();
+            if !expected.is_zero() || !actual.is_zero() {
+                reconciliations.push(FundingReconciliation {
+                    market,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        self.reconciled_until = Some(until);
+
+        Ok(reconciliations)
+    }
+
+    /// Accrual of `market`, accrued up to `time`
+    fn accrual_at(&mut self, market: &str, time: DateTime<Utc>) -> &mut FundingAccrual {
+        self.reconciled_until.get_or_insert(time);
+        let accrual = self.markets.entry(market.into()).or_default();
+        accrual.accrue(time);
+        accrual
+    }
+}
+
+/// End of the hour containing `time`, when its funding payment is due
+fn hour_end(time: DateTime<Utc>) -> DateTime<Utc> {
+    let start = time.duration_trunc(Duration::hours(1)).unwrap_or(time);
+    start + Duration::hours(1)
+}
@@ -0,0 +1,157 @@
+//! Portfolio state derived from fills, e.g. to follow positions without
+//! polling the REST API.
+//!
+//! ```no_run
+//! # async fn example() -> ftx::client::Result<()> {
+//! use ftx::portfolio::PositionTracker;
+//! use ftx::rest::Rest;
+//! use ftx::ws::{Channel, Data, Ws};
+//!
+//! let api = Rest::new("<api key>".into(), "<api secret>".into(), None);
+//! let mut positions = PositionTracker::from_rest(&api).await?;
+//!
+//! let mut ws = Ws::connect("<api key>".into(), "<api secret>".into(), None).await?;
+//! ws.subscribe(vec![Channel::Fills]).await?;
+//! while let Some((_, data)) = ws.next().await? {
+//!     if let Data::Fill(fill) = data {
+//!         let position = positions.apply(&fill);
+//!         println!("{}: {} @ {:?}", fill.market, position.net_size, position.entry_price);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod funding;
+mod risk;
+#[cfg(test)]
+mod tests;
+
+pub use funding::*;
+pub use risk::*;
+
+use crate::rest::{self, Fill, Position, Rest, Side, Symbol};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Position in a single market
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TrackedPosition {
+    /// Positive when long, negative when short
+    pub net_size: Decimal,
+    /// Average price at which the open position was entered, `None` when flat
+    pub entry_price: Option<Decimal>,
+    /// Profit of closed size, excluding fees
+    pub realized_pnl: Decimal,
+    /// Fees paid by applied fills, in their fee currency (usually USD)
+    pub fees: Decimal,
+}
+
+impl TrackedPosition {
+    /// Profit of the open position if closed at `price`
+    pub fn unrealized_pnl(&self, price: Decimal) -> Decimal {
+        match self.entry_price {
+            Some(entry_price) => (price - entry_price) * self.net_size,
+            None => Decimal::ZERO,
+        }
+    }
+
+    fn apply(&mut self, side: Side, price: Decimal, size: Decimal) {
+        let signed_size = match side {
+            Side::Buy => size,
+            Side::Sell => -size,
+        };
+        let entry_price = self.entry_price.unwrap_or(price);
+
+        if self.net_size.is_zero() || self.net_size.is_sign_positive() == (side == Side::Buy) {
+            // Increasing the position, average the entry price
+            let open = self.net_size.abs();
+            self.entry_price = Some((entry_price * open + price * size) / (open + size));
+        } else {
+            // Reducing, closing, or flipping the position
+            let closed = size.min(self.net_size.abs());
+            let pnl = (price - entry_price) * closed;
+            self.realized_pnl += if self.net_size.is_sign_positive() {
+                pnl
+            } else {
+                -pnl
+            };
+            if size > closed {
+                self.entry_price = Some(price);
+            } else if size == closed && closed == self.net_size.abs() {
+                self.entry_price = None;
+            }
+        }
+
+        self.net_size += signed_size;
+    }
+}
+
+/// Maintains net position, average entry price, and realized PnL per market
+/// by applying fills. Each fill must only be applied once.
+#[derive(Clone, Debug, Default)]
+pub struct PositionTracker {
+    positions: HashMap<Symbol, TrackedPosition>,
+}
+
+impl PositionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Seeds the tracker with the current positions of the account, see
+    /// `Rest::get_positions`
+    pub async fn from_rest(rest: &Rest) -> rest::Result<Self> {
+        let mut tracker = Self::new();
+        tracker.seed(&rest.get_positions().await?);
+        Ok(tracker)
+    }
+
+    /// Replaces the tracked positions of the markets of `positions`
+    pub fn seed(&mut self, positions: &[Position]) {
+        for position in positions {
+            self.positions.insert(
+                position.future.clone(),
+                TrackedPosition {
+                    net_size: position.net_size,
+                    entry_price: position
+                        .entry_price
+                        .filter(|_| !position.net_size.is_zero()),
+                    realized_pnl: position.realized_pnl,
+                    fees: Decimal::ZERO,
+                },
+            );
+        }
+    }
+
+    /// Applies `fill` and returns the updated position of its market
+    pub fn apply(&mut self, fill: &Fill) -> &TrackedPosition {
+        let position = self.positions.entry(fill.market.clone()).or_default();
+        position.apply(fill.side, fill.price, fill.size);
+        position.fees += fill.fee;
+        position
+    }
+
+    /// Replaces the position of `market`, e.g. after reconciling it with the
+    /// REST API
+    pub fn set(&mut self, market: Symbol, position: TrackedPosition) {
+        self.positions.insert(market, position);
+    }
+
+    pub fn get(&self, market: &str) -> Option<&TrackedPosition> {
+        self.positions.get(market)
+    }
+
+    /// Positions of all markets with fills or seeded positions
+    pub fn positions(&self) -> impl Iterator<Item = (&Symbol, &TrackedPosition)> {
+        self.positions.iter()
+    }
+
+    /// Realized PnL summed over all markets, excluding fees
+    pub fn realized_pnl(&self) -> Decimal {
+        self.positions
+            .values()
+            .map(|position| position.realized_pnl)
+            .sum()
+    }
+}
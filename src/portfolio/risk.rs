@@ -0,0 +1,201 @@
+use super::{PositionTracker, TrackedPosition};
+use crate::rest::{
+    self, round_to_increment, ApiError, ConditionalOrderInfo, ConditionalOrderRequest, Error, Fill,
+    Id, OrderSide, Rest, Symbol,
+};
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::collections::HashMap;
+
+/// Distance of a stop order from the entry price of its position
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopDistance {
+    /// Fraction of the entry price, e.g. `0.05` for 5%
+    Relative(Decimal),
+    /// Price difference in the quote currency
+    Absolute(Decimal),
+}
+
+impl StopDistance {
+    /// Trigger price of the stop protecting `position`, `None` when flat
+    pub fn trigger_price(&self, position: &TrackedPosition) -> Option<Decimal> {
+        let entry_price = position
+            .entry_price
+            .filter(|_| !position.net_size.is_zero())?;
+        let distance = match *self {
+            StopDistance::Relative(fraction) => entry_price * fraction,
+            StopDistance::Absolute(distance) => distance,
+        };
+        Some(if position.net_size.is_sign_positive() {
+            entry_price - distance
+        } else {
+            entry_price + distance
+        })
+    }
+}
+
+/// Change made to the stop orders by `RiskGuard::sync_stops`
+#[derive(Clone, Debug)]
+pub enum StopAction {
+    Placed(ConditionalOrderInfo),
+    /// The stop was modified, which gives it a new ID
+    Modified(ConditionalOrderInfo),
+    Cancelled {
+        market: Symbol,
+        id: Id,
+    },
+}
+
+/// Keeps a reduce-only stop order at a fixed distance from the entry price
+/// of every open position. Positions are followed by applying fills, e.g.
+/// received on the `fills` channel, and can be refreshed from the REST API.
+/// `sync_stops` then places, modifies, or cancels the stop orders so that
+/// they cover the positions.
+///
+/// Stop orders placed before the guard was created aren't managed by it.
+///
+/// ```no_run
+/// # async fn example() -> ftx::client::Result<()> {
+/// use ftx::portfolio::{RiskGuard, StopDistance};
+/// use ftx::rest::Rest;
+/// use ftx::ws::{Channel, Data, Ws};
+/// use rust_decimal_macros::dec;
+///
+/// let api = Rest::new("<api key>".into(), "<api secret>".into(), None);
+/// let mut guard = RiskGuard::new(StopDistance::Relative(dec!(0.05)));
+/// guard.refresh(&api).await?;
+/// guard.sync_stops(&api).await?;
+///
+/// let mut ws = Ws::connect("<api key>".into(), "<api secret>".into(), None).await?;
+/// ws.subscribe(vec![Channel::Fills]).await?;
+/// while let Some((_, data)) = ws.next().await? {
+///     if let Data::Fill(fill) = data {
+///         guard.apply(&fill);
+///         for action in guard.sync_stops(&api).await? {
+///             println!("{:?}", action);
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RiskGuard {
+    distance: StopDistance,
+    price_increments: HashMap<Symbol, Decimal>,
+    positions: PositionTracker,
+    /// Stop order protecting the position of each market
+    stops: HashMap<Symbol, ConditionalOrderInfo>,
+}
+
+impl RiskGuard {
+    pub fn new(distance: StopDistance) -> Self {
+        Self {
+            distance,
+            price_increments: HashMap::new(),
+            positions: PositionTracker::new(),
+            stops: HashMap::new(),
+        }
+    }
+
+    /// Rounds the trigger prices of `market` to the nearest multiple of
+    /// `increment`, see `Market::price_increment`
+    pub fn price_increment(mut self, market: &str, increment: Decimal) -> Self {
+        self.price_increments.insert(market.into(), increment);
+        self
+    }
+
+    /// Applies a fill to the position of its market
+    pub fn apply(&mut self, fill: &Fill) {
+        self.positions.apply(fill);
+    }
+
+    /// Replaces the positions with those returned by `Rest::get_positions`,
+    /// e.g. on start or after fills may have been missed
+    pub async fn refresh(&mut self, rest: &Rest) -> rest::Result<()> {
+        self.positions = PositionTracker::from_rest(rest).await?;
+        Ok(())
+    }
+
+    pub fn positions(&self) -> &PositionTracker {
+        &self.positions
+    }
+
+    /// Stop order protecting the position of `market`, if any
+    pub fn stop(&self, market: &str) -> Option<&ConditionalOrderInfo> {
+        self.stops.get(market)
+    }
+
+    /// Places stops for new positions, moves stops of positions whose size
+    /// or entry price changed, and cancels stops of closed positions
+    pub async fn sync_stops(&mut self, rest: &Rest) -> rest::Result<Vec<StopAction>> {
+        let mut markets: Vec<Symbol> = self
+            .positions
+            .positions()
+            .map(|(market, _)| market.clone())
+            .chain(self.stops.keys().cloned())
+            .collect();
+        markets.sort();
+        markets.dedup();
+
+        let mut actions = Vec::new();
+        for market in markets {
+            let position = self.positions.get(&market).cloned().unwrap_or_default();
+            let side = if position.net_size.is_sign_positive() {
+                OrderSide::Sell
+            } else {
+                OrderSide::Buy
+            };
+            let size = position.net_size.abs();
+            let wanted = self.distance.trigger_price(&position).map(|price| {
+                match self.price_increments.get(&market) {
+                    Some(&increment) => {
+                        round_to_increment(price, increment, RoundingStrategy::MidpointAwayFromZero)
+                    }
+                    None => price,
+                }
+            });
+
+            match (self.stops.get(&market), wanted) {
+                (Some(stop), Some(trigger_price))
+                    if stop.side == side
+                        && stop.size == size
+                        && stop.trigger_price == Some(trigger_price) => {}
+                (Some(stop), Some(trigger_price)) if stop.side == side => {
+                    let info = rest
+                        .modify_conditional_order(stop.id, size, Some(trigger_price), None, None)
+                        .await?;
+                    self.stops.insert(market, info.clone());
+                    actions.push(StopAction::Modified(info));
+                }
+                (stop, wanted) => {
+                    if let Some(stop) = stop {
+                        let id = stop.id;
+                        cancel_stop(rest, id).await?;
+                        self.stops.remove(&market);
+                        actions.push(StopAction::Cancelled {
+                            market: market.clone(),
+                            id,
+                        });
+                    }
+                    if let Some(trigger_price) = wanted {
+                        let order =
+                            ConditionalOrderRequest::stop(&market, side, size, trigger_price)
+                                .reduce_only(true);
+                        let info = rest.place_conditional_order(&order).await?;
+                        self.stops.insert(market, info.clone());
+                        actions.push(StopAction::Placed(info));
+                    }
+                }
+            }
+        }
+        Ok(actions)
+    }
+}
+
+/// Cancels a stop order, which may have triggered already
+async fn cancel_stop(rest: &Rest, id: Id) -> rest::Result<()> {
+    match rest.cancel_conditional_order(id).await {
+        Ok(_) | Err(Error::Api(ApiError::OrderAlreadyClosed)) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
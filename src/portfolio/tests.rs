@@ -0,0 +1,168 @@
+use super::*;
+use rust_decimal_macros::dec;
+use serde_json::json;
+
+fn fill(side: &str, price: Decimal, size: Decimal) -> Fill {
+    serde_json::from_value(json!({"id": 1, "market": "BTC-PERP", "future": "BTC-PERP", "baseCurrency": null, "quoteCurrency": null, "type": "order", "side": side, "price": price, "size": size, "orderId": 1, "tradeId": 1, "time": "2021-05-23T05:24:24.315884+00:00", "fee": 0.5, "feeRate": 0.0002, "feeCurrency": "USD", "liquidity": "taker"})).unwrap()
+}
+
+#[test]
+fn position_tracker() {
+    let mut tracker = PositionTracker::new();
+
+    tracker.apply(&fill("buy", dec!(100), dec!(1)));
+    let position = tracker.apply(&fill("buy", dec!(110), dec!(3)));
+    assert_eq!(position.net_size, dec!(4));
+    assert_eq!(position.entry_price, Some(dec!(107.5)));
+    assert_eq!(position.unrealized_pnl(dec!(110)), dec!(10));
+
+    // Reducing keeps the entry price
+    let position = tracker.apply(&fill("sell", dec!(120), dec!(2)));
+    assert_eq!(position.net_size, dec!(2));
+    assert_eq!(position.entry_price, Some(dec!(107.5)));
+    assert_eq!(position.realized_pnl, dec!(25));
+
+    // Flipping enters the rest at the fill price
+    let position = tracker.apply(&fill("sell", dec!(100), dec!(3)));
+    assert_eq!(position.net_size, dec!(-1));
+    assert_eq!(position.entry_price, Some(dec!(100)));
+    assert_eq!(position.realized_pnl, dec!(10));
+    assert_eq!(position.unrealized_pnl(dec!(90)), dec!(10));
+
+    let position = tracker.apply(&fill("buy", dec!(90), dec!(1)));
+    assert_eq!(position.net_size, dec!(0));
+    assert_eq!(position.entry_price, None);
+    assert_eq!(position.fees, dec!(2.5));
+    assert_eq!(tracker.realized_pnl(), dec!(20));
+}
+
+#[test]
+fn seed_positions() {
+    let position: Position = serde_json::from_value(json!({"cost": -4000.0, "entryPrice": 40000.0, "estimatedLiquidationPrice": null, "future": "BTC-PERP", "initialMarginRequirement": 0.1, "longOrderSize": 0.0, "maintenanceMarginRequirement": 0.03, "netSize": -0.1, "openSize": 0.1, "realizedPnl": 5.0, "shortOrderSize": 0.0, "side": "sell", "size": 0.1, "unrealizedPnl": 0.0, "collateralUsed": 400.0})).unwrap();
+
+    let mut tracker = PositionTracker::new();
+    tracker.seed(&[position]);
+    let position = tracker.apply(&fill("buy", dec!(39000), dec!(0.1)));
+    assert_eq!(position.net_size, dec!(0));
+    assert_eq!(position.realized_pnl, dec!(105));
+}
+
+#[tokio::test]
+async fn risk_guard() {
+    use crate::testing::MockRest;
+    use serde_json::Value;
+
+    let stop = |id: u64, side: &str, size: Decimal, trigger_price: Decimal| -> Value {
+        json!({"createdAt": "2021-05-23T05:24:24.315884+00:00", "error": null, "future": "BTC-PERP", "id": id, "market": "BTC-PERP", "orderId": null, "orderPrice": null, "reduceOnly": true, "side": side, "size": size, "status": "open", "trailStart": null, "trailValue": null, "triggerPrice": trigger_price, "triggeredAt": null, "type": "stop", "orderType": "market", "filledSize": 0, "avgFillPrice": null, "retryUntilFilled": true})
+    };
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "POST",
+        "/conditional_orders",
+        stop(1, "sell", dec!(1), dec!(95)),
+    );
+    server.respond_ok(
+        "POST",
+        "/conditional_orders/1/modify",
+        stop(2, "sell", dec!(2), dec!(99)),
+    );
+    server.respond_ok(
+        "DELETE",
+        "/conditional_orders/2",
+        json!("Order queued for cancellation"),
+    );
+    let rest = server.rest();
+
+    let mut guard =
+        RiskGuard::new(StopDistance::Relative(dec!(0.05))).price_increment("BTC-PERP", dec!(1));
+    assert!(guard.sync_stops(&rest).await.unwrap().is_empty());
+
+    // Opening a position places a reduce-only stop
+    guard.apply(&fill("buy", dec!(100), dec!(1)));
+    let actions = guard.sync_stops(&rest).await.unwrap();
+    assert!(matches!(actions.as_slice(), [StopAction::Placed(stop)] if stop.id == 1));
+    assert_eq!(
+        server.requests()[0].json(),
+        json!({"market": "BTC-PERP", "side": "sell", "size": "1", "type": "stop", "reduceOnly": true, "retryUntilFilled": true, "triggerPrice": "95"})
+    );
+    assert!(guard.sync_stops(&rest).await.unwrap().is_empty());
+
+    // Increasing the position moves the stop, rounded to the increment
+    guard.apply(&fill("buy", dec!(108), dec!(1)));
+    let actions = guard.sync_stops(&rest).await.unwrap();
+    assert!(matches!(actions.as_slice(), [StopAction::Modified(stop)] if stop.id == 2));
+    let modify = server.requests()[1].json();
+    assert_eq!(
+        (modify["size"].clone(), modify["triggerPrice"].clone()),
+        (json!("2"), json!("99"))
+    );
+    assert_eq!(guard.stop("BTC-PERP").unwrap().id, 2);
+
+    // Closing the position cancels the stop
+    guard.apply(&fill("sell", dec!(110), dec!(2)));
+    let actions = guard.sync_stops(&rest).await.unwrap();
+    assert!(matches!(
+        actions.as_slice(),
+        [StopAction::Cancelled { id: 2, .. }]
+    ));
+    assert!(guard.stop("BTC-PERP").is_none());
+    assert!(guard.sync_stops(&rest).await.unwrap().is_empty());
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn funding_tracker() {
+    use crate::testing::MockRest;
+    use chrono::{TimeZone, Utc};
+
+    let time = |hour: u32, minute: u32| Utc.with_ymd_and_hms(2021, 5, 23, hour, minute, 0).unwrap();
+    let payment = |id: u64, future: &str, payment: f64, hour: u32| json!({"id": id, "future": future, "payment": payment, "rate": 0.0001, "time": time(hour, 0).to_rfc3339()});
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "GET",
+        "/funding_payments",
+        json!([
+            payment(2, "BTC-PERP", 0.41, 7),
+            payment(1, "BTC-PERP", 0.2, 6)
+        ]),
+    );
+    server.respond_ok("GET", "/funding_payments", json!([]));
+    let rest = server.rest();
+
+    // Long 1 BTC at 40000 paying 0.01% per hour from 5:30
+    let mut funding = FundingTracker::new();
+    funding.set_position("BTC-PERP", dec!(1), time(5, 30));
+    funding.set_price("BTC-PERP", dec!(40000), time(5, 30));
+    funding.set_rate("BTC-PERP", dec!(0.0001), time(5, 30));
+
+    // Half as much is paid once half of the position is sold at 6:30
+    let mut sell = fill("sell", dec!(40000), dec!(0.5));
+    sell.time = time(6, 30);
+    funding.apply(&sell);
+    funding.accrue(time(7, 15));
+    let accrual = funding.get("BTC-PERP").unwrap();
+    assert_eq!(accrual.pending[&time(6, 0)], dec!(2));
+    assert_eq!(accrual.pending[&time(7, 0)], dec!(3));
+    assert_eq!(accrual.pending[&time(8, 0)], dec!(0.5));
+
+    // Payments are compared up to the last full hour
+    let reconciliations = funding.reconcile_until(&rest, time(7, 20)).await.unwrap();
+    assert_eq!(
+        reconciliations,
+        vec![FundingReconciliation {
+            market: "BTC-PERP".into(),
+            expected: dec!(5),
+            actual: dec!(0.61),
+        }]
+    );
+    assert_eq!(reconciliations[0].difference(), dec!(-4.39));
+    assert_eq!(funding.get("BTC-PERP").unwrap().expected(), dec!(0.5));
+    assert!(server.requests()[0]
+        .path
+        .contains(&format!("end_time={}", time(7, 0).timestamp())));
+    assert!(funding
+        .reconcile_until(&rest, time(7, 40))
+        .await
+        .unwrap()
+        .is_empty());
+}
@@ -1,10 +1,35 @@
+use super::{Id, IncrementError};
+use crate::options::Exchange;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::fmt;
+use std::time::Duration;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
     Reqwest(reqwest::Error),
-    Api(String),
+    /// FTX answered with `{"success": false, "error": ...}`
+    Api(ApiError),
     PlacingLimitOrderRequiresPrice,
+    /// The request was not sent because it would exceed the rate limit
+    RateLimited,
+    /// FTX rejected the request with HTTP 429 because of its rate limit
+    TooManyRequests(RateLimitInfo),
+    /// The market name passed to `Symbol::parse` is malformed
+    InvalidSymbol(String),
+    /// The leveraged token redemption polled by
+    /// `Rest::wait_for_lt_redemption` is not listed
+    RedemptionNotFound(Id),
+    /// The endpoint is not available on the exchange, e.g. futures and
+    /// options on ftx.us
+    UnsupportedOnExchange(Exchange),
+    /// The order doesn't match the increments of its market and wasn't
+    /// sent, see `IncrementPolicy`
+    InvalidIncrement(IncrementError),
+    /// The endpoint passed to `Rest::new_with_endpoint` isn't an http(s) URL
+    InvalidEndpoint(String),
 }
 
 impl From<reqwest::Error> for Error {
@@ -12,3 +37,107 @@ impl From<reqwest::Error> for Error {
         Error::Reqwest(err)
     }
 }
+
+impl From<IncrementError> for Error {
+    fn from(err: IncrementError) -> Error {
+        Error::InvalidIncrement(err)
+    }
+}
+
+/// Rate limit headers of a response rejected with HTTP 429
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    /// Delay requested by the `Retry-After` header, given in seconds or as
+    /// a date
+    pub retry_after: Option<Duration>,
+    /// `X-RateLimit-Limit` header
+    pub limit: Option<u64>,
+    /// `X-RateLimit-Remaining` header
+    pub remaining: Option<u64>,
+}
+
+impl RateLimitInfo {
+    pub(super) fn from_headers(headers: &HeaderMap) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        let number = |name: &str| header(name).and_then(|value| value.trim().parse().ok());
+
+        Self {
+            retry_after: header(RETRY_AFTER.as_str()).and_then(parse_retry_after),
+            limit: number("x-ratelimit-limit"),
+            remaining: number("x-ratelimit-remaining"),
+        }
+    }
+}
+
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now())
+        .to_std()
+        .ok()
+        .or(Some(Duration::ZERO))
+}
+
+/// Error message returned by FTX, with common messages parsed into variants
+/// so that they can be matched. Compares equal to the message string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ApiError {
+    /// "Size too small"
+    SizeTooSmall,
+    /// "Not logged in", e.g. because of an invalid key or signature
+    NotLoggedIn,
+    /// "Invalid price"
+    InvalidPrice,
+    /// "Account does not have enough margin for order."
+    NotEnoughMargin,
+    /// "Order already closed"
+    OrderAlreadyClosed,
+    Other(String),
+}
+
+impl ApiError {
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::SizeTooSmall => "Size too small",
+            ApiError::NotLoggedIn => "Not logged in",
+            ApiError::InvalidPrice => "Invalid price",
+            ApiError::NotEnoughMargin => "Account does not have enough margin for order.",
+            ApiError::OrderAlreadyClosed => "Order already closed",
+            ApiError::Other(message) => message,
+        }
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        match message.trim_end_matches('.') {
+            "Size too small" => ApiError::SizeTooSmall,
+            "Invalid price" => ApiError::InvalidPrice,
+            "Order already closed" => ApiError::OrderAlreadyClosed,
+            m if m.starts_with("Not logged in") => ApiError::NotLoggedIn,
+            m if m.starts_with("Account does not have enough margin") => ApiError::NotEnoughMargin,
+            _ => ApiError::Other(message),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl PartialEq<str> for ApiError {
+    fn eq(&self, other: &str) -> bool {
+        self.message() == other
+    }
+}
+
+impl PartialEq<&str> for ApiError {
+    fn eq(&self, other: &&str) -> bool {
+        self.message() == *other
+    }
+}
@@ -0,0 +1,194 @@
+use super::{round_to_increment, Market, OrderRequest, OrderSide, Symbol};
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// What `Rest::submit_order` does with orders whose price or size doesn't
+/// match the increments of their market, which FTX rejects with e.g.
+/// "Price not divisible by increment". The increments are loaded with
+/// `GET /markets` when an order is placed on a market not seen before.
+/// Orders on markets that aren't listed are sent unchanged.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum IncrementPolicy {
+    /// Send orders unchanged
+    #[default]
+    Off,
+    /// Return `Error::InvalidIncrement` without sending the order
+    Validate,
+    /// Round buy prices down and sell prices up, so that the order is never
+    /// more aggressive than requested, and sizes down. Orders whose size
+    /// becomes too small return `Error::InvalidIncrement`.
+    Round,
+}
+
+/// Price and size increments of a market, see `Market`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MarketIncrements {
+    pub price_increment: Decimal,
+    pub size_increment: Decimal,
+    /// Minimum size of orders providing liquidity, only checked for post
+    /// only orders since other orders may take liquidity
+    pub min_provide_size: Decimal,
+}
+
+impl From<&Market> for MarketIncrements {
+    fn from(market: &Market) -> Self {
+        Self {
+            price_increment: market.price_increment,
+            size_increment: market.size_increment,
+            min_provide_size: market.min_provide_size,
+        }
+    }
+}
+
+/// Order rejected locally by an `IncrementPolicy`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IncrementError {
+    PriceNotDivisible {
+        market: Symbol,
+        price: Decimal,
+        increment: Decimal,
+    },
+    SizeNotDivisible {
+        market: Symbol,
+        size: Decimal,
+        increment: Decimal,
+    },
+    /// The size is zero after rounding, or a post only order is smaller
+    /// than the minimum provide size
+    SizeTooSmall {
+        market: Symbol,
+        size: Decimal,
+        min_size: Decimal,
+    },
+}
+
+impl fmt::Display for IncrementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncrementError::PriceNotDivisible {
+                market,
+                price,
+                increment,
+            } => write!(
+                f,
+                "Price {} of {} not divisible by increment {}",
+                price, market, increment
+            ),
+            IncrementError::SizeNotDivisible {
+                market,
+                size,
+                increment,
+            } => write!(
+                f,
+                "Size {} of {} not divisible by increment {}",
+                size, market, increment
+            ),
+            IncrementError::SizeTooSmall {
+                market,
+                size,
+                min_size,
+            } => write!(
+                f,
+                "Size {} of {} smaller than the minimum of {}",
+                size, market, min_size
+            ),
+        }
+    }
+}
+
+impl MarketIncrements {
+    /// Checks that the price and size of `order` are multiples of the
+    /// increments, or rounds them if `round` is set
+    pub fn apply(&self, order: &mut OrderRequest, round: bool) -> Result<(), IncrementError> {
+        if let (Some(price), true) = (order.price, self.price_increment > Decimal::ZERO) {
+            let steps = price / self.price_increment;
+            if !steps.fract().is_zero() {
+                if !round {
+                    return Err(IncrementError::PriceNotDivisible {
+                        market: order.market.clone(),
+                        price,
+                        increment: self.price_increment,
+                    });
+                }
+                let strategy = match order.side {
+                    OrderSide::Buy => RoundingStrategy::ToNegativeInfinity,
+                    OrderSide::Sell => RoundingStrategy::ToPositiveInfinity,
+                };
+                order.price = Some(round_to_increment(price, self.price_increment, strategy));
+            }
+        }
+
+        if self.size_increment > Decimal::ZERO {
+            let steps = order.size / self.size_increment;
+            if !steps.fract().is_zero() {
+                if !round {
+                    return Err(IncrementError::SizeNotDivisible {
+                        market: order.market.clone(),
+                        size: order.size,
+                        increment: self.size_increment,
+                    });
+                }
+                order.size =
+                    round_to_increment(order.size, self.size_increment, RoundingStrategy::ToZero);
+            }
+        }
+
+        let min_size = if order.flags.post_only {
+            self.min_provide_size.max(self.size_increment)
+        } else {
+            self.size_increment
+        };
+        if order.size <= Decimal::ZERO || order.size < min_size {
+            return Err(IncrementError::SizeTooSmall {
+                market: order.market.clone(),
+                size: order.size,
+                min_size,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Increments of the markets by name, shared by the handles of a `Rest`
+/// client
+#[derive(Clone, Debug, Default)]
+pub(super) struct IncrementCache(Arc<Mutex<Increments>>);
+
+#[derive(Debug, Default)]
+struct Increments {
+    markets: HashMap<Symbol, MarketIncrements>,
+    /// When all markets were last loaded, markets missing since are not
+    /// reloaded before `IncrementCache::MISS_TTL`
+    loaded_at: Option<Instant>,
+}
+
+impl IncrementCache {
+    /// How long a market missing from `GET /markets` is considered unlisted
+    const MISS_TTL: Duration = Duration::from_secs(60);
+
+    pub(super) fn get(&self, market: &str) -> Option<MarketIncrements> {
+        self.0.lock().unwrap().markets.get(market).copied()
+    }
+
+    /// Whether all markets were loaded within `MISS_TTL`, so that a missing
+    /// market isn't listed
+    pub(super) fn is_fresh(&self) -> bool {
+        let increments = self.0.lock().unwrap();
+        increments
+            .loaded_at
+            .is_some_and(|loaded_at| loaded_at.elapsed() < Self::MISS_TTL)
+    }
+
+    pub(super) fn update(&self, markets: &[Market]) {
+        let mut increments = self.0.lock().unwrap();
+        for market in markets {
+            increments
+                .markets
+                .insert(market.name.clone(), market.into());
+        }
+        increments.loaded_at = Some(Instant::now());
+    }
+}
@@ -1,76 +1,364 @@
 //! This module is used to interact with the REST API.
 
 mod error;
+mod increments;
 mod model;
+#[cfg(feature = "us")]
+mod nft;
+mod paginate;
+mod rate_limit;
+mod retry;
 #[cfg(test)]
 mod tests;
 
 pub use error::*;
+pub use increments::{IncrementError, IncrementPolicy, MarketIncrements};
 pub use model::*;
+#[cfg(feature = "us")]
+pub use nft::*;
+pub use paginate::{Paged, Paginated};
+pub use rate_limit::{RateLimitBudget, RateLimitPolicy};
+pub use retry::RetryPolicy;
 
-use chrono::{DateTime, Utc};
-use hmac_sha256::HMAC;
+/// Former name of `api::OrderEntry`, kept for compatibility
+pub use crate::api::OrderEntry as ExchangeApi;
+
+/// Future returned by the methods of `ExchangeApi`, kept for compatibility
+pub type ApiFuture<'a, T> = crate::api::BoxFuture<'a, Result<T>>;
+
+use crate::options::{Exchange, Options};
+use crate::signer::{HmacSigner, Signer};
+use chrono::{DateTime, SubsecRound, Utc};
+use increments::IncrementCache;
+use rate_limit::RateLimiter;
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
-    Client, ClientBuilder, Method,
+    Client, ClientBuilder, Method, StatusCode, Url,
 };
+use retry::Transient;
 use rust_decimal::prelude::*;
 use serde::de::DeserializeOwned;
-use serde_json::{json, Map, Value};
-use std::time::{SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 pub struct Rest {
-    secret: String,
+    signer: Arc<dyn Signer>,
+    /// Keeps connections alive between requests, so that orders don't wait
+    /// for a new connection
     client: Client,
+    /// Sent with every request, kept to rebuild the client
+    headers: HeaderMap,
     subaccount: Option<String>,
-    endpoint: &'static str,
-    header_prefix: &'static str,
+    endpoint: Url,
+    exchange: Exchange,
+    ts_header: HeaderName,
+    sign_header: HeaderName,
+    subaccount_header: HeaderName,
+    rate_limiter: RateLimiter,
+    retry_policy: RetryPolicy,
+    /// Milliseconds added to the local time when signing requests
+    clock_offset: Arc<AtomicI64>,
+    increment_policy: IncrementPolicy,
+    increments: IncrementCache,
 }
 
 impl Rest {
     pub const ENDPOINT: &'static str = "https://ftx.com/api";
     pub const ENDPOINT_US: &'static str = "https://ftx.us/api";
 
-    fn new_with_endpoint(
-        endpoint: &'static str,
-        header_prefix: &'static str,
+    /// Maximum number of candles returned by a single request
+    const CANDLES_PER_REQUEST: u32 = 1500;
+
+    /// Maximum number of trades returned by a single request
+    const TRADES_PER_REQUEST: u32 = 5000;
+
+    /// Maximum number of funding rates returned by a single request
+    const FUNDING_RATES_PER_REQUEST: usize = 500;
+
+    /// Paths of endpoints only available on exchanges listing futures
+    const FUTURES_PATHS: &'static [&'static str] = &[
+        "/futures",
+        "/funding_rates",
+        "/funding_payments",
+        "/indexes",
+    ];
+
+    /// Paths of endpoints only available on exchanges listing options
+    const OPTIONS_PATHS: &'static [&'static str] = &["/options", "/stats/24h_options_volume"];
+
+    /// Connects to a custom endpoint, e.g. a `testing::MockRest` server, a
+    /// local replay, or a mirror. `header_prefix` is `FTX` or `FTXUS`, which
+    /// also selects the `Exchange` whose endpoints are available. Fails with
+    /// `Error::InvalidEndpoint` if `endpoint` isn't an http(s) URL.
+    pub fn new_with_endpoint(
+        endpoint: &str,
+        header_prefix: &str,
         key: String,
         secret: String,
         subaccount: Option<String>,
+    ) -> Result<Self> {
+        let signer = Arc::new(HmacSigner::from_secret(secret));
+        Self::new_with_signer(endpoint, header_prefix, key, signer, subaccount)
+    }
+
+    /// Connects to a custom endpoint, signing requests with `signer` instead
+    /// of a secret held in memory, see `signer::Signer`
+    pub fn new_with_signer(
+        endpoint: &str,
+        header_prefix: &str,
+        key: String,
+        signer: Arc<dyn Signer>,
+        subaccount: Option<String>,
+    ) -> Result<Self> {
+        let endpoint = Self::parse_endpoint(endpoint)?;
+        Ok(Self::new_with_url(
+            endpoint,
+            header_prefix,
+            key,
+            signer,
+            subaccount,
+        ))
+    }
+
+    fn parse_endpoint(endpoint: &str) -> Result<Url> {
+        match Url::parse(endpoint.trim_end_matches('/')) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && !url.cannot_be_a_base() => {
+                Ok(url)
+            }
+            _ => Err(Error::InvalidEndpoint(endpoint.to_string())),
+        }
+    }
+
+    fn new_with_url(
+        endpoint: Url,
+        header_prefix: &str,
+        key: String,
+        signer: Arc<dyn Signer>,
+        subaccount: Option<String>,
     ) -> Self {
         // Set default headers.
         let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
         headers.insert(
             HeaderName::from_str(&format!("{}-KEY", header_prefix)).unwrap(),
             HeaderValue::from_str(&key).unwrap(),
         );
-        if let Some(subaccount) = subaccount.to_owned() {
-            headers.insert(
-                HeaderName::from_str(&format!("{}-SUBACCOUNT", header_prefix)).unwrap(),
-                HeaderValue::from_str(&subaccount).unwrap(),
-            );
-        }
 
-        let client = ClientBuilder::new()
-            .default_headers(headers)
-            .build()
-            .unwrap();
+        let client = Self::client_builder(&headers).build().unwrap();
 
         Self {
-            secret,
+            signer,
             client,
+            headers,
             subaccount,
             endpoint,
-            header_prefix,
+            exchange: match header_prefix {
+                "FTXUS" => Exchange::Us,
+                _ => Exchange::Com,
+            },
+            ts_header: HeaderName::from_str(&format!("{}-TS", header_prefix)).unwrap(),
+            sign_header: HeaderName::from_str(&format!("{}-SIGN", header_prefix)).unwrap(),
+            subaccount_header: HeaderName::from_str(&format!("{}-SUBACCOUNT", header_prefix))
+                .unwrap(),
+            rate_limiter: RateLimiter::new(),
+            retry_policy: RetryPolicy::default(),
+            clock_offset: Arc::new(AtomicI64::new(0)),
+            increment_policy: IncrementPolicy::default(),
+            increments: IncrementCache::default(),
+        }
+    }
+
+    /// Returns a handle making requests on behalf of `subaccount`, or the
+    /// main account if `None`, with the same credentials and settings. The
+    /// handles share their connections, clock offset, market increments, and
+    /// rate limits, unless a limit is changed on one of them.
+    pub fn with_subaccount(&self, subaccount: Option<&str>) -> Self {
+        Self {
+            signer: self.signer.clone(),
+            client: self.client.clone(),
+            headers: self.headers.clone(),
+            subaccount: subaccount.map(str::to_string),
+            endpoint: self.endpoint.clone(),
+            exchange: self.exchange,
+            ts_header: self.ts_header.clone(),
+            sign_header: self.sign_header.clone(),
+            subaccount_header: self.subaccount_header.clone(),
+            rate_limiter: self.rate_limiter.share(),
+            retry_policy: self.retry_policy,
+            clock_offset: self.clock_offset.clone(),
+            increment_policy: self.increment_policy,
+            increments: self.increments.clone(),
         }
     }
 
     pub fn new(key: String, secret: String, subaccount: Option<String>) -> Self {
-        Self::new_with_endpoint(Self::ENDPOINT, "FTX", key, secret, subaccount)
+        Self::new_with_exchange(Exchange::Com, key, secret, subaccount)
     }
 
     pub fn new_us(key: String, secret: String, subaccount: Option<String>) -> Self {
-        Self::new_with_endpoint(Self::ENDPOINT_US, "FTXUS", key, secret, subaccount)
+        Self::new_with_exchange(Exchange::Us, key, secret, subaccount)
+    }
+
+    /// Connects to the endpoint of `exchange`
+    pub fn new_with_exchange(
+        exchange: Exchange,
+        key: String,
+        secret: String,
+        subaccount: Option<String>,
+    ) -> Self {
+        // The endpoints of the exchanges are known to be valid
+        let endpoint = Url::parse(exchange.rest()).unwrap();
+        Self::new_with_url(
+            endpoint,
+            exchange.header_prefix(),
+            key,
+            Arc::new(HmacSigner::from_secret(secret)),
+            subaccount,
+        )
+    }
+
+    fn client_builder(headers: &HeaderMap) -> ClientBuilder {
+        ClientBuilder::new()
+            .default_headers(headers.clone())
+            .tcp_nodelay(true)
+            .tcp_keepalive(Duration::from_secs(30))
+            .pool_idle_timeout(None)
+            // Connections negotiated as HTTP/2 are pinged while idle, so
+            // that a dropped connection is noticed before the next order
+            .http2_keep_alive_interval(Duration::from_secs(30))
+            .http2_keep_alive_while_idle(true)
+    }
+
+    /// Set the proxy requests are sent through, e.g.
+    /// `reqwest::Proxy::all("http://proxy:3128")`, or `None` to connect
+    /// directly. By default, the proxy configured by the `HTTPS_PROXY`,
+    /// `HTTP_PROXY`, or `ALL_PROXY` environment variables is used.
+    pub fn set_proxy(&mut self, proxy: Option<reqwest::Proxy>) -> Result<()> {
+        let builder = Self::client_builder(&self.headers);
+        let builder = match proxy {
+            Some(proxy) => builder.proxy(proxy),
+            None => builder.no_proxy(),
+        };
+        self.client = builder.build()?;
+
+        Ok(())
+    }
+
+    /// Connects to the exchange of `options` with its credentials, see
+    /// `Options::from_env`
+    pub fn from_options(options: &Options) -> Self {
+        Self::new_with_exchange(
+            options.exchange,
+            options.key.clone().unwrap_or_default(),
+            options.secret.clone().unwrap_or_default(),
+            options.subaccount.clone(),
+        )
+    }
+
+    /// Endpoint requests are sent to, e.g. `Rest::ENDPOINT`
+    pub fn endpoint(&self) -> &str {
+        self.endpoint.as_str().trim_end_matches('/')
+    }
+
+    /// Exchange whose endpoints are available, see `Exchange`
+    pub fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+
+    /// Subaccount all requests are made on behalf of, if any
+    pub fn subaccount(&self) -> Option<&str> {
+        self.subaccount.as_deref()
+    }
+
+    /// Set what happens to requests that would exceed the rate limit,
+    /// `RateLimitPolicy::Wait` by default
+    pub fn set_rate_limit_policy(&mut self, policy: RateLimitPolicy) {
+        self.rate_limiter.policy = policy;
+    }
+
+    /// Set the maximum number of requests per second, including order
+    /// placement. Defaults to FTX's limit of 30 requests per second.
+    pub fn set_rate_limit(&mut self, requests_per_second: u32) {
+        self.rate_limiter
+            .set_requests_per_second(requests_per_second);
+    }
+
+    /// Set the maximum number of orders placed or modified per second.
+    /// Defaults to 10, the limit of the lowest account tier.
+    pub fn set_order_rate_limit(&mut self, orders_per_second: u32) {
+        self.rate_limiter.set_orders_per_second(orders_per_second);
+    }
+
+    /// Rate limits this handle draws from, pass it to
+    /// `set_rate_limit_budget` of other clients using the same API key
+    pub fn rate_limit_budget(&self) -> RateLimitBudget {
+        self.rate_limiter.budget.clone()
+    }
+
+    /// Draw from `budget`, shared with the other clients using it, instead
+    /// of separate rate limits. Replaced again by `set_rate_limit` and
+    /// `set_order_rate_limit`.
+    pub fn set_rate_limit_budget(&mut self, budget: RateLimitBudget) {
+        self.rate_limiter.budget = budget;
+    }
+
+    /// Set how requests that failed with HTTP 429, a server error, or a
+    /// dropped connection are retried, see `RetryPolicy`
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    /// Set whether orders are checked against, or rounded to, the price and
+    /// size increments of their market before being sent, see
+    /// `IncrementPolicy`
+    pub fn set_increment_policy(&mut self, increment_policy: IncrementPolicy) {
+        self.increment_policy = increment_policy;
+    }
+
+    /// Set the difference between the server's and the local clock, which
+    /// is added to the local time when signing requests
+    pub fn set_clock_offset(&self, offset: chrono::Duration) {
+        self.clock_offset
+            .store(offset.num_milliseconds(), Ordering::Relaxed);
+    }
+
+    pub fn clock_offset(&self) -> chrono::Duration {
+        chrono::Duration::milliseconds(self.clock_offset.load(Ordering::Relaxed))
+    }
+
+    /// Measures the difference between the server's and the local clock and
+    /// applies it to subsequent requests. Use when requests are rejected
+    /// because the local clock drifted.
+    pub async fn sync_clock(&self) -> Result<chrono::Duration> {
+        let sent = Utc::now();
+        let server_time = self.get_server_time().await?;
+        let received = Utc::now();
+
+        // Assume the server time was taken halfway through the round trip
+        let offset = server_time - (sent + (received - sent) / 2);
+        self.set_clock_offset(offset);
+
+        Ok(self.clock_offset())
+    }
+
+    /// Opens a connection ahead of time, so that the first order doesn't
+    /// wait for the TCP and TLS handshakes. Connections are kept alive
+    /// between requests.
+    pub async fn warm_up(&self) -> Result<()> {
+        self.get_server_time().await?;
+
+        Ok(())
+    }
+
+    pub async fn get_server_time(&self) -> Result<DateTime<Utc>> {
+        self.get("/time", None).await
     }
 
     async fn get<T: DeserializeOwned>(&self, path: &str, params: Option<Value>) -> Result<T> {
@@ -85,6 +373,10 @@ impl Rest {
         self.request(Method::DELETE, path, None, body).await
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, params, body))
+    )]
     async fn request<T: DeserializeOwned>(
         &self,
         method: Method,
@@ -92,50 +384,126 @@ impl Rest {
         params: Option<Value>,
         body: Option<Value>,
     ) -> Result<T> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
+        let unsupported = |paths: &[&str]| paths.iter().any(|p| path.starts_with(p));
+        if (!self.exchange.has_futures() && unsupported(Self::FUTURES_PATHS))
+            || (!self.exchange.has_options() && unsupported(Self::OPTIONS_PATHS))
+        {
+            return Err(Error::UnsupportedOnExchange(self.exchange));
+        }
+
+        let order = method == Method::POST
+            && (path.starts_with("/orders") || path.starts_with("/conditional_orders"));
+        // POST requests, e.g. orders or accepting quotes, may be retried after
+        // an ambiguous failure only if FTX can detect duplicates by their
+        // client ID
+        let idempotent = method != Method::POST
+            || body
+                .as_ref()
+                .and_then(|body| body.get("clientId"))
+                .is_some_and(|client_id| !client_id.is_null());
         let body = if let Some(body) = body {
             format!("{}", body)
         } else {
             String::new()
         };
-        let url = format!("{}{}", self.endpoint, path);
-        let sign_payload = format!("{}{}/api{}{}", timestamp, method, path, body);
-        let sign = HMAC::mac(sign_payload.as_bytes(), self.secret.as_bytes());
-        let sign = hex::encode(sign);
-        let params = params.map(|value| {
-            if let Value::Object(map) = value {
+        let mut url = self.endpoint.clone();
+        url.set_path(&format!(
+            "{}{}",
+            self.endpoint.path().trim_end_matches('/'),
+            path
+        ));
+        if let Some(params) = params {
+            let params = if let Value::Object(map) = params {
                 map.into_iter()
                     .filter(|(_, v)| v != &Value::Null)
-                    .collect::<Map<String, Value>>()
+                    .map(|(k, v)| match v {
+                        Value::String(v) => (k, v),
+                        v => (k, v.to_string()),
+                    })
+                    .collect::<Vec<(String, String)>>()
             } else {
                 panic!("Invalid params.");
+            };
+            if !params.is_empty() {
+                url.query_pairs_mut().extend_pairs(params);
             }
-        });
+        }
 
-        log::trace!("timestamp: {}", timestamp);
-        log::trace!("method: {}", method);
-        log::trace!("path: {}", path);
-        log::trace!("body: {}", body);
+        let mut attempt = 1;
+        loop {
+            let (result, transient) = self.send(&method, &url, &body, order).await;
 
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            reqwest::header::CONTENT_TYPE,
-            HeaderValue::from_static("application/json"),
-        );
-        headers.insert(
-            HeaderName::from_str(&format!("{}-TS", self.header_prefix)).unwrap(),
-            HeaderValue::from_str(&format!("{}", timestamp)).unwrap(),
-        );
+            let retry = match transient {
+                Some(Transient::NotProcessed) => true,
+                Some(Transient::MaybeProcessed) => idempotent,
+                None => false,
+            };
+            if !retry || attempt >= self.retry_policy.max_attempts {
+                return result;
+            }
+
+            // Wait as long as FTX asks to, if it does
+            let delay = match &result {
+                Err(Error::TooManyRequests(RateLimitInfo {
+                    retry_after: Some(retry_after),
+                    ..
+                })) => *retry_after,
+                _ => self.retry_policy.backoff(attempt),
+            };
+            warn!(
+                "Request {} {} failed ({:?}), retrying in {:?}",
+                method,
+                path,
+                result.err(),
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Signs and sends a single attempt of a request. Failures that may
+    /// succeed if retried are returned along with their `Transient` reason.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: &str,
+        order: bool,
+    ) -> (Result<T>, Option<Transient>) {
+        let wait = match self.rate_limiter.reserve(order) {
+            Ok(wait) => wait,
+            Err(err) => return (Err(err), None),
+        };
+        if wait > Duration::from_secs(0) {
+            debug!("Rate limit reached, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+
+        let timestamp = Utc::now().timestamp_millis() + self.clock_offset.load(Ordering::Relaxed);
+        // The signature covers the query string as well
+        let path_and_query = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let sign_payload = format!("{}{}{}{}", timestamp, method, path_and_query, body);
+        let sign = hex::encode(self.signer.sign(sign_payload.as_bytes()));
+
+        trace!("timestamp: {}", timestamp);
+        trace!("method: {}", method);
+        trace!("path: {}", path_and_query);
+        trace!("body: {}", body);
+
+        // Key and content type are default headers of the client
+        let mut headers = HeaderMap::with_capacity(3);
+        headers.insert(self.ts_header.clone(), HeaderValue::from(timestamp));
         headers.insert(
-            HeaderName::from_str(&format!("{}-SIGN", self.header_prefix)).unwrap(),
+            self.sign_header.clone(),
             HeaderValue::from_str(&sign).unwrap(),
         );
         if let Some(subaccount) = &self.subaccount {
             headers.insert(
-                HeaderName::from_str(&format!("{}-SUBACCOUNT", self.header_prefix)).unwrap(),
+                self.subaccount_header.clone(),
                 HeaderValue::from_str(subaccount).unwrap(),
             );
         }
@@ -144,7 +512,6 @@ impl Rest {
         let response: String = self
             .client
             .request(method, url)
-            .query(&params)
             .headers(headers)
             .body(body)
             .send()
@@ -160,27 +527,74 @@ impl Rest {
         panic!("{:#?}", response);
         */
 
-        let response: Response<T> = self
+        let started = Instant::now();
+        let response = match self
             .client
-            .request(method, url)
-            .query(&params)
+            .request(method.clone(), url.clone())
             .headers(headers)
-            .body(body)
+            .body(body.to_string())
             .send()
-            .await?
-            .json()
-            .await?;
+            .await
+        {
+            Ok(response) => response,
+            Err(err) => {
+                debug!(
+                    "{} {} failed after {:?}: {}",
+                    method,
+                    url.path(),
+                    started.elapsed(),
+                    err
+                );
+                // Nothing was sent if the connection could not be established
+                let transient = if err.is_connect() {
+                    Transient::NotProcessed
+                } else {
+                    Transient::MaybeProcessed
+                };
+                return (Err(err.into()), Some(transient));
+            }
+        };
+
+        let status = response.status();
+        debug!(
+            "{} {} returned {} after {:?}",
+            method,
+            url.path(),
+            status,
+            started.elapsed()
+        );
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let info = RateLimitInfo::from_headers(response.headers());
+            return (
+                Err(Error::TooManyRequests(info)),
+                Some(Transient::NotProcessed),
+            );
+        }
+        let transient = if status.is_server_error() {
+            Some(Transient::MaybeProcessed)
+        } else {
+            None
+        };
+
+        let result = match response.json::<Response<T>>().await {
+            Ok(Response::Result { result, .. }) => Ok(result),
+            Ok(Response::Error { error, .. }) => Err(Error::Api(error.into())),
+            Err(err) => Err(err.into()),
+        };
 
-        match response {
-            Response::Result { result, .. } => Ok(result),
-            Response::Error { error, .. } => Err(Error::Api(error)),
+        match result {
+            Ok(result) => (Ok(result), None),
+            Err(err) => (Err(err), transient),
         }
     }
 
+    /// Requires account-wide API credentials.
+    /// See https://docs.ftx.com/#get-all-subaccounts.
     pub async fn get_subaccounts(&self) -> Result<Subaccounts> {
         self.get("/subaccounts", None).await
     }
 
+    /// See https://docs.ftx.com/#create-subaccount.
     pub async fn create_subaccount(&self, nickname: &str) -> Result<Create> {
         self.post(
             "/subaccounts",
@@ -191,6 +605,7 @@ impl Rest {
         .await
     }
 
+    /// See https://docs.ftx.com/#change-subaccount-name.
     pub async fn change_subaccount_name(
         &self,
         nickname: &str,
@@ -206,6 +621,7 @@ impl Rest {
         .await
     }
 
+    /// See https://docs.ftx.com/#delete-subaccount.
     pub async fn delete_subaccount(&self, nickname: &str) -> Result<Delete> {
         self.delete(
             "/subaccounts",
@@ -216,11 +632,15 @@ impl Rest {
         .await
     }
 
+    /// See https://docs.ftx.com/#get-subaccount-balances.
     pub async fn get_subaccount_balances(&self, nickname: &str) -> Result<Balances> {
         self.get(&format!("/subaccounts/{}/balances", nickname), None)
             .await
     }
 
+    /// Transfers `size` of `coin` from `source` to `destination`, use "main"
+    /// for the main account.
+    /// See https://docs.ftx.com/#transfer-between-subaccounts.
     pub async fn transfer_between_subaccounts(
         &self,
         coin: &str,
@@ -240,14 +660,36 @@ impl Rest {
         .await
     }
 
+    /// Returns all markets.
+    /// See https://docs.ftx.com/#get-markets.
     pub async fn get_markets(&self) -> Result<Markets> {
-        self.get("/markets", None).await
+        let markets: Markets = self.get("/markets", None).await?;
+        self.increments.update(&markets);
+        Ok(markets)
     }
 
+    /// Returns a single market.
+    /// See https://docs.ftx.com/#get-single-market.
     pub async fn get_market(&self, market_name: &str) -> Result<Market> {
         self.get(&format!("/markets/{}", market_name), None).await
     }
 
+    /// Returns the increments of a market, cached by `get_markets` which is
+    /// called if the market wasn't seen yet. `None` if it isn't listed, in
+    /// which case the markets are loaded again at most once a minute.
+    pub async fn market_increments(&self, market_name: &str) -> Result<Option<MarketIncrements>> {
+        if let Some(increments) = self.increments.get(market_name) {
+            return Ok(Some(increments));
+        }
+        if self.increments.is_fresh() {
+            return Ok(None);
+        }
+        self.get_markets().await?;
+        Ok(self.increments.get(market_name))
+    }
+
+    /// Returns the orderbook of a market, `depth` defaults to 20 (max 100).
+    /// See https://docs.ftx.com/#get-orderbook.
     pub async fn get_orderbook(&self, market_name: &str, depth: Option<u32>) -> Result<Orderbook> {
         self.get(
             &format!("/markets/{}/orderbook", market_name),
@@ -258,6 +700,8 @@ impl Rest {
         .await
     }
 
+    /// Returns the latest trades of a market, `limit` defaults to 20 (max 100).
+    /// See https://docs.ftx.com/#get-trades.
     pub async fn get_trades(
         &self,
         market_name: &str,
@@ -276,6 +720,59 @@ impl Rest {
         .await
     }
 
+    /// Returns all trades of a market between `start_time` and `end_time`,
+    /// sorted by time. Pages through the trades backwards from `end_time`.
+    /// See https://docs.ftx.com/#get-trades.
+    pub async fn get_all_trades(
+        &self,
+        market_name: &str,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Trades> {
+        let mut trades = Vec::new();
+        let mut seen = HashSet::new();
+        let mut cursor = end_time;
+
+        while cursor >= start_time {
+            let page = self
+                .get_trades(
+                    market_name,
+                    Some(Self::TRADES_PER_REQUEST),
+                    Some(start_time),
+                    Some(cursor),
+                )
+                .await?;
+
+            let earliest = match page.iter().map(|trade| trade.time).min() {
+                Some(earliest) => earliest.trunc_subsecs(0),
+                None => break,
+            };
+
+            // Cursors have a resolution of one second, so the trades of the
+            // earliest second are requested again with the next page
+            let len = trades.len();
+            trades.extend(page.into_iter().filter(|trade| seen.insert(trade.id)));
+            if trades.len() == len {
+                break;
+            }
+
+            cursor = if earliest < cursor.trunc_subsecs(0) {
+                earliest
+            } else {
+                // The whole page is within a single second
+                cursor.trunc_subsecs(0) - chrono::Duration::seconds(1)
+            };
+        }
+
+        trades.retain(|trade| trade.time >= start_time && trade.time <= end_time);
+        trades.sort_by_key(|trade| (trade.time, trade.id));
+
+        Ok(trades)
+    }
+
+    /// Returns candles of a market, `resolution` is the window length in
+    /// seconds (15, 60, 300, 900, 3600, 14400, 86400 or multiples of 86400).
+    /// See https://docs.ftx.com/#get-historical-prices.
     pub async fn get_historical_prices(
         &self,
         market_name: &str,
@@ -296,6 +793,52 @@ impl Rest {
         .await
     }
 
+    /// Returns all candles of a market between `start_time` and `end_time`,
+    /// sorted by time. Pages through the 1500 candles FTX returns per request,
+    /// starting at `end_time` and moving backwards.
+    /// See https://docs.ftx.com/#get-historical-prices.
+    pub async fn get_historical_candles(
+        &self,
+        market_name: &str,
+        resolution: u32,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Candles> {
+        let mut candles = BTreeMap::new();
+        let mut cursor = end_time;
+
+        while cursor >= start_time {
+            let page = self
+                .get_historical_prices(
+                    market_name,
+                    resolution,
+                    Some(Self::CANDLES_PER_REQUEST),
+                    Some(start_time),
+                    Some(cursor),
+                )
+                .await?;
+
+            let earliest = match page.iter().map(|candle| candle.start_time).min() {
+                // Stop if the page is empty or doesn't go further back
+                Some(earliest) if earliest < cursor => earliest,
+                _ => break,
+            };
+
+            // Pages overlap at the boundary, keyed by time to deduplicate
+            for candle in page {
+                candles.insert(candle.start_time, candle);
+            }
+
+            cursor = earliest - chrono::Duration::seconds(1);
+        }
+
+        Ok(candles
+            .into_iter()
+            .filter(|(time, _)| *time >= start_time && *time <= end_time)
+            .map(|(_, candle)| candle)
+            .collect())
+    }
+
     pub async fn get_futures(&self) -> Result<Futures> {
         self.get("/futures", None).await
     }
@@ -304,6 +847,111 @@ impl Rest {
         self.get(&format!("/futures/{}", future_name), None).await
     }
 
+    /// See https://docs.ftx.com/#get-future-stats.
+    pub async fn get_future_stats(&self, future_name: &str) -> Result<FutureStats> {
+        self.get(&format!("/futures/{}/stats", future_name), None)
+            .await
+    }
+
+    /// Returns funding rates, optionally only those of `future`.
+    /// See https://docs.ftx.com/#get-funding-rates.
+    pub async fn get_funding_rates(
+        &self,
+        future: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<FundingRates> {
+        self.get(
+            "/funding_rates",
+            Some(json!({
+                "future": future,
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
+    /// Returns all funding rates between `start_time` and `end_time`,
+    /// optionally only those of `future`, sorted by time. Pages through the
+    /// rates backwards from `end_time`.
+    /// See https://docs.ftx.com/#get-funding-rates.
+    pub async fn get_all_funding_rates(
+        &self,
+        future: Option<&str>,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<FundingRates> {
+        let mut rates = Vec::new();
+        let mut seen = HashSet::new();
+        let mut cursor = end_time;
+
+        while cursor >= start_time {
+            let page = self
+                .get_funding_rates(future, Some(start_time), Some(cursor))
+                .await?;
+            let full = page.len() >= Self::FUNDING_RATES_PER_REQUEST;
+
+            let earliest = match page.iter().map(|rate| rate.time).min() {
+                Some(earliest) => earliest.trunc_subsecs(0),
+                None => break,
+            };
+
+            // Rates of several futures share the same time, so the rates of
+            // the earliest second are requested again with the next page
+            let len = rates.len();
+            rates.extend(
+                page.into_iter()
+                    .filter(|rate| seen.insert((rate.future.clone(), rate.time))),
+            );
+            if !full || rates.len() == len {
+                break;
+            }
+
+            cursor = if earliest < cursor.trunc_subsecs(0) {
+                earliest
+            } else {
+                cursor.trunc_subsecs(0) - chrono::Duration::seconds(1)
+            };
+        }
+
+        rates.retain(|rate| rate.time >= start_time && rate.time <= end_time);
+        rates.sort_by(|a, b| (a.time, &a.future).cmp(&(b.time, &b.future)));
+
+        Ok(rates)
+    }
+
+    /// Returns the weights of the coins of an index, e.g. ALT, by coin.
+    /// See https://docs.ftx.com/#get-index-weights.
+    pub async fn get_index_weights(&self, index_name: &str) -> Result<HashMap<Coin, Decimal>> {
+        self.get(&format!("/indexes/{}/weights", index_name), None)
+            .await
+    }
+
+    /// Returns candles of the index underlying a future, e.g. BTC for
+    /// BTC-PERP, BTC-0325, and BTC-MOVE-0325. See `get_historical_prices`
+    /// for `resolution`.
+    /// See https://docs.ftx.com/#get-historical-index.
+    pub async fn get_index_candles(
+        &self,
+        index_name: &str,
+        resolution: u32,
+        limit: Option<u32>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<IndexCandles> {
+        self.get(
+            &format!("/indexes/{}/candles", index_name),
+            Some(json!({
+                "resolution": resolution,
+                "limit": limit,
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
     pub async fn get_account(&self) -> Result<Account> {
         self.get("/account", None).await
     }
@@ -312,85 +960,146 @@ impl Rest {
         self.get("/positions", None).await
     }
 
+    /// See https://docs.ftx.com/#change-account-leverage.
+    pub async fn change_account_leverage(&self, leverage: u32) -> Result<ChangeLeverage> {
+        self.post(
+            "/account/leverage",
+            Some(json!({
+                "leverage": leverage,
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#get-deposit-address.
     pub async fn get_wallet_deposit_address(
         &self,
         coin: &str,
         method: Option<&str>,
     ) -> Result<WalletDepositAddress> {
         self.get(
-            &format!(
-                "/wallet/deposit_address/{}{}",
-                coin,
-                if let Some(method) = method {
-                    format!("?method={}", method)
-                } else {
-                    "".to_string()
-                }
-            ),
-            None,
+            &format!("/wallet/deposit_address/{}", coin),
+            Some(json!({
+                "method": method,
+            })),
         )
         .await
     }
 
+    /// See https://docs.ftx.com/#get-coins.
+    pub async fn get_wallet_coins(&self) -> Result<Vec<WalletCoin>> {
+        self.get("/wallet/coins", None).await
+    }
+
+    /// Balances of fiat currencies, e.g. USD or EUR, as flagged by
+    /// `Rest::get_wallet_coins`
+    pub async fn get_fiat_balances(&self) -> Result<Vec<WalletBalance>> {
+        let fiat: HashSet<Coin> = self
+            .get_wallet_coins()
+            .await?
+            .into_iter()
+            .filter(|coin| coin.fiat)
+            .map(|coin| coin.id)
+            .collect();
+        let mut balances = self.get_wallet_balances().await?;
+        balances.retain(|balance| fiat.contains(&balance.coin));
+        Ok(balances)
+    }
+
+    /// See https://docs.ftx.com/#get-balances.
     pub async fn get_wallet_balances(&self) -> Result<Vec<WalletBalance>> {
         self.get("/wallet/balances", None).await
     }
 
+    /// Returns the balances of the main account and all subaccounts, keyed by
+    /// the subaccount nickname ("main" for the main account).
+    /// See https://docs.ftx.com/#get-balances-of-all-accounts.
+    pub async fn get_wallet_all_balances(&self) -> Result<HashMap<String, Vec<WalletBalance>>> {
+        self.get("/wallet/all_balances", None).await
+    }
+
+    /// See https://docs.ftx.com/#get-deposit-history.
     pub async fn get_wallet_deposits(
         &self,
         limit: Option<usize>,
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
     ) -> Result<Vec<WalletDeposit>> {
-        let mut params = vec![];
-        if let Some(limit) = limit {
-            params.push(format!("limit={}", limit));
-        }
-        if let Some(start_time) = start_time {
-            params.push(format!("start_time={}", start_time));
-        }
-        if let Some(end_time) = end_time {
-            params.push(format!("end_time={}", end_time));
-        }
+        self.get(
+            "/wallet/deposits",
+            Some(json!({
+                "limit": limit,
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
 
+    /// See https://docs.ftx.com/#get-withdrawal-history.
+    pub async fn get_wallet_withdrawals(
+        &self,
+        limit: Option<usize>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<WalletWithdrawal>> {
         self.get(
-            &format!(
-                "/wallet/deposits{}{}",
-                if params.is_empty() { "" } else { "?" },
-                params.join("&")
-            ),
-            None,
+            "/wallet/withdrawals",
+            Some(json!({
+                "limit": limit,
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#request-withdrawal.
+    pub async fn request_withdrawal(
+        &self,
+        withdrawal: &WithdrawalRequest,
+    ) -> Result<WalletWithdrawal> {
+        self.post(
+            "/wallet/withdrawals",
+            Some(serde_json::to_value(withdrawal).unwrap()),
         )
         .await
     }
 
     pub async fn get_open_orders(&self, market: &str) -> Result<Vec<OrderInfo>> {
-        self.get(&format!("/orders?market={}", market), None).await
+        self.get("/orders", Some(json!({ "market": market }))).await
+    }
+
+    /// Open orders of all markets.
+    /// See https://docs.ftx.com/#get-open-orders.
+    pub async fn get_all_open_orders(&self) -> Result<Vec<OrderInfo>> {
+        self.get("/orders", None).await
     }
 
+    /// Returns closed orders, optionally only those of `market`, newest
+    /// first. See `Rest::paginate_order_history` to page through all of
+    /// them.
+    /// See https://docs.ftx.com/#get-order-history.
     pub async fn get_order_history(
         &self,
-        market: &str,
+        market: Option<&str>,
         limit: Option<usize>,
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
     ) -> Result<Vec<OrderInfo>> {
-        let mut params = vec![format!("market={}", market)];
-        if let Some(limit) = limit {
-            params.push(format!("limit={}", limit));
-        }
-        if let Some(start_time) = start_time {
-            params.push(format!("start_time={}", start_time));
-        }
-        if let Some(end_time) = end_time {
-            params.push(format!("end_time={}", end_time));
-        }
-
-        self.get(&format!("/orders/history?{}", params.join("&")), None)
-            .await
-    }
-
-    #[allow(clippy::too_many_arguments)]
+        self.get(
+            "/orders/history",
+            Some(json!({
+                "market": market,
+                "limit": limit,
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub async fn place_order(
         &self,
         market: &str,
@@ -403,44 +1112,84 @@ impl Rest {
         post_only: Option<bool>,
         client_id: Option<&str>,
     ) -> Result<OrderInfo> {
+        self.submit_order(&OrderRequest {
+            market: market.into(),
+            side,
+            price,
+            r#type,
+            size,
+            flags: OrderFlags {
+                reduce_only: reduce_only.unwrap_or(false),
+                ioc: ioc.unwrap_or(false),
+                post_only: post_only.unwrap_or(false),
+                reject_on_price_band: false,
+            },
+            client_id: client_id.map(|id| id.to_string()),
+        })
+        .await
+    }
+
+    /// Places an order built with `OrderRequest`.
+    /// See https://docs.ftx.com/#place-order.
+    pub async fn submit_order(&self, order: &OrderRequest) -> Result<OrderInfo> {
         // Limit orders should have price specified
-        if let OrderType::Limit = r#type {
-            if price.is_none() {
+        if let OrderType::Limit = order.r#type {
+            if order.price.is_none() {
                 return Err(Error::PlacingLimitOrderRequiresPrice);
             }
         }
 
+        let mut order = Cow::Borrowed(order);
+        if self.increment_policy != IncrementPolicy::Off {
+            if let Some(increments) = self.market_increments(&order.market).await? {
+                let round = self.increment_policy == IncrementPolicy::Round;
+                increments.apply(order.to_mut(), round)?;
+            }
+        }
+
+        let mut body = serde_json::to_value(&order).unwrap();
+        // As per docs, send null for market orders
+        if let OrderType::Market = order.r#type {
+            body["price"] = Value::Null;
+        }
+
+        self.post("/orders", Some(body)).await
+    }
+
+    pub async fn modify_order(
+        &self,
+        order_id: Id,
+        price: Option<Decimal>,
+        size: Option<Decimal>,
+        client_id: Option<&str>,
+    ) -> Result<OrderInfo> {
         self.post(
-            "/orders",
+            format!("/orders/{}/modify", order_id).as_str(),
             Some(json!({
-                "market": market,
-                "side": side,
-                // As per docs, send null for market orders
-                "price": if let OrderType::Limit = r#type { price } else { None },
-                "type": r#type,
+                "price": price,
                 "size": size,
-                "reduceOnly": reduce_only.unwrap_or(false),
-                "ioc": ioc.unwrap_or(false),
-                "postOnly": post_only.unwrap_or(false),
                 "clientId": client_id,
             })),
         )
         .await
     }
 
-    pub async fn modify_order(
+    /// Modifies the order placed with `client_id`, which is replaced by a new
+    /// order with a new ID and optionally `new_client_id`.
+    /// See https://docs.ftx.com/#modify-order-by-client-id.
+    pub async fn modify_order_by_client_id(
         &self,
-        order_id: Id,
+        client_id: &str,
         price: Option<Decimal>,
         size: Option<Decimal>,
-        client_id: Option<&str>,
+        new_client_id: Option<&str>,
     ) -> Result<OrderInfo> {
         self.post(
-            format!("/orders/{}/modify", order_id).as_str(),
+            &format!("/orders/by_client_id/{}/modify", client_id),
             Some(json!({
                 "price": price,
                 "size": size,
-                "clientId": client_id,
+                "clientId": new_client_id,
             })),
         )
         .await
@@ -459,8 +1208,537 @@ impl Rest {
         self.delete(&format!("/orders/{}", order_id), None).await
     }
 
+    /// Cancels the open orders matching `filter`, e.g. all open orders with
+    /// `CancelAllFilter::new()`.
+    /// See https://docs.ftx.com/#cancel-all-orders.
+    pub async fn cancel_all_orders(&self, filter: &CancelAllFilter) -> Result<String> {
+        self.delete("/orders", Some(serde_json::to_value(filter).unwrap()))
+            .await
+    }
+
     pub async fn cancel_order_by_client_id(&self, client_id: &str) -> Result<String> {
         self.delete(&dbg!(format!("/orders/by_client_id/{}", client_id)), None)
             .await
     }
+
+    /// Returns open conditional orders, optionally filtered by market and type.
+    /// See https://docs.ftx.com/#get-open-trigger-orders.
+    pub async fn get_open_conditional_orders(
+        &self,
+        market: Option<&str>,
+        r#type: Option<ConditionalOrderType>,
+    ) -> Result<Vec<ConditionalOrderInfo>> {
+        self.get(
+            "/conditional_orders",
+            Some(json!({
+                "market": market,
+                "type": r#type.map(conditional_order_type_param),
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#get-trigger-order-triggers.
+    pub async fn get_conditional_order_triggers(&self, order_id: Id) -> Result<Vec<Trigger>> {
+        self.get(&format!("/conditional_orders/{}/triggers", order_id), None)
+            .await
+    }
+
+    /// See https://docs.ftx.com/#get-trigger-order-history.
+    pub async fn get_conditional_order_history(
+        &self,
+        market: Option<&str>,
+        limit: Option<usize>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<ConditionalOrderInfo>> {
+        self.get(
+            "/conditional_orders/history",
+            Some(json!({
+                "market": market,
+                "limit": limit,
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#place-trigger-order.
+    pub async fn place_conditional_order(
+        &self,
+        order: &ConditionalOrderRequest,
+    ) -> Result<ConditionalOrderInfo> {
+        self.post(
+            "/conditional_orders",
+            Some(serde_json::to_value(order).unwrap()),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#modify-trigger-order.
+    pub async fn modify_conditional_order(
+        &self,
+        order_id: Id,
+        size: Decimal,
+        trigger_price: Option<Decimal>,
+        order_price: Option<Decimal>,
+        trail_value: Option<Decimal>,
+    ) -> Result<ConditionalOrderInfo> {
+        self.post(
+            &format!("/conditional_orders/{}/modify", order_id),
+            Some(json!({
+                "size": size,
+                "triggerPrice": trigger_price,
+                "orderPrice": order_price,
+                "trailValue": trail_value,
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#cancel-open-trigger-order.
+    pub async fn cancel_conditional_order(&self, order_id: Id) -> Result<String> {
+        self.delete(&format!("/conditional_orders/{}", order_id), None)
+            .await
+    }
+
+    /// Returns fills, optionally only those of `market`.
+    /// See https://docs.ftx.com/#fills.
+    pub async fn get_fills(
+        &self,
+        market: Option<&str>,
+        limit: Option<usize>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Fills> {
+        self.get(
+            "/fills",
+            Some(json!({
+                "market": market,
+                "limit": limit,
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
+    /// Returns funding payments, optionally only those of `future`.
+    /// See https://docs.ftx.com/#funding-payments.
+    pub async fn get_funding_payments(
+        &self,
+        future: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<FundingPayments> {
+        self.get(
+            "/funding_payments",
+            Some(json!({
+                "future": future,
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
+    /// Requests a quote to convert `size` of `from_coin` into `to_coin`,
+    /// e.g. to convert dust balances.
+    /// See https://docs.ftx.com/#request-quote.
+    pub async fn request_quote(
+        &self,
+        from_coin: &str,
+        to_coin: &str,
+        size: Decimal,
+    ) -> Result<QuoteId> {
+        self.post(
+            "/otc/quotes",
+            Some(json!({
+                "fromCoin": from_coin,
+                "toCoin": to_coin,
+                "size": size,
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#get-quote-status.
+    pub async fn get_quote_status(&self, quote_id: Id) -> Result<Quote> {
+        self.get(&format!("/otc/quotes/{}", quote_id), None).await
+    }
+
+    /// Accepts a quote before it expires, see `Quote::expiry`.
+    /// See https://docs.ftx.com/#accept-quote.
+    pub async fn accept_quote(&self, quote_id: Id) -> Result<AcceptQuote> {
+        self.post(&format!("/otc/quotes/{}/accept", quote_id), None)
+            .await
+    }
+
+    /// See https://docs.ftx.com/#get-stakes.
+    pub async fn get_stakes(&self) -> Result<Vec<Stake>> {
+        self.get("/staking/stakes", None).await
+    }
+
+    /// See https://docs.ftx.com/#unstake-request.
+    pub async fn get_unstake_requests(&self) -> Result<Vec<UnstakeRequest>> {
+        self.get("/staking/unstake_requests", None).await
+    }
+
+    /// See https://docs.ftx.com/#get-stake-balances.
+    pub async fn get_stake_balances(&self) -> Result<Vec<StakeBalance>> {
+        self.get("/staking/balances", None).await
+    }
+
+    /// Requests to unstake `size` of `coin`, which becomes available at
+    /// `UnstakeRequest::unlock_at`.
+    /// See https://docs.ftx.com/#unstake-request-2.
+    pub async fn request_unstake(&self, coin: &str, size: Decimal) -> Result<UnstakeRequest> {
+        self.post(
+            "/staking/unstake_requests",
+            Some(json!({
+                "coin": coin,
+                "size": size,
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#cancel-unstake-request.
+    pub async fn cancel_unstake_request(&self, request_id: Id) -> Result<String> {
+        self.delete(&format!("/staking/unstake_requests/{}", request_id), None)
+            .await
+    }
+
+    /// See https://docs.ftx.com/#get-staking-rewards.
+    pub async fn get_staking_rewards(&self) -> Result<Vec<StakingReward>> {
+        self.get("/staking/staking_rewards", None).await
+    }
+
+    /// Stakes `size` of SRM, MSRM, or SRM_LOCKED.
+    /// See https://docs.ftx.com/#stake-request.
+    pub async fn stake_srm(&self, coin: &str, size: Decimal) -> Result<Stake> {
+        self.post(
+            "/srm_stakes/stakes",
+            Some(json!({
+                "coin": coin,
+                "size": size,
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#list-leveraged-tokens.
+    pub async fn get_lt_tokens(&self) -> Result<Vec<LeveragedToken>> {
+        self.get("/lt/tokens", None).await
+    }
+
+    /// See https://docs.ftx.com/#get-token-info.
+    pub async fn get_lt_token(&self, token: &str) -> Result<LeveragedToken> {
+        self.get(&format!("/lt/{}", token), None).await
+    }
+
+    /// See https://docs.ftx.com/#get-leveraged-token-balances.
+    pub async fn get_lt_balances(&self) -> Result<Vec<LeveragedTokenBalance>> {
+        self.get("/lt/balances", None).await
+    }
+
+    /// See https://docs.ftx.com/#list-leveraged-token-creation-requests.
+    pub async fn get_lt_creations(&self) -> Result<Vec<LeveragedTokenCreation>> {
+        self.get("/lt/creations", None).await
+    }
+
+    /// Requests the creation of `size` tokens, paid in USD.
+    /// See https://docs.ftx.com/#request-leveraged-token-creation.
+    pub async fn create_lt(&self, token: &str, size: Decimal) -> Result<LeveragedTokenCreation> {
+        self.post(
+            &format!("/lt/{}/create", token),
+            Some(json!({
+                "size": size,
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#list-leveraged-token-redemption-requests.
+    pub async fn get_lt_redemptions(&self) -> Result<Vec<LeveragedTokenRedemption>> {
+        self.get("/lt/redemptions", None).await
+    }
+
+    /// Requests the redemption of `size` tokens for USD, which is processed
+    /// asynchronously, see `Rest::wait_for_lt_redemption`.
+    /// See https://docs.ftx.com/#request-leveraged-token-redemption.
+    pub async fn redeem_lt(&self, token: &str, size: Decimal) -> Result<LeveragedTokenRedemption> {
+        self.post(
+            &format!("/lt/{}/redeem", token),
+            Some(json!({
+                "size": size,
+            })),
+        )
+        .await
+    }
+
+    /// Polls the redemption requests every `poll_interval` until the one with
+    /// `redemption_id` is no longer pending, and returns it with its proceeds.
+    pub async fn wait_for_lt_redemption(
+        &self,
+        redemption_id: Id,
+        poll_interval: Duration,
+    ) -> Result<LeveragedTokenRedemption> {
+        loop {
+            let redemption = self
+                .get_lt_redemptions()
+                .await?
+                .into_iter()
+                .find(|redemption| redemption.id == redemption_id)
+                .ok_or(Error::RedemptionNotFound(redemption_id))?;
+            if !redemption.pending {
+                return Ok(redemption);
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Open quote requests of all users.
+    /// See https://docs.ftx.com/#list-quote-requests.
+    pub async fn get_option_quote_requests(&self) -> Result<Vec<OptionQuoteRequest>> {
+        self.get("/options/requests", None).await
+    }
+
+    /// See https://docs.ftx.com/#your-quote-requests.
+    pub async fn get_my_option_quote_requests(&self) -> Result<Vec<OptionQuoteRequest>> {
+        self.get("/options/my_requests", None).await
+    }
+
+    /// See https://docs.ftx.com/#create-quote-request.
+    pub async fn create_option_quote_request(
+        &self,
+        request: &OptionRequest,
+    ) -> Result<OptionQuoteRequest> {
+        self.post(
+            "/options/requests",
+            Some(serde_json::to_value(request).unwrap()),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#cancel-quote-request.
+    pub async fn cancel_option_quote_request(&self, request_id: Id) -> Result<OptionQuoteRequest> {
+        self.delete(&format!("/options/requests/{}", request_id), None)
+            .await
+    }
+
+    /// Quotes received on one of your quote requests.
+    /// See https://docs.ftx.com/#get-quotes-for-your-quote-request.
+    pub async fn get_option_quotes(&self, request_id: Id) -> Result<Vec<OptionQuote>> {
+        self.get(&format!("/options/requests/{}/quotes", request_id), None)
+            .await
+    }
+
+    /// Quotes `price` per contract on the quote request of another user.
+    /// See https://docs.ftx.com/#create-quote.
+    pub async fn create_option_quote(&self, request_id: Id, price: Decimal) -> Result<OptionQuote> {
+        self.post(
+            &format!("/options/requests/{}/quotes", request_id),
+            Some(json!({
+                "price": price,
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#get-my-quotes.
+    pub async fn get_my_option_quotes(&self) -> Result<Vec<OptionQuote>> {
+        self.get("/options/my_quotes", None).await
+    }
+
+    /// See https://docs.ftx.com/#cancel-quote.
+    pub async fn cancel_option_quote(&self, quote_id: Id) -> Result<OptionQuote> {
+        self.delete(&format!("/options/quotes/{}", quote_id), None)
+            .await
+    }
+
+    /// Accepts a quote received on one of your quote requests.
+    /// See https://docs.ftx.com/#accept-options-quote.
+    pub async fn accept_option_quote(&self, quote_id: Id) -> Result<OptionQuote> {
+        self.post(&format!("/options/quotes/{}/accept", quote_id), None)
+            .await
+    }
+
+    /// See https://docs.ftx.com/#get-options-positions.
+    pub async fn get_option_positions(&self) -> Result<Vec<OptionPosition>> {
+        self.get("/options/positions", None).await
+    }
+
+    /// Recent option trades of all users.
+    /// See https://docs.ftx.com/#get-public-options-trades.
+    pub async fn get_option_trades(
+        &self,
+        limit: Option<usize>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<OptionTrade>> {
+        self.get(
+            "/options/trades",
+            Some(json!({
+                "limit": limit,
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#get-24h-option-volume.
+    pub async fn get_option_volume_24h(&self) -> Result<OptionVolume> {
+        self.get("/stats/24h_options_volume", None).await
+    }
+
+    /// See https://docs.ftx.com/#get-lending-rates.
+    pub async fn get_lending_rates(&self) -> Result<Vec<SpotMarginRate>> {
+        self.get("/spot_margin/lending_rates", None).await
+    }
+
+    /// See https://docs.ftx.com/#get-borrow-rates.
+    pub async fn get_borrow_rates(&self) -> Result<Vec<SpotMarginRate>> {
+        self.get("/spot_margin/borrow_rates", None).await
+    }
+
+    /// See https://docs.ftx.com/#get-my-borrow-history.
+    pub async fn get_borrow_history(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<BorrowHistory>> {
+        self.get(
+            "/spot_margin/borrow_history",
+            Some(json!({
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#get-my-lending-history.
+    pub async fn get_lending_history(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<LendingHistory>> {
+        self.get(
+            "/spot_margin/lending_history",
+            Some(json!({
+                "start_time": start_time.map(|t| t.timestamp()),
+                "end_time": end_time.map(|t| t.timestamp()),
+            })),
+        )
+        .await
+    }
+
+    /// See https://docs.ftx.com/#get-lending-offers.
+    pub async fn get_lending_offers(&self) -> Result<Vec<LendingOffer>> {
+        self.get("/spot_margin/offers", None).await
+    }
+
+    /// See https://docs.ftx.com/#get-lending-info.
+    pub async fn get_lending_info(&self) -> Result<Vec<LendingInfo>> {
+        self.get("/spot_margin/lending_info", None).await
+    }
+
+    /// Offers to lend `size` of `coin` at a minimum hourly `rate`, replacing
+    /// the previous offer of `coin`. A size of zero cancels the offer.
+    /// See https://docs.ftx.com/#submit-lending-offer.
+    pub async fn submit_lending_offer(
+        &self,
+        coin: &str,
+        size: Decimal,
+        rate: Decimal,
+    ) -> Result<SubmitLendingOffer> {
+        self.post(
+            "/spot_margin/offers",
+            Some(json!({
+                "coin": coin,
+                "size": size,
+                "rate": rate,
+            })),
+        )
+        .await
+    }
+
+    /// Pages through fills, optionally only those of `market`, newest first.
+    /// See https://docs.ftx.com/#fills.
+    pub fn paginate_fills(
+        &self,
+        market: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Paginated<'_, Fill> {
+        let params = json!({ "market": market });
+        Paginated::new(self, "/fills", params, start_time, end_time)
+    }
+
+    /// Pages through closed orders, optionally only those of `market`,
+    /// newest first.
+    /// See https://docs.ftx.com/#get-order-history.
+    pub fn paginate_order_history(
+        &self,
+        market: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Paginated<'_, OrderInfo> {
+        let params = json!({ "market": market });
+        Paginated::new(self, "/orders/history", params, start_time, end_time)
+    }
+
+    /// Pages through closed trigger orders, optionally only those of
+    /// `market`, newest first.
+    /// See https://docs.ftx.com/#get-trigger-order-history.
+    pub fn paginate_conditional_order_history(
+        &self,
+        market: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Paginated<'_, ConditionalOrderInfo> {
+        let params = json!({ "market": market });
+        Paginated::new(
+            self,
+            "/conditional_orders/history",
+            params,
+            start_time,
+            end_time,
+        )
+    }
+
+    /// Pages through funding payments, optionally only those of `future`,
+    /// newest first.
+    /// See https://docs.ftx.com/#funding-payments.
+    pub fn paginate_funding_payments(
+        &self,
+        future: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Paginated<'_, FundingPayment> {
+        let params = json!({ "future": future });
+        Paginated::new(self, "/funding_payments", params, start_time, end_time)
+    }
+
+    /// Pages through deposits, newest first.
+    /// See https://docs.ftx.com/#get-deposit-history.
+    pub fn paginate_deposits(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Paginated<'_, WalletDeposit> {
+        Paginated::new(self, "/wallet/deposits", json!({}), start_time, end_time)
+    }
+}
+
+/// Query parameters use snake_case, unlike the request body
+fn conditional_order_type_param(r#type: ConditionalOrderType) -> &'static str {
+    match r#type {
+        ConditionalOrderType::Stop => "stop",
+        ConditionalOrderType::TrailingStop => "trailing_stop",
+        ConditionalOrderType::TakeProfit => "take_profit",
+    }
 }
@@ -1,10 +1,148 @@
+use super::Error;
 use chrono::{DateTime, Utc};
 use rust_decimal::prelude::*;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_with::{serde_as, TimestampSeconds, TimestampSecondsWithFrac};
+use std::borrow::Borrow;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
 
 pub type Id = u64;
 pub type Coin = String;
-pub type Symbol = String;
+
+/// Name of a market, e.g. `BTC/USD`, `BTC-PERP`, or `BTC-0325`. Cheap to
+/// clone and usable wherever a `&str` is expected.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(Arc<str>);
+
+/// Kind of market a `Symbol` refers to, derived from its name
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// e.g. `BTC/USD`
+    Spot,
+    /// e.g. `BTC-PERP`
+    Perpetual,
+    /// e.g. `BTC-MOVE-0325` or `BTC-MOVE-WK-0402`
+    Move,
+    /// Dated future, e.g. `BTC-0325`
+    Future,
+    /// Any other market, e.g. prediction markets
+    Other,
+}
+
+impl Symbol {
+    /// Parses a market name, which must consist of uppercase letters, digits,
+    /// and at most one `/` or `-` separated parts
+    pub fn parse(name: &str) -> Result<Self, Error> {
+        let valid_char = |c: char| c.is_ascii_uppercase() || c.is_ascii_digit();
+        let valid = if let Some((base, quote)) = name.split_once('/') {
+            [base, quote]
+                .iter()
+                .all(|part| !part.is_empty() && part.chars().all(valid_char))
+        } else {
+            name.split('-')
+                .all(|part| !part.is_empty() && part.chars().all(valid_char))
+        };
+
+        if valid {
+            Ok(Self(name.into()))
+        } else {
+            Err(Error::InvalidSymbol(name.to_string()))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn kind(&self) -> SymbolKind {
+        if self.0.contains('/') {
+            SymbolKind::Spot
+        } else if self.0.ends_with("-PERP") {
+            SymbolKind::Perpetual
+        } else if self.0.contains("-MOVE-") {
+            SymbolKind::Move
+        } else if self.0.contains('-') {
+            SymbolKind::Future
+        } else {
+            SymbolKind::Other
+        }
+    }
+}
+
+impl std::str::FromStr for Symbol {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        Self::parse(name)
+    }
+}
+
+/// Unchecked conversion, use `Symbol::parse` to validate user input
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Self(name.into())
+    }
+}
+
+/// Unchecked conversion, use `Symbol::parse` to validate user input
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Self(name.into())
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// Market names sent by FTX are not validated
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -67,7 +205,7 @@ pub struct Transfer {
 
 // REST API -> Markets
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum MarketType {
     Future,
@@ -101,6 +239,49 @@ pub struct Market {
     pub volume_usd24h: Decimal,
 }
 
+impl Market {
+    /// Rounds `price` to the nearest multiple of the price increment,
+    /// midpoints away from zero
+    pub fn round_price(&self, price: Decimal) -> Decimal {
+        round_to_increment(
+            price,
+            self.price_increment,
+            RoundingStrategy::MidpointAwayFromZero,
+        )
+    }
+
+    /// Rounds `size` down to a multiple of the size increment, so that
+    /// orders never exceed the intended size
+    pub fn round_size(&self, size: Decimal) -> Decimal {
+        round_to_increment(size, self.size_increment, RoundingStrategy::ToZero)
+    }
+
+    /// Smallest size accepted for an order, see `min_provide_size` for post
+    /// only orders
+    pub fn min_order_size(&self) -> Decimal {
+        self.size_increment
+    }
+
+    /// Value of `size` at `price` in the quote currency, e.g. USD for
+    /// `BTC-PERP` and `BTC/USD`
+    pub fn notional(&self, price: Decimal, size: Decimal) -> Decimal {
+        price * size.abs()
+    }
+}
+
+/// Rounds `value` to a multiple of `increment` with `strategy`, unchanged if
+/// the increment isn't positive
+pub fn round_to_increment(
+    value: Decimal,
+    increment: Decimal,
+    strategy: RoundingStrategy,
+) -> Decimal {
+    if increment <= Decimal::ZERO {
+        return value;
+    }
+    ((value / increment).round_dp_with_strategy(0, strategy) * increment).normalize()
+}
+
 pub type Markets = Vec<Market>;
 
 #[derive(Clone, Debug, Deserialize)]
@@ -110,7 +291,7 @@ pub struct Orderbook {
     pub bids: Vec<(Decimal, Decimal)>,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum Side {
     Buy,
@@ -143,6 +324,9 @@ pub struct Price {
 
 pub type Prices = Vec<Price>;
 
+pub type Candle = Price;
+pub type Candles = Vec<Candle>;
+
 // REST API -> Futures
 
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
@@ -187,15 +371,19 @@ pub struct Future {
 
 pub type Futures = Vec<Future>;
 
+/// Returned by GET /futures/{future_name}/stats.
+/// Funding fields are only set for perpetuals, expiration fields only for
+/// dated futures and MOVE contracts.
+/// See https://docs.ftx.com/#get-future-stats.
 #[derive(Copy, Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FutureStats {
     pub volume: Decimal,
-    pub next_funding_rate: Decimal,
-    pub next_funding_time: DateTime<Utc>,
-    pub expiration_price: Decimal,
-    pub predicted_expiration_price: Decimal,
-    pub strike_price: Decimal,
+    pub next_funding_rate: Option<Decimal>,
+    pub next_funding_time: Option<DateTime<Utc>>,
+    pub expiration_price: Option<Decimal>,
+    pub predicted_expiration_price: Option<Decimal>,
+    pub strike_price: Option<Decimal>,
     pub open_interest: Decimal,
 }
 
@@ -209,6 +397,20 @@ pub struct FundingRate {
 
 pub type FundingRates = Vec<FundingRate>;
 
+/// Candle of an index, which has no volume
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexCandle {
+    pub close: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub open: Decimal,
+    pub volume: Option<Decimal>,
+    pub start_time: DateTime<Utc>,
+}
+
+pub type IndexCandles = Vec<IndexCandle>;
+
 // REST API -> Account
 
 /// Returned by GET /account.
@@ -249,7 +451,7 @@ pub struct Position {
     pub cost: Decimal,
     pub entry_price: Option<Decimal>,
     pub estimated_liquidation_price: Option<Decimal>,
-    pub future: String,
+    pub future: Symbol,
     pub initial_margin_requirement: Decimal,
     pub long_order_size: Decimal,
     pub maintenance_margin_requirement: Decimal,
@@ -263,8 +465,30 @@ pub struct Position {
     pub collateral_used: Decimal,
 }
 
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeLeverage;
+
 // REST API -> Wallet
-// TODO
+
+/// Coin supported by the wallet, see https://docs.ftx.com/#get-coins.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletCoin {
+    pub id: Coin,
+    pub name: String,
+    /// Whether the coin is a fiat currency, e.g. USD or EUR
+    pub fiat: bool,
+    pub can_deposit: bool,
+    pub can_withdraw: bool,
+    pub can_convert: Option<bool>,
+    pub collateral: Option<bool>,
+    pub collateral_weight: Option<Decimal>,
+    pub has_tag: Option<bool>,
+    /// Blockchains usable for deposits and withdrawals, e.g. "erc20"
+    #[serde(default)]
+    pub methods: Vec<String>,
+}
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -301,17 +525,93 @@ pub struct WalletDeposit {
     pub id: Id,
     pub coin: String,
     pub size: Decimal,
-    pub time: String,
+    pub time: DateTime<Utc>,
     pub status: DepositStatus,
     pub confirmations: Option<usize>,
-    pub confirmed_time: Option<String>,
+    pub confirmed_time: Option<DateTime<Utc>>,
+    pub fee: Option<Decimal>, // fee, not included in size
+    pub txid: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum WithdrawalStatus {
+    Requested,
+    Processing,
+    Sent,
+    Complete,
+    Cancelled,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletWithdrawal {
+    pub id: Id,
+    pub coin: String,
+    pub size: Decimal,
+    pub time: DateTime<Utc>,
+    pub status: WithdrawalStatus,
+    pub address: Option<String>,
+    pub tag: Option<String>,
     pub fee: Option<Decimal>, // fee, not included in size
     pub txid: Option<String>,
     pub notes: Option<String>,
 }
 
+/// Withdrawal sent by `Rest::request_withdrawal`.
+/// See https://docs.ftx.com/#request-withdrawal.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WithdrawalRequest {
+    pub coin: String,
+    pub size: Decimal,
+    pub address: String,
+    /// Memo or tag required by some chains
+    pub tag: Option<String>,
+    /// Blockchain to use, e.g. "erc20", "trx", "sol", "bsc"
+    pub method: Option<String>,
+    /// Withdrawal password, if enabled
+    pub password: Option<String>,
+    /// 2FA code, if enabled
+    pub code: Option<String>,
+}
+
+impl WithdrawalRequest {
+    pub fn new(coin: &str, size: Decimal, address: &str) -> Self {
+        Self {
+            coin: coin.to_string(),
+            size,
+            address: address.to_string(),
+            tag: None,
+            method: None,
+            password: None,
+            code: None,
+        }
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn method(mut self, method: &str) -> Self {
+        self.method = Some(method.to_string());
+        self
+    }
+
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    pub fn code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+}
+
 // REST API -> Orders
-// TODO
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
@@ -327,7 +627,25 @@ pub enum OrderSide {
     Sell,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+impl From<Side> for OrderSide {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Buy => OrderSide::Buy,
+            Side::Sell => OrderSide::Sell,
+        }
+    }
+}
+
+impl From<OrderSide> for Side {
+    fn from(side: OrderSide) -> Self {
+        match side {
+            OrderSide::Buy => Side::Buy,
+            OrderSide::Sell => Side::Sell,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum OrderStatus {
     New, // accepted but not processed yet
@@ -335,12 +653,12 @@ pub enum OrderStatus {
     Closed, // filled or cancelled
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderInfo {
     pub id: Id,
-    pub market: String,
-    pub future: Option<String>,
+    pub market: Symbol,
+    pub future: Option<Symbol>,
     pub r#type: OrderType,
     pub side: OrderSide,
     pub price: Option<Decimal>, // null for new market orders
@@ -356,3 +674,734 @@ pub struct OrderInfo {
     pub created_at: DateTime<Utc>,
     pub client_id: Option<String>,
 }
+
+/// Execution options of an `OrderRequest`, all disabled by default.
+/// See https://docs.ftx.com/#place-order.
+#[derive(Copy, Clone, Debug, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderFlags {
+    /// Only reduce the position, never open or increase one
+    pub reduce_only: bool,
+    /// Immediate or cancel: the part not filled right away is cancelled
+    pub ioc: bool,
+    /// Cancel the order instead of taking liquidity
+    pub post_only: bool,
+    /// Reject the order instead of adjusting its price when it is outside
+    /// of the price band
+    pub reject_on_price_band: bool,
+}
+
+impl OrderFlags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reduce_only(mut self, reduce_only: bool) -> Self {
+        self.reduce_only = reduce_only;
+        self
+    }
+
+    pub fn ioc(mut self, ioc: bool) -> Self {
+        self.ioc = ioc;
+        self
+    }
+
+    pub fn post_only(mut self, post_only: bool) -> Self {
+        self.post_only = post_only;
+        self
+    }
+
+    pub fn reject_on_price_band(mut self, reject_on_price_band: bool) -> Self {
+        self.reject_on_price_band = reject_on_price_band;
+        self
+    }
+}
+
+/// Order sent by `Rest::submit_order`, constructed with `OrderRequest::limit`
+/// or `OrderRequest::market` and refined with the builder methods.
+/// See https://docs.ftx.com/#place-order.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderRequest {
+    pub market: Symbol,
+    pub side: OrderSide,
+    pub price: Option<Decimal>, // null for market orders
+    pub r#type: OrderType,
+    pub size: Decimal,
+    #[serde(flatten)]
+    pub flags: OrderFlags,
+    pub client_id: Option<String>,
+}
+
+impl OrderRequest {
+    pub fn limit(market: &str, side: OrderSide, price: Decimal, size: Decimal) -> Self {
+        Self {
+            market: market.into(),
+            side,
+            price: Some(price),
+            r#type: OrderType::Limit,
+            size,
+            flags: OrderFlags::default(),
+            client_id: None,
+        }
+    }
+
+    pub fn market(market: &str, side: OrderSide, size: Decimal) -> Self {
+        Self {
+            market: market.into(),
+            side,
+            price: None,
+            r#type: OrderType::Market,
+            size,
+            flags: OrderFlags::default(),
+            client_id: None,
+        }
+    }
+
+    pub fn reduce_only(mut self, reduce_only: bool) -> Self {
+        self.flags.reduce_only = reduce_only;
+        self
+    }
+
+    pub fn ioc(mut self, ioc: bool) -> Self {
+        self.flags.ioc = ioc;
+        self
+    }
+
+    pub fn post_only(mut self, post_only: bool) -> Self {
+        self.flags.post_only = post_only;
+        self
+    }
+
+    pub fn reject_on_price_band(mut self, reject_on_price_band: bool) -> Self {
+        self.flags.reject_on_price_band = reject_on_price_band;
+        self
+    }
+
+    pub fn flags(mut self, flags: OrderFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn client_id(mut self, client_id: &str) -> Self {
+        self.client_id = Some(client_id.to_string());
+        self
+    }
+}
+
+/// Orders cancelled by `Rest::cancel_all_orders`, all open orders by
+/// default.
+/// See https://docs.ftx.com/#cancel-all-orders.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CancelAllFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<Symbol>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<OrderSide>,
+    /// Only cancel trigger orders
+    pub conditional_orders_only: bool,
+    /// Only cancel limit orders, not trigger orders
+    pub limit_orders_only: bool,
+}
+
+impl CancelAllFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn market(mut self, market: &str) -> Self {
+        self.market = Some(market.into());
+        self
+    }
+
+    pub fn side(mut self, side: OrderSide) -> Self {
+        self.side = Some(side);
+        self
+    }
+
+    pub fn conditional_orders_only(mut self, conditional_orders_only: bool) -> Self {
+        self.conditional_orders_only = conditional_orders_only;
+        self
+    }
+
+    pub fn limit_orders_only(mut self, limit_orders_only: bool) -> Self {
+        self.limit_orders_only = limit_orders_only;
+        self
+    }
+}
+
+// REST API -> Conditional orders
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConditionalOrderType {
+    Stop,
+    // FTX expects camelCase in requests but returns snake_case
+    #[serde(alias = "trailing_stop")]
+    TrailingStop,
+    #[serde(alias = "take_profit")]
+    TakeProfit,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ConditionalOrderStatus {
+    Open,
+    Cancelled,
+    Triggered,
+}
+
+/// Returned by the conditional order endpoints.
+/// See https://docs.ftx.com/#get-open-trigger-orders.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionalOrderInfo {
+    pub id: Id,
+    pub market: Symbol,
+    pub future: Option<Symbol>,
+    pub r#type: ConditionalOrderType,
+    pub order_type: OrderType,
+    pub side: OrderSide,
+    pub size: Decimal,
+    pub status: ConditionalOrderStatus,
+    pub trigger_price: Option<Decimal>,
+    pub order_price: Option<Decimal>, // null for market orders
+    pub trail_value: Option<Decimal>,
+    pub trail_start: Option<Decimal>,
+    pub reduce_only: bool,
+    pub retry_until_filled: bool,
+    pub filled_size: Option<Decimal>,
+    pub avg_fill_price: Option<Decimal>,
+    pub order_id: Option<Id>, // id of the order placed when triggered
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub triggered_at: Option<DateTime<Utc>>,
+}
+
+/// Returned by GET /conditional_orders/{id}/triggers.
+/// See https://docs.ftx.com/#get-trigger-order-triggers.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trigger {
+    pub time: DateTime<Utc>,
+    pub order_size: Option<Decimal>,
+    pub filled_size: Option<Decimal>,
+    pub order_id: Option<Id>,
+    pub error: Option<String>,
+}
+
+/// Conditional order sent by `Rest::place_conditional_order`, constructed
+/// with `ConditionalOrderRequest::stop`, `take_profit` or `trailing_stop`.
+/// Stop and take profit orders are executed as market orders unless an
+/// `order_price` is set.
+/// See https://docs.ftx.com/#place-trigger-order.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionalOrderRequest {
+    pub market: Symbol,
+    pub side: OrderSide,
+    pub size: Decimal,
+    pub r#type: ConditionalOrderType,
+    pub reduce_only: bool,
+    pub retry_until_filled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_price: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_price: Option<Decimal>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trail_value: Option<Decimal>,
+}
+
+impl ConditionalOrderRequest {
+    fn new(market: &str, side: OrderSide, size: Decimal, r#type: ConditionalOrderType) -> Self {
+        Self {
+            market: market.into(),
+            side,
+            size,
+            r#type,
+            reduce_only: false,
+            // FTX defaults to retrying market orders until filled
+            retry_until_filled: true,
+            trigger_price: None,
+            order_price: None,
+            trail_value: None,
+        }
+    }
+
+    pub fn stop(market: &str, side: OrderSide, size: Decimal, trigger_price: Decimal) -> Self {
+        Self {
+            trigger_price: Some(trigger_price),
+            ..Self::new(market, side, size, ConditionalOrderType::Stop)
+        }
+    }
+
+    pub fn take_profit(
+        market: &str,
+        side: OrderSide,
+        size: Decimal,
+        trigger_price: Decimal,
+    ) -> Self {
+        Self {
+            trigger_price: Some(trigger_price),
+            ..Self::new(market, side, size, ConditionalOrderType::TakeProfit)
+        }
+    }
+
+    /// `trail_value` is negative for sell orders and positive for buy orders
+    pub fn trailing_stop(
+        market: &str,
+        side: OrderSide,
+        size: Decimal,
+        trail_value: Decimal,
+    ) -> Self {
+        Self {
+            trail_value: Some(trail_value),
+            ..Self::new(market, side, size, ConditionalOrderType::TrailingStop)
+        }
+    }
+
+    /// Places a limit order at `order_price` when triggered
+    pub fn order_price(mut self, order_price: Decimal) -> Self {
+        self.order_price = Some(order_price);
+        self
+    }
+
+    pub fn reduce_only(mut self, reduce_only: bool) -> Self {
+        self.reduce_only = reduce_only;
+        self
+    }
+
+    pub fn retry_until_filled(mut self, retry_until_filled: bool) -> Self {
+        self.retry_until_filled = retry_until_filled;
+        self
+    }
+}
+
+// REST API -> Fills
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fill {
+    pub id: Id,
+    pub market: Symbol,
+    pub future: Option<Symbol>,
+    pub base_currency: Option<Coin>,
+    pub quote_currency: Option<Coin>,
+    pub r#type: String, // e.g. "order"
+    pub side: Side,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub order_id: Id,
+    pub trade_id: Id,
+    pub time: DateTime<Utc>,
+    pub fee: Decimal,
+    pub fee_rate: Decimal,
+    pub fee_currency: Coin,
+    pub liquidity: Liquidity,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Liquidity {
+    Maker,
+    Taker,
+}
+
+pub type Fills = Vec<Fill>;
+
+// REST API -> Funding Payments
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FundingPayment {
+    pub id: Id,
+    pub future: Symbol,
+    pub payment: Decimal,
+    pub rate: Decimal,
+    pub time: DateTime<Utc>,
+}
+
+pub type FundingPayments = Vec<FundingPayment>;
+
+// REST API -> Convert
+
+/// Quote requested by `Rest::request_quote`
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteId {
+    pub quote_id: Id,
+}
+
+/// Quote to convert `cost` of `from_coin` into `proceeds` of `to_coin`.
+/// See https://docs.ftx.com/#get-quote-status.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Quote {
+    pub id: Id,
+    pub base_coin: Coin,
+    pub quote_coin: Coin,
+    pub from_coin: Coin,
+    pub to_coin: Coin,
+    pub side: Side,
+    /// Price of the base coin in the quote coin
+    pub price: Decimal,
+    /// Size of `from_coin` that is converted
+    pub cost: Decimal,
+    /// Size of `to_coin` that is received
+    pub proceeds: Decimal,
+    #[serde_as(as = "TimestampSecondsWithFrac<f64>")]
+    pub expiry: DateTime<Utc>,
+    pub expired: bool,
+    /// Whether the quote was accepted and filled
+    pub filled: bool,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptQuote;
+
+// REST API -> Staking
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stake {
+    pub id: Id,
+    pub coin: Coin,
+    pub size: Decimal,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum UnstakeRequestStatus {
+    Pending,
+    Cancelled,
+    Processed,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnstakeRequest {
+    pub id: Id,
+    pub coin: Coin,
+    pub size: Decimal,
+    pub status: UnstakeRequestStatus,
+    pub created_at: DateTime<Utc>,
+    /// When the unstaked size becomes available
+    pub unlock_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StakeBalance {
+    pub coin: Coin,
+    pub staked: Decimal,
+    /// Size of pending unstake requests
+    pub scheduled_to_unstake: Decimal,
+    pub lifetime_rewards: Decimal,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StakingReward {
+    pub id: Id,
+    pub coin: Coin,
+    pub size: Decimal,
+    pub status: String, // e.g. "complete"
+    pub created_at: DateTime<Utc>,
+}
+
+// REST API -> Leveraged Tokens
+
+/// Leveraged token, e.g. BULL or ETHBEAR.
+/// See https://docs.ftx.com/#list-leveraged-tokens.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeveragedToken {
+    pub name: Coin,
+    pub description: String,
+    /// Future the token is leveraged on, e.g. BTC-PERP
+    pub underlying: Symbol,
+    pub leverage: Decimal,
+    /// Number of tokens outstanding
+    pub outstanding: Decimal,
+    pub price_per_share: Decimal,
+    /// Size of the underlying future held per token
+    pub position_per_share: Decimal,
+    pub underlying_mark: Decimal,
+    pub contract_address: Option<String>,
+    pub change_1h: Decimal,
+    pub change_24h: Decimal,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeveragedTokenBalance {
+    pub token: Coin,
+    pub balance: Decimal,
+}
+
+/// See https://docs.ftx.com/#request-leveraged-token-creation.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeveragedTokenCreation {
+    pub id: Id,
+    pub token: Coin,
+    pub requested_size: Decimal,
+    pub pending: bool,
+    pub created_at: DateTime<Utc>,
+    /// Cost in USD, including fees
+    pub cost: Decimal,
+    pub price: Option<Decimal>,
+    pub fee: Option<Decimal>,
+    pub created_size: Option<Decimal>,
+    pub fulfilled_at: Option<DateTime<Utc>>,
+}
+
+/// See https://docs.ftx.com/#request-leveraged-token-redemption.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeveragedTokenRedemption {
+    pub id: Id,
+    pub token: Coin,
+    pub size: Decimal,
+    /// Whether the redemption is still being processed
+    pub pending: bool,
+    pub requested_at: DateTime<Utc>,
+    pub projected_proceeds: Option<Decimal>,
+    /// Proceeds in USD after fees, once fulfilled
+    pub proceeds: Option<Decimal>,
+    pub price: Option<Decimal>,
+    pub fee: Option<Decimal>,
+    pub fulfilled_at: Option<DateTime<Utc>>,
+}
+
+// REST API -> Options
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum OptionType {
+    Call,
+    Put,
+}
+
+/// European option on `underlying`, e.g. BTC
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionContract {
+    pub underlying: Coin,
+    pub r#type: OptionType,
+    pub strike: Decimal,
+    pub expiry: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum OptionStatus {
+    Open,
+    Filled,
+    Cancelled,
+}
+
+/// Request for quotes on an option.
+/// See https://docs.ftx.com/#list-quote-requests.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionQuoteRequest {
+    pub id: Id,
+    pub option: OptionContract,
+    pub side: Side,
+    pub size: Decimal,
+    pub time: DateTime<Utc>,
+    pub request_expiry: DateTime<Utc>,
+    pub status: OptionStatus,
+    /// Only returned for own requests
+    pub limit_price: Option<Decimal>,
+    pub hide_limit_price: Option<bool>,
+    /// Quotes received, only returned for own requests
+    pub quotes: Option<Vec<OptionQuote>>,
+}
+
+/// Quote on a quote request.
+/// See https://docs.ftx.com/#get-quotes-for-your-quote-request.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionQuote {
+    pub id: Id,
+    pub request_id: Id,
+    pub option: OptionContract,
+    /// Price per contract
+    pub price: Decimal,
+    pub size: Decimal,
+    pub collateral: Option<Decimal>,
+    pub quote_expiry: Option<DateTime<Utc>>,
+    pub quoter_side: Side,
+    pub request_side: Side,
+    pub status: OptionStatus,
+    pub time: DateTime<Utc>,
+}
+
+/// Quote request sent by `Rest::create_option_quote_request`.
+/// See https://docs.ftx.com/#create-quote-request.
+#[serde_as]
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionRequest {
+    pub underlying: Coin,
+    pub r#type: OptionType,
+    pub strike: Decimal,
+    #[serde_as(as = "TimestampSeconds<i64>")]
+    pub expiry: DateTime<Utc>,
+    pub side: OrderSide,
+    pub size: Decimal,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit_price: Option<Decimal>,
+    /// Whether the limit price is hidden from quoters
+    pub hide_limit_price: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde_as(as = "Option<TimestampSeconds<i64>>")]
+    pub request_expiry: Option<DateTime<Utc>>,
+    /// Only this counterparty may quote
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty_id: Option<Id>,
+}
+
+impl OptionRequest {
+    pub fn new(
+        underlying: &str,
+        r#type: OptionType,
+        strike: Decimal,
+        expiry: DateTime<Utc>,
+        side: OrderSide,
+        size: Decimal,
+    ) -> Self {
+        Self {
+            underlying: underlying.to_string(),
+            r#type,
+            strike,
+            expiry,
+            side,
+            size,
+            limit_price: None,
+            hide_limit_price: true,
+            request_expiry: None,
+            counterparty_id: None,
+        }
+    }
+
+    pub fn limit_price(mut self, limit_price: Decimal) -> Self {
+        self.limit_price = Some(limit_price);
+        self
+    }
+
+    pub fn hide_limit_price(mut self, hide_limit_price: bool) -> Self {
+        self.hide_limit_price = hide_limit_price;
+        self
+    }
+
+    pub fn request_expiry(mut self, request_expiry: DateTime<Utc>) -> Self {
+        self.request_expiry = Some(request_expiry);
+        self
+    }
+
+    pub fn counterparty_id(mut self, counterparty_id: Id) -> Self {
+        self.counterparty_id = Some(counterparty_id);
+        self
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionPosition {
+    pub option: OptionContract,
+    pub side: Side,
+    pub size: Decimal,
+    /// Positive when long, negative when short
+    pub net_size: Decimal,
+    pub entry_price: Decimal,
+    pub pessimistic_valuation: Option<Decimal>,
+    pub pessimistic_index_price: Option<Decimal>,
+    pub pessimistic_volatility: Option<Decimal>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptionTrade {
+    pub id: Id,
+    pub option: OptionContract,
+    pub price: Decimal,
+    pub size: Decimal,
+    pub time: DateTime<Utc>,
+}
+
+/// See https://docs.ftx.com/#get-24h-option-volume.
+#[derive(Clone, Debug, Deserialize)]
+pub struct OptionVolume {
+    /// Number of contracts traded
+    pub contracts: Decimal,
+    /// Notional traded in the underlying
+    pub underlying_total: Decimal,
+}
+
+// REST API -> Spot Margin
+
+/// Hourly rate of a coin, see https://docs.ftx.com/#get-lending-rates.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpotMarginRate {
+    pub coin: Coin,
+    /// Rate of the previous hour
+    pub previous: Decimal,
+    /// Estimated rate of the next hour
+    pub estimate: Decimal,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BorrowHistory {
+    pub coin: Coin,
+    /// Interest paid
+    pub cost: Decimal,
+    pub rate: Decimal,
+    pub size: Decimal,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LendingHistory {
+    pub coin: Coin,
+    /// Interest earned
+    pub proceeds: Decimal,
+    pub rate: Decimal,
+    pub size: Decimal,
+    pub time: DateTime<Utc>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LendingOffer {
+    pub coin: Coin,
+    /// Minimum hourly rate
+    pub rate: Decimal,
+    pub size: Decimal,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LendingInfo {
+    pub coin: Coin,
+    /// Size that can be offered
+    pub lendable: Decimal,
+    /// Size currently lent
+    pub locked: Decimal,
+    pub min_rate: Option<Decimal>,
+    /// Size currently offered
+    pub offered: Decimal,
+}
+
+#[derive(Copy, Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmitLendingOffer;
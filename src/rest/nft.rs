@@ -0,0 +1,118 @@
+//! Endpoints of the NFT marketplace of ftx.us, use a `Rest` handle
+//! connected with `Rest::new_us`.
+
+use super::{Coin, Id, Rest, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::json;
+
+/// See https://docs.ftx.com/#list-nfts.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Nft {
+    pub id: Id,
+    pub name: String,
+    pub description: String,
+    pub issuer: String,
+    pub collection: String,
+    pub series: Option<String>,
+    pub sol_mint_address: Option<String>,
+    pub eth_contract_address: Option<String>,
+    pub image_url: Option<String>,
+    pub video_url: Option<String>,
+    pub animation_url: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub quote_currency: Option<Coin>,
+    /// Price at which the NFT is offered for sale, if listed
+    pub offer_price: Option<Decimal>,
+    pub auction: Option<NftAuction>,
+    pub last_price: Option<Decimal>,
+    pub total_quantity: Option<Decimal>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NftAuction {
+    pub best_bid: Option<Decimal>,
+    pub min_next_bid: Decimal,
+    pub end_time: DateTime<Utc>,
+    /// Number of bids
+    pub bids: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NftTrade {
+    pub id: Id,
+    pub price: Decimal,
+    pub time: DateTime<Utc>,
+}
+
+impl Rest {
+    /// See https://docs.ftx.com/#list-nfts.
+    pub async fn get_nfts(&self) -> Result<Vec<Nft>> {
+        self.get("/nft/nfts", None).await
+    }
+
+    /// See https://docs.ftx.com/#get-nft-info.
+    pub async fn get_nft(&self, nft_id: Id) -> Result<Nft> {
+        self.get(&format!("/nft/{}", nft_id), None).await
+    }
+
+    /// See https://docs.ftx.com/#get-nft-trades.
+    pub async fn get_nft_trades(&self, nft_id: Id) -> Result<Vec<NftTrade>> {
+        self.get(&format!("/nft/{}/trades", nft_id), None).await
+    }
+
+    /// NFTs held by the account.
+    /// See https://docs.ftx.com/#get-balances-2.
+    pub async fn get_nft_balances(&self) -> Result<Vec<Nft>> {
+        self.get("/nft/balances", None).await
+    }
+
+    /// Lists an NFT held by the account for sale at `price`.
+    /// See https://docs.ftx.com/#make-offer.
+    pub async fn make_nft_offer(&self, nft_id: Id, price: Decimal) -> Result<Nft> {
+        self.post(
+            "/nft/offer",
+            Some(json!({
+                "nftId": nft_id,
+                "price": price,
+            })),
+        )
+        .await
+    }
+
+    /// Buys an NFT listed for sale at `price`.
+    /// See https://docs.ftx.com/#buy-nft.
+    pub async fn buy_nft(&self, nft_id: Id, price: Decimal) -> Result<Nft> {
+        self.post(
+            "/nft/buy",
+            Some(json!({
+                "nftId": nft_id,
+                "price": price,
+            })),
+        )
+        .await
+    }
+
+    /// NFTs with open bids of the account.
+    /// See https://docs.ftx.com/#get-bids.
+    pub async fn get_nft_bids(&self) -> Result<Vec<Nft>> {
+        self.get("/nft/bids", None).await
+    }
+
+    /// Bids `price` on an NFT being auctioned.
+    /// See https://docs.ftx.com/#place-bid.
+    pub async fn place_nft_bid(&self, nft_id: Id, price: Decimal) -> Result<Nft> {
+        self.post(
+            "/nft/bids",
+            Some(json!({
+                "nftId": nft_id,
+                "price": price,
+            })),
+        )
+        .await
+    }
+}
@@ -0,0 +1,209 @@
+use super::{
+    ConditionalOrderInfo, Fill, FundingPayment, Id, OrderInfo, Rest, Result, WalletDeposit,
+};
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{HashSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Item of an endpoint that can be paged through by time
+pub trait Paged {
+    fn id(&self) -> Id;
+    fn time(&self) -> DateTime<Utc>;
+}
+
+impl Paged for Fill {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+}
+
+impl Paged for OrderInfo {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn time(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+impl Paged for ConditionalOrderInfo {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn time(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+}
+
+impl Paged for FundingPayment {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+}
+
+impl Paged for WalletDeposit {
+    fn id(&self) -> Id {
+        self.id
+    }
+
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+}
+
+type Page<'a, T> = Pin<Box<dyn Future<Output = Result<Vec<T>>> + Send + 'a>>;
+
+/// Lazily pages through the items of an endpoint between two times, newest
+/// first. Each page is requested with the `end_time` of the oldest item of
+/// the previous one, so items of that second are requested again and
+/// skipped. Stops at the first page without new items.
+///
+/// ```no_run
+/// # async fn example() -> ftx::rest::Result<()> {
+/// use ftx::rest::Rest;
+///
+/// let api = Rest::new("<api key>".into(), "<api secret>".into(), None);
+/// let mut fills = api.paginate_fills(Some("BTC-PERP"), None, None);
+/// while let Some(fill) = fills.next().await {
+///     let fill = fill?;
+///     println!("{} {:?} {} @ {}", fill.time, fill.side, fill.size, fill.price);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Paginated<'a, T> {
+    rest: &'a Rest,
+    path: &'static str,
+    /// Query parameters besides `start_time` and `end_time`
+    params: Value,
+    start_time: Option<DateTime<Utc>>,
+    /// `end_time` of the next page, `None` when done
+    cursor: Option<DateTime<Utc>>,
+    /// IDs of the items of the second the next page starts at
+    seen: HashSet<Id>,
+    buf: VecDeque<T>,
+    page: Option<Page<'a, T>>,
+}
+
+impl<'a, T: Paged + DeserializeOwned + Send + 'a> Paginated<'a, T> {
+    pub(super) fn new(
+        rest: &'a Rest,
+        path: &'static str,
+        params: Value,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            rest,
+            path,
+            params,
+            start_time,
+            cursor: Some(end_time.unwrap_or_else(Utc::now)),
+            seen: HashSet::new(),
+            buf: VecDeque::new(),
+            page: None,
+        }
+    }
+
+    /// Returns the next item, requesting the next page if needed
+    pub async fn next(&mut self) -> Option<Result<T>> {
+        std::future::poll_fn(|cx| self.poll_item(cx)).await
+    }
+
+    /// Collects the remaining items
+    pub async fn try_collect(mut self) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        while let Some(item) = self.next().await {
+            items.push(item?);
+        }
+        Ok(items)
+    }
+
+    fn poll_item(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<T>>> {
+        loop {
+            if let Some(item) = self.buf.pop_front() {
+                return Poll::Ready(Some(Ok(item)));
+            }
+            let cursor = match self.cursor {
+                Some(cursor) => cursor,
+                None => return Poll::Ready(None),
+            };
+
+            if self.page.is_none() {
+                let mut params = self.params.clone();
+                params["start_time"] = self.start_time.map(|t| t.timestamp()).into();
+                params["end_time"] = cursor.timestamp().into();
+                let (rest, path) = (self.rest, self.path);
+                self.page = Some(Box::pin(async move { rest.get(path, Some(params)).await }));
+            }
+            let page = match self.page.as_mut().unwrap().as_mut().poll(cx) {
+                Poll::Ready(page) => page,
+                Poll::Pending => return Poll::Pending,
+            };
+            self.page = None;
+
+            match page {
+                Ok(page) => self.add_page(page, cursor),
+                Err(err) => {
+                    self.cursor = None;
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+    }
+
+    fn add_page(&mut self, mut page: Vec<T>, cursor: DateTime<Utc>) {
+        page.sort_by_key(|item| std::cmp::Reverse((item.time(), item.id())));
+        page.retain(|item| !self.seen.contains(&item.id()));
+
+        let earliest = match page.last() {
+            Some(item) => item.time().trunc_subsecs(0),
+            None => {
+                self.cursor = None;
+                return;
+            }
+        };
+        if earliest < cursor.trunc_subsecs(0) {
+            self.seen = page
+                .iter()
+                .filter(|item| item.time().trunc_subsecs(0) == earliest)
+                .map(Paged::id)
+                .collect();
+            self.cursor = Some(earliest);
+        } else {
+            // The whole page is within a single second
+            self.seen.clear();
+            self.cursor = Some(cursor.trunc_subsecs(0) - chrono::Duration::seconds(1));
+        }
+        if matches!((self.cursor, self.start_time), (Some(c), Some(s)) if c < s) {
+            self.cursor = None;
+        }
+
+        self.buf.extend(page);
+    }
+}
+
+#[cfg(feature = "futures-util")]
+impl<'a, T: Paged + DeserializeOwned + Send + Unpin + 'a> futures_util::Stream
+    for Paginated<'a, T>
+{
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_item(cx)
+    }
+}
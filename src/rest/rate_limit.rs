@@ -0,0 +1,176 @@
+use super::{Error, Result};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// What to do with a request that would exceed the rate limit
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RateLimitPolicy {
+    /// Wait until the request can be sent without exceeding the limit
+    #[default]
+    Wait,
+    /// Return `Error::RateLimited` without sending the request
+    Error,
+    /// Wait up to the given duration, requests that would have to wait
+    /// longer are dropped and `Error::RateLimited` is returned
+    Shed(Duration),
+}
+
+/// Token bucket holding up to `burst` tokens, refilled at `rate` tokens per
+/// second. Tokens may go negative, representing requests already waiting.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: u32, burst: u32) -> Self {
+        Self {
+            rate: rate.max(1) as f64,
+            burst: burst.max(1) as f64,
+            state: Mutex::new((burst.max(1) as f64, Instant::now())),
+        }
+    }
+
+    /// Takes a token and returns how long to wait before it can be used, or
+    /// `None` without taking a token if the wait would exceed `max_wait`
+    fn reserve(&self, max_wait: Option<Duration>) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap();
+        let (tokens, updated) = &mut *state;
+
+        let now = Instant::now();
+        *tokens =
+            (*tokens + now.duration_since(*updated).as_secs_f64() * self.rate).min(self.burst);
+        *updated = now;
+
+        let wait = if *tokens >= 1.0 {
+            Duration::from_secs(0)
+        } else {
+            Duration::from_secs_f64((1.0 - *tokens) / self.rate)
+        };
+        if max_wait.is_some_and(|max_wait| wait > max_wait) {
+            return None;
+        }
+
+        *tokens -= 1.0;
+        Some(wait)
+    }
+
+    /// Returns a token taken by `reserve`
+    fn refund(&self) {
+        self.state.lock().unwrap().0 += 1.0;
+    }
+}
+
+/// Request and order rate limits that several `Rest` handles can draw
+/// from, e.g. handles of different subaccounts using one API key, so that
+/// their combined rate stays under FTX's limits. Clones share the budget.
+///
+/// ```
+/// use ftx::rest::{RateLimitBudget, Rest};
+///
+/// let budget = RateLimitBudget::new(30, 10);
+/// let mut bot = Rest::new("<api key>".into(), "<api secret>".into(), Some("bot".into()));
+/// let mut hedger = Rest::new("<api key>".into(), "<api secret>".into(), Some("hedger".into()));
+/// bot.set_rate_limit_budget(budget.clone());
+/// hedger.set_rate_limit_budget(budget);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RateLimitBudget {
+    requests: Arc<TokenBucket>,
+    orders: Arc<TokenBucket>,
+}
+
+impl RateLimitBudget {
+    /// FTX allows 30 requests per second per account
+    pub const REQUESTS_PER_SECOND: u32 = 30;
+
+    /// Order placement limits depend on the account tier, the lowest allows
+    /// 2 orders per 200ms
+    pub const ORDERS_PER_SECOND: u32 = 10;
+
+    /// Allows `requests_per_second` requests including orders, and
+    /// `orders_per_second` orders placed or modified
+    pub fn new(requests_per_second: u32, orders_per_second: u32) -> Self {
+        Self {
+            requests: Self::request_bucket(requests_per_second),
+            orders: Self::order_bucket(orders_per_second),
+        }
+    }
+
+    fn request_bucket(requests: u32) -> Arc<TokenBucket> {
+        Arc::new(TokenBucket::new(requests, requests))
+    }
+
+    /// Order limits apply per 200ms window, so only a fifth of the orders per
+    /// second can be sent at once
+    fn order_bucket(orders: u32) -> Arc<TokenBucket> {
+        Arc::new(TokenBucket::new(orders, orders / 5))
+    }
+}
+
+impl Default for RateLimitBudget {
+    fn default() -> Self {
+        Self::new(Self::REQUESTS_PER_SECOND, Self::ORDERS_PER_SECOND)
+    }
+}
+
+/// Client-side rate limiter applied to every request sent by `Rest`
+pub(super) struct RateLimiter {
+    pub(super) policy: RateLimitPolicy,
+    pub(super) budget: RateLimitBudget,
+}
+
+impl RateLimiter {
+    pub(super) fn new() -> Self {
+        Self {
+            policy: RateLimitPolicy::default(),
+            budget: RateLimitBudget::default(),
+        }
+    }
+
+    /// Rate limiter with the same policy, drawing from the same budget
+    pub(super) fn share(&self) -> Self {
+        Self {
+            policy: self.policy,
+            budget: self.budget.clone(),
+        }
+    }
+
+    pub(super) fn set_requests_per_second(&mut self, requests: u32) {
+        self.budget.requests = RateLimitBudget::request_bucket(requests);
+    }
+
+    pub(super) fn set_orders_per_second(&mut self, orders: u32) {
+        self.budget.orders = RateLimitBudget::order_bucket(orders);
+    }
+
+    /// Returns how long to wait before sending a request, or
+    /// `Error::RateLimited` if the policy rejects it. Orders count towards
+    /// both the request and the order limit.
+    pub(super) fn reserve(&self, order: bool) -> Result<Duration> {
+        let max_wait = match self.policy {
+            RateLimitPolicy::Wait => None,
+            RateLimitPolicy::Error => Some(Duration::from_secs(0)),
+            RateLimitPolicy::Shed(max_wait) => Some(max_wait),
+        };
+
+        let RateLimitBudget { requests, orders } = &self.budget;
+        let order_wait = if order {
+            orders.reserve(max_wait).ok_or(Error::RateLimited)?
+        } else {
+            Duration::from_secs(0)
+        };
+
+        match requests.reserve(max_wait) {
+            Some(wait) => Ok(wait.max(order_wait)),
+            None => {
+                if order {
+                    orders.refund();
+                }
+                Err(Error::RateLimited)
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How failed requests are retried. Requests rejected with HTTP 429 are
+/// always safe to retry. Server errors and dropped connections are only
+/// retried for idempotent requests, i.e. `GET` and `DELETE` requests and
+/// `POST` requests with a `clientId`, because the request may have been
+/// processed.
+/// Requests rejected with a `Retry-After` header are retried after the
+/// requested delay instead of the backoff.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts including the first one, `1` disables retries
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each further retry
+    pub base_delay: Duration,
+    /// Upper bound of the delay between two attempts
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never retry failed requests
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retrying after `attempt` failed attempts. Between half
+    /// and the full exponential delay, so that clients don't retry in lockstep.
+    pub(super) fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .checked_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+
+        // Sub-second clock noise is random enough for jitter
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let jitter = nanos as f64 / 1_000_000_000.0;
+
+        delay / 2 + delay.mul_f64(jitter / 2.0)
+    }
+}
+
+/// Reason a request failed in a way that may succeed if retried
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum Transient {
+    /// The request was rejected without being processed, e.g. HTTP 429
+    NotProcessed,
+    /// The request may have been processed, e.g. HTTP 5xx or a dropped
+    /// connection
+    MaybeProcessed,
+}
@@ -1,4 +1,5 @@
 use super::*;
+use chrono::TimeZone;
 use dotenv::dotenv;
 use rust_decimal_macros::dec;
 use std::env::var;
@@ -73,7 +74,7 @@ async fn get_subaccount_balances() {
         None => "Bot",
         Some(sub) => sub,
     };
-    rest.get_subaccount_balances(&subaccount).await.unwrap_err();
+    rest.get_subaccount_balances(subaccount).await.unwrap_err();
 }
 
 #[tokio::test]
@@ -87,6 +88,34 @@ async fn transfer_between_subaccounts() {
     }
 }
 
+#[tokio::test]
+async fn subaccounts_deserialization() {
+    // Sanitized responses from FTX API.
+    let json = r#"[{
+        "nickname": "sub1",
+        "deletable": true,
+        "editable": true,
+        "competition": false
+    }]"#;
+    let subaccounts: Subaccounts = serde_json::from_str(json).unwrap();
+    assert_eq!(subaccounts[0].nickname, "sub1");
+
+    let json = r#"{
+        "id": 316450,
+        "coin": "XRP",
+        "size": 10000,
+        "time": "2019-03-05T09:56:55.728933+00:00",
+        "notes": "",
+        "status": "complete"
+    }"#;
+    let transfer: Transfer = serde_json::from_str(json).unwrap();
+    assert_eq!(transfer.size, dec!(10000));
+
+    // Endpoints without a result body
+    let _: ChangeName = serde_json::from_str("null").unwrap();
+    let _: Delete = serde_json::from_str("null").unwrap();
+}
+
 #[tokio::test]
 async fn get_markets() {
     init_api().await.get_markets().await.unwrap();
@@ -139,6 +168,94 @@ async fn get_future() {
     init_api().await.get_future("BTC-PERP").await.unwrap();
 }
 
+#[tokio::test]
+async fn get_future_stats() {
+    let stats = init_api().await.get_future_stats("BTC-PERP").await.unwrap();
+    assert!(stats.next_funding_time.is_some());
+}
+
+#[tokio::test]
+async fn get_funding_rates() {
+    init_api()
+        .await
+        .get_funding_rates(Some("BTC-PERP"), None, None)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn future_stats_deserialization() {
+    // Sanitized response from FTX API.
+    let json = r#"{
+        "volume": 1000.23,
+        "nextFundingRate": 0.00025,
+        "nextFundingTime": "2019-03-29T03:00:00+00:00",
+        "openInterest": 21124.583
+    }"#;
+    let stats: FutureStats = serde_json::from_str(json).unwrap();
+    assert_eq!(stats.next_funding_rate, Some(dec!(0.00025)));
+    assert_eq!(stats.expiration_price, None);
+}
+
+#[tokio::test]
+async fn change_account_leverage() {
+    let rest = init_api().await;
+    read_only(rest.change_account_leverage(10).await);
+}
+
+#[tokio::test]
+async fn get_fills() {
+    init_api()
+        .await
+        .get_fills(Some("BTC-PERP"), None, None, None)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn get_funding_payments() {
+    init_api()
+        .await
+        .get_funding_payments(None, None, None)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn fills_funding_payments_deserialization() {
+    // Sanitized responses from FTX API.
+    let json = r#"[{
+        "fee": 20.1374935,
+        "feeCurrency": "USD",
+        "feeRate": 0.0005,
+        "future": "EOS-0329",
+        "id": 11215,
+        "liquidity": "taker",
+        "market": "EOS-0329",
+        "baseCurrency": null,
+        "quoteCurrency": null,
+        "orderId": 8436981,
+        "tradeId": 1013912,
+        "price": 4.201,
+        "side": "buy",
+        "size": 9587,
+        "time": "2019-03-27T19:15:10.204619+00:00",
+        "type": "order"
+    }]"#;
+    let fills: Fills = serde_json::from_str(json).unwrap();
+    assert_eq!(fills[0].liquidity, Liquidity::Taker);
+
+    let json = r#"[{
+        "future": "ETH-PERP",
+        "id": 33830,
+        "payment": 0.0441342,
+        "time": "2019-05-15T18:00:00+00:00",
+        "rate": 0.0001
+    }]"#;
+    let payments: FundingPayments = serde_json::from_str(json).unwrap();
+    assert_eq!(payments[0].payment, dec!(0.0441342));
+}
+
 #[tokio::test]
 async fn account_deserialization() {
     // Sanitized response from FTX API.
@@ -184,6 +301,200 @@ async fn account_deserialization() {
     let _account: Account = serde_json::from_str(json).unwrap();
 }
 
+#[tokio::test]
+async fn markets_deserialization() {
+    // Sanitized response from FTX API.
+    let json = r#"[{
+        "name":"BTC-0628",
+        "baseCurrency":null,
+        "quoteCurrency":null,
+        "quoteVolume24h":28914.76,
+        "change1h":0.012,
+        "change24h":0.0299,
+        "changeBod":0.0156,
+        "highLeverageFeeExempt":false,
+        "minProvideSize":0.001,
+        "type":"future",
+        "underlying":"BTC",
+        "enabled":true,
+        "ask":3949.25,
+        "bid":3949,
+        "last":10579.52,
+        "postOnly":false,
+        "price":10579.52,
+        "priceIncrement":0.25,
+        "sizeIncrement":0.0001,
+        "restricted":false,
+        "volumeUsd24h":28914.76
+    }]"#;
+    let markets: Markets = serde_json::from_str(json).unwrap();
+    assert_eq!(markets[0].market_type, MarketType::Future);
+    assert_eq!(markets[0].price_increment, dec!(0.25));
+}
+
+#[tokio::test]
+async fn orderbook_trades_prices_deserialization() {
+    // Sanitized responses from FTX API.
+    let json = r#"{
+        "asks":[[4114.25,6.263]],
+        "bids":[[4112.25,49.29],[4112.0,3.2]]
+    }"#;
+    let orderbook: Orderbook = serde_json::from_str(json).unwrap();
+    assert_eq!(orderbook.bids[1], (dec!(4112), dec!(3.2)));
+
+    let json = r#"[{
+        "id":3855995,
+        "liquidation":false,
+        "price":3857.75,
+        "side":"buy",
+        "size":0.111,
+        "time":"2019-03-20T18:16:23.397991+00:00"
+    }]"#;
+    let trades: Trades = serde_json::from_str(json).unwrap();
+    assert_eq!(trades[0].side, Side::Buy);
+
+    let json = r#"[{
+        "close":11055.25,
+        "high":11089.0,
+        "low":11043.5,
+        "open":11059.25,
+        "startTime":"2019-06-24T17:15:00+00:00",
+        "volume":464193.95725
+    }]"#;
+    let prices: Prices = serde_json::from_str(json).unwrap();
+    assert_eq!(prices[0].close, dec!(11055.25));
+}
+
+#[tokio::test]
+async fn order_request() {
+    let order = OrderRequest::limit("ETH-PERP", OrderSide::Buy, dec!(1234.5), dec!(0.001))
+        .post_only(true)
+        .client_id("my-order");
+    assert_eq!(
+        serde_json::to_value(&order).unwrap(),
+        json!({
+            "market": "ETH-PERP",
+            "side": "buy",
+            "price": "1234.5",
+            "type": "limit",
+            "size": "0.001",
+            "reduceOnly": false,
+            "ioc": false,
+            "postOnly": true,
+            "rejectOnPriceBand": false,
+            "clientId": "my-order",
+        })
+    );
+
+    // Flags are serialized as separate fields
+    let flags = OrderFlags::new().reduce_only(true).ioc(true);
+    let order = OrderRequest::market("ETH-PERP", OrderSide::Sell, dec!(0.001))
+        .flags(flags)
+        .reject_on_price_band(true);
+    assert_eq!(
+        serde_json::to_value(&order).unwrap(),
+        json!({
+            "market": "ETH-PERP",
+            "side": "sell",
+            "price": null,
+            "type": "market",
+            "size": "0.001",
+            "reduceOnly": true,
+            "ioc": true,
+            "postOnly": false,
+            "rejectOnPriceBand": true,
+            "clientId": null,
+        })
+    );
+    assert_eq!(OrderSide::from(Side::Sell), OrderSide::Sell);
+
+    // Limit orders are validated before being sent
+    let mut order = OrderRequest::market("ETH-PERP", OrderSide::Sell, dec!(0.001));
+    order.r#type = OrderType::Limit;
+    let rest = Rest::new("key".to_string(), "secret".to_string(), None);
+    assert!(matches!(
+        rest.submit_order(&order).await,
+        Err(Error::PlacingLimitOrderRequiresPrice)
+    ));
+}
+
+#[tokio::test]
+async fn conditional_orders() {
+    let order = ConditionalOrderRequest::trailing_stop(
+        "XRP-PERP",
+        OrderSide::Sell,
+        dec!(31431),
+        dec!(-0.05),
+    )
+    .reduce_only(true);
+    assert_eq!(
+        serde_json::to_value(&order).unwrap(),
+        json!({
+            "market": "XRP-PERP",
+            "side": "sell",
+            "size": "31431",
+            "type": "trailingStop",
+            "reduceOnly": true,
+            "retryUntilFilled": true,
+            "trailValue": "-0.05",
+        })
+    );
+
+    // Sanitized response from FTX API.
+    let json = r#"{
+        "createdAt": "2019-03-05T09:56:55.728933+00:00",
+        "error": null,
+        "future": "XRP-PERP",
+        "id": 50001,
+        "market": "XRP-PERP",
+        "orderId": null,
+        "orderPrice": null,
+        "reduceOnly": true,
+        "side": "sell",
+        "size": 31431,
+        "status": "open",
+        "trailStart": 0.35,
+        "trailValue": -0.05,
+        "triggerPrice": 0.3,
+        "triggeredAt": null,
+        "type": "trailing_stop",
+        "orderType": "market",
+        "filledSize": 0,
+        "avgFillPrice": null,
+        "retryUntilFilled": true
+    }"#;
+    let info: ConditionalOrderInfo = serde_json::from_str(json).unwrap();
+    assert_eq!(info.r#type, ConditionalOrderType::TrailingStop);
+    assert_eq!(info.status, ConditionalOrderStatus::Open);
+    assert_eq!(info.trail_value, Some(dec!(-0.05)));
+}
+
+#[tokio::test]
+async fn wallet_withdrawal_deserialization() {
+    // Sanitized response from FTX API.
+    let json = r#"{
+        "coin": "TUSD",
+        "address": "0x83a127952d266A6eA306c40Ac62A4a70668FE3BE",
+        "tag": null,
+        "fee": 0,
+        "id": 1,
+        "size": 20.2,
+        "status": "requested",
+        "time": "2019-03-05T09:56:55.728933+00:00",
+        "txid": null
+    }"#;
+    let withdrawal: WalletWithdrawal = serde_json::from_str(json).unwrap();
+    assert_eq!(withdrawal.status, WithdrawalStatus::Requested);
+    assert_eq!(withdrawal.size, dec!(20.2));
+
+    let json = r#"{
+        "main": [{"coin": "USD", "free": 4321.2, "total": 4340.2, "spotBorrow": 0, "availableWithoutBorrow": 2320.2}],
+        "Battle Royale": [{"coin": "BTC", "free": 0.1, "total": 0.1, "spotBorrow": 0, "availableWithoutBorrow": 0.1}]
+    }"#;
+    let balances: HashMap<String, Vec<WalletBalance>> = serde_json::from_str(json).unwrap();
+    assert_eq!(balances["Battle Royale"][0].coin, "BTC");
+}
+
 #[tokio::test]
 async fn place_modify_cancel_order() {
     let api = init_api().await;
@@ -260,3 +571,1212 @@ async fn place_modify_cancel_order() {
     assert_eq!(None, cancelled_order.avg_fill_price);
     assert_eq!(OrderStatus::Closed, cancelled_order.status);
 }
+
+#[test]
+fn rate_limiter() {
+    let mut limiter = RateLimiter::new();
+    limiter.set_requests_per_second(4);
+    limiter.set_orders_per_second(10);
+    limiter.policy = RateLimitPolicy::Error;
+
+    // Burst of 2 orders per 200ms
+    assert_eq!(limiter.reserve(true).unwrap(), Duration::from_secs(0));
+    assert_eq!(limiter.reserve(true).unwrap(), Duration::from_secs(0));
+    assert!(matches!(limiter.reserve(true), Err(Error::RateLimited)));
+
+    // Rejected orders don't count towards the request limit
+    assert_eq!(limiter.reserve(false).unwrap(), Duration::from_secs(0));
+    assert_eq!(limiter.reserve(false).unwrap(), Duration::from_secs(0));
+    assert!(matches!(limiter.reserve(false), Err(Error::RateLimited)));
+
+    limiter.policy = RateLimitPolicy::Shed(Duration::from_millis(100));
+    assert!(matches!(limiter.reserve(false), Err(Error::RateLimited)));
+
+    limiter.policy = RateLimitPolicy::Wait;
+    let wait = limiter.reserve(false).unwrap();
+    assert!(wait > Duration::from_millis(200) && wait <= Duration::from_millis(250));
+}
+
+#[test]
+fn retry_backoff() {
+    let policy = RetryPolicy {
+        max_attempts: 5,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(300),
+    };
+
+    let first = policy.backoff(1);
+    assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+    let second = policy.backoff(2);
+    assert!(second >= Duration::from_millis(100) && second <= Duration::from_millis(200));
+    // Capped by the maximum delay
+    let fourth = policy.backoff(4);
+    assert!(fourth >= Duration::from_millis(150) && fourth <= Duration::from_millis(300));
+    assert_eq!(RetryPolicy::none().max_attempts, 1);
+}
+
+/// Starts a local HTTP server answering each request with the next of
+/// `responses`. The server returns the lowercased requests it received.
+async fn mock_api(
+    responses: Vec<(&'static str, String)>,
+) -> (Rest, tokio::task::JoinHandle<Vec<String>>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("http://{}/api", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut requests = Vec::new();
+        for (status, body) in responses {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 4096];
+            let len = socket.read(&mut buf).await.unwrap();
+            requests.push(String::from_utf8_lossy(&buf[..len]).to_lowercase());
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+        requests
+    });
+
+    let api = Rest::new_with_endpoint(
+        &endpoint,
+        "FTX",
+        "key".to_string(),
+        "secret".to_string(),
+        None,
+    )
+    .unwrap();
+
+    (api, server)
+}
+
+#[tokio::test]
+async fn retry_rate_limited() {
+    // Rejects the first request, answers the second
+    let (mut api, server) = mock_api(vec![
+        (
+            "429 Too Many Requests",
+            r#"{"success":false,"error":"Do not send more than 30 requests per second"}"#
+                .to_string(),
+        ),
+        ("200 OK", r#"{"success":true,"result":[]}"#.to_string()),
+    ])
+    .await;
+    api.set_retry_policy(RetryPolicy {
+        base_delay: Duration::from_millis(10),
+        ..RetryPolicy::default()
+    });
+
+    assert!(api.get_subaccounts().await.unwrap().is_empty());
+
+    assert_eq!(server.await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn retry_after() {
+    // The headers are appended to the status line
+    let rate_limited = || {
+        (
+            "429 Too Many Requests\r\nRetry-After: 0\r\nX-RateLimit-Limit: 30\r\nX-RateLimit-Remaining: 0",
+            r#"{"success":false,"error":"Do not send more than 30 requests per second"}"#
+                .to_string(),
+        )
+    };
+
+    // Retries right away instead of after the backoff
+    let (mut api, server) = mock_api(vec![
+        rate_limited(),
+        ("200 OK", r#"{"success":true,"result":[]}"#.to_string()),
+    ])
+    .await;
+    api.set_retry_policy(RetryPolicy {
+        base_delay: Duration::from_secs(60),
+        max_delay: Duration::from_secs(60),
+        ..RetryPolicy::default()
+    });
+    let result = tokio::time::timeout(Duration::from_secs(5), api.get_subaccounts()).await;
+    assert!(result.unwrap().unwrap().is_empty());
+    assert_eq!(server.await.unwrap().len(), 2);
+
+    let (mut api, server) = mock_api(vec![rate_limited()]).await;
+    api.set_retry_policy(RetryPolicy::none());
+    match api.get_subaccounts().await {
+        Err(Error::TooManyRequests(info)) => assert_eq!(
+            info,
+            RateLimitInfo {
+                retry_after: Some(Duration::ZERO),
+                limit: Some(30),
+                remaining: Some(0),
+            }
+        ),
+        result => panic!("unexpected result: {:?}", result),
+    }
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn get_historical_candles() {
+    fn candles(times: &[i64]) -> String {
+        let candles: Vec<String> = times
+            .iter()
+            .map(|time| {
+                format!(
+                    r#"{{"startTime": "{}", "time": {}, "open": 1.0, "high": 2.0, "low": 0.5, "close": 1.5, "volume": 10.0}}"#,
+                    Utc.timestamp_opt(*time, 0).unwrap().to_rfc3339(),
+                    time * 1000
+                )
+            })
+            .collect();
+        format!(r#"{{"success": true, "result": [{}]}}"#, candles.join(","))
+    }
+
+    // Two overlapping pages walking backwards, then an empty page
+    let (api, server) = mock_api(vec![
+        ("200 OK", candles(&[6000, 7000, 8000])),
+        ("200 OK", candles(&[4000, 5000, 6000])),
+        ("200 OK", candles(&[])),
+    ])
+    .await;
+
+    let candles = api
+        .get_historical_candles(
+            "BTC-PERP",
+            1000,
+            Utc.timestamp_opt(3000, 0).unwrap(),
+            Utc.timestamp_opt(8000, 0).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let times: Vec<i64> = candles.iter().map(|c| c.start_time.timestamp()).collect();
+    assert_eq!(times, vec![4000, 5000, 6000, 7000, 8000]);
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].contains("end_time=8000"));
+    assert!(requests[1].contains("end_time=5999"));
+    assert!(requests[2].contains("end_time=3999"));
+}
+
+#[tokio::test]
+async fn get_all_trades() {
+    fn trades(trades: &[(u64, &str)]) -> String {
+        let trades: Vec<String> = trades
+            .iter()
+            .map(|(id, time)| {
+                format!(
+                    r#"{{"id": {}, "liquidation": false, "price": 40000.0, "side": "buy", "size": 0.1, "time": "{}"}}"#,
+                    id, time
+                )
+            })
+            .collect();
+        format!(r#"{{"success": true, "result": [{}]}}"#, trades.join(","))
+    }
+
+    // Newest first, pages overlap within the second of their earliest trade
+    let (api, server) = mock_api(vec![
+        (
+            "200 OK",
+            trades(&[
+                (5, "1970-01-01T02:13:20.5+00:00"),
+                (4, "1970-01-01T01:56:40+00:00"),
+                (3, "1970-01-01T01:40:00.2+00:00"),
+            ]),
+        ),
+        (
+            "200 OK",
+            trades(&[
+                (3, "1970-01-01T01:40:00.2+00:00"),
+                (2, "1970-01-01T01:40:00.1+00:00"),
+                (1, "1970-01-01T01:23:20+00:00"),
+            ]),
+        ),
+        ("200 OK", trades(&[(1, "1970-01-01T01:23:20+00:00")])),
+    ])
+    .await;
+
+    let trades = api
+        .get_all_trades(
+            "BTC-PERP",
+            Utc.timestamp_opt(3000, 0).unwrap(),
+            Utc.timestamp_opt(9000, 0).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let ids: Vec<u64> = trades.iter().map(|trade| trade.id).collect();
+    assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].contains("end_time=9000"));
+    assert!(requests[1].contains("end_time=6000"));
+    assert!(requests[2].contains("end_time=5000"));
+}
+
+#[tokio::test]
+async fn sync_clock() {
+    let (api, server) = mock_api(vec![
+        (
+            "200 OK",
+            r#"{"success": true, "result": "2050-01-01T00:00:00+00:00"}"#.to_string(),
+        ),
+        ("200 OK", r#"{"success": true, "result": []}"#.to_string()),
+    ])
+    .await;
+
+    let offset = api.sync_clock().await.unwrap();
+    assert_eq!(api.clock_offset(), offset);
+    api.get_subaccounts().await.unwrap();
+
+    // Signed with the server's time
+    let requests = server.await.unwrap();
+    let server_time = Utc.timestamp_opt(2524608000, 0).unwrap().timestamp_millis();
+    let timestamp: i64 = requests[1]
+        .lines()
+        .find_map(|line| line.strip_prefix("ftx-ts: "))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((timestamp - server_time).abs() < 1000);
+}
+
+#[test]
+fn symbol() {
+    let kinds = [
+        ("BTC/USD", SymbolKind::Spot),
+        ("BTC-PERP", SymbolKind::Perpetual),
+        ("BTC-0325", SymbolKind::Future),
+        ("BTC-MOVE-WK-0402", SymbolKind::Move),
+        ("TRUMP2024", SymbolKind::Other),
+    ];
+    for (name, kind) in kinds.iter() {
+        let symbol: Symbol = name.parse().unwrap();
+        assert_eq!(symbol.kind(), *kind);
+        assert_eq!(symbol, *name);
+    }
+
+    for name in [
+        "",
+        "btc-perp",
+        "BTC--PERP",
+        "BTC/",
+        "BTC/USD/EUR",
+        "BTC PERP",
+    ]
+    .iter()
+    {
+        assert!(matches!(Symbol::parse(name), Err(Error::InvalidSymbol(_))));
+    }
+
+    // Usable as a map key looked up by name
+    let mut markets = HashMap::new();
+    markets.insert(Symbol::from("BTC-PERP"), 1);
+    assert_eq!(markets.get("BTC-PERP"), Some(&1));
+}
+
+#[tokio::test]
+async fn mock_rest() {
+    use crate::testing::{MockRest, RecordedRequest};
+
+    let server = MockRest::with_fixtures().await.unwrap();
+    let api = server.rest();
+
+    let markets = api.get_markets().await.unwrap();
+    assert_eq!(markets[0].name, "BTC-PERP");
+    assert_eq!(markets[1].name.kind(), SymbolKind::Spot);
+
+    let account = api.get_account().await.unwrap();
+    assert_eq!(account.positions[0].future, "ETH-PERP");
+
+    let order = api
+        .place_order(
+            "BTC-PERP",
+            OrderSide::Buy,
+            Some(dec!(40000)),
+            OrderType::Limit,
+            dec!(0.001),
+            None,
+            None,
+            None,
+            Some("my-order"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(order.status, OrderStatus::New);
+
+    let requests = server.requests();
+    assert!(requests.iter().all(|request| request.signature_valid));
+    let place: &RecordedRequest = &requests[2];
+    assert_eq!(place.method, "POST");
+    assert_eq!(place.path, "/api/orders");
+    assert_eq!(place.header("FTX-KEY"), Some(MockRest::KEY));
+    assert_eq!(place.json()["clientId"], "my-order");
+
+    // Responses are served in order, the last one repeatedly
+    server.respond_error("GET", "/subaccounts", 400, "Not allowed with subaccount");
+    server.respond_ok("GET", "/subaccounts", serde_json::json!([]));
+    assert!(api.get_subaccounts().await.is_err());
+    assert!(api.get_subaccounts().await.unwrap().is_empty());
+    assert!(api.get_subaccounts().await.unwrap().is_empty());
+
+    // Wrongly signed requests are rejected
+    let api = Rest::new_with_endpoint(
+        &server.endpoint(),
+        "FTX",
+        MockRest::KEY.to_string(),
+        "wrong".to_string(),
+        None,
+    )
+    .unwrap();
+    match api.get_markets().await {
+        Err(Error::Api(error)) => assert_eq!(error, "Not logged in"),
+        _ => panic!("Unauthorized request expected."),
+    }
+    assert!(!server.requests().last().unwrap().signature_valid);
+}
+
+#[tokio::test]
+async fn convert_quote() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok("POST", "/otc/quotes", json!({"quoteId": 1031}));
+    server.respond_ok(
+        "GET",
+        "/otc/quotes/1031",
+        json!({"baseCoin": "BTC", "cost": 1.0, "expired": false, "expiry": 1630269420.0, "filled": false, "fromCoin": "USD", "id": 1031, "price": 0.0001, "proceeds": 0.0001, "quoteCoin": "USD", "side": "sell", "toCoin": "BTC"}),
+    );
+    server.respond_ok("POST", "/otc/quotes/1031/accept", json!(null));
+    let api = server.rest();
+
+    let quote_id = api
+        .request_quote("USD", "BTC", dec!(1))
+        .await
+        .unwrap()
+        .quote_id;
+    assert_eq!(quote_id, 1031);
+    let quote = api.get_quote_status(quote_id).await.unwrap();
+    assert_eq!(quote.to_coin, "BTC");
+    assert_eq!(quote.proceeds, dec!(0.0001));
+    assert_eq!(quote.expiry.timestamp(), 1630269420);
+    assert!(!quote.expired && !quote.filled);
+    api.accept_quote(quote_id).await.unwrap();
+
+    let requests = server.requests();
+    assert!(requests.iter().all(|request| request.signature_valid));
+    assert_eq!(requests[0].json()["fromCoin"], "USD");
+    assert_eq!(requests[2].path, "/api/otc/quotes/1031/accept");
+}
+
+#[tokio::test]
+async fn accept_quote_not_retried() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    // Accepting a quote has no body with a client ID to detect duplicates,
+    // so it isn't retried after the server failed
+    let server = MockRest::bind().await.unwrap();
+    server.respond_error("POST", "/otc/quotes/1031/accept", 500, "Internal error");
+    server.respond_ok("POST", "/otc/quotes/1031/accept", json!(null));
+    let mut api = server.rest();
+    api.set_retry_policy(RetryPolicy {
+        base_delay: Duration::from_millis(10),
+        ..RetryPolicy::default()
+    });
+
+    assert!(api.accept_quote(1031).await.is_err());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn staking() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "GET",
+        "/staking/stakes",
+        json!([{"coin": "SRM", "createdAt": "2020-08-12T14:39:06.210522+00:00", "id": 1, "size": 12.5}]),
+    );
+    server.respond_ok(
+        "GET",
+        "/staking/balances",
+        json!([{"coin": "SRM", "lifetimeRewards": 0.5, "scheduledToUnstake": 2.0, "staked": 12.5}]),
+    );
+    server.respond_ok(
+        "POST",
+        "/staking/unstake_requests",
+        json!({"coin": "SRM", "createdAt": "2020-09-12T14:39:06.210522+00:00", "id": 2, "size": 2.0, "status": "pending", "unlockAt": "2020-09-19T14:39:06.210522+00:00"}),
+    );
+    server.respond_ok("DELETE", "/staking/unstake_requests/2", json!("Cancelled"));
+    server.respond_ok(
+        "GET",
+        "/staking/staking_rewards",
+        json!([{"coin": "SRM", "createdAt": "2020-09-13T00:00:00+00:00", "id": 3, "size": 0.5, "status": "complete"}]),
+    );
+    server.respond_ok(
+        "POST",
+        "/srm_stakes/stakes",
+        json!({"coin": "SRM", "createdAt": "2020-09-14T00:00:00+00:00", "id": 4, "size": 1.0}),
+    );
+    let api = server.rest();
+
+    assert_eq!(api.get_stakes().await.unwrap()[0].size, dec!(12.5));
+    let balance = &api.get_stake_balances().await.unwrap()[0];
+    assert_eq!(balance.scheduled_to_unstake, dec!(2));
+    let request = api.request_unstake("SRM", dec!(2)).await.unwrap();
+    assert_eq!(request.status, UnstakeRequestStatus::Pending);
+    assert_eq!(
+        api.cancel_unstake_request(request.id).await.unwrap(),
+        "Cancelled"
+    );
+    assert_eq!(
+        api.get_staking_rewards().await.unwrap()[0].status,
+        "complete"
+    );
+    assert_eq!(api.stake_srm("SRM", dec!(1)).await.unwrap().id, 4);
+
+    let requests = server.requests();
+    assert!(requests.iter().all(|request| request.signature_valid));
+    assert_eq!(requests[2].json()["size"], "2");
+}
+
+#[tokio::test]
+async fn leveraged_tokens() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+    use std::time::Duration;
+
+    let redemption = |pending: bool| json!({"id": 7, "token": "BULL", "size": 2.0, "pending": pending, "requestedAt": "2019-11-23T05:24:24.315884+00:00", "projectedProceeds": 1000.0, "proceeds": if pending { Value::Null } else { json!(999.5) }, "price": null, "fee": null, "fulfilledAt": null});
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "GET",
+        "/lt/BULL",
+        json!({"name": "BULL", "description": "3x Long Bitcoin Token", "underlying": "BTC-PERP", "leverage": 3.0, "outstanding": 10.0, "pricePerShare": 500.0, "positionPerShare": 0.03, "underlyingMark": 50000.0, "contractAddress": "0x4e0f...", "change1h": 0.01, "change24h": -0.02}),
+    );
+    server.respond_ok(
+        "GET",
+        "/lt/balances",
+        json!([{"token": "BULL", "balance": 2.0}]),
+    );
+    server.respond_ok("POST", "/lt/BULL/redeem", redemption(true));
+    server.respond_ok("GET", "/lt/redemptions", json!([redemption(true)]));
+    server.respond_ok("GET", "/lt/redemptions", json!([redemption(false)]));
+    let api = server.rest();
+
+    let token = api.get_lt_token("BULL").await.unwrap();
+    assert_eq!(token.underlying, "BTC-PERP");
+    assert_eq!(token.change_24h, dec!(-0.02));
+    assert_eq!(api.get_lt_balances().await.unwrap()[0].balance, dec!(2));
+
+    let redemption = api.redeem_lt("BULL", dec!(2)).await.unwrap();
+    assert!(redemption.pending);
+    let redemption = api
+        .wait_for_lt_redemption(redemption.id, Duration::from_millis(10))
+        .await
+        .unwrap();
+    assert_eq!(redemption.proceeds, Some(dec!(999.5)));
+    assert_eq!(server.requests().len(), 5);
+
+    assert!(matches!(
+        api.wait_for_lt_redemption(8, Duration::from_millis(10))
+            .await,
+        Err(Error::RedemptionNotFound(8))
+    ));
+}
+
+#[tokio::test]
+async fn options() {
+    use crate::testing::MockRest;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    let option = json!({"underlying": "BTC", "type": "call", "strike": 7800, "expiry": "2020-01-08T03:00:00+00:00"});
+    let quote = json!({"collateral": 445.55, "id": 3, "option": option, "price": 150.0, "quoteExpiry": null, "quoterSide": "sell", "requestId": 2, "requestSide": "buy", "size": 1.2, "status": "open", "time": "2020-01-08T02:30:00+00:00"});
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "POST",
+        "/options/requests",
+        json!({"id": 2, "option": option, "side": "buy", "size": 1.2, "time": "2020-01-08T02:30:00+00:00", "requestExpiry": "2020-01-08T02:35:00+00:00", "status": "open", "limitPrice": 160.0, "hideLimitPrice": false, "quotes": []}),
+    );
+    server.respond_ok("GET", "/options/requests/2/quotes", json!([quote]));
+    let mut filled = quote.clone();
+    filled["status"] = json!("filled");
+    server.respond_ok("POST", "/options/quotes/3/accept", filled);
+    server.respond_ok(
+        "GET",
+        "/options/positions",
+        json!([{"entryPrice": 150.0, "netSize": 1.2, "option": option, "side": "buy", "size": 1.2}]),
+    );
+    server.respond_ok(
+        "GET",
+        "/stats/24h_options_volume",
+        json!({"contracts": 216.2, "underlying_total": 3.2}),
+    );
+    let api = server.rest();
+
+    let expiry = Utc.with_ymd_and_hms(2020, 1, 8, 3, 0, 0).unwrap();
+    let request = api
+        .create_option_quote_request(
+            &OptionRequest::new(
+                "BTC",
+                OptionType::Call,
+                dec!(7800),
+                expiry,
+                OrderSide::Buy,
+                dec!(1.2),
+            )
+            .limit_price(dec!(160))
+            .hide_limit_price(false),
+        )
+        .await
+        .unwrap();
+    assert_eq!(request.status, OptionStatus::Open);
+    assert_eq!(request.option.expiry, expiry);
+    let body = server.requests()[0].json();
+    assert_eq!(body["expiry"], expiry.timestamp());
+    assert_eq!(body["type"], "call");
+    assert!(body.get("requestExpiry").is_none());
+
+    let quotes = api.get_option_quotes(request.id).await.unwrap();
+    assert_eq!(quotes[0].quoter_side, Side::Sell);
+    let accepted = api.accept_option_quote(quotes[0].id).await.unwrap();
+    assert_eq!(accepted.status, OptionStatus::Filled);
+
+    let positions = api.get_option_positions().await.unwrap();
+    assert_eq!(positions[0].option.r#type, OptionType::Call);
+    assert_eq!(positions[0].net_size, dec!(1.2));
+    let volume = api.get_option_volume_24h().await.unwrap();
+    assert_eq!(volume.underlying_total, dec!(3.2));
+}
+
+#[tokio::test]
+async fn accept_option_quote_not_retried() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_error("POST", "/options/quotes/7/accept", 502, "Bad gateway");
+    server.respond_ok("POST", "/options/quotes/7/accept", json!(null));
+    let mut api = server.rest();
+    api.set_retry_policy(RetryPolicy {
+        base_delay: Duration::from_millis(10),
+        ..RetryPolicy::default()
+    });
+
+    // The quote may have been accepted, so the request isn't sent again
+    assert!(api.accept_option_quote(7).await.is_err());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn spot_margin_lending() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "GET",
+        "/spot_margin/lending_rates",
+        json!([{"coin": "USD", "estimate": 0.000002, "previous": 0.000001}]),
+    );
+    server.respond_ok(
+        "GET",
+        "/spot_margin/lending_info",
+        json!([{"coin": "USD", "lendable": 1000.0, "locked": 100.0, "minRate": 0.000001, "offered": 100.0}]),
+    );
+    server.respond_ok("POST", "/spot_margin/offers", json!(null));
+    server.respond_ok(
+        "GET",
+        "/spot_margin/lending_history",
+        json!([{"coin": "USD", "proceeds": 0.0002, "rate": 0.000002, "size": 100.0, "time": "2021-05-23T05:00:00+00:00"}]),
+    );
+    let api = server.rest();
+
+    let rates = api.get_lending_rates().await.unwrap();
+    assert_eq!(rates[0].estimate, dec!(0.000002));
+    let info = &api.get_lending_info().await.unwrap()[0];
+    assert_eq!(info.lendable, dec!(1000));
+    api.submit_lending_offer("USD", info.lendable, rates[0].estimate)
+        .await
+        .unwrap();
+    let history = api.get_lending_history(None, None).await.unwrap();
+    assert_eq!(history[0].proceeds, dec!(0.0002));
+
+    let body = server.requests()[2].json();
+    assert_eq!(body["coin"], "USD");
+    assert_eq!(body["size"], "1000");
+}
+
+#[tokio::test]
+async fn fiat_balances() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "GET",
+        "/wallet/coins",
+        json!([
+            {"canDeposit": true, "canWithdraw": true, "hasTag": false, "id": "USD", "name": "US Dollar", "fiat": true, "methods": []},
+            {"canDeposit": true, "canWithdraw": true, "hasTag": false, "id": "USDT", "name": "USD Tether", "fiat": false, "collateral": true, "collateralWeight": 0.975, "methods": ["omni", "erc20", "trx", "sol"]}
+        ]),
+    );
+    server.respond_ok(
+        "GET",
+        "/wallet/balances",
+        json!([
+            {"coin": "USD", "free": 100.0, "total": 100.0, "spotBorrow": 0, "availableWithoutBorrow": 100.0},
+            {"coin": "USDT", "free": 50.0, "total": 50.0, "spotBorrow": 0, "availableWithoutBorrow": 50.0}
+        ]),
+    );
+    let api = server.rest();
+
+    let coins = api.get_wallet_coins().await.unwrap();
+    assert_eq!(coins[1].methods, ["omni", "erc20", "trx", "sol"]);
+    let balances = api.get_fiat_balances().await.unwrap();
+    assert_eq!(balances.len(), 1);
+    assert_eq!(balances[0].coin, "USD");
+}
+
+#[cfg(feature = "us")]
+#[tokio::test]
+async fn nft() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let nft = json!({"id": 123, "name": "Serum Red Dragon", "description": "Dragon", "issuer": "FTX", "collection": "Dragons", "series": null, "solMintAddress": null, "ethContractAddress": null, "imageUrl": "https://example.com/dragon.png", "videoUrl": null, "animationUrl": null, "thumbnailUrl": null, "quoteCurrency": "USD", "offerPrice": null, "auction": {"bestBid": 150.0, "minNextBid": 155.0, "endTime": "2021-10-01T00:00:00+00:00", "bids": 3}, "lastPrice": 120.0, "totalQuantity": 1});
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok("GET", "/nft/nfts", json!([nft]));
+    server.respond_ok("POST", "/nft/bids", nft.clone());
+    server.respond_ok("GET", "/nft/balances", json!([]));
+    let api = server.rest();
+
+    let nfts = api.get_nfts().await.unwrap();
+    let auction = nfts[0].auction.as_ref().unwrap();
+    assert_eq!(auction.min_next_bid, dec!(155));
+    api.place_nft_bid(nfts[0].id, auction.min_next_bid)
+        .await
+        .unwrap();
+    assert!(api.get_nft_balances().await.unwrap().is_empty());
+    assert_eq!(server.requests()[1].json()["nftId"], 123);
+}
+
+#[tokio::test]
+async fn get_all_funding_rates() {
+    use crate::testing::MockRest;
+    use serde_json::{json, Value};
+
+    // Hourly rates of two futures, newest first
+    let rates = |hours: std::ops::RangeInclusive<i64>| -> Value {
+        let rates: Vec<Value> = hours
+            .rev()
+            .flat_map(|hour| {
+                let time = Utc.timestamp_opt(hour * 3600, 0).unwrap();
+                ["BTC-PERP", "ETH-PERP"]
+                    .iter()
+                    .map(move |future| json!({"future": future, "rate": 0.0001, "time": time}))
+            })
+            .collect();
+        Value::from(rates)
+    };
+
+    // The first page is full, the second overlaps it at hour 50
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok("GET", "/funding_rates", rates(50..=299));
+    server.respond_ok("GET", "/funding_rates", rates(0..=50));
+    let api = server.rest();
+
+    let rates = api
+        .get_all_funding_rates(
+            None,
+            Utc.timestamp_opt(0, 0).unwrap(),
+            Utc.timestamp_opt(299 * 3600, 0).unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(rates.len(), 600);
+    assert_eq!(rates[0].time, Utc.timestamp_opt(0, 0).unwrap());
+    assert_eq!(rates[0].future, "BTC-PERP");
+    assert_eq!(rates[1].future, "ETH-PERP");
+    assert!(rates.windows(2).all(|pair| pair[0].time <= pair[1].time));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[1].path.contains("end_time=180000"));
+}
+
+#[tokio::test]
+async fn index_weights_candles() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "GET",
+        "/indexes/ALT/weights",
+        json!({"BCH": 0.3492, "BNB": 2.8632, "BSV": 0.0, "EOS": 19.1858}),
+    );
+    server.respond_ok(
+        "GET",
+        "/indexes/BTC/candles",
+        json!([{"close": 40010.0, "high": 40020.0, "low": 39990.0, "open": 40000.0, "startTime": "2021-05-23T05:00:00+00:00", "volume": null}]),
+    );
+    let api = server.rest();
+
+    let weights = api.get_index_weights("ALT").await.unwrap();
+    assert_eq!(weights["EOS"], dec!(19.1858));
+
+    let candles = api
+        .get_index_candles("BTC", 3600, Some(1), None, None)
+        .await
+        .unwrap();
+    assert_eq!(candles[0].close, dec!(40010));
+    assert_eq!(candles[0].volume, None);
+    assert!(server.requests()[1].path.contains("resolution=3600"));
+}
+
+#[tokio::test]
+async fn paginate_fills() {
+    use crate::testing::MockRest;
+    use serde_json::{json, Value};
+
+    let fills = |fills: &[(u64, &str)]| -> Value {
+        fills
+            .iter()
+            .map(|(id, time)| json!({"id": id, "market": "BTC-PERP", "future": "BTC-PERP", "baseCurrency": null, "quoteCurrency": null, "type": "order", "side": "buy", "price": 40000.0, "size": 0.1, "orderId": 1, "tradeId": id, "time": time, "fee": 0.0, "feeRate": 0.0, "feeCurrency": "USD", "liquidity": "maker"}))
+            .collect()
+    };
+
+    // Pages overlap within the second of their earliest fill
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "GET",
+        "/fills",
+        fills(&[
+            (5, "1970-01-01T02:13:20+00:00"),
+            (4, "1970-01-01T01:56:40+00:00"),
+            (3, "1970-01-01T01:40:00.2+00:00"),
+        ]),
+    );
+    server.respond_ok(
+        "GET",
+        "/fills",
+        fills(&[
+            (3, "1970-01-01T01:40:00.2+00:00"),
+            (2, "1970-01-01T01:40:00.1+00:00"),
+            (1, "1970-01-01T01:23:20+00:00"),
+        ]),
+    );
+    server.respond_ok("GET", "/fills", fills(&[(1, "1970-01-01T01:23:20+00:00")]));
+    let api = server.rest();
+
+    let mut pages = api.paginate_fills(
+        Some("BTC-PERP"),
+        Some(Utc.timestamp_opt(3000, 0).unwrap()),
+        Some(Utc.timestamp_opt(9000, 0).unwrap()),
+    );
+    assert_eq!(pages.next().await.unwrap().unwrap().id, 5);
+    assert_eq!(server.requests().len(), 1);
+    let fills = pages.try_collect().await.unwrap();
+    let ids: Vec<u64> = fills.iter().map(|fill| fill.id).collect();
+    assert_eq!(ids, vec![4, 3, 2, 1]);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0].path.contains("market=BTC-PERP"));
+    assert!(requests[0].path.contains("end_time=9000"));
+    assert!(requests[1].path.contains("end_time=6000"));
+    assert!(requests[2].path.contains("end_time=5000"));
+    assert!(requests[2].path.contains("start_time=3000"));
+
+    // Also a stream, which requires `futures-util` of the `ws` feature
+    #[cfg(feature = "ws")]
+    {
+        use futures_util::StreamExt;
+
+        let fills: Vec<_> = api
+            .paginate_fills(None, None, None)
+            .map(|fill| fill.unwrap().id)
+            .collect()
+            .await;
+        assert_eq!(fills, vec![1]);
+    }
+}
+
+#[tokio::test]
+async fn order_history() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let order = json!({"id": 1, "clientId": null, "market": "BTC-PERP", "type": "limit", "side": "buy", "size": 0.1, "price": 40000.0, "reduceOnly": false, "ioc": false, "postOnly": false, "status": "closed", "filledSize": 0.1, "remainingSize": 0.0, "avgFillPrice": 40000.0, "createdAt": "1970-01-01T01:40:00+00:00"});
+    let trigger = json!({"createdAt": "1970-01-01T01:40:00+00:00", "error": null, "future": "XRP-PERP", "id": 50001, "market": "XRP-PERP", "orderId": 2, "orderPrice": null, "reduceOnly": true, "side": "sell", "size": 31431, "status": "triggered", "trailStart": null, "trailValue": null, "triggerPrice": 0.3, "triggeredAt": "1970-01-01T01:45:00+00:00", "type": "stop", "orderType": "market", "filledSize": 31431, "avgFillPrice": 0.3, "retryUntilFilled": true});
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok("GET", "/orders/history", json!([order]));
+    server.respond_ok("GET", "/conditional_orders/history", json!([trigger]));
+    let api = server.rest();
+
+    let orders = api
+        .get_order_history(
+            None,
+            Some(10),
+            Some(Utc.timestamp_opt(3000, 0).unwrap()),
+            Some(Utc.timestamp_opt(9000, 0).unwrap()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(orders[0].status, OrderStatus::Closed);
+    let path = &server.requests()[0].path;
+    assert!(path.contains("start_time=3000") && path.contains("end_time=9000"));
+    assert!(!path.contains("market"));
+
+    let triggers = api
+        .paginate_conditional_order_history(Some("XRP-PERP"), None, None)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(triggers.len(), 1);
+    assert_eq!(triggers[0].status, ConditionalOrderStatus::Triggered);
+}
+
+#[tokio::test]
+async fn orders_by_client_id() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let order = |id: u64, client_id: &str, size: f64| json!({"id": id, "clientId": client_id, "market": "BTC-PERP", "type": "limit", "side": "buy", "size": size, "price": 40000.0, "reduceOnly": false, "ioc": false, "postOnly": false, "status": "open", "filledSize": 0.0, "remainingSize": size, "avgFillPrice": null, "createdAt": "2021-05-23T05:24:24.315884+00:00"});
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok(
+        "GET",
+        "/orders/by_client_id/my-order",
+        order(1, "my-order", 0.1),
+    );
+    server.respond_ok(
+        "POST",
+        "/orders/by_client_id/my-order/modify",
+        order(2, "my-order-2", 0.2),
+    );
+    server.respond_ok(
+        "DELETE",
+        "/orders/by_client_id/my-order-2",
+        json!("Order queued for cancelation"),
+    );
+    let api = server.rest();
+
+    // e.g. after restarting, find the order placed before
+    let order = api.get_order_by_client_id("my-order").await.unwrap();
+    assert_eq!(order.id, 1);
+    let modified = api
+        .modify_order_by_client_id("my-order", None, Some(dec!(0.2)), Some("my-order-2"))
+        .await
+        .unwrap();
+    assert_eq!(modified.id, 2);
+    assert_eq!(server.requests()[1].json()["clientId"], "my-order-2");
+    api.cancel_order_by_client_id("my-order-2").await.unwrap();
+}
+
+#[tokio::test]
+async fn cancel_all_orders() {
+    use crate::testing::MockRest;
+    use serde_json::json;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok("DELETE", "/orders", json!("Orders queued for cancelation"));
+    let api = server.rest();
+
+    api.cancel_all_orders(&CancelAllFilter::new())
+        .await
+        .unwrap();
+    api.cancel_all_orders(
+        &CancelAllFilter::new()
+            .market("BTC-PERP")
+            .side(OrderSide::Buy)
+            .limit_orders_only(true),
+    )
+    .await
+    .unwrap();
+
+    let requests = server.requests();
+    assert_eq!(
+        requests[0].json(),
+        json!({"conditionalOrdersOnly": false, "limitOrdersOnly": false})
+    );
+    assert_eq!(
+        requests[1].json(),
+        json!({"market": "BTC-PERP", "side": "buy", "conditionalOrdersOnly": false, "limitOrdersOnly": true})
+    );
+}
+
+#[tokio::test]
+async fn api_errors() {
+    use crate::testing::MockRest;
+
+    let server = MockRest::bind().await.unwrap();
+    let api = server.rest();
+
+    let errors = [
+        ("Size too small", ApiError::SizeTooSmall),
+        ("Invalid price", ApiError::InvalidPrice),
+        (
+            "Account does not have enough margin for order.",
+            ApiError::NotEnoughMargin,
+        ),
+        ("Order already closed", ApiError::OrderAlreadyClosed),
+        (
+            "Order already queued for cancellation",
+            ApiError::Other("Order already queued for cancellation".to_string()),
+        ),
+    ];
+    for (message, _) in &errors {
+        server.respond_error("DELETE", "/orders/1", 400, message);
+    }
+    for (message, expected) in errors {
+        match api.cancel_order(1).await {
+            Err(Error::Api(error)) => {
+                assert_eq!(error, expected);
+                assert_eq!(error, message);
+            }
+            result => panic!("API error expected, got {:?}", result),
+        }
+    }
+
+    let api = Rest::new_with_endpoint(
+        &server.endpoint(),
+        "FTX",
+        MockRest::KEY.to_string(),
+        "wrong".to_string(),
+        None,
+    )
+    .unwrap();
+    assert!(matches!(
+        api.get_markets().await,
+        Err(Error::Api(ApiError::NotLoggedIn))
+    ));
+}
+
+#[test]
+fn invalid_endpoint() {
+    let rest = |endpoint: &str| {
+        Rest::new_with_endpoint(endpoint, "FTX", "key".into(), "secret".into(), None)
+    };
+    for endpoint in ["", "ftx.com/api", "ws://ftx.com/ws", "mailto:api@ftx.com"] {
+        assert!(matches!(
+            rest(endpoint),
+            Err(Error::InvalidEndpoint(invalid)) if invalid == endpoint
+        ));
+    }
+    assert_eq!(
+        rest("http://127.0.0.1:8080/api/").unwrap().endpoint(),
+        "http://127.0.0.1:8080/api"
+    );
+}
+
+#[tokio::test]
+async fn unsupported_on_exchange() {
+    use crate::options::Exchange;
+    use crate::testing::MockRest;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok("GET", "/markets", json!([]));
+    // Authentication headers of ftx.us
+    let api = Rest::new_with_endpoint(
+        &server.endpoint(),
+        "FTXUS",
+        MockRest::KEY.to_string(),
+        MockRest::SECRET.to_string(),
+        None,
+    )
+    .unwrap();
+    assert_eq!(api.exchange(), Exchange::Us);
+
+    assert!(matches!(
+        api.get_futures().await,
+        Err(Error::UnsupportedOnExchange(Exchange::Us))
+    ));
+    assert!(matches!(
+        api.get_funding_payments(None, None, None).await,
+        Err(Error::UnsupportedOnExchange(Exchange::Us))
+    ));
+    assert!(matches!(
+        api.get_option_positions().await,
+        Err(Error::UnsupportedOnExchange(Exchange::Us))
+    ));
+    // Rejected without sending a request
+    assert!(server.requests().is_empty());
+
+    assert!(api.get_markets().await.unwrap().is_empty());
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn with_subaccount() {
+    use crate::testing::MockRest;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok("GET", "/wallet/balances", json!([]));
+    let api = server.rest();
+    api.set_clock_offset(chrono::Duration::seconds(1));
+
+    let bot = api.with_subaccount(Some("bot"));
+    assert_eq!(bot.subaccount(), Some("bot"));
+    assert_eq!(bot.clock_offset(), chrono::Duration::seconds(1));
+    let main = bot.with_subaccount(None);
+
+    bot.get_wallet_balances().await.unwrap();
+    main.get_wallet_balances().await.unwrap();
+    api.get_wallet_balances().await.unwrap();
+
+    let requests = server.requests();
+    let subaccounts: Vec<_> = requests
+        .iter()
+        .map(|request| request.header("FTX-SUBACCOUNT"))
+        .collect();
+    assert_eq!(subaccounts, vec![Some("bot"), None, None]);
+    assert!(requests.iter().all(|request| request.signature_valid));
+
+    // Rate limits are shared, so the handles together can't exceed them
+    let mut api = server.rest();
+    api.set_rate_limit(1);
+    api.set_rate_limit_policy(RateLimitPolicy::Error);
+    let bot = api.with_subaccount(Some("bot"));
+    api.get_wallet_balances().await.unwrap();
+    assert!(matches!(
+        bot.get_wallet_balances().await,
+        Err(Error::RateLimited)
+    ));
+}
+
+#[tokio::test]
+async fn rate_limit_budget() {
+    use crate::testing::MockRest;
+
+    let server = MockRest::bind().await.unwrap();
+    server.respond_ok("GET", "/wallet/balances", json!([]));
+
+    // Separately constructed clients drawing from one budget
+    let budget = RateLimitBudget::new(2, 1);
+    let mut clients: Vec<_> = (0..3).map(|_| server.rest()).collect();
+    for client in &mut clients {
+        client.set_rate_limit_budget(budget.clone());
+        client.set_rate_limit_policy(RateLimitPolicy::Error);
+    }
+
+    clients[0].get_wallet_balances().await.unwrap();
+    clients[1].get_wallet_balances().await.unwrap();
+    assert!(matches!(
+        clients[2].get_wallet_balances().await,
+        Err(Error::RateLimited)
+    ));
+
+    // A client with its own limits is unaffected
+    let other = server.rest();
+    other.get_wallet_balances().await.unwrap();
+    assert_eq!(server.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn increment_policy() {
+    use crate::testing::MockRest;
+
+    let server = MockRest::with_fixtures().await.unwrap();
+    let mut rest = server.rest();
+    let order = OrderRequest::limit("BTC-PERP", OrderSide::Buy, dec!(40000.5), dec!(0.00125));
+
+    // Orders are sent unchanged by default
+    rest.submit_order(&order).await.unwrap();
+
+    rest.set_increment_policy(IncrementPolicy::Validate);
+    assert!(matches!(
+        rest.submit_order(&order).await,
+        Err(Error::InvalidIncrement(IncrementError::PriceNotDivisible { increment, .. }))
+            if increment == dec!(1)
+    ));
+    let order = OrderRequest::limit("BTC-PERP", OrderSide::Buy, dec!(40000), dec!(0.00125));
+    assert!(matches!(
+        rest.submit_order(&order).await,
+        Err(Error::InvalidIncrement(
+            IncrementError::SizeNotDivisible { .. }
+        ))
+    ));
+    let post_only =
+        OrderRequest::limit("BTC-PERP", OrderSide::Buy, dec!(40000), dec!(0.0005)).post_only(true);
+    assert!(matches!(
+        rest.submit_order(&post_only).await,
+        Err(Error::InvalidIncrement(IncrementError::SizeTooSmall { min_size, .. }))
+            if min_size == dec!(0.001)
+    ));
+
+    // Rounding never makes orders more aggressive
+    rest.set_increment_policy(IncrementPolicy::Round);
+    let sell = OrderRequest::limit("BTC-PERP", OrderSide::Sell, dec!(40000.5), dec!(0.00125));
+    rest.submit_order(&sell).await.unwrap();
+    let too_small = OrderRequest::market("BTC-PERP", OrderSide::Sell, dec!(0.00005));
+    assert!(matches!(
+        rest.submit_order(&too_small).await,
+        Err(Error::InvalidIncrement(IncrementError::SizeTooSmall { .. }))
+    ));
+
+    // Unlisted markets don't reload the markets on every order
+    let unlisted = OrderRequest::market("NEW-PERP", OrderSide::Sell, dec!(0.00005));
+    rest.submit_order(&unlisted).await.unwrap();
+    rest.submit_order(&unlisted).await.unwrap();
+
+    // The increments were loaded once, only valid orders were sent
+    let requests = server.requests();
+    let paths: Vec<_> = requests
+        .iter()
+        .map(|request| (request.method.as_str(), request.path.as_str()))
+        .collect();
+    assert_eq!(
+        paths,
+        vec![
+            ("POST", "/api/orders"),
+            ("GET", "/api/markets"),
+            ("POST", "/api/orders"),
+            ("POST", "/api/orders"),
+            ("POST", "/api/orders"),
+        ]
+    );
+    let sent = requests[2].json();
+    assert_eq!(sent["price"], json!("40001"));
+    assert_eq!(sent["size"], json!("0.0012"));
+    assert_eq!(
+        rest.market_increments("BTC/USD").await.unwrap(),
+        Some(MarketIncrements {
+            price_increment: dec!(1),
+            size_increment: dec!(0.0001),
+            min_provide_size: dec!(0.0001),
+        })
+    );
+}
+
+#[test]
+fn market_helpers() {
+    use crate::testing::fixtures;
+
+    let markets: Value = serde_json::from_str(fixtures::MARKETS).unwrap();
+    let markets: Markets = serde_json::from_value(markets["result"].clone()).unwrap();
+    let market = &markets[0];
+    assert_eq!(market.round_price(dec!(40000.5)), dec!(40001));
+    assert_eq!(market.round_price(dec!(40000.49)), dec!(40000));
+    assert_eq!(market.round_size(dec!(0.12349)), dec!(0.1234));
+    assert_eq!(market.round_size(dec!(-0.12349)), dec!(-0.1234));
+    assert_eq!(market.min_order_size(), dec!(0.0001));
+    assert_eq!(market.notional(dec!(40000), dec!(-0.5)), dec!(20000));
+    assert_eq!(
+        round_to_increment(dec!(1.37), dec!(0.25), RoundingStrategy::ToPositiveInfinity),
+        dec!(1.5)
+    );
+}
@@ -0,0 +1,98 @@
+//! Signing of REST requests and websocket logins with the API secret.
+//!
+//! `Rest` and `Ws` only keep a `Signer`, not the secret itself. Implement
+//! `Signer` to keep the secret out of process memory entirely, e.g. in an
+//! HSM or a remote signing service.
+//!
+//! ```no_run
+//! use ftx::rest::Rest;
+//! use ftx::signer::Signer;
+//! use std::sync::Arc;
+//!
+//! struct RemoteSigner;
+//!
+//! impl Signer for RemoteSigner {
+//!     fn sign(&self, payload: &[u8]) -> Vec<u8> {
+//!         unimplemented!("Ask the signing service for the signature of {:?}", payload)
+//!     }
+//! }
+//!
+//! let api = Rest::new_with_signer(
+//!     Rest::ENDPOINT,
+//!     "FTX",
+//!     "key".to_string(),
+//!     Arc::new(RemoteSigner),
+//!     None,
+//! )
+//! .unwrap();
+//! ```
+
+#[cfg(test)]
+mod tests;
+
+use hmac_sha256::Hash;
+use zeroize::Zeroize;
+
+/// Signs payloads with the API secret, used for both REST request headers
+/// and the websocket login op
+pub trait Signer: Send + Sync {
+    /// Returns the HMAC-SHA256 of `payload` keyed with the API secret. The
+    /// signature is hex encoded by the caller.
+    fn sign(&self, payload: &[u8]) -> Vec<u8>;
+}
+
+/// HMAC-SHA256 keyed with the API secret. The padded key blocks are hashed
+/// once up front, so signing a request only hashes the payload. Only the
+/// hash states are kept, the secret and key blocks are zeroized.
+#[derive(Copy, Clone)]
+pub struct HmacSigner {
+    inner: Hash,
+    outer: Hash,
+}
+
+impl HmacSigner {
+    pub fn new(secret: &str) -> Self {
+        let mut hashed_key = [0u8; 32];
+        let key = if secret.len() > 64 {
+            hashed_key.copy_from_slice(&Hash::hash(secret.as_bytes()));
+            &hashed_key[..]
+        } else {
+            secret.as_bytes()
+        };
+
+        let pad = |byte: u8| {
+            let mut padded = [byte; 64];
+            for (p, k) in padded.iter_mut().zip(key.iter()) {
+                *p ^= k;
+            }
+            let mut hash = Hash::new();
+            hash.update(&padded[..]);
+            padded.zeroize();
+            hash
+        };
+
+        let signer = Self {
+            inner: pad(0x36),
+            outer: pad(0x5c),
+        };
+        hashed_key.zeroize();
+        signer
+    }
+
+    /// Derives the signer from `secret` and zeroizes it
+    pub fn from_secret(mut secret: String) -> Self {
+        let signer = Self::new(&secret);
+        secret.zeroize();
+        signer
+    }
+}
+
+impl Signer for HmacSigner {
+    fn sign(&self, payload: &[u8]) -> Vec<u8> {
+        let mut inner = self.inner;
+        inner.update(payload);
+        let mut outer = self.outer;
+        outer.update(inner.finalize());
+        outer.finalize().to_vec()
+    }
+}
@@ -0,0 +1,54 @@
+use super::*;
+use hmac_sha256::HMAC;
+
+#[test]
+fn hmac_signer() {
+    let payload = b"1588591511721GET/api/markets";
+    for secret in ["T4lPid48QtjNxjLUFOcUZghD7CUJ7sTVsfuvQZF2", &"x".repeat(100)].iter() {
+        let expected = HMAC::mac(payload, secret.as_bytes());
+        assert_eq!(HmacSigner::new(secret).sign(payload), expected);
+        assert_eq!(
+            HmacSigner::from_secret(secret.to_string()).sign(payload),
+            expected
+        );
+    }
+}
+
+#[tokio::test]
+async fn custom_signer() {
+    use crate::rest::Rest;
+    use crate::testing::MockRest;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Stands in for a remote signing service
+    struct CountingSigner {
+        signer: HmacSigner,
+        count: AtomicUsize,
+    }
+
+    impl Signer for CountingSigner {
+        fn sign(&self, payload: &[u8]) -> Vec<u8> {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            self.signer.sign(payload)
+        }
+    }
+
+    let server = MockRest::with_fixtures().await.unwrap();
+    let signer = Arc::new(CountingSigner {
+        signer: HmacSigner::new(MockRest::SECRET),
+        count: AtomicUsize::new(0),
+    });
+    let api = Rest::new_with_signer(
+        &server.endpoint(),
+        "FTX",
+        MockRest::KEY.to_string(),
+        signer.clone(),
+        None,
+    )
+    .unwrap();
+
+    api.get_markets().await.unwrap();
+    assert!(server.requests()[0].signature_valid);
+    assert_eq!(signer.count.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,44 @@
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    Serde(serde_json::Error),
+    /// The Postgres connection failed, or the server rejected a statement,
+    /// see `tokio_postgres::Error::code`
+    #[cfg(feature = "postgres")]
+    Postgres(tokio_postgres::Error),
+    /// TLS connector of the Postgres connection couldn't be created
+    #[cfg(feature = "postgres")]
+    Tls(native_tls::Error),
+    /// The Redis connection failed, or the server replied with an error,
+    /// e.g. on a wrong password
+    #[cfg(feature = "redis")]
+    Redis(redis::RedisError),
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Serde(err)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<tokio_postgres::Error> for Error {
+    fn from(err: tokio_postgres::Error) -> Error {
+        Error::Postgres(err)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<native_tls::Error> for Error {
+    fn from(err: native_tls::Error) -> Error {
+        Error::Tls(err)
+    }
+}
+
+#[cfg(feature = "redis")]
+impl From<redis::RedisError> for Error {
+    fn from(err: redis::RedisError) -> Error {
+        Error::Redis(err)
+    }
+}
@@ -0,0 +1,13 @@
+//! Sinks writing websocket and REST data to other systems, so that data
+//! capture deployments don't have to build their own pipeline, and several
+//! processes can consume the data of a single FTX connection.
+
+mod error;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(feature = "redis")]
+pub mod redis;
+#[cfg(test)]
+mod tests;
+
+pub use error::*;
@@ -0,0 +1,539 @@
+//! Bulk-inserts trades, candles, fills, and orderbook data into Postgres or
+//! TimescaleDB. Records are batched per table and inserted with `COPY`,
+//! which is considerably faster than individual `INSERT`s.
+//!
+//! ```no_run
+//! # async fn example() -> ftx::sinks::Result<()> {
+//! use ftx::sinks::postgres::PostgresSink;
+//! use ftx::ws::{Channel, Ws};
+//! use std::time::Duration;
+//!
+//! let config = "host=127.0.0.1 user=ftx password=secret dbname=market_data sslmode=require";
+//! let mut sink = PostgresSink::connect(config)
+//!     .await?
+//!     .batch_size(5000)
+//!     .flush_interval(Duration::from_secs(5));
+//! sink.create_tables().await?;
+//!
+//! let mut ws = Ws::connect_public().await.unwrap();
+//! ws.subscribe(vec![
+//!     Channel::Trades("BTC-PERP".into()),
+//!     Channel::Orderbook("BTC-PERP".into()),
+//! ])
+//! .await
+//! .unwrap();
+//! while let Some((market, data)) = ws.next().await.unwrap() {
+//!     sink.insert_data(market.as_deref(), &data).await?;
+//! }
+//! sink.flush().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use super::{Error, Result};
+use crate::rest::{self, Candle, Fill, Liquidity, Side};
+use crate::ws::{self, Data, OrderbookAction, OrderbookData};
+use bytes::Bytes;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::SinkExt;
+use postgres_native_tls::MakeTlsConnector;
+use std::time::{Duration, Instant};
+use tokio_postgres::{Client, Config};
+
+/// Maximum size of a `CopyData` message sent to the server
+const COPY_CHUNK: usize = 1 << 16;
+
+/// Delay before the first reconnect, doubled on every further attempt
+const RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Table records are inserted into, see `PostgresRecord`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Table {
+    Trades,
+    Candles,
+    Fills,
+    /// One row per level of orderbook snapshots and updates
+    Orderbook,
+}
+
+impl Table {
+    pub const ALL: [Table; 4] = [
+        Table::Trades,
+        Table::Candles,
+        Table::Fills,
+        Table::Orderbook,
+    ];
+
+    /// Name of the table, without the prefix set with
+    /// `PostgresSink::table_prefix`
+    pub fn name(self) -> &'static str {
+        match self {
+            Table::Trades => "trades",
+            Table::Candles => "candles",
+            Table::Fills => "fills",
+            Table::Orderbook => "orderbook",
+        }
+    }
+
+    /// Columns of the table, in the order of `PostgresRecord::rows`
+    fn columns(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Table::Trades => &[
+                ("time", "TIMESTAMPTZ NOT NULL"),
+                ("market", "TEXT"),
+                ("id", "BIGINT NOT NULL"),
+                ("side", "TEXT NOT NULL"),
+                ("price", "NUMERIC NOT NULL"),
+                ("size", "NUMERIC NOT NULL"),
+                ("liquidation", "BOOLEAN NOT NULL"),
+            ],
+            Table::Candles => &[
+                ("start_time", "TIMESTAMPTZ NOT NULL"),
+                ("market", "TEXT"),
+                ("open", "NUMERIC NOT NULL"),
+                ("high", "NUMERIC NOT NULL"),
+                ("low", "NUMERIC NOT NULL"),
+                ("close", "NUMERIC NOT NULL"),
+                ("volume", "NUMERIC NOT NULL"),
+            ],
+            Table::Fills => &[
+                ("time", "TIMESTAMPTZ NOT NULL"),
+                ("market", "TEXT NOT NULL"),
+                ("id", "BIGINT NOT NULL"),
+                ("order_id", "BIGINT NOT NULL"),
+                ("trade_id", "BIGINT NOT NULL"),
+                ("side", "TEXT NOT NULL"),
+                ("price", "NUMERIC NOT NULL"),
+                ("size", "NUMERIC NOT NULL"),
+                ("fee", "NUMERIC NOT NULL"),
+                ("fee_currency", "TEXT NOT NULL"),
+                ("fee_rate", "NUMERIC NOT NULL"),
+                ("liquidity", "TEXT NOT NULL"),
+            ],
+            Table::Orderbook => &[
+                ("time", "TIMESTAMPTZ NOT NULL"),
+                ("market", "TEXT"),
+                ("action", "TEXT NOT NULL"),
+                ("side", "TEXT NOT NULL"),
+                ("price", "NUMERIC NOT NULL"),
+                ("size", "NUMERIC NOT NULL"),
+                ("checksum", "BIGINT NOT NULL"),
+            ],
+        }
+    }
+}
+
+/// Type inserted into Postgres by a `PostgresSink`
+pub trait PostgresRecord {
+    const TABLE: Table;
+
+    /// Values of the rows the record is inserted as, in the order of the
+    /// columns of its table, with `None` for `NULL`. `market` is the market
+    /// the record was received for, used by records that don't include it.
+    fn rows(&self, market: Option<&str>) -> Vec<Vec<Option<String>>>;
+}
+
+/// Inserts records into Postgres in batches, see the module documentation.
+///
+/// Records are buffered until `batch_size` rows are pending or a record is
+/// inserted at least `flush_interval` after the last flush, so `flush`
+/// should be called before the sink is dropped. Each table is then loaded
+/// with a single `COPY` statement.
+///
+/// If the connection fails, it is re-established with an exponential
+/// backoff up to `retries` times. Rows stay buffered until they were
+/// inserted, but a connection failing after the server received a batch
+/// may insert it twice, so delivery is at least once. Trades and fills can
+/// be deduplicated by their `id`. Rows rejected by the server, e.g. because
+/// a table doesn't exist, are discarded as they would be rejected again.
+///
+/// The connection is encrypted with TLS if the server supports it, unless
+/// `sslmode=disable` is configured. Configure `sslmode=require` to never
+/// send a password unencrypted.
+pub struct PostgresSink {
+    config: Config,
+    tls: MakeTlsConnector,
+    client: Option<Client>,
+    table_prefix: String,
+    batch_size: usize,
+    flush_interval: Duration,
+    retries: u32,
+    batches: [Batch; 4],
+    last_flush: Instant,
+}
+
+#[derive(Default)]
+struct Batch {
+    data: Vec<u8>,
+    rows: usize,
+}
+
+impl PostgresSink {
+    /// Default prefix of the table names
+    pub const TABLE_PREFIX: &'static str = "ftx_";
+    /// Default number of pending rows that triggers a flush
+    pub const BATCH_SIZE: usize = 1000;
+    /// Default interval between flushes
+    pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+    /// Default number of reconnects before an operation fails
+    pub const RETRIES: u32 = 3;
+
+    /// Connects to the server configured by `config`, either a connection
+    /// string like `host=127.0.0.1 user=ftx dbname=market_data` or a URL like
+    /// `postgresql://ftx@127.0.0.1/market_data`, see `tokio_postgres::Config`
+    pub async fn connect(config: &str) -> Result<Self> {
+        let mut config: Config = config.parse()?;
+        config.application_name("ftx");
+        let tls = MakeTlsConnector::new(native_tls::TlsConnector::new()?);
+        let client = open(&config, &tls).await?;
+
+        Ok(Self {
+            config,
+            tls,
+            client: Some(client),
+            table_prefix: Self::TABLE_PREFIX.into(),
+            batch_size: Self::BATCH_SIZE,
+            flush_interval: Self::FLUSH_INTERVAL,
+            retries: Self::RETRIES,
+            batches: Default::default(),
+            last_flush: Instant::now(),
+        })
+    }
+
+    /// Prefix of the table names, which may include a schema, e.g.
+    /// `market_data.`
+    pub fn table_prefix(mut self, prefix: &str) -> Self {
+        self.table_prefix = prefix.into();
+        self
+    }
+
+    /// Flushes when at least `rows` rows are pending
+    pub fn batch_size(mut self, rows: usize) -> Self {
+        self.batch_size = rows;
+        self
+    }
+
+    /// Flushes when a record is inserted at least `interval` after the last
+    /// flush. Zero flushes after every record.
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Number of reconnects before an operation fails
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Name of `table` including the prefix
+    pub fn table(&self, table: Table) -> String {
+        format!("{}{}", self.table_prefix, table.name())
+    }
+
+    /// Number of rows that weren't inserted yet
+    pub fn pending(&self) -> usize {
+        self.batches.iter().map(|batch| batch.rows).sum()
+    }
+
+    /// Creates the tables that don't exist yet. For TimescaleDB, they can
+    /// then be converted to hypertables, e.g. with
+    /// `SELECT create_hypertable('ftx_trades', 'time')`.
+    pub async fn create_tables(&mut self) -> Result<()> {
+        for table in Table::ALL {
+            let columns: Vec<_> = table
+                .columns()
+                .iter()
+                .map(|(name, definition)| format!("{} {}", name, definition))
+                .collect();
+            let statement = format!(
+                "CREATE TABLE IF NOT EXISTS {} ({})",
+                self.table(table),
+                columns.join(", ")
+            );
+            self.execute(&statement, None).await?;
+        }
+        Ok(())
+    }
+
+    /// Buffers the rows of `record`, see `PostgresRecord::rows` for
+    /// `market`, and flushes if the batch is full or the flush interval
+    /// elapsed
+    pub async fn insert<R: PostgresRecord>(
+        &mut self,
+        market: Option<&str>,
+        record: &R,
+    ) -> Result<()> {
+        let batch = &mut self.batches[R::TABLE as usize];
+        for row in record.rows(market) {
+            write_row(&mut batch.data, &row);
+            batch.rows += 1;
+        }
+
+        if self.pending() >= self.batch_size || self.last_flush.elapsed() >= self.flush_interval {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Inserts every record of `records`
+    pub async fn insert_all<'a, R, I>(&mut self, market: Option<&str>, records: I) -> Result<()>
+    where
+        R: PostgresRecord + 'a,
+        I: IntoIterator<Item = &'a R>,
+    {
+        for record in records {
+            self.insert(market, record).await?;
+        }
+        Ok(())
+    }
+
+    /// Inserts `data` of `market`, as returned by `Ws::next`. Trades, fills,
+    /// and orderbook data are inserted, other data is ignored.
+    pub async fn insert_data(&mut self, market: Option<&str>, data: &Data) -> Result<()> {
+        match data {
+            Data::Trade(trade) => self.insert(market, trade).await,
+            Data::Fill(fill) => self.insert(market, fill).await,
+            Data::OrderbookData(orderbook) => self.insert(market, orderbook).await,
+            _ => Ok(()),
+        }
+    }
+
+    /// Inserts all pending rows
+    pub async fn flush(&mut self) -> Result<()> {
+        self.last_flush = Instant::now();
+
+        for table in Table::ALL {
+            if self.batches[table as usize].rows == 0 {
+                continue;
+            }
+            let columns: Vec<_> = table.columns().iter().map(|(name, _)| *name).collect();
+            let statement = format!(
+                "COPY {} ({}) FROM STDIN",
+                self.table(table),
+                columns.join(", ")
+            );
+
+            let data = std::mem::take(&mut self.batches[table as usize].data);
+            match self.execute(&statement, Some(&data)).await {
+                Ok(()) => self.batches[table as usize].rows = 0,
+                Err(err) if is_rejected(&err) => {
+                    self.batches[table as usize].rows = 0;
+                    return Err(err);
+                }
+                Err(err) => {
+                    self.batches[table as usize].data = data;
+                    return Err(err);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes `statement`, or copies `copy_data` with it if given, and
+    /// reconnects if the connection fails
+    async fn execute(&mut self, statement: &str, copy_data: Option<&[u8]>) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            let result = match &self.client {
+                Some(client) => execute(client, statement, copy_data).await,
+                None => match open(&self.config, &self.tls).await {
+                    Ok(client) => {
+                        let client = self.client.insert(client);
+                        execute(client, statement, copy_data).await
+                    }
+                    Err(err) => Err(err),
+                },
+            };
+
+            match result {
+                Err(err) if !is_rejected(&err) && attempt < self.retries => {
+                    let delay = RETRY_DELAY * 2u32.pow(attempt);
+                    warn!(
+                        "Postgres connection failed, reconnecting in {:?}: {:?}",
+                        delay, err
+                    );
+                    self.client = None;
+                    attempt += 1;
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => {
+                    // The connection is only usable after errors reported
+                    // by the server
+                    if !is_rejected(&err) {
+                        self.client = None;
+                    }
+                    return Err(err);
+                }
+                Ok(()) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Opens a connection, which is driven by a spawned task until the client
+/// is dropped
+async fn open(config: &Config, tls: &MakeTlsConnector) -> Result<Client> {
+    let (client, connection) = config.connect(tls.clone()).await?;
+    tokio::spawn(async move {
+        if let Err(err) = connection.await {
+            warn!("Postgres connection closed: {}", err);
+        }
+    });
+    Ok(client)
+}
+
+async fn execute(client: &Client, statement: &str, copy_data: Option<&[u8]>) -> Result<()> {
+    let copy_data = match copy_data {
+        Some(copy_data) => copy_data,
+        None => return Ok(client.batch_execute(statement).await?),
+    };
+
+    let sink = client.copy_in(statement).await?;
+    futures_util::pin_mut!(sink);
+    for chunk in copy_data.chunks(COPY_CHUNK) {
+        sink.feed(Bytes::copy_from_slice(chunk)).await?;
+    }
+    sink.finish().await?;
+    Ok(())
+}
+
+/// Whether the server rejected the statement, which would be rejected
+/// again, rather than the connection failing
+fn is_rejected(err: &Error) -> bool {
+    matches!(err, Error::Postgres(err) if err.as_db_error().is_some())
+}
+
+/// Appends a row in the text format of `COPY`
+fn write_row(data: &mut Vec<u8>, row: &[Option<String>]) {
+    for (i, value) in row.iter().enumerate() {
+        if i > 0 {
+            data.push(b'\t');
+        }
+        let value = match value {
+            Some(value) => value,
+            None => {
+                data.extend_from_slice(b"\\N");
+                continue;
+            }
+        };
+        for byte in value.bytes() {
+            match byte {
+                b'\\' => data.extend_from_slice(b"\\\\"),
+                b'\t' => data.extend_from_slice(b"\\t"),
+                b'\n' => data.extend_from_slice(b"\\n"),
+                b'\r' => data.extend_from_slice(b"\\r"),
+                byte => data.push(byte),
+            }
+        }
+    }
+    data.push(b'\n');
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+fn format_side(side: Side) -> String {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    }
+    .into()
+}
+
+/// Trades of the REST and websocket APIs have the same fields
+macro_rules! impl_trade_record {
+    ($trade:ty) => {
+        impl PostgresRecord for $trade {
+            const TABLE: Table = Table::Trades;
+
+            fn rows(&self, market: Option<&str>) -> Vec<Vec<Option<String>>> {
+                vec![vec![
+                    Some(format_time(self.time)),
+                    market.map(String::from),
+                    Some(self.id.to_string()),
+                    Some(format_side(self.side)),
+                    Some(self.price.to_string()),
+                    Some(self.size.to_string()),
+                    Some(self.liquidation.to_string()),
+                ]]
+            }
+        }
+    };
+}
+
+impl_trade_record!(rest::Trade);
+impl_trade_record!(ws::Trade);
+
+impl PostgresRecord for Candle {
+    const TABLE: Table = Table::Candles;
+
+    fn rows(&self, market: Option<&str>) -> Vec<Vec<Option<String>>> {
+        vec![vec![
+            Some(format_time(self.start_time)),
+            market.map(String::from),
+            Some(self.open.to_string()),
+            Some(self.high.to_string()),
+            Some(self.low.to_string()),
+            Some(self.close.to_string()),
+            Some(self.volume.to_string()),
+        ]]
+    }
+}
+
+impl PostgresRecord for Fill {
+    const TABLE: Table = Table::Fills;
+
+    /// Fills include their market, so `market` is ignored
+    fn rows(&self, _market: Option<&str>) -> Vec<Vec<Option<String>>> {
+        vec![vec![
+            Some(format_time(self.time)),
+            Some(self.market.to_string()),
+            Some(self.id.to_string()),
+            Some(self.order_id.to_string()),
+            Some(self.trade_id.to_string()),
+            Some(format_side(self.side)),
+            Some(self.price.to_string()),
+            Some(self.size.to_string()),
+            Some(self.fee.to_string()),
+            Some(self.fee_currency.clone()),
+            Some(self.fee_rate.to_string()),
+            Some(
+                match self.liquidity {
+                    Liquidity::Maker => "maker",
+                    Liquidity::Taker => "taker",
+                }
+                .into(),
+            ),
+        ]]
+    }
+}
+
+/// Snapshots and updates are inserted as one row per level with the bids
+/// first, and an `action` of `partial` or `update`
+impl PostgresRecord for OrderbookData {
+    const TABLE: Table = Table::Orderbook;
+
+    fn rows(&self, market: Option<&str>) -> Vec<Vec<Option<String>>> {
+        let action = match self.action {
+            OrderbookAction::Partial => "partial",
+            OrderbookAction::Update => "update",
+        };
+        let bids = self.bids.iter().map(|level| (Side::Buy, level));
+        let asks = self.asks.iter().map(|level| (Side::Sell, level));
+        bids.chain(asks)
+            .map(|(side, (price, size))| {
+                vec![
+                    Some(format_time(self.time)),
+                    market.map(String::from),
+                    Some(action.into()),
+                    Some(format_side(side)),
+                    Some(price.to_string()),
+                    Some(size.to_string()),
+                    Some(self.checksum.to_string()),
+                ]
+            })
+            .collect()
+    }
+}
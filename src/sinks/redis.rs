@@ -0,0 +1,112 @@
+use super::Result;
+use crate::ws::Data;
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncCommands, Client};
+use serde_json::Value;
+
+/// Publishes websocket data to Redis, one pub/sub channel per FTX channel
+/// and market, e.g. `ftx:trades:BTC-PERP` or `ftx:fills`. Strategy processes
+/// subscribe to the channels they need, or to patterns like `ftx:trades:*`,
+/// instead of each opening their own connection to FTX.
+///
+/// Messages are JSON objects with the market, if any, and the data as
+/// serialized by `Data`, e.g.
+/// `{"market": "BTC-PERP", "type": "trade", "data": {"id": 1, ...}}`.
+///
+/// The connection is re-established once if publishing fails, in which
+/// case a message may be published twice.
+///
+/// ```no_run
+/// # async fn example() -> ftx::sinks::Result<()> {
+/// use ftx::sinks::redis::RedisSink;
+/// use ftx::ws::{Channel, Ws};
+///
+/// let mut sink = RedisSink::connect("redis://127.0.0.1:6379").await?;
+/// let mut ws = Ws::connect_public().await.unwrap();
+/// ws.subscribe(vec![
+///     Channel::Trades("BTC-PERP".into()),
+///     Channel::Ticker("BTC-PERP".into()),
+/// ])
+/// .await
+/// .unwrap();
+/// while let Some((market, data)) = ws.next().await.unwrap() {
+///     sink.publish(market.as_deref(), &data).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct RedisSink {
+    client: Client,
+    prefix: String,
+    connection: MultiplexedConnection,
+}
+
+impl RedisSink {
+    /// Default prefix of the channel names
+    pub const PREFIX: &'static str = "ftx";
+
+    /// Connects to the Redis server at `url`, e.g. `redis://127.0.0.1:6379`,
+    /// `redis://:<password>@127.0.0.1:6379/<database>`, or `rediss://` for
+    /// TLS, see `redis::IntoConnectionInfo`
+    pub async fn connect(url: &str) -> Result<Self> {
+        let client = Client::open(url)?;
+        let connection = client.get_multiplexed_async_connection().await?;
+
+        Ok(Self {
+            client,
+            prefix: Self::PREFIX.into(),
+            connection,
+        })
+    }
+
+    /// Prefix of the channel names, e.g. to separate environments
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Name of the Redis channel `data` of `market` is published to
+    pub fn channel(&self, market: Option<&str>, data: &Data) -> String {
+        let name = match data {
+            Data::Trade(_) => "trades",
+            Data::Liquidation(_) => "liquidations",
+            Data::OrderbookData(_) => "orderbook",
+            Data::GroupedOrderbook(_) => "orderbookGrouped",
+            Data::Fill(_) => "fills",
+            Data::Order(_) => "orders",
+            Data::Markets(_) => "markets",
+            Data::Ticker(_) => "ticker",
+            Data::Integrity(_) => "integrity",
+            Data::SpotMarginRates(_) => "spotMarginRates",
+        };
+        let market = match data {
+            Data::Integrity(issue) => Some(issue.market().as_str()),
+            _ => market,
+        };
+
+        match market {
+            Some(market) => format!("{}:{}:{}", self.prefix, name, market),
+            None => format!("{}:{}", self.prefix, name),
+        }
+    }
+
+    /// Publishes `data` of `market`, as returned by `Ws::next`, and returns
+    /// the number of clients that received it
+    pub async fn publish(&mut self, market: Option<&str>, data: &Data) -> Result<u64> {
+        let mut message = serde_json::to_value(data)?;
+        if let Value::Object(fields) = &mut message {
+            fields.insert("market".into(), market.into());
+        }
+        let channel = self.channel(market, data);
+        let message = message.to_string();
+
+        match self.connection.publish(&channel, &message).await {
+            Err(err) if err.is_connection_dropped() || err.is_io_error() => {
+                warn!("Redis connection failed, reconnecting: {}", err);
+                self.connection = self.client.get_multiplexed_async_connection().await?;
+                Ok(self.connection.publish(&channel, &message).await?)
+            }
+            receivers => Ok(receivers?),
+        }
+    }
+}
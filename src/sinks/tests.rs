@@ -0,0 +1,290 @@
+#[cfg(feature = "postgres")]
+use super::postgres::*;
+#[cfg(feature = "redis")]
+use super::redis::*;
+use super::*;
+#[cfg(feature = "redis")]
+use crate::ws::Data;
+#[cfg(feature = "postgres")]
+use crate::ws::OrderbookData;
+use crate::ws::Trade;
+use serde_json::json;
+#[cfg(feature = "redis")]
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "postgres")]
+use std::time::Duration;
+#[cfg(feature = "redis")]
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+#[cfg(feature = "postgres")]
+use tokio::net::TcpStream;
+
+#[cfg(feature = "postgres")]
+/// Reads a message of the client, the startup message has no tag
+async fn read_message(socket: &mut TcpStream, tagged: bool) -> Option<(u8, Vec<u8>)> {
+    let tag = if tagged {
+        socket.read_u8().await.ok()?
+    } else {
+        0
+    };
+    let len = socket.read_i32().await.ok()?;
+    let mut body = vec![0; len as usize - 4];
+    socket.read_exact(&mut body).await.ok()?;
+    Some((tag, body))
+}
+
+#[cfg(feature = "postgres")]
+async fn write_message(socket: &mut TcpStream, tag: u8, body: &[u8]) {
+    socket.write_u8(tag).await.unwrap();
+    socket.write_i32(body.len() as i32 + 4).await.unwrap();
+    socket.write_all(body).await.unwrap();
+}
+
+#[cfg(feature = "postgres")]
+/// Reads the null-terminated string at the start of `body`
+fn read_str(body: &[u8]) -> (String, &[u8]) {
+    let end = body.iter().position(|byte| *byte == 0).unwrap();
+    let value = String::from_utf8(body[..end].to_vec()).unwrap();
+    (value, &body[end + 1..])
+}
+
+#[cfg(feature = "postgres")]
+#[tokio::test]
+async fn postgres_sink() {
+    use tokio_postgres::error::SqlState;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let config = format!(
+        "host={} port={} user=ftx dbname=ftx sslmode=disable",
+        addr.ip(),
+        addr.port()
+    );
+    let statements = Arc::new(Mutex::new(Vec::new()));
+
+    // Drops the first authenticated connection on its first COPY to make the
+    // sink reconnect, and rejects copies into tables prefixed with `missing_`
+    let received = statements.clone();
+    tokio::spawn(async move {
+        let mut authenticated = 0;
+        for _ in 0..3 {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let (_, startup) = read_message(&mut socket, false).await.unwrap();
+            assert!(startup.windows(9).any(|param| param == b"user\0ftx\0"));
+
+            // MD5 of the password "secret" salted with 1, 2, 3, 4
+            write_message(&mut socket, b'R', &[0, 0, 0, 5, 1, 2, 3, 4]).await;
+            let (tag, password) = read_message(&mut socket, true).await.unwrap();
+            assert_eq!(tag, b'p');
+            if password != b"md5c0c2fc60aa56f83da4fc3315b96233ab\0" {
+                write_message(
+                    &mut socket,
+                    b'E',
+                    b"SFATAL\0C28P01\0Mpassword authentication failed\0\0",
+                )
+                .await;
+                continue;
+            }
+            write_message(&mut socket, b'R', &0i32.to_be_bytes()).await;
+            authenticated += 1;
+            write_message(&mut socket, b'Z', b"I").await;
+
+            let mut failed = false;
+            let mut data = None;
+            while let Some((tag, body)) = read_message(&mut socket, true).await {
+                match tag {
+                    b'Q' => {
+                        received.lock().unwrap().push(read_str(&body).0);
+                        write_message(&mut socket, b'C', b"CREATE TABLE\0").await;
+                        write_message(&mut socket, b'Z', b"I").await;
+                    }
+                    // The COPY statement is prepared and then executed
+                    b'P' => {
+                        let (_, query) = read_str(&body);
+                        let (statement, _) = read_str(query);
+                        if authenticated == 1 {
+                            break;
+                        } else if statement.contains("missing_") {
+                            failed = true;
+                            write_message(
+                                &mut socket,
+                                b'E',
+                                b"SERROR\0C42P01\0Mrelation does not exist\0\0",
+                            )
+                            .await;
+                        } else {
+                            received.lock().unwrap().push(statement);
+                            write_message(&mut socket, b'1', &[]).await;
+                        }
+                    }
+                    b'D' if !failed => {
+                        write_message(&mut socket, b't', &[0, 0]).await;
+                        write_message(&mut socket, b'n', &[]).await;
+                    }
+                    b'B' => write_message(&mut socket, b'2', &[]).await,
+                    b'E' => {
+                        data = Some(Vec::new());
+                        write_message(&mut socket, b'G', &[0, 0, 0]).await;
+                    }
+                    b'd' => data.as_mut().unwrap().extend(body),
+                    b'c' => {
+                        let data = String::from_utf8(data.take().unwrap()).unwrap();
+                        received.lock().unwrap().push(data);
+                        write_message(&mut socket, b'C', b"COPY 1\0").await;
+                    }
+                    b'C' => write_message(&mut socket, b'3', &[]).await,
+                    // Syncs are ignored while copying
+                    b'S' if data.is_none() => {
+                        failed = false;
+                        write_message(&mut socket, b'Z', b"I").await;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    });
+
+    // The server rejects the password
+    assert!(matches!(
+        PostgresSink::connect(&format!("{} password=wrong", config)).await,
+        Err(Error::Postgres(err)) if err.code() == Some(&SqlState::INVALID_PASSWORD)
+    ));
+
+    let mut sink = PostgresSink::connect(&format!("{} password=secret", config))
+        .await
+        .unwrap()
+        .batch_size(3)
+        .flush_interval(Duration::from_secs(3600));
+    sink.create_tables().await.unwrap();
+    assert_eq!(statements.lock().unwrap().len(), 4);
+    assert!(statements.lock().unwrap()[0].starts_with(
+        "CREATE TABLE IF NOT EXISTS ftx_trades (time TIMESTAMPTZ NOT NULL, market TEXT,"
+    ));
+    statements.lock().unwrap().clear();
+
+    let trade: Trade = serde_json::from_value(json!({"id": 1, "price": 40000.5, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"})).unwrap();
+    sink.insert(Some("BTC\tPERP"), &trade).await.unwrap();
+    assert_eq!(sink.pending(), 1);
+
+    // The batch is full after the two levels, and inserted once after the
+    // connection failed
+    let data: OrderbookData = serde_json::from_value(json!({"action": "partial", "bids": [[40000.0, 1.5]], "asks": [[40001.0, 2.0]], "checksum": 1234, "time": 1621747464.5})).unwrap();
+    sink.insert(None, &data).await.unwrap();
+    assert_eq!(sink.pending(), 0);
+    assert_eq!(
+        *statements.lock().unwrap(),
+        vec![
+            "COPY ftx_trades (time, market, id, side, price, size, liquidation) FROM STDIN"
+                .to_string(),
+            "2021-05-23T05:24:24.315884Z\tBTC\\tPERP\t1\tbuy\t40000.5\t0.1\tfalse\n".into(),
+            "COPY ftx_orderbook (time, market, action, side, price, size, checksum) FROM STDIN"
+                .into(),
+            "2021-05-23T05:24:24.500Z\t\\N\tpartial\tbuy\t40000\t1.5\t1234\n\
+             2021-05-23T05:24:24.500Z\t\\N\tpartial\tsell\t40001\t2\t1234\n"
+                .into(),
+        ]
+    );
+
+    // Rejected rows are discarded
+    let mut sink = sink.table_prefix("missing_");
+    sink.insert(Some("BTC-PERP"), &trade).await.unwrap();
+    match sink.flush().await {
+        Err(Error::Postgres(err)) => assert_eq!(err.code(), Some(&SqlState::UNDEFINED_TABLE)),
+        result => panic!("unexpected result {:?}", result),
+    }
+    assert_eq!(sink.pending(), 0);
+}
+
+#[cfg(feature = "redis")]
+/// Reads a command sent as an array of bulk strings
+async fn read_command<R: AsyncBufReadExt + Unpin>(reader: &mut R) -> Option<Vec<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await.ok()? == 0 {
+        return None;
+    }
+    let count: usize = line.trim_start_matches('*').trim_end().parse().unwrap();
+
+    let mut args = Vec::new();
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).await.unwrap();
+        let len: usize = line.trim_start_matches('$').trim_end().parse().unwrap();
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg).await.unwrap();
+        arg.truncate(len);
+        args.push(String::from_utf8(arg).unwrap());
+    }
+    Some(args)
+}
+
+#[cfg(feature = "redis")]
+#[tokio::test]
+async fn redis_sink() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let commands = Arc::new(Mutex::new(Vec::new()));
+
+    // Replies to the AUTH and one PUBLISH per connection, then disconnects
+    let received = commands.clone();
+    tokio::spawn(async move {
+        for receivers in [2, 0, 0] {
+            let (socket, _) = listener.accept().await.unwrap();
+            let mut socket = BufReader::new(socket);
+            while let Some(command) = read_command(&mut socket).await {
+                let reply = match command[0].as_str() {
+                    "AUTH" if command[1] == "secret" => "+OK\r\n".to_string(),
+                    "AUTH" => "-WRONGPASS invalid password\r\n".to_string(),
+                    // Name and version of the client library
+                    "CLIENT" => {
+                        socket.write_all(b"+OK\r\n").await.unwrap();
+                        continue;
+                    }
+                    _ => format!(":{}\r\n", receivers),
+                };
+                let publish = command[0] == "PUBLISH";
+                received.lock().unwrap().push(command);
+                socket.write_all(reply.as_bytes()).await.unwrap();
+                if publish {
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut sink = RedisSink::connect(&format!("redis://:secret@{}", addr))
+        .await
+        .unwrap()
+        .prefix("test");
+    let trade: Trade = serde_json::from_value(json!({"id": 1, "price": 40000.5, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"})).unwrap();
+    let data = Data::Trade(trade);
+    assert_eq!(sink.publish(Some("BTC-PERP"), &data).await.unwrap(), 2);
+
+    // The sink reconnects after the connection was closed
+    assert_eq!(sink.publish(Some("ETH-PERP"), &data).await.unwrap(), 0);
+
+    let commands = commands.lock().unwrap().clone();
+    let channels: Vec<_> = commands.iter().map(|c| c[..2].join(" ")).collect();
+    assert_eq!(
+        channels,
+        [
+            "AUTH secret",
+            "PUBLISH test:trades:BTC-PERP",
+            "AUTH secret",
+            "PUBLISH test:trades:ETH-PERP"
+        ]
+    );
+    let message: Value = serde_json::from_str(&commands[1][2]).unwrap();
+    assert_eq!(
+        message,
+        json!({"market": "BTC-PERP", "type": "trade", "data": {"id": 1, "price": "40000.5", "size": "0.1", "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884Z"}})
+    );
+    assert_eq!(sink.channel(None, &Data::Order(serde_json::from_value(json!({"id": 1, "clientId": null, "market": "BTC-PERP", "type": "limit", "side": "buy", "size": 0.1, "price": 40000.0, "reduceOnly": false, "ioc": false, "postOnly": false, "status": "new", "filledSize": 0.0, "remainingSize": 0.1, "avgFillPrice": null, "createdAt": "2021-05-23T05:24:24.315884+00:00"})).unwrap())), "test:orders");
+
+    // A wrong password is rejected
+    match RedisSink::connect(&format!("redis://:wrong@{}", addr)).await {
+        Err(Error::Redis(err)) => assert_eq!(err.kind(), ::redis::ErrorKind::AuthenticationFailed),
+        _ => panic!("Authentication error expected."),
+    }
+}
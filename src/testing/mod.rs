@@ -0,0 +1,10 @@
+//! Mock FTX servers for testing code built on this crate without network
+//! access or real credentials. Enabled with the `testing` feature.
+
+mod rest;
+#[cfg(feature = "ws")]
+mod ws;
+
+pub use rest::*;
+#[cfg(feature = "ws")]
+pub use ws::*;
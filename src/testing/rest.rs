@@ -0,0 +1,292 @@
+use crate::rest::Rest;
+use hmac_sha256::HMAC;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Recorded responses of FTX served by `MockRest::with_fixtures`
+pub mod fixtures {
+    /// `GET /markets`, a perpetual future and a spot market
+    pub const MARKETS: &str = include_str!("fixtures/markets.json");
+    /// `GET /account` with a single position
+    pub const ACCOUNT: &str = include_str!("fixtures/account.json");
+    /// `GET /orders`, a single open limit order
+    pub const OPEN_ORDERS: &str = include_str!("fixtures/orders.json");
+    /// `POST /orders`, the newly placed limit order
+    pub const ORDER: &str = include_str!("fixtures/order.json");
+}
+
+/// Request received by `MockRest`
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path including the `/api` prefix and the query string
+    pub path: String,
+    /// Headers with lowercase names
+    pub headers: HashMap<String, String>,
+    pub body: String,
+    /// Whether the request was signed with `MockRest::KEY` and
+    /// `MockRest::SECRET`
+    pub signature_valid: bool,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(&name.to_lowercase()).map(String::as_str)
+    }
+
+    /// Body parsed as JSON, `Value::Null` if empty or invalid
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Responses by method and path without query, the last one is repeated
+    routes: HashMap<(String, String), VecDeque<(u16, String)>>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// Local HTTP server imitating the FTX REST API. Verifies the signature of
+/// every request, answering HTTP 401 if it is invalid, and serves the
+/// responses registered for its method and path.
+///
+/// ```no_run
+/// # async fn example() -> ftx::rest::Result<()> {
+/// use ftx::testing::MockRest;
+///
+/// let server = MockRest::with_fixtures().await.unwrap();
+/// let api = server.rest();
+///
+/// let markets = api.get_markets().await?;
+/// assert_eq!(markets[0].name, "BTC-PERP");
+///
+/// let request = &server.requests()[0];
+/// assert_eq!(request.path, "/api/markets");
+/// assert!(request.signature_valid);
+/// # Ok(())
+/// # }
+/// ```
+pub struct MockRest {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl MockRest {
+    /// API key expected by the server
+    pub const KEY: &'static str = "key";
+    /// API secret expected by the server
+    pub const SECRET: &'static str = "secret";
+
+    /// Binds to a random local port without any responses registered.
+    /// Requests without a response are answered with HTTP 404.
+    pub async fn bind() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let task = tokio::spawn(Self::accept(listener, state.clone()));
+
+        Ok(Self { addr, state, task })
+    }
+
+    /// Binds to a random local port, serving the recorded `fixtures`
+    pub async fn with_fixtures() -> io::Result<Self> {
+        let server = Self::bind().await?;
+        server.respond("GET", "/markets", 200, fixtures::MARKETS);
+        server.respond("GET", "/account", 200, fixtures::ACCOUNT);
+        server.respond("GET", "/orders", 200, fixtures::OPEN_ORDERS);
+        server.respond("POST", "/orders", 200, fixtures::ORDER);
+        Ok(server)
+    }
+
+    /// Endpoint to pass to `Rest::new_with_endpoint`
+    pub fn endpoint(&self) -> String {
+        format!("http://{}/api", self.addr)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Client connected to this server with `KEY` and `SECRET`
+    pub fn rest(&self) -> Rest {
+        Rest::new_with_endpoint(
+            &self.endpoint(),
+            "FTX",
+            Self::KEY.to_string(),
+            Self::SECRET.to_string(),
+            None,
+        )
+        .unwrap()
+    }
+
+    /// Answers requests to `path`, relative to the endpoint and without a
+    /// query, with `status` and `body`. Responses registered for the same
+    /// method and path are served in order, the last one repeatedly.
+    pub fn respond(&self, method: &str, path: &str, status: u16, body: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .routes
+            .entry((method.to_uppercase(), path.to_string()))
+            .or_default()
+            .push_back((status, body.to_string()));
+    }
+
+    /// Answers requests to `path` successfully with `result`
+    pub fn respond_ok(&self, method: &str, path: &str, result: Value) {
+        let body = json!({"success": true, "result": result});
+        self.respond(method, path, 200, &body.to_string());
+    }
+
+    /// Answers requests to `path` with an API error
+    pub fn respond_error(&self, method: &str, path: &str, status: u16, error: &str) {
+        let body = json!({"success": false, "error": error});
+        self.respond(method, path, status, &body.to_string());
+    }
+
+    /// All requests received so far
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    async fn accept(listener: TcpListener, state: Arc<Mutex<State>>) {
+        while let Ok((socket, _)) = listener.accept().await {
+            tokio::spawn(Self::serve(socket, state.clone()));
+        }
+    }
+
+    /// Serves requests on a kept-alive connection until it is closed
+    async fn serve(socket: TcpStream, state: Arc<Mutex<State>>) -> io::Result<()> {
+        let mut reader = BufReader::new(socket);
+
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(());
+            }
+            let mut parts = line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let path = parts.next().unwrap_or_default().to_string();
+
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).await?;
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                }
+            }
+
+            let length = headers
+                .get("content-length")
+                .and_then(|length| length.parse().ok())
+                .unwrap_or(0);
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).await?;
+
+            let request = RecordedRequest {
+                signature_valid: false,
+                method,
+                path,
+                headers,
+                body: String::from_utf8_lossy(&body).to_string(),
+            };
+            let (status, body) = Self::handle_request(request, &state);
+
+            let response = format!(
+                "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                status,
+                reason(status),
+                body.len(),
+                body
+            );
+            reader.get_mut().write_all(response.as_bytes()).await?;
+        }
+    }
+
+    /// Records the request and returns the status and body of the response
+    fn handle_request(mut request: RecordedRequest, state: &Mutex<State>) -> (u16, String) {
+        request.signature_valid = verify_signature(&request);
+        let authorized = request.signature_valid;
+
+        let route = request
+            .path
+            .split('?')
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("/api")
+            .to_string();
+        let key = (request.method.clone(), route);
+
+        let mut state = state.lock().unwrap();
+        state.requests.push(request);
+
+        if !authorized {
+            return (
+                401,
+                json!({"success": false, "error": "Not logged in"}).to_string(),
+            );
+        }
+        match state.routes.get_mut(&key) {
+            Some(responses) if responses.len() > 1 => responses.pop_front().unwrap(),
+            Some(responses) => responses.front().cloned().unwrap(),
+            None => (
+                404,
+                json!({"success": false, "error": "Not Found"}).to_string(),
+            ),
+        }
+    }
+}
+
+impl Drop for MockRest {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Checks the key, timestamp, and signature headers, e.g. `FTX-KEY`,
+/// `FTX-TS`, and `FTX-SIGN`, or their `FTXUS-` counterparts
+fn verify_signature(request: &RecordedRequest) -> bool {
+    let header = |suffix: &str| {
+        request
+            .headers
+            .iter()
+            .find(|(name, _)| name.starts_with("ftx") && name.ends_with(suffix))
+            .map(|(_, value)| value.as_str())
+    };
+
+    let (key, ts, sign) = match (header("-key"), header("-ts"), header("-sign")) {
+        (Some(key), Some(ts), Some(sign)) => (key, ts, sign),
+        _ => return false,
+    };
+
+    let payload = format!("{}{}{}{}", ts, request.method, request.path, request.body);
+    let expected = hex::encode(HMAC::mac(payload.as_bytes(), MockRest::SECRET.as_bytes()));
+
+    key == MockRest::KEY && sign == expected
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        _ => "",
+    }
+}
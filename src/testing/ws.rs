@@ -0,0 +1,243 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+#[derive(Default)]
+struct State {
+    /// Frames waiting to be sent to each connected client
+    clients: Vec<UnboundedSender<Message>>,
+    /// Ops received from all clients
+    received: Vec<Value>,
+    /// Active `(channel, market)` subscriptions
+    subscriptions: Vec<(String, Option<String>)>,
+    /// Answer logins with FTX's error for invalid credentials
+    reject_logins: bool,
+}
+
+/// Local websocket server imitating FTX. Accepts any login unless
+/// `set_reject_logins` is set, confirms
+/// subscriptions and answers pings. Data frames are only sent when injected
+/// by the test.
+///
+/// ```no_run
+/// # async fn example() -> ftx::ws::Result<()> {
+/// use ftx::testing::MockWsServer;
+/// use ftx::ws::{Channel, Data, Ws};
+/// use serde_json::json;
+///
+/// let server = MockWsServer::bind().await?;
+/// let mut ws = Ws::builder()
+///     .endpoint(&server.endpoint())
+///     .credentials("key", "secret")
+///     .connect()
+///     .await?;
+/// ws.subscribe(vec![Channel::Trades("BTC-PERP".into())]).await?;
+///
+/// server.send_trades(
+///     "BTC-PERP",
+///     json!([{
+///         "id": 1,
+///         "price": 40000.0,
+///         "size": 0.1,
+///         "side": "buy",
+///         "liquidation": false,
+///         "time": "2021-05-23T05:24:24.315884+00:00"
+///     }]),
+/// );
+/// assert!(matches!(ws.next().await?, Some((_, Data::Trade(_)))));
+/// # Ok(())
+/// # }
+/// ```
+pub struct MockWsServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl MockWsServer {
+    /// Binds to a random local port and starts accepting connections
+    pub async fn bind() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let task = tokio::spawn(Self::accept(listener, state.clone()));
+
+        Ok(Self { addr, state, task })
+    }
+
+    /// Endpoint to pass to `WsBuilder::endpoint`
+    pub fn endpoint(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    async fn accept(listener: TcpListener, state: Arc<Mutex<State>>) {
+        while let Ok((socket, _)) = listener.accept().await {
+            // Register before the handshake completes, so that frames sent
+            // as soon as the client is connected are not lost
+            let (sender, receiver) = mpsc::unbounded_channel();
+            state.lock().unwrap().clients.push(sender);
+
+            tokio::spawn(Self::serve(socket, receiver, state.clone()));
+        }
+    }
+
+    async fn serve(
+        socket: TcpStream,
+        mut receiver: UnboundedReceiver<Message>,
+        state: Arc<Mutex<State>>,
+    ) {
+        let mut stream = match tokio_tungstenite::accept_async(socket).await {
+            Ok(stream) => stream,
+            Err(_) => return,
+        };
+
+        loop {
+            tokio::select! {
+                message = receiver.recv() => match message {
+                    Some(message) => {
+                        if stream.send(message).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Disconnected by the test
+                    None => break,
+                },
+                message = stream.next() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Some(reply) = Self::handle_op(&text, &state) {
+                            if stream.send(Message::Text(reply.to_string())).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) => {
+                        // Completes the closing handshake
+                        let _ = stream.close(None).await;
+                        break;
+                    }
+                    Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+    }
+
+    /// Records an op sent by a client and returns the reply, if any
+    fn handle_op(text: &str, state: &Mutex<State>) -> Option<Value> {
+        let op: Value = serde_json::from_str(text).ok()?;
+        let mut state = state.lock().unwrap();
+        state.received.push(op.clone());
+
+        let channel = op["channel"].as_str().map(str::to_string);
+        let market = op["market"].as_str().map(str::to_string);
+        match op["op"].as_str()? {
+            "ping" => Some(json!({"type": "pong"})),
+            "login" if state.reject_logins => {
+                Some(json!({"type": "error", "code": 400, "msg": "Invalid login credentials"}))
+            }
+            "subscribe" => {
+                let channel = channel?;
+                state.subscriptions.push((channel.clone(), market.clone()));
+                Some(json!({"type": "subscribed", "channel": channel, "market": market}))
+            }
+            "unsubscribe" => {
+                let channel = channel?;
+                state
+                    .subscriptions
+                    .retain(|(c, m)| *c != channel || *m != market);
+                Some(json!({"type": "unsubscribed", "channel": channel, "market": market}))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether to reject subsequent logins as FTX does for invalid
+    /// credentials
+    pub fn set_reject_logins(&self, reject: bool) {
+        self.state.lock().unwrap().reject_logins = reject;
+    }
+
+    /// Sends a raw text frame to all connected clients
+    pub fn send(&self, message: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .clients
+            .retain(|client| client.send(Message::Text(message.to_string())).is_ok());
+    }
+
+    /// Sends a frame of the given type with `data` to all connected clients
+    pub fn send_data(&self, r#type: &str, channel: &str, market: Option<&str>, data: Value) {
+        let message = json!({
+            "type": r#type,
+            "channel": channel,
+            "market": market,
+            "data": data,
+        });
+        self.send(&message.to_string());
+    }
+
+    /// Sends an update on the `trades` channel, `data` being an array of
+    /// trades
+    pub fn send_trades(&self, market: &str, data: Value) {
+        self.send_data("update", "trades", Some(market), data);
+    }
+
+    /// Sends orderbook data, as a partial or update depending on its
+    /// `action` field
+    pub fn send_orderbook(&self, market: &str, data: Value) {
+        let r#type = data["action"].as_str().unwrap_or("update").to_string();
+        self.send_data(&r#type, "orderbook", Some(market), data);
+    }
+
+    /// Sends an update on the `fills` channel
+    pub fn send_fill(&self, data: Value) {
+        self.send_data("update", "fills", None, data);
+    }
+
+    /// Sends an update on the `orders` channel
+    pub fn send_order(&self, data: Value) {
+        self.send_data("update", "orders", None, data);
+    }
+
+    /// Drops all client connections without a close frame, e.g. to test
+    /// reconnecting. The server keeps accepting new connections.
+    pub fn disconnect(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.clients.clear();
+        state.subscriptions.clear();
+    }
+
+    /// Drops the connection of the `index`th currently connected client
+    pub fn disconnect_client(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.clients.retain(|client| !client.is_closed());
+        if index < state.clients.len() {
+            state.clients.remove(index);
+        }
+    }
+
+    /// All ops received so far, including logins and pings
+    pub fn received(&self) -> Vec<Value> {
+        self.state.lock().unwrap().received.clone()
+    }
+
+    /// Active `(channel, market)` subscriptions
+    pub fn subscriptions(&self) -> Vec<(String, Option<String>)> {
+        self.state.lock().unwrap().subscriptions.clone()
+    }
+}
+
+impl Drop for MockWsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.disconnect();
+    }
+}
@@ -0,0 +1,30 @@
+//! Logging macros emitting `tracing` events when the `tracing` feature is
+//! enabled and `log` records otherwise.
+
+// Not every macro is used without the `ws` feature
+#![allow(unused_macros)]
+
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        log::$level!($($arg)+);
+    }};
+}
+
+macro_rules! trace {
+    ($($arg:tt)+) => { event!(trace, $($arg)+) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => { event!(debug, $($arg)+) };
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => { event!(info, $($arg)+) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => { event!(warn, $($arg)+) };
+}
@@ -0,0 +1,136 @@
+use super::{Data, Error, OrderbookAction, OrderbookData, Result, Symbol};
+use rust_decimal::Decimal;
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+
+/// What to do with data received while the buffer is full
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BufferPolicy {
+    /// Drop the oldest buffered data to make room
+    #[default]
+    DropOldest,
+    /// Drop the received data
+    DropNewest,
+    /// Merge orderbook updates into the latest buffered orderbook data of
+    /// the same market, so that the book stays consistent. Other data drops
+    /// the oldest buffered data.
+    CoalesceOrderbooks,
+    /// Drop the received data and return `Error::BufferFull`
+    Error,
+}
+
+/// Data received but not yet returned to the user
+pub(super) struct Buffer {
+    items: VecDeque<(Option<Symbol>, Data)>,
+    /// Maximum number of buffered items, unbounded if `None`
+    pub(super) max_size: Option<usize>,
+    pub(super) policy: BufferPolicy,
+    /// Merge orderbook updates into buffered orderbook data of the same
+    /// market, even if the buffer isn't full
+    pub(super) coalesce_orderbooks: bool,
+}
+
+impl Buffer {
+    pub(super) fn new() -> Self {
+        Self {
+            items: VecDeque::new(),
+            max_size: None,
+            policy: BufferPolicy::default(),
+            coalesce_orderbooks: false,
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub(super) fn pop_front(&mut self) -> Option<(Option<Symbol>, Data)> {
+        self.items.pop_front()
+    }
+
+    /// Buffers `item`, applying the policy if the buffer is full
+    pub(super) fn push(&mut self, item: (Option<Symbol>, Data)) -> Result<()> {
+        let full = self
+            .max_size
+            .is_some_and(|max_size| self.items.len() >= max_size);
+
+        let coalesce =
+            self.coalesce_orderbooks || (full && self.policy == BufferPolicy::CoalesceOrderbooks);
+        if let (true, (market, Data::OrderbookData(data))) = (coalesce, &item) {
+            if let Some(buffered) = self.latest_orderbook(market) {
+                buffered.coalesce(data);
+                return Ok(());
+            }
+        }
+
+        if !full {
+            self.items.push_back(item);
+            return Ok(());
+        }
+
+        match self.policy {
+            BufferPolicy::DropOldest | BufferPolicy::CoalesceOrderbooks => {
+                debug!("Buffer full, dropping the oldest data");
+                self.items.pop_front();
+            }
+            BufferPolicy::DropNewest => {
+                debug!("Buffer full, dropping {:?}", item);
+                return Ok(());
+            }
+            BufferPolicy::Error => return Err(Error::BufferFull),
+        }
+
+        // A maximum size of zero doesn't leave room after dropping
+        if self.max_size != Some(0) {
+            self.items.push_back(item);
+        }
+        Ok(())
+    }
+
+    /// Latest buffered orderbook data of `market`
+    fn latest_orderbook(&mut self, market: &Option<Symbol>) -> Option<&mut OrderbookData> {
+        self.items
+            .iter_mut()
+            .rev()
+            .find_map(|(m, data)| match data {
+                Data::OrderbookData(data) if m == market => Some(data),
+                _ => None,
+            })
+    }
+}
+
+impl OrderbookData {
+    /// Merges `later` into this data, so that applying the result to a book
+    /// is equivalent to applying both in order
+    fn coalesce(&mut self, later: &OrderbookData) {
+        // A snapshot replaces everything before it
+        if later.action == OrderbookAction::Partial {
+            *self = later.clone();
+            return;
+        }
+
+        let partial = self.action == OrderbookAction::Partial;
+        merge_levels(&mut self.bids, &later.bids, partial);
+        merge_levels(&mut self.asks, &later.asks, partial);
+        // Keep sending levels in 'best' order
+        self.bids.sort_by_key(|(price, _)| Reverse(*price));
+        self.asks.sort_by_key(|(price, _)| *price);
+        self.checksum = later.checksum;
+        self.time = later.time;
+    }
+}
+
+/// Overwrites `levels` with the sizes of `later`. Removed levels are kept
+/// with a size of zero in updates, and dropped from snapshots.
+fn merge_levels(levels: &mut Vec<(Decimal, Decimal)>, later: &[(Decimal, Decimal)], partial: bool) {
+    for (price, size) in later {
+        match levels.iter_mut().find(|(p, _)| p == price) {
+            Some(level) => level.1 = *size,
+            None => levels.push((*price, *size)),
+        }
+    }
+
+    if partial {
+        levels.retain(|(_, size)| !size.is_zero());
+    }
+}
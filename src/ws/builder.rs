@@ -0,0 +1,300 @@
+use super::{
+    BufferPolicy, Credentials, Proxy, RawMessageHook, Result, TlsConnector, TradeGuard, Ws,
+};
+use crate::options::{Exchange, Options};
+use crate::rest::Rest;
+use crate::signer::{HmacSigner, Signer};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Builder for a websocket connection, created with `Ws::builder()`.
+///
+/// The `permessage-deflate` extension isn't negotiated since tungstenite
+/// doesn't support it, so messages are always received uncompressed.
+///
+/// ```no_run
+/// # async fn example() -> ftx::ws::Result<()> {
+/// use ftx::options::Exchange;
+/// use ftx::ws::Ws;
+/// use std::time::Duration;
+///
+/// let ws = Ws::builder()
+///     .exchange(Exchange::Us)
+///     .credentials("<api key>", "<api secret>")
+///     .subaccount("<subaccount>")
+///     .ping_interval(Duration::from_secs(10))
+///     .verify_checksums(true)
+///     .connect()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct WsBuilder {
+    endpoint: String,
+    exchange: Exchange,
+    key: Option<(String, Arc<dyn Signer>)>,
+    subaccount: Option<String>,
+    clock_offset: chrono::Duration,
+    ping_interval: Duration,
+    stale_timeout: Duration,
+    subscription_timeout: Duration,
+    verify_checksums: bool,
+    auto_reconnect: bool,
+    tls_connector: Option<TlsConnector>,
+    proxy: Option<Proxy>,
+    /// Whether to use `Proxy::from_env` if no proxy is set
+    env_proxy: bool,
+    raw_message_hook: Option<RawMessageHook>,
+    max_buffer_size: Option<usize>,
+    buffer_policy: BufferPolicy,
+    coalesce_orderbooks: bool,
+    trade_guard: Option<TradeGuard>,
+    fast_orders: bool,
+    /// REST endpoint of the fast order path, the exchange's by default
+    rest_endpoint: Option<String>,
+}
+
+impl Default for WsBuilder {
+    fn default() -> Self {
+        Self {
+            endpoint: Ws::ENDPOINT.to_string(),
+            exchange: Exchange::Com,
+            key: None,
+            subaccount: None,
+            clock_offset: chrono::Duration::zero(),
+            ping_interval: Ws::PING_INTERVAL,
+            stale_timeout: Ws::STALE_TIMEOUT,
+            subscription_timeout: Ws::SUBSCRIPTION_TIMEOUT,
+            verify_checksums: false,
+            auto_reconnect: true,
+            tls_connector: None,
+            proxy: None,
+            env_proxy: true,
+            raw_message_hook: None,
+            max_buffer_size: None,
+            buffer_policy: BufferPolicy::default(),
+            coalesce_orderbooks: false,
+            trade_guard: None,
+            fast_orders: false,
+            rest_endpoint: None,
+        }
+    }
+}
+
+impl WsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Endpoint to connect to, `Ws::ENDPOINT` by default. Channels are
+    /// checked against the exchange set with `exchange`, ftx.com by default.
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = endpoint.to_string();
+        self
+    }
+
+    /// Exchange to connect to, setting its endpoint. Subscribing to channels
+    /// it doesn't offer fails with `Error::UnsupportedOnExchange`.
+    pub fn exchange(mut self, exchange: Exchange) -> Self {
+        self.endpoint = exchange.ws().to_string();
+        self.exchange = exchange;
+        self
+    }
+
+    /// API key and secret used to log in. Without credentials, only public
+    /// channels can be subscribed to.
+    pub fn credentials(mut self, key: &str, secret: &str) -> Self {
+        self.key = Some((key.to_string(), Arc::new(HmacSigner::new(secret))));
+        self
+    }
+
+    /// API key used to log in, signing the login op with `signer` instead of
+    /// a secret held in memory, see `signer::Signer`
+    pub fn signer(mut self, key: &str, signer: Arc<dyn Signer>) -> Self {
+        self.key = Some((key.to_string(), signer));
+        self
+    }
+
+    /// Exchange, credentials, and subaccount of `options`, see
+    /// `Options::from_env`
+    pub fn options(mut self, options: &Options) -> Self {
+        self = self.exchange(options.exchange);
+        if let (Some(key), Some(secret)) = (&options.key, &options.secret) {
+            self = self.credentials(key, secret);
+        }
+        self.subaccount = options.subaccount.clone();
+        self
+    }
+
+    /// Subaccount to log in to, ignored without credentials
+    pub fn subaccount(mut self, subaccount: &str) -> Self {
+        self.subaccount = Some(subaccount.to_string());
+        self
+    }
+
+    /// Difference between the server's and the local clock, added to the
+    /// local time when signing the login op. See `Rest::sync_clock`.
+    pub fn clock_offset(mut self, offset: chrono::Duration) -> Self {
+        self.clock_offset = offset;
+        self
+    }
+
+    /// Time between pings, 15 seconds by default
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = interval;
+        self
+    }
+
+    /// See `Ws::set_stale_timeout`
+    pub fn stale_timeout(mut self, timeout: Duration) -> Self {
+        self.stale_timeout = timeout;
+        self
+    }
+
+    /// See `Ws::set_subscription_timeout`
+    pub fn subscription_timeout(mut self, timeout: Duration) -> Self {
+        self.subscription_timeout = timeout;
+        self
+    }
+
+    /// See `Ws::set_verify_checksums`
+    pub fn verify_checksums(mut self, verify_checksums: bool) -> Self {
+        self.verify_checksums = verify_checksums;
+        self
+    }
+
+    /// See `Ws::set_auto_reconnect`
+    pub fn auto_reconnect(mut self, auto_reconnect: bool) -> Self {
+        self.auto_reconnect = auto_reconnect;
+        self
+    }
+
+    /// TLS connector used for `wss` endpoints, e.g. to add root certificates
+    /// when connecting through a proxy. Also used when reconnecting.
+    #[cfg(feature = "native-tls")]
+    pub fn tls_connector(mut self, connector: TlsConnector) -> Self {
+        self.tls_connector = Some(connector);
+        self
+    }
+
+    /// Proxy to tunnel the connection through. By default, the proxy
+    /// configured by the environment is used, see `Proxy::from_env`.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Connect directly, ignoring the proxy configured by the environment
+    pub fn no_proxy(mut self) -> Self {
+        self.proxy = None;
+        self.env_proxy = false;
+        self
+    }
+
+    /// See `Ws::set_raw_message_hook`
+    pub fn raw_message_hook<F: FnMut(&str) + Send + 'static>(mut self, hook: F) -> Self {
+        self.raw_message_hook = Some(Box::new(hook));
+        self
+    }
+
+    /// See `Ws::set_max_buffer_size`
+    pub fn max_buffer_size(mut self, max_size: usize) -> Self {
+        self.max_buffer_size = Some(max_size);
+        self
+    }
+
+    /// See `Ws::set_buffer_policy`
+    pub fn buffer_policy(mut self, policy: BufferPolicy) -> Self {
+        self.buffer_policy = policy;
+        self
+    }
+
+    /// See `Ws::set_coalesce_orderbooks`
+    pub fn coalesce_orderbooks(mut self, coalesce: bool) -> Self {
+        self.coalesce_orderbooks = coalesce;
+        self
+    }
+
+    /// See `Ws::set_trade_guard`
+    pub fn trade_guard(mut self, guard: TradeGuard) -> Self {
+        self.trade_guard = Some(guard);
+        self
+    }
+
+    /// Keeps a REST connection to the exchange open along with the
+    /// websocket, signed with the same credentials, to place orders without
+    /// waiting for TCP and TLS handshakes, see `Ws::rest`. FTX doesn't accept
+    /// orders over the websocket. Ignored without credentials.
+    pub fn fast_orders(mut self, enabled: bool) -> Self {
+        self.fast_orders = enabled;
+        self
+    }
+
+    /// REST endpoint of the fast order path, the one of the exchange set
+    /// with `exchange` by default
+    pub fn rest_endpoint(mut self, endpoint: &str) -> Self {
+        self.rest_endpoint = Some(endpoint.to_string());
+        self
+    }
+
+    /// Opens the connection and logs in if credentials were given
+    pub async fn connect(self) -> Result<Ws> {
+        let subaccount = self.subaccount;
+        let clock_offset = self.clock_offset;
+        let credentials = self.key.map(|(key, signer)| Credentials {
+            key,
+            signer,
+            subaccount,
+            clock_offset,
+        });
+
+        let endpoint = &self.endpoint;
+        let proxy = match self.env_proxy {
+            true => self.proxy.or_else(|| Proxy::from_env(endpoint)),
+            false => self.proxy,
+        };
+
+        // Opened first, so that a failing endpoint doesn't leave a
+        // websocket connection behind
+        let rest = match &credentials {
+            Some(credentials) if self.fast_orders => {
+                let exchange = self.exchange;
+                let rest = Rest::new_with_signer(
+                    self.rest_endpoint
+                        .as_deref()
+                        .unwrap_or_else(|| exchange.rest()),
+                    exchange.header_prefix(),
+                    credentials.key.clone(),
+                    credentials.signer.clone(),
+                    credentials.subaccount.clone(),
+                )?;
+                rest.set_clock_offset(clock_offset);
+                rest.warm_up().await?;
+                Some(rest)
+            }
+            _ => None,
+        };
+
+        let mut ws = Ws::connect_with_options(
+            &self.endpoint,
+            self.exchange,
+            credentials,
+            self.tls_connector,
+            proxy,
+            self.ping_interval,
+        )
+        .await?;
+        ws.set_stale_timeout(self.stale_timeout);
+        ws.set_subscription_timeout(self.subscription_timeout);
+        ws.set_verify_checksums(self.verify_checksums);
+        ws.set_auto_reconnect(self.auto_reconnect);
+        ws.raw_message_hook = self.raw_message_hook;
+        ws.set_max_buffer_size(self.max_buffer_size);
+        ws.set_buffer_policy(self.buffer_policy);
+        ws.set_coalesce_orderbooks(self.coalesce_orderbooks);
+        ws.set_trade_guard(self.trade_guard);
+        ws.rest = rest;
+
+        Ok(ws)
+    }
+}
@@ -0,0 +1,151 @@
+use super::Trade;
+use crate::rest::Candle;
+use chrono::{DateTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+
+/// Aggregates trades into OHLCV candles of a fixed resolution, aligned to
+/// wall-clock boundaries like those returned by `Rest::get_historical_candles`.
+/// Volume is measured in the quote currency, i.e. the sum of price times size.
+///
+/// ```no_run
+/// # async fn example() -> ftx::ws::Result<()> {
+/// use ftx::ws::{CandleBuilder, Channel, Data, Ws};
+///
+/// let mut ws = Ws::connect_public().await?;
+/// ws.subscribe(vec![Channel::Trades("BTC-PERP".into())]).await?;
+///
+/// let mut candles = CandleBuilder::new(60);
+/// while let Some((_, data)) = ws.next().await? {
+///     if let Data::Trade(trade) = data {
+///         for candle in candles.update(&trade) {
+///             println!("{:?}", candle);
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CandleBuilder {
+    resolution: i64,
+    fill_gaps: bool,
+    current: Option<Candle>,
+    /// Most recently completed candle
+    last: Option<Candle>,
+}
+
+impl CandleBuilder {
+    /// Creates a builder for candles of `resolution` seconds, between one
+    /// second and one day.
+    ///
+    /// # Panics
+    ///
+    /// If `resolution` is zero or longer than a day.
+    pub fn new(resolution: u32) -> Self {
+        assert!(
+            (1..=86400).contains(&resolution),
+            "Candle resolution must be between 1 second and 1 day"
+        );
+
+        Self {
+            resolution: resolution as i64,
+            fill_gaps: true,
+            current: None,
+            last: None,
+        }
+    }
+
+    /// Set whether intervals without trades produce a candle at the previous
+    /// close with zero volume, `true` by default. Otherwise they are skipped.
+    pub fn set_fill_gaps(&mut self, fill_gaps: bool) {
+        self.fill_gaps = fill_gaps;
+    }
+
+    /// Candle of the current interval, updated by every trade
+    pub fn current(&self) -> Option<&Candle> {
+        self.current.as_ref()
+    }
+
+    /// Adds a trade, returning the candles completed by it. Trades older
+    /// than the current candle are ignored.
+    pub fn update(&mut self, trade: &Trade) -> Vec<Candle> {
+        let start_time = self.start_of(trade.time);
+
+        if let Some(candle) = &mut self.current {
+            if candle.start_time == start_time {
+                candle.high = candle.high.max(trade.price);
+                candle.low = candle.low.min(trade.price);
+                candle.close = trade.price;
+                candle.volume += trade.price * trade.size;
+                return Vec::new();
+            }
+        }
+        let latest = self.current.or(self.last).map(|candle| candle.start_time);
+        if latest.is_some_and(|latest| latest >= start_time) {
+            debug!("Ignoring trade {} older than the current candle", trade.id);
+            return Vec::new();
+        }
+
+        let completed = self.complete_before(start_time);
+        self.current = Some(Candle {
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: trade.price * trade.size,
+            start_time,
+        });
+
+        completed
+    }
+
+    /// Returns the candles that ended before `now`, e.g. to emit candles of
+    /// quiet markets on a timer rather than waiting for the next trade
+    pub fn flush(&mut self, now: DateTime<Utc>) -> Vec<Candle> {
+        let start_time = self.start_of(now);
+        match &self.current {
+            Some(candle) if candle.start_time >= start_time => Vec::new(),
+            _ => self.complete_before(start_time),
+        }
+    }
+
+    /// Completes the current candle and, if gaps are filled, the empty ones
+    /// following it up to `start_time`
+    fn complete_before(&mut self, start_time: DateTime<Utc>) -> Vec<Candle> {
+        let mut completed = Vec::new();
+        if let Some(candle) = self.current.take() {
+            completed.push(candle);
+            self.last = Some(candle);
+        }
+
+        if self.fill_gaps {
+            if let Some(mut last) = self.last {
+                let resolution = chrono::Duration::seconds(self.resolution);
+                while last.start_time + resolution < start_time {
+                    last = Self::gap(&last, last.start_time + resolution);
+                    completed.push(last);
+                }
+                self.last = Some(last);
+            }
+        }
+
+        completed
+    }
+
+    /// Candle without trades following `previous`
+    fn gap(previous: &Candle, start_time: DateTime<Utc>) -> Candle {
+        Candle {
+            open: previous.close,
+            high: previous.close,
+            low: previous.close,
+            close: previous.close,
+            volume: Decimal::ZERO,
+            start_time,
+        }
+    }
+
+    fn start_of(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let seconds = time.timestamp().div_euclid(self.resolution) * self.resolution;
+        Utc.timestamp_opt(seconds, 0).unwrap()
+    }
+}
@@ -1,19 +1,46 @@
-use crate::ws::Channel;
+use crate::ws::{Channel, Symbol};
 use tokio_tungstenite::tungstenite;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
-    Tungstenite(tungstenite::Error),
+    // Boxed to keep `Result` small, tungstenite's error is large
+    Tungstenite(Box<tungstenite::Error>),
     Serde(serde_json::Error),
+    /// Reading or writing a recorded session failed
+    Io(std::io::Error),
+    /// The proxy URL passed to `Proxy::new` is malformed or has an
+    /// unsupported scheme
+    InvalidProxy(String),
+    /// FTX rejected the login, e.g. because of invalid credentials
+    AuthenticationFailed(String),
     NotSubscribedToThisChannel(Channel),
-    MissingSubscriptionConfirmation,
+    MissingSubscriptionConfirmation(Channel),
+    ChecksumMismatch(Symbol),
+    /// Nothing, not even a pong, was received within the stale timeout
+    ConnectionStale,
+    /// Data was dropped because the buffer is full, see `BufferPolicy::Error`
+    BufferFull,
+    /// The channel is not available on the exchange, e.g. futures markets on
+    /// ftx.us
+    UnsupportedOnExchange(Channel),
+    /// The channel is polled from the REST API instead of being sent by FTX,
+    /// see `Client::subscribe`
+    PolledChannel(Channel),
+    /// Error sent by FTX, e.g. on invalid login credentials or markets
+    Api {
+        code: Option<u16>,
+        msg: String,
+    },
+    /// The REST connection of the fast order path couldn't be opened, see
+    /// `WsBuilder::fast_orders`
+    Rest(crate::rest::Error),
 }
 
 impl From<tungstenite::Error> for Error {
     fn from(err: tungstenite::Error) -> Error {
-        Error::Tungstenite(err)
+        Error::Tungstenite(Box::new(err))
     }
 }
 
@@ -22,3 +49,15 @@ impl From<serde_json::Error> for Error {
         Error::Serde(err)
     }
 }
+
+impl From<crate::rest::Error> for Error {
+    fn from(err: crate::rest::Error) -> Error {
+        Error::Rest(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Error {
+        Error::Io(err)
+    }
+}
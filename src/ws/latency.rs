@@ -0,0 +1,87 @@
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Number of most recent samples kept per measurement
+const MAX_SAMPLES: usize = 1000;
+
+/// Median and 95th percentile of the most recent samples
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Percentiles<T> {
+    pub p50: T,
+    pub p95: T,
+    /// Number of samples the percentiles are computed from
+    pub samples: usize,
+}
+
+/// Feed health of a connection, see `Ws::latency_stats`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Time between sending a ping and receiving its pong
+    pub round_trip: Option<Percentiles<Duration>>,
+    /// Local receipt time minus `Trade::time`, which includes the offset
+    /// between the local and the server's clock, so it is negative if the
+    /// local clock is behind
+    pub trade_delay: Option<Percentiles<chrono::Duration>>,
+}
+
+/// Collects latency samples of a connection
+#[derive(Debug, Default)]
+pub(super) struct LatencyTracker {
+    /// Send times of pings awaiting their pong, FTX answers them in order
+    pings: VecDeque<Instant>,
+    round_trips: VecDeque<Duration>,
+    trade_delays: VecDeque<chrono::Duration>,
+}
+
+impl LatencyTracker {
+    pub(super) fn ping_sent(&mut self) {
+        self.pings.push_back(Instant::now());
+    }
+
+    pub(super) fn pong_received(&mut self) {
+        if let Some(sent) = self.pings.pop_front() {
+            push(&mut self.round_trips, sent.elapsed());
+        }
+    }
+
+    pub(super) fn trade_received(&mut self, time: DateTime<Utc>) {
+        push(&mut self.trade_delays, Utc::now() - time);
+    }
+
+    /// Forgets pings sent on a connection that was replaced
+    pub(super) fn reset_pings(&mut self) {
+        self.pings.clear();
+    }
+
+    pub(super) fn stats(&self) -> LatencyStats {
+        LatencyStats {
+            round_trip: percentiles(&self.round_trips),
+            trade_delay: percentiles(&self.trade_delays),
+        }
+    }
+}
+
+fn push<T>(samples: &mut VecDeque<T>, sample: T) {
+    if samples.len() == MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
+
+/// Nearest-rank percentiles, `None` without samples
+fn percentiles<T: Copy + Ord>(samples: &VecDeque<T>) -> Option<Percentiles<T>> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted: Vec<T> = samples.iter().copied().collect();
+    sorted.sort_unstable();
+    let at = |percent: usize| sorted[(sorted.len() - 1) * percent / 100];
+
+    Some(Percentiles {
+        p50: at(50),
+        p95: at(95),
+        samples: sorted.len(),
+    })
+}
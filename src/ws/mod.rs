@@ -1,49 +1,315 @@
 //! This module is used to interact with the Websocket API.
 
+mod buffer;
+mod builder;
+mod candles;
 mod error;
+mod latency;
 mod model;
+mod orderbooks;
+mod pool;
+mod proxy;
+pub mod replay;
 #[cfg(test)]
 mod tests;
+mod trade_guard;
 
+pub use buffer::BufferPolicy;
+pub use builder::*;
+pub use candles::*;
 pub use error::*;
+pub use latency::{LatencyStats, Percentiles};
 pub use model::*;
+pub use orderbooks::*;
+pub use pool::*;
+pub use proxy::Proxy;
+pub use trade_guard::{DataIntegrity, TradeGuard};
 
-use futures_util::{SinkExt, StreamExt};
-use hmac_sha256::HMAC;
+use crate::options::Exchange;
+use crate::rest::Rest;
+use crate::signer::{HmacSigner, Signer};
+use buffer::Buffer;
+use chrono::{DateTime, Utc};
+use futures_util::{task::noop_waker_ref, SinkExt, Stream, StreamExt};
+use latency::LatencyTracker;
+#[cfg(feature = "native-tls")]
+use native_tls::TlsConnector;
+use rust_decimal::prelude::ToPrimitive;
 use serde_json::json;
-use std::collections::VecDeque;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio::time; // 1.3.0
-use tokio::time::Interval;
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tokio::time::{Interval, Sleep};
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+use tokio_tungstenite::client_async;
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+use tokio_tungstenite::{client_async_tls_with_config, Connector};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{
+        self, client::IntoClientRequest, error::UrlError, handshake::client::Request, Message,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type Reconnect = Pin<Box<dyn Future<Output = Result<WsStream>> + Send>>;
+
+/// Custom TLS connectors are only supported by the `native-tls` backend
+#[cfg(not(feature = "native-tls"))]
+#[derive(Clone)]
+enum TlsConnector {}
+
+/// Callback receiving the text of every message before it is deserialized
+pub type RawMessageHook = Box<dyn FnMut(&str) + Send>;
+
+/// API credentials used to log in, `None` for public connections
+#[derive(Clone)]
+struct Credentials {
+    key: String,
+    signer: Arc<dyn Signer>,
+    subaccount: Option<String>,
+    /// Added to the local time when signing the login op
+    clock_offset: chrono::Duration,
+}
 
 pub struct Ws {
     channels: Vec<Channel>,
-    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
-    buf: VecDeque<Data>,
+    stream: WsStream,
+    /// Received data along with the market it belongs to, if any
+    buf: Buffer,
+    verify_checksums: bool,
+    subscription_timeout: Duration,
+    /// Subscribe and unsubscribe ops awaiting confirmation, keyed by channel
+    /// name and market
+    pending: HashMap<(String, Symbol), Type>,
+    /// Local orderbooks per market, only maintained when verifying checksums
+    orderbooks: HashMap<Symbol, Orderbook>,
+    /// Time of the last update of each local orderbook matching its checksum
+    orderbooks_valid_at: HashMap<Symbol, DateTime<Utc>>,
     ping_timer: Interval,
+    /// Fires when nothing was received within `stale_timeout`
+    stale_timer: Pin<Box<Sleep>>,
+    stale_timeout: Duration,
+    endpoint: String,
+    exchange: Exchange,
+    credentials: Option<Credentials>,
+    tls_connector: Option<TlsConnector>,
+    proxy: Option<Proxy>,
+    auto_reconnect: bool,
+    /// In-flight reconnection, only used when polled as a `Stream`
+    reconnecting: Option<Reconnect>,
+    /// Set by `close`, after which only buffered data is returned
+    closed: bool,
+    raw_message_hook: Option<RawMessageHook>,
+    /// Failure while reading ahead, returned by the next read
+    read_ahead_failure: Option<Error>,
+    latency: LatencyTracker,
+    trade_guard: Option<TradeGuard>,
+    /// Warm REST connection of the fast order path
+    rest: Option<Rest>,
 }
 
 impl Ws {
     pub const ENDPOINT: &'static str = "wss://ftx.com/ws";
     pub const ENDPOINT_US: &'static str = "wss://ftx.us/ws";
 
-    async fn connect_with_endpoint(
+    /// Number of attempts made to re-establish a dropped connection before
+    /// the underlying error is returned to the caller
+    const RECONNECT_ATTEMPTS: u32 = 5;
+
+    /// Default time between pings
+    const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+    /// Default time without any message, including pongs, after which the
+    /// connection is considered stale
+    const STALE_TIMEOUT: Duration = Duration::from_secs(45);
+
+    /// Maximum number of messages handled ahead when coalescing orderbook
+    /// updates
+    const READ_AHEAD: usize = 1000;
+
+    /// Time to wait for FTX to acknowledge a close frame
+    const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Time to wait for the pong sent after the login op
+    const LOGIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Default time to wait for FTX to confirm a subscribe or unsubscribe op
+    const SUBSCRIPTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Returns a builder to configure the connection before opening it
+    pub fn builder() -> WsBuilder {
+        WsBuilder::new()
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(credentials, tls_connector, ping_interval))
+    )]
+    async fn connect_with_options(
         endpoint: &str,
-        key: String,
-        secret: String,
-        subaccount: Option<String>,
+        exchange: Exchange,
+        credentials: Option<Credentials>,
+        tls_connector: Option<TlsConnector>,
+        proxy: Option<Proxy>,
+        ping_interval: Duration,
     ) -> Result<Self> {
-        let (mut stream, _) = connect_async(endpoint).await?;
+        let stream = Self::open_stream(endpoint, &credentials, &tls_connector, &proxy, &[]).await?;
+
+        Ok(Self {
+            channels: Vec::new(),
+            stream,
+            buf: Buffer::new(),
+            verify_checksums: false,
+            subscription_timeout: Self::SUBSCRIPTION_TIMEOUT,
+            pending: HashMap::new(),
+            orderbooks: HashMap::new(),
+            orderbooks_valid_at: HashMap::new(),
+            ping_timer: time::interval(ping_interval),
+            stale_timer: Box::pin(time::sleep(Self::STALE_TIMEOUT)),
+            stale_timeout: Self::STALE_TIMEOUT,
+            endpoint: endpoint.to_string(),
+            exchange,
+            credentials,
+            tls_connector,
+            proxy,
+            auto_reconnect: true,
+            reconnecting: None,
+            closed: false,
+            raw_message_hook: None,
+            read_ahead_failure: None,
+            latency: LatencyTracker::default(),
+            trade_guard: None,
+            rest: None,
+        })
+    }
+
+    /// Opens a websocket connection to `endpoint`, sends the login op if
+    /// `credentials` are given and subscribes to `channels`.
+    ///
+    /// `permessage-deflate` isn't offered: tungstenite 0.13 has no support for
+    /// extensions and rejects frames with the RSV1 (compressed) bit set.
+    async fn open_stream(
+        endpoint: &str,
+        credentials: &Option<Credentials>,
+        tls_connector: &Option<TlsConnector>,
+        proxy: &Option<Proxy>,
+        channels: &[Channel],
+    ) -> Result<WsStream> {
+        let mut stream = match (tls_connector, proxy) {
+            (None, None) => connect_async(endpoint).await?.0,
+            _ => Self::connect_via(endpoint, tls_connector, proxy).await?,
+        };
+        debug!("Connected to {}", endpoint);
+
+        if let Some(credentials) = credentials {
+            Self::login(&mut stream, credentials).await?;
+        }
+
+        // Confirmations are consumed by `handle_response` like any other
+        // response without data
+        for (i, channel) in channels.iter().enumerate() {
+            // Channels sharing a subscription are only subscribed once
+            if channels[..i]
+                .iter()
+                .any(|c| c.name_and_market() == channel.name_and_market())
+            {
+                continue;
+            }
+            stream
+                .send(Self::subscription_message(channel, true))
+                .await?;
+        }
+
+        Ok(stream)
+    }
+
+    /// Opens a websocket connection to `endpoint` through `proxy` and using
+    /// a custom TLS connector, if given
+    async fn connect_via(
+        endpoint: &str,
+        tls_connector: &Option<TlsConnector>,
+        proxy: &Option<Proxy>,
+    ) -> Result<WsStream> {
+        let request = endpoint.into_client_request()?;
+        let uri = request.uri();
+        let host = uri
+            .host()
+            .ok_or(tungstenite::Error::Url(UrlError::NoHostName))?
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let port = match (uri.port_u16(), uri.scheme_str()) {
+            (Some(port), _) => port,
+            (None, Some("wss")) => 443,
+            (None, Some("ws")) => 80,
+            _ => return Err(tungstenite::Error::Url(UrlError::UnsupportedUrlScheme).into()),
+        };
 
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
+        let socket = match proxy {
+            Some(proxy) => proxy.connect(host, port).await,
+            None => TcpStream::connect((host, port)).await,
+        }
+        .map_err(tungstenite::Error::Io)?;
+
+        Self::handshake(request, socket, tls_connector).await
+    }
+
+    #[cfg(any(feature = "native-tls", feature = "rustls"))]
+    async fn handshake(
+        request: Request,
+        socket: TcpStream,
+        tls_connector: &Option<TlsConnector>,
+    ) -> Result<WsStream> {
+        #[cfg(feature = "native-tls")]
+        let connector = tls_connector.clone().map(Connector::NativeTls);
+        // rustls connects with the bundled root certificates
+        #[cfg(not(feature = "native-tls"))]
+        let connector = tls_connector
+            .as_ref()
+            .map(|connector| -> Connector { match *connector {} });
+        let (stream, _) = client_async_tls_with_config(request, socket, None, connector).await?;
+
+        Ok(stream)
+    }
+
+    #[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+    async fn handshake(
+        request: Request,
+        socket: TcpStream,
+        tls_connector: &Option<TlsConnector>,
+    ) -> Result<WsStream> {
+        if let Some(tls_connector) = tls_connector {
+            match *tls_connector {}
+        }
+        if request.uri().scheme_str() == Some("wss") {
+            return Err(tungstenite::Error::Url(UrlError::TlsFeatureNotEnabled).into());
+        }
+        let (stream, _) = client_async(request, MaybeTlsStream::Plain(socket)).await?;
+
+        Ok(stream)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(subaccount = ?credentials.subaccount))
+    )]
+    async fn login(stream: &mut WsStream, credentials: &Credentials) -> Result<()> {
+        let Credentials {
+            key,
+            signer,
+            subaccount,
+            clock_offset,
+        } = credentials;
+
+        let timestamp = (Utc::now() + *clock_offset).timestamp_millis();
         let sign_payload = format!("{}websocket_login", timestamp);
-        let sign = HMAC::mac(sign_payload.as_bytes(), secret.as_bytes());
-        let sign = hex::encode(sign);
+        let sign = hex::encode(signer.sign(sign_payload.as_bytes()));
 
         stream
             .send(Message::Text(
@@ -52,7 +318,7 @@ impl Ws {
                     "args": {
                         "key": key,
                         "sign": sign,
-                        "time": timestamp as u64,
+                        "time": timestamp,
                         "subaccount": subaccount,
                     }
                 })
@@ -60,16 +326,53 @@ impl Ws {
             ))
             .await?;
 
-        Ok(Self {
-            channels: Vec::new(),
-            stream,
-            buf: VecDeque::new(),
-            ping_timer: time::interval(Duration::from_secs(15)),
-        })
+        // FTX doesn't acknowledge logins, but answers ops in order: a pong
+        // received before any error means the login succeeded
+        stream
+            .send(Message::Text(json!({"op": "ping"}).to_string()))
+            .await?;
+        time::timeout(Self::LOGIN_TIMEOUT, Self::await_login(stream))
+            .await
+            .map_err(|_| Error::ConnectionStale)?
+    }
+
+    async fn await_login(stream: &mut WsStream) -> Result<()> {
+        while let Some(message) = stream.next().await {
+            let text = match message? {
+                Message::Text(text) => text,
+                _ => continue,
+            };
+            let response: Response = text.parse()?;
+            match response.r#type {
+                Type::Pong => return Ok(()),
+                Type::Error => {
+                    return Err(Error::AuthenticationFailed(
+                        response.msg.unwrap_or_default(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        Err(tungstenite::Error::ConnectionClosed.into())
     }
 
     pub async fn connect(key: String, secret: String, subaccount: Option<String>) -> Result<Self> {
-        Self::connect_with_endpoint(Self::ENDPOINT, key, secret, subaccount).await
+        let credentials = Credentials {
+            key,
+            signer: Arc::new(HmacSigner::from_secret(secret)),
+            subaccount,
+            clock_offset: chrono::Duration::zero(),
+        };
+        Self::connect_with_options(
+            Self::ENDPOINT,
+            Exchange::Com,
+            Some(credentials),
+            None,
+            Proxy::from_env(Self::ENDPOINT),
+            Self::PING_INTERVAL,
+        )
+        .await
     }
 
     pub async fn connect_us(
@@ -77,7 +380,33 @@ impl Ws {
         secret: String,
         subaccount: Option<String>,
     ) -> Result<Self> {
-        Self::connect_with_endpoint(Self::ENDPOINT_US, key, secret, subaccount).await
+        let credentials = Credentials {
+            key,
+            signer: Arc::new(HmacSigner::from_secret(secret)),
+            subaccount,
+            clock_offset: chrono::Duration::zero(),
+        };
+        Self::connect_with_options(
+            Self::ENDPOINT_US,
+            Exchange::Us,
+            Some(credentials),
+            None,
+            Proxy::from_env(Self::ENDPOINT_US),
+            Self::PING_INTERVAL,
+        )
+        .await
+    }
+
+    /// Connect without logging in. Only public channels (e.g. trades,
+    /// orderbooks, ticker) can be subscribed to.
+    pub async fn connect_public() -> Result<Self> {
+        Self::builder().connect().await
+    }
+
+    /// Connect to FTX US without logging in. Only public channels (e.g. trades,
+    /// orderbooks, ticker) can be subscribed to.
+    pub async fn connect_public_us() -> Result<Self> {
+        Self::builder().exchange(Exchange::Us).connect().await
     }
 
     async fn ping(&mut self) -> Result<()> {
@@ -89,35 +418,282 @@ impl Ws {
                 .to_string(),
             ))
             .await?;
+        self.latency.ping_sent();
 
         Ok(())
     }
 
-    /// Subscribe to specified `Channel`s
-    pub async fn subscribe(&mut self, channels: Vec<Channel>) -> Result<()> {
-        for channel in channels.iter() {
-            self.channels.push(channel.clone());
+    /// Exchange the channels are checked against, see `WsBuilder::exchange`
+    pub fn exchange(&self) -> Exchange {
+        self.exchange
+    }
+
+    /// Enable or disable automatic reconnection (enabled by default).
+    /// When enabled, a dropped connection is re-established, re-authenticated,
+    /// and all currently subscribed `Channel`s are subscribed to again.
+    pub fn set_auto_reconnect(&mut self, auto_reconnect: bool) {
+        self.auto_reconnect = auto_reconnect;
+    }
+
+    /// Set the difference between the server's and the local clock, which is
+    /// added to the local time when signing the login op on reconnection.
+    /// See `Rest::sync_clock` to measure it.
+    pub fn set_clock_offset(&mut self, offset: chrono::Duration) {
+        if let Some(credentials) = &mut self.credentials {
+            credentials.clock_offset = offset;
         }
+    }
+
+    /// Set a callback that receives the text of every message, including
+    /// pongs and confirmations, before it is deserialized. Useful to record
+    /// sessions for debugging or replay.
+    pub fn set_raw_message_hook<F: FnMut(&str) + Send + 'static>(&mut self, hook: F) {
+        self.raw_message_hook = Some(Box::new(hook));
+    }
 
-        self.subscribe_or_unsubscribe(channels, true).await?;
+    /// Set the time without receiving any message, including pongs, after
+    /// which the connection is considered stale. A stale connection is
+    /// re-established if automatic reconnection is enabled, otherwise
+    /// `Error::ConnectionStale` is returned.
+    pub fn set_stale_timeout(&mut self, timeout: Duration) {
+        self.stale_timeout = timeout;
+        self.reset_stale_timer();
+    }
+
+    /// Called whenever a message is received or the connection is replaced
+    fn reset_stale_timer(&mut self) {
+        let deadline = time::Instant::now() + self.stale_timeout;
+        self.stale_timer.as_mut().reset(deadline);
+    }
+
+    /// Set the time to wait for FTX to confirm the ops of a subscribe or
+    /// unsubscribe call before `Error::MissingSubscriptionConfirmation` is
+    /// returned
+    pub fn set_subscription_timeout(&mut self, timeout: Duration) {
+        self.subscription_timeout = timeout;
+    }
+
+    /// Set the maximum number of received items buffered until they are
+    /// returned by `next`, unbounded by default. See `set_buffer_policy` for
+    /// what happens once the buffer is full.
+    pub fn set_max_buffer_size(&mut self, max_size: Option<usize>) {
+        self.buf.max_size = max_size;
+    }
+
+    /// Set what happens to data received while the buffer is full,
+    /// `BufferPolicy::DropOldest` by default. Dropping orderbook data leaves
+    /// books maintained by the caller inconsistent, unless orderbook updates
+    /// are coalesced.
+    pub fn set_buffer_policy(&mut self, policy: BufferPolicy) {
+        self.buf.policy = policy;
+    }
+
+    /// Enable or disable merging orderbook updates of a market while they
+    /// wait in the buffer (disabled by default). When enabled, messages that
+    /// have already arrived are handled before data is returned, so that a
+    /// slow consumer receives a single, up to date orderbook update instead
+    /// of a backlog of stale ones.
+    pub fn set_coalesce_orderbooks(&mut self, coalesce: bool) {
+        self.buf.coalesce_orderbooks = coalesce;
+    }
+
+    /// Check trades with `guard` (disabled by default). Duplicate trades,
+    /// e.g. sent again after a reconnect, are dropped, and every problem
+    /// detected is returned as `Data::Integrity` before the trade.
+    pub fn set_trade_guard(&mut self, guard: Option<TradeGuard>) {
+        self.trade_guard = guard;
+    }
+
+    /// REST client of the fast order path enabled with
+    /// `WsBuilder::fast_orders`. Its connection was opened along with the
+    /// websocket and is kept alive, so orders placed with it don't wait for
+    /// a handshake.
+    pub fn rest(&self) -> Option<&Rest> {
+        self.rest.as_ref()
+    }
+
+    /// Round-trip latency of pings and delay of trades relative to their
+    /// timestamp, over the most recent samples. Pings are sent every ping
+    /// interval while data is read.
+    pub fn latency_stats(&self) -> LatencyStats {
+        self.latency.stats()
+    }
+
+    /// Number of received items waiting to be returned by `next`
+    pub fn buffer_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Enable or disable orderbook checksum verification (disabled by default).
+    /// When enabled, a local `Orderbook` is maintained for each subscribed
+    /// market and every orderbook update is checked against the checksum sent
+    /// by FTX. A mismatch is returned as `Error::ChecksumMismatch`, after which
+    /// the market's book is discarded until the next partial snapshot. The
+    /// following call to `next` returns a
+    /// `DataIntegrity::OrderbookChecksumMismatch` event for monitoring.
+    pub fn set_verify_checksums(&mut self, verify_checksums: bool) {
+        self.verify_checksums = verify_checksums;
+        if !verify_checksums {
+            self.orderbooks.clear();
+            self.orderbooks_valid_at.clear();
+        }
+    }
+
+    /// Returns the locally maintained orderbook of `symbol`, only available
+    /// when checksum verification is enabled
+    pub fn orderbook(&self, symbol: &str) -> Option<&Orderbook> {
+        self.orderbooks.get(symbol)
+    }
+
+    /// Re-establishes the connection and replays the current subscriptions
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), fields(endpoint = %self.endpoint)))]
+    pub async fn reconnect(&mut self) -> Result<()> {
+        self.stream = Self::open_stream(
+            &self.endpoint,
+            &self.credentials,
+            &self.tls_connector,
+            &self.proxy,
+            &self.channels,
+        )
+        .await?;
+        self.latency.reset_pings();
+        self.reset_stale_timer();
+
+        Ok(())
+    }
+
+    /// Re-establishes the connection logged in to `subaccount`, or the main
+    /// account if `None`, and replays the current subscriptions. Data of the
+    /// previous account that is still buffered is returned first. Only the
+    /// subaccount of connections with credentials can be switched.
+    pub async fn reconnect_with_subaccount(&mut self, subaccount: Option<&str>) -> Result<()> {
+        if let Some(credentials) = &mut self.credentials {
+            credentials.subaccount = subaccount.map(str::to_string);
+        }
+        self.reconnect().await
+    }
+
+    /// Subaccount the connection is logged in to, if any
+    pub fn subaccount(&self) -> Option<&str> {
+        self.credentials
+            .as_ref()
+            .and_then(|credentials| credentials.subaccount.as_deref())
+    }
+
+    /// Returns a future that repeatedly tries to re-establish the connection
+    /// after it failed with `err`. The future owns its state so that it can
+    /// also be driven from `Stream::poll_next`.
+    fn reconnect_with_retries(&self, err: Error) -> Reconnect {
+        let endpoint = self.endpoint.clone();
+        let credentials = self.credentials.clone();
+        let tls_connector = self.tls_connector.clone();
+        let proxy = self.proxy.clone();
+        let channels = self.channels.clone();
+
+        Box::pin(async move {
+            let mut last_err = err;
+            for attempt in 0..Self::RECONNECT_ATTEMPTS {
+                warn!(
+                    "Websocket disconnected ({:?}), reconnecting (attempt {})",
+                    last_err,
+                    attempt + 1
+                );
+                time::sleep(Duration::from_millis(500 * 2u64.pow(attempt))).await;
+
+                let stream =
+                    Self::open_stream(&endpoint, &credentials, &tls_connector, &proxy, &channels);
+                match stream.await {
+                    Ok(stream) => return Ok(stream),
+                    Err(err) => last_err = err,
+                }
+            }
+
+            Err(last_err)
+        })
+    }
+
+    /// Called when the connection failed with `err`. Reconnects if automatic
+    /// reconnection is enabled, otherwise returns the error.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), fields(endpoint = %self.endpoint)))]
+    async fn handle_disconnect(&mut self, err: Error) -> Result<()> {
+        if !self.auto_reconnect {
+            return Err(err);
+        }
+
+        self.stream = self.reconnect_with_retries(err).await?;
+        self.latency.reset_pings();
+        self.reset_stale_timer();
 
         Ok(())
     }
 
+    /// Closes the connection with a close frame. Messages received until FTX
+    /// acknowledges the close are added to the buffer, afterwards `next`
+    /// returns the buffered data followed by `None`.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    pub async fn close(&mut self) -> Result<()> {
+        self.closed = true;
+        self.reconnecting = None;
+        self.stream.close(None).await?;
+
+        let drain = async {
+            while let Some(msg) = self.stream.next().await {
+                match msg {
+                    Ok(msg) => {
+                        if let Some(response) = self.parse_message(msg)? {
+                            self.handle_response(response)?;
+                        }
+                    }
+                    Err(tungstenite::Error::ConnectionClosed) => break,
+                    Err(err) => return Err(err.into()),
+                }
+            }
+            Ok(())
+        };
+
+        match time::timeout(Self::CLOSE_TIMEOUT, drain).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!("Websocket close was not acknowledged, dropping the connection");
+                Ok(())
+            }
+        }
+    }
+
+    /// Subscribe to specified `Channel`s. All ops are sent at once and their
+    /// confirmations awaited together. Fails with
+    /// `Error::MissingSubscriptionConfirmation` for the first channel that
+    /// wasn't confirmed, after subscribing to the others.
+    pub async fn subscribe(&mut self, channels: Vec<Channel>) -> Result<()> {
+        for (_, result) in self.subscribe_or_unsubscribe(channels, true).await? {
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Subscribe to specified `Channel`s like `subscribe`, but return the
+    /// result of each channel instead of failing on the first unconfirmed
+    /// one. Fails as a whole on connection and API errors.
+    pub async fn subscribe_each(
+        &mut self,
+        channels: Vec<Channel>,
+    ) -> Result<Vec<(Channel, Result<()>)>> {
+        self.subscribe_or_unsubscribe(channels, true).await
+    }
+
     /// Unsubscribe from specified `Channel`s
     pub async fn unsubscribe(&mut self, channels: Vec<Channel>) -> Result<()> {
         // Check that the specified channels match an existing one
         for channel in channels.iter() {
-            if !self.channels.contains(&channel) {
+            if !self.channels.contains(channel) {
                 return Err(Error::NotSubscribedToThisChannel(channel.clone()));
             }
         }
 
-        self.subscribe_or_unsubscribe(channels.clone(), false)
-            .await?;
-
-        // Unsubscribe successful, remove specified channels from self.channels
-        self.channels.retain(|c| !channels.contains(c));
+        for (_, result) in self.subscribe_or_unsubscribe(channels, false).await? {
+            result?;
+        }
 
         Ok(())
     }
@@ -131,124 +707,589 @@ impl Ws {
         Ok(())
     }
 
+    /// Sends the ops for all channels, then waits for their confirmations
+    /// within a single subscription timeout. `self.channels` is updated for
+    /// each confirmed channel, so it stays accurate if others fail.
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
     async fn subscribe_or_unsubscribe(
         &mut self,
         channels: Vec<Channel>,
         subscribe: bool,
-    ) -> Result<()> {
-        let op = if subscribe {
-            "subscribe"
+    ) -> Result<Vec<(Channel, Result<()>)>> {
+        let expected = if subscribe {
+            Type::Subscribed
         } else {
-            "unsubscribe"
+            Type::Unsubscribed
         };
+        if subscribe {
+            for channel in &channels {
+                self.check_supported(channel)?;
+            }
+        }
 
-        'channels: for channel in channels {
-            let (channel, symbol) = match channel {
-                Channel::Orderbook(symbol) => ("orderbook", symbol),
-                Channel::Trades(symbol) => ("trades", symbol),
-                Channel::Ticker(symbol) => ("ticker", symbol),
-                Channel::Fills => ("fills", "".to_string()),
+        // Pending key of each channel, `None` if it needs no op
+        let mut ops: Vec<(Channel, Option<(String, Symbol)>)> = Vec::new();
+        let mut messages = Vec::new();
+        for channel in channels {
+            // Channels sharing FTX's subscription with another subscribed
+            // channel don't need an op, e.g. trades and liquidations
+            let shared = self
+                .channels
+                .iter()
+                .any(|c| c != &channel && c.name_and_market() == channel.name_and_market());
+            if shared {
+                if subscribe {
+                    if !self.channels.contains(&channel) {
+                        self.channels.push(channel.clone());
+                    }
+                } else {
+                    self.channels.retain(|c| c != &channel);
+                }
+                ops.push((channel, None));
+                continue;
+            }
+
+            // Channels of the same op, e.g. trades and liquidations
+            // subscribed to together, share its confirmation
+            let key = Self::pending_key(&channel);
+            if !ops.iter().any(|(_, k)| k.as_ref() == Some(&key)) {
+                messages.push(Self::subscription_message(&channel, subscribe));
+            }
+            ops.push((channel, Some(key)));
+        }
+
+        let keys: Vec<_> = ops.iter().filter_map(|(_, key)| key.clone()).collect();
+        // Registered before sending, so that no confirmation is missed
+        for key in &keys {
+            self.pending.insert(key.clone(), expected);
+        }
+        let mut result = self.send_all(messages).await;
+        if result.is_ok() {
+            result = self.await_confirmations(&keys).await;
+        }
+        let unconfirmed: Vec<_> = keys
+            .iter()
+            .filter(|key| self.pending.remove(*key).is_some())
+            .collect();
+        result?;
+
+        let mut results = Vec::with_capacity(ops.len());
+        for (channel, key) in ops {
+            let key = match key {
+                Some(key) => key,
+                None => {
+                    results.push((channel, Ok(())));
+                    continue;
+                }
             };
+            if unconfirmed.contains(&&key) {
+                warn!("No confirmation received for {:?}", channel);
+                let err = Error::MissingSubscriptionConfirmation(channel.clone());
+                results.push((channel, Err(err)));
+                continue;
+            }
+            debug!("{:?} confirmed for {:?}", expected, channel);
 
-            self.stream
-                .send(Message::Text(
-                    json!({
-                        "op": op,
-                        "channel": channel,
-                        "market": symbol,
-                    })
-                    .to_string(),
-                ))
-                .await?;
+            if subscribe {
+                if !self.channels.contains(&channel) {
+                    self.channels.push(channel.clone());
+                }
+            } else {
+                if let Channel::Orderbook(symbol) = &channel {
+                    self.orderbooks.remove(symbol);
+                    self.orderbooks_valid_at.remove(symbol);
+                }
+                self.channels.retain(|c| c != &channel);
+            }
+            results.push((channel, Ok(())));
+        }
 
-            // Confirmation should arrive within the next 100 updates
-            for _ in 0..100 {
-                let response = self.next_response().await?;
-                match response {
-                    Response {
-                        r#type: Type::Subscribed,
-                        ..
-                    } if subscribe => {
-                        // Subscribe confirmed
-                        continue 'channels;
-                    }
-                    Response {
-                        r#type: Type::Unsubscribed,
-                        ..
-                    } if !subscribe => {
-                        // Unsubscribe confirmed
-                        continue 'channels;
-                    }
-                    _ => {
-                        // Otherwise, continue adding contents to buffer
-                        self.handle_response(response);
-                    }
+        Ok(results)
+    }
+
+    /// Fails with `Error::UnsupportedOnExchange` for channels of futures
+    /// markets on exchanges without futures
+    fn check_supported(&self, channel: &Channel) -> Result<()> {
+        let symbol = match channel {
+            Channel::Orderbook(symbol)
+            | Channel::Trades(symbol)
+            | Channel::Liquidations(symbol)
+            | Channel::Ticker(symbol)
+            | Channel::OrderbookGrouped { market: symbol, .. } => symbol,
+            Channel::Fills | Channel::Orders | Channel::Markets => return Ok(()),
+            Channel::SpotMarginRates => return Err(Error::PolledChannel(channel.clone())),
+        };
+        let future = matches!(
+            symbol.kind(),
+            SymbolKind::Perpetual | SymbolKind::Future | SymbolKind::Move
+        );
+        if future && !self.exchange.has_futures() {
+            return Err(Error::UnsupportedOnExchange(channel.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Writes `messages` to the socket and flushes them together
+    async fn send_all(&mut self, messages: Vec<Message>) -> Result<()> {
+        for message in messages {
+            self.stream.feed(message).await?;
+        }
+        self.stream.flush().await?;
+
+        Ok(())
+    }
+
+    /// Requests a fresh `partial` snapshot of the orderbook of `market` by
+    /// unsubscribing from and resubscribing to its orderbook channel, without
+    /// touching other subscriptions. Useful after `Error::ChecksumMismatch`.
+    /// The snapshot is returned by `next` like any other orderbook data.
+    pub async fn request_orderbook_snapshot(&mut self, market: &str) -> Result<()> {
+        let channel = Channel::Orderbook(market.into());
+        if !self.channels.contains(&channel) {
+            return Err(Error::NotSubscribedToThisChannel(channel));
+        }
+        self.orderbooks.remove(market);
+
+        let key = Self::pending_key(&channel);
+        self.pending.insert(key.clone(), Type::Unsubscribed);
+        let mut result = self
+            .send_all(vec![
+                Self::subscription_message(&channel, false),
+                Self::subscription_message(&channel, true),
+            ])
+            .await;
+        // Confirmations arrive in the order of the ops
+        for expected in [Type::Unsubscribed, Type::Subscribed] {
+            if result.is_ok() {
+                self.pending.insert(key.clone(), expected);
+                result = self.await_confirmations(std::slice::from_ref(&key)).await;
+            }
+            if self.pending.remove(&key).is_some() {
+                result?;
+                warn!("No confirmation received for {:?}", channel);
+                if expected == Type::Subscribed {
+                    self.channels.retain(|c| c != &channel);
                 }
+                return Err(Error::MissingSubscriptionConfirmation(channel));
             }
+        }
+
+        result
+    }
 
-            return Err(Error::MissingSubscriptionConfirmation);
+    /// Handles responses until none of the pending ops `keys` is left.
+    /// Unconfirmed ops are left pending after the subscription timeout.
+    async fn await_confirmations(&mut self, keys: &[(String, Symbol)]) -> Result<()> {
+        let deadline = time::Instant::now() + self.subscription_timeout;
+
+        while keys.iter().any(|key| self.pending.contains_key(key)) {
+            match time::timeout_at(deadline, self.next_response()).await {
+                // Resolves pending ops and adds other contents to the buffer
+                Ok(response) => self.handle_response(response?)?,
+                Err(_) => break,
+            }
         }
 
         Ok(())
     }
 
+    /// Key of a pending subscribe or unsubscribe op for `channel`
+    fn pending_key(channel: &Channel) -> (String, Symbol) {
+        let (name, market) = channel.name_and_market();
+        (name.to_string(), market.into())
+    }
+
+    /// Builds the subscribe or unsubscribe op for `channel`
+    fn subscription_message(channel: &Channel, subscribe: bool) -> Message {
+        let op = if subscribe {
+            "subscribe"
+        } else {
+            "unsubscribe"
+        };
+
+        let (name, symbol) = channel.name_and_market();
+        let mut message = json!({
+            "op": op,
+            "channel": name,
+            "market": symbol,
+        });
+        if let Channel::OrderbookGrouped { grouping, .. } = channel {
+            message["grouping"] = grouping.to_f64().into();
+        }
+
+        Message::Text(message.to_string())
+    }
+
     async fn next_response(&mut self) -> Result<Response> {
         loop {
             tokio::select! {
                 _ = self.ping_timer.tick() => {
-                    self.ping().await?;
+                    if let Err(err) = self.ping().await {
+                        self.handle_disconnect(err).await?;
+                    }
+                },
+                _ = &mut self.stale_timer => {
+                    self.handle_disconnect(Error::ConnectionStale).await?;
                 },
-                Some(msg) = self.stream.next() => {
-                    let msg = msg?;
-                    if let Message::Text(text) = msg {
-                        // println!("{}", text); // Uncomment for debugging
-                        let response: Response = serde_json::from_str(&text)?;
-
-                        // Don't return Pong responses
-                        if let Response { r#type: Type::Pong, .. } = response {
+                msg = self.stream.next() => {
+                    let msg = match msg {
+                        Some(Ok(msg)) => {
+                            self.reset_stale_timer();
+                            msg
+                        }
+                        Some(Err(err)) => {
+                            self.handle_disconnect(err.into()).await?;
                             continue;
                         }
-
-                        return Ok(response)
+                        None => {
+                            self.handle_disconnect(tungstenite::Error::ConnectionClosed.into())
+                                .await?;
+                            continue;
+                        }
+                    };
+                    if let Some(response) = self.parse_message(msg)? {
+                        return Ok(response);
                     }
                 },
             }
         }
     }
 
+    /// Deserializes a text message into a `Response`. Returns `None` for
+    /// non-text messages and pongs.
+    fn parse_message(&mut self, msg: Message) -> Result<Option<Response>> {
+        if let Message::Text(text) = msg {
+            trace!("Websocket message: {}", text);
+            if let Some(hook) = &mut self.raw_message_hook {
+                hook(&text);
+            }
+            let response: Response = match text.parse() {
+                Ok(response) => response,
+                Err(err) => {
+                    warn!("Failed to parse websocket message ({}): {}", err, text);
+                    return Err(err.into());
+                }
+            };
+
+            // Don't return Pong responses
+            if let Response {
+                r#type: Type::Pong, ..
+            } = response
+            {
+                self.latency.pong_received();
+                return Ok(None);
+            }
+
+            return Ok(Some(response));
+        }
+
+        Ok(None)
+    }
+
     /// Helper function that takes a response and adds the contents to the buffer
-    fn handle_response(&mut self, response: Response) {
+    fn handle_response(&mut self, response: Response) -> Result<()> {
+        match response.r#type {
+            Type::Error => {
+                return Err(Error::Api {
+                    code: response.code,
+                    msg: response.msg.unwrap_or_default(),
+                });
+            }
+            Type::Info => {
+                info!(
+                    "Websocket info ({:?}): {}",
+                    response.code,
+                    response.msg.as_deref().unwrap_or_default()
+                );
+            }
+            _ => {}
+        }
+
+        // Resolve the pending op matching this channel and market, if any
+        if let Type::Subscribed | Type::Unsubscribed = response.r#type {
+            let key = (
+                response.channel.clone().unwrap_or_default(),
+                response.market.clone().unwrap_or_default(),
+            );
+            if self.pending.get(&key) == Some(&response.r#type) {
+                self.pending.remove(&key);
+            }
+        }
+
         if let Some(data) = response.data {
             match data {
                 ResponseData::Trades(trades) => {
+                    let (all, liquidations) = match &response.market {
+                        Some(market) => (
+                            self.channels.contains(&Channel::Trades(market.clone())),
+                            self.channels
+                                .contains(&Channel::Liquidations(market.clone())),
+                        ),
+                        None => (true, false),
+                    };
+
                     // Trades channel returns an array of single trades.
                     // Buffer so that the user receives trades one at a time
                     for trade in trades {
-                        self.buf.push_back(Data::Trade(trade));
+                        self.latency.trade_received(trade.time);
+                        if let (Some(guard), Some(market)) =
+                            (&mut self.trade_guard, &response.market)
+                        {
+                            if let Some(issue) = guard.check(market, &trade) {
+                                let duplicate =
+                                    matches!(issue, DataIntegrity::DuplicateTrade { .. });
+                                self.buf
+                                    .push((response.market.clone(), Data::Integrity(issue)))?;
+                                if duplicate {
+                                    continue;
+                                }
+                            }
+                        }
+                        if liquidations && trade.liquidation {
+                            self.buf
+                                .push((response.market.clone(), Data::Liquidation(trade)))?;
+                        }
+                        if all || !liquidations {
+                            self.buf
+                                .push((response.market.clone(), Data::Trade(trade)))?;
+                        }
                     }
                 }
                 ResponseData::OrderbookData(orderbook) => {
-                    self.buf.push_back(Data::OrderbookData(orderbook));
+                    if let (true, Some(symbol)) = (self.verify_checksums, &response.market) {
+                        self.verify_checksum(symbol, &orderbook)?;
+                    }
+                    self.buf
+                        .push((response.market, Data::OrderbookData(orderbook)))?;
+                }
+                ResponseData::GroupedOrderbook(orderbook) => {
+                    self.buf
+                        .push((response.market, Data::GroupedOrderbook(orderbook)))?;
                 }
                 ResponseData::Fill(fill) => {
-                    self.buf.push_back(Data::Fill(fill));
+                    self.buf.push((response.market, Data::Fill(fill)))?;
+                }
+                ResponseData::Order(order) => {
+                    self.buf.push((response.market, Data::Order(order)))?;
+                }
+                ResponseData::Markets(markets) => {
+                    self.buf.push((response.market, Data::Markets(markets)))?;
+                }
+                ResponseData::Ticker(ticker) => {
+                    self.buf.push((response.market, Data::Ticker(ticker)))?;
                 }
             }
         }
+
+        Ok(())
+    }
+
+    /// Applies `data` to the local orderbook of `symbol` and verifies the result
+    /// against the checksum sent by FTX
+    fn verify_checksum(&mut self, symbol: &Symbol, data: &OrderbookData) -> Result<()> {
+        let orderbook = match data.action {
+            OrderbookAction::Partial => self
+                .orderbooks
+                .entry(symbol.clone())
+                .or_insert_with(|| Orderbook::new(symbol.clone())),
+            OrderbookAction::Update => match self.orderbooks.get_mut(symbol) {
+                Some(orderbook) => orderbook,
+                // Waiting for a new snapshot after a mismatch
+                None => return Ok(()),
+            },
+        };
+
+        orderbook.update(data);
+
+        if !orderbook.verify_checksum(data.checksum) {
+            self.orderbooks.remove(symbol);
+            let issue = DataIntegrity::OrderbookChecksumMismatch {
+                market: symbol.clone(),
+                last_valid: self.orderbooks_valid_at.get(symbol).copied(),
+            };
+            self.buf
+                .push((Some(symbol.clone()), Data::Integrity(issue)))?;
+            return Err(Error::ChecksumMismatch(symbol.clone()));
+        }
+        self.orderbooks_valid_at.insert(symbol.clone(), data.time);
+
+        Ok(())
     }
 
-    pub async fn next(&mut self) -> Result<Option<Data>> {
+    /// Returns the next data received on any subscribed channel, along with
+    /// the market it belongs to. The market is `None` for channels that are
+    /// not tied to a market, i.e. fills, orders, and markets.
+    pub async fn next(&mut self) -> Result<Option<(Option<Symbol>, Data)>> {
         loop {
             // If buffer contains data, we can directly return it.
             if let Some(data) = self.buf.pop_front() {
                 return Ok(Some(data));
             }
 
+            if self.closed {
+                return Ok(None);
+            }
+
+            if let Some(err) = self.read_ahead_failure.take() {
+                self.handle_disconnect(err).await?;
+            }
+
             // Fetch new response if buffer is empty.
             let response = self.next_response().await?;
 
             // Handle the response, possibly adding to the buffer
-            self.handle_response(response);
+            self.handle_response(response)?;
+
+            self.read_ahead(&mut Context::from_waker(noop_waker_ref()))?;
+        }
+    }
+
+    /// Handles messages that have already arrived when coalescing orderbook
+    /// updates, so that they are merged before any is returned. A failure is
+    /// kept for the next read.
+    fn read_ahead(&mut self, cx: &mut Context<'_>) -> Result<()> {
+        if !self.buf.coalesce_orderbooks || self.read_ahead_failure.is_some() {
+            return Ok(());
+        }
+
+        for _ in 0..Self::READ_AHEAD {
+            match self.stream.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(msg))) => {
+                    self.reset_stale_timer();
+                    if let Some(response) = self.parse_message(msg)? {
+                        self.handle_response(response)?;
+                    }
+                }
+                Poll::Ready(Some(Err(err))) => {
+                    self.read_ahead_failure = Some(err.into());
+                    break;
+                }
+                Poll::Ready(None) => {
+                    self.read_ahead_failure = Some(tungstenite::Error::ConnectionClosed.into());
+                    break;
+                }
+                Poll::Pending => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Ws {
+    /// Returns the next data if it is available without waiting, `Ok(None)`
+    /// otherwise. Meant for tick-driven loops that must not block. Pings,
+    /// stale connection detection, and reconnection only make progress while
+    /// this is called regularly.
+    pub fn try_next(&mut self) -> Result<Option<(Option<Symbol>, Data)>> {
+        let mut cx = Context::from_waker(noop_waker_ref());
+
+        match Pin::new(self).poll_next(&mut cx) {
+            Poll::Ready(Some(result)) => result.map(Some),
+            Poll::Ready(None) | Poll::Pending => Ok(None),
+        }
+    }
+}
+
+impl Stream for Ws {
+    type Item = Result<(Option<Symbol>, Data)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        loop {
+            // If buffer contains data, we can directly return it.
+            if let Some(data) = this.buf.pop_front() {
+                return Poll::Ready(Some(Ok(data)));
+            }
+
+            if this.closed {
+                return Poll::Ready(None);
+            }
+
+            // Finish a pending reconnection before reading from the socket
+            if let Some(reconnecting) = this.reconnecting.as_mut() {
+                match reconnecting.as_mut().poll(cx) {
+                    Poll::Ready(Ok(stream)) => {
+                        this.stream = stream;
+                        this.reconnecting = None;
+                        this.latency.reset_pings();
+                        this.reset_stale_timer();
+                    }
+                    Poll::Ready(Err(err)) => {
+                        this.reconnecting = None;
+                        return Poll::Ready(Some(Err(err)));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            let mut failure = this.read_ahead_failure.take();
+
+            if failure.is_none() && this.ping_timer.poll_tick(cx).is_ready() {
+                if let Err(err) = this.poll_ping(cx) {
+                    failure = Some(err);
+                }
+            }
+
+            if failure.is_none() && this.stale_timer.as_mut().poll(cx).is_ready() {
+                failure = Some(Error::ConnectionStale);
+            }
+
+            if failure.is_none() {
+                match this.stream.poll_next_unpin(cx) {
+                    Poll::Ready(Some(Ok(msg))) => {
+                        this.reset_stale_timer();
+                        match this.parse_message(msg) {
+                            Ok(Some(response)) => {
+                                if let Err(err) = this
+                                    .handle_response(response)
+                                    .and_then(|_| this.read_ahead(cx))
+                                {
+                                    return Poll::Ready(Some(Err(err)));
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(err) => return Poll::Ready(Some(Err(err))),
+                        }
+                    }
+                    Poll::Ready(Some(Err(err))) => failure = Some(err.into()),
+                    Poll::Ready(None) => {
+                        failure = Some(tungstenite::Error::ConnectionClosed.into())
+                    }
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            if let Some(err) = failure {
+                if !this.auto_reconnect {
+                    return Poll::Ready(Some(Err(err)));
+                }
+                this.reconnecting = Some(this.reconnect_with_retries(err));
+            }
         }
     }
 }
+
+impl Ws {
+    /// Queues a ping on the socket without waiting for it to be written.
+    /// The ping is skipped if the socket is not ready to accept a message.
+    fn poll_ping(&mut self, cx: &mut Context<'_>) -> Result<()> {
+        if let Poll::Ready(ready) = self.stream.poll_ready_unpin(cx) {
+            ready?;
+            self.stream.start_send_unpin(Message::Text(
+                json!({
+                    "op": "ping",
+                })
+                .to_string(),
+            ))?;
+            self.latency.ping_sent();
+            if let Poll::Ready(flushed) = self.stream.poll_flush_unpin(cx) {
+                flushed?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,19 +1,59 @@
-pub use crate::rest::{Coin, Id, MarketType, Side, Symbol};
+use super::DataIntegrity;
+pub use crate::rest::{
+    Coin, Fill, Id, Liquidity, MarketType, OrderInfo, Side, SpotMarginRate, Symbol, SymbolKind,
+};
 use chrono::{DateTime, Utc};
 use crc32fast::Hasher;
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
 use serde_with::{serde_as, TimestampSecondsWithFrac};
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum Channel {
     Orderbook(Symbol),
     Trades(Symbol),
+    /// Trades of a market that are liquidations, returned as
+    /// `Data::Liquidation`. Shares FTX's trades subscription with
+    /// `Channel::Trades` of the same market.
+    Liquidations(Symbol),
     Ticker(Symbol),
+    /// Orderbook of `market` with its levels grouped into price bands of
+    /// size `grouping`, returned as `Data::GroupedOrderbook`. Only one
+    /// grouping per market can be subscribed to on a connection.
+    OrderbookGrouped {
+        market: Symbol,
+        grouping: Decimal,
+    },
     Fills,
+    Orders,
+    Markets,
+    /// Spot margin borrow and lending rates, returned as
+    /// `Data::SpotMarginRates`. FTX has no such channel, the rates are polled
+    /// from the REST API by a `Client` subscribed with `Client::subscribe`.
+    SpotMarginRates,
+}
+
+impl Channel {
+    /// Returns the channel name and market as used by FTX, the market is
+    /// empty for channels that are not specific to a market
+    pub(crate) fn name_and_market(&self) -> (&'static str, &str) {
+        match self {
+            Channel::Orderbook(symbol) => ("orderbook", symbol.as_str()),
+            Channel::Trades(symbol) | Channel::Liquidations(symbol) => ("trades", symbol.as_str()),
+            Channel::Ticker(symbol) => ("ticker", symbol.as_str()),
+            Channel::OrderbookGrouped { market, .. } => ("orderbookGrouped", market.as_str()),
+            Channel::Fills => ("fills", ""),
+            Channel::Orders => ("orders", ""),
+            Channel::Markets => ("markets", ""),
+            Channel::SpotMarginRates => ("spotMarginRates", ""),
+        }
+    }
 }
 
 /*
@@ -28,12 +68,60 @@ pub struct Response {
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Response {
+    pub channel: Option<String>,
     pub market: Option<Symbol>,
     pub r#type: Type,
     pub data: Option<ResponseData>,
+    /// Only set for `error` and `info` responses
+    pub code: Option<u16>,
+    pub msg: Option<String>,
+}
+
+/// Parses a message, deserializing its data directly into the type of its
+/// channel. Faster than deserializing a `Response` with serde, which tries
+/// each `ResponseData` variant in turn.
+impl FromStr for Response {
+    type Err = serde_json::Error;
+
+    fn from_str(text: &str) -> serde_json::Result<Self> {
+        let raw: RawResponse = serde_json::from_str(text)?;
+        let mut data = match raw.data {
+            Some(data) => Some(ResponseData::parse(raw.channel.as_deref(), data.get())?),
+            None => None,
+        };
+        if let (Some(ResponseData::GroupedOrderbook(grouped)), Type::Update) =
+            (&mut data, raw.r#type)
+        {
+            grouped.action = OrderbookAction::Update;
+        }
+
+        Ok(Response {
+            channel: raw.channel.map(Cow::into_owned),
+            market: raw.market.map(|market| Symbol::from(&*market)),
+            r#type: raw.r#type,
+            data,
+            code: raw.code,
+            msg: raw.msg.map(Cow::into_owned),
+        })
+    }
+}
+
+/// `Response` borrowing from the message, with its data left unparsed
+#[derive(Deserialize)]
+struct RawResponse<'a> {
+    #[serde(borrow)]
+    channel: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    market: Option<Cow<'a, str>>,
+    r#type: Type,
+    #[serde(borrow)]
+    data: Option<&'a RawValue>,
+    code: Option<u16>,
+    #[serde(borrow)]
+    msg: Option<Cow<'a, str>>,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum Type {
     Subscribed,
@@ -42,7 +130,7 @@ pub enum Type {
     Error,
     Partial,
     Pong,
-    // Info,         // May need this in the future
+    Info,
 }
 
 /// Represents the response received from FTX, and is used for
@@ -54,17 +142,63 @@ pub enum ResponseData {
     Trades(Vec<Trade>),
     OrderbookData(OrderbookData),
     Fill(Fill),
+    Order(OrderInfo),
+    Markets(MarketsData),
+    // Must come after OrderbookData, whose fields are a superset
+    GroupedOrderbook(GroupedOrderbookData),
+    // Ticker must come last, its fields are mostly optional
+    Ticker(Ticker),
 }
 
-/// Represents the data we return to the user
-#[derive(Clone, Debug)]
+impl ResponseData {
+    /// Deserializes the data of a message of `channel`, trying each variant
+    /// in turn for unknown channels
+    fn parse(channel: Option<&str>, data: &str) -> serde_json::Result<Self> {
+        Ok(match channel {
+            Some("trades") => ResponseData::Trades(serde_json::from_str(data)?),
+            Some("orderbook") => ResponseData::OrderbookData(serde_json::from_str(data)?),
+            Some("orderbookGrouped") => ResponseData::GroupedOrderbook(serde_json::from_str(data)?),
+            Some("fills") => ResponseData::Fill(serde_json::from_str(data)?),
+            Some("orders") => ResponseData::Order(serde_json::from_str(data)?),
+            Some("markets") => ResponseData::Markets(serde_json::from_str(data)?),
+            Some("ticker") => ResponseData::Ticker(serde_json::from_str(data)?),
+            _ => serde_json::from_str(data)?,
+        })
+    }
+}
+
+/// Represents the data we return to the user. Serialized as an object with
+/// the variant name in `type` and its contents in `data`, e.g. by
+/// `sinks::RedisSink`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "camelCase")]
 pub enum Data {
     Trade(Trade),
+    /// Liquidation trade, only sent when subscribed to `Channel::Liquidations`
+    Liquidation(Trade),
     OrderbookData(OrderbookData),
+    GroupedOrderbook(GroupedOrderbookData),
     Fill(Fill),
+    Order(OrderInfo),
+    Markets(MarketsData),
+    Ticker(Ticker),
+    /// Problem detected by the `TradeGuard` set with `Ws::set_trade_guard`
+    Integrity(DataIntegrity),
+    /// Rates polled for `Channel::SpotMarginRates`
+    SpotMarginRates(SpotMarginRates),
+}
+
+/// Borrow and lending rates of all coins, see `Channel::SpotMarginRates`
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpotMarginRates {
+    pub borrow: Vec<SpotMarginRate>,
+    pub lending: Vec<SpotMarginRate>,
+    /// Time the rates were fetched
+    pub time: DateTime<Utc>,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Trade {
     pub id: Id,
@@ -75,10 +209,24 @@ pub struct Trade {
     pub time: DateTime<Utc>, // API returns "2021-05-23T05:24:24.315884+00:00"
 }
 
+/// Best bid and offer of a market, sent whenever either of them changes
+#[serde_as]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ticker {
+    pub bid: Option<Decimal>,
+    pub ask: Option<Decimal>,
+    pub bid_size: Option<Decimal>,
+    pub ask_size: Option<Decimal>,
+    pub last: Option<Decimal>,
+    #[serde_as(as = "TimestampSecondsWithFrac<f64>")]
+    pub time: DateTime<Utc>, // API returns 1621740952.5079553
+}
+
 /// Order book data received from FTX which is used for initializing and updating
 /// the OrderBook struct
 #[serde_as]
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderbookData {
     pub action: OrderbookAction,
@@ -91,9 +239,25 @@ pub struct OrderbookData {
     pub time: DateTime<Utc>, // API returns 1621740952.5079553
 }
 
-type Checksum = u32;
+pub type Checksum = u32;
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+/// Snapshot or update of an orderbook grouped into price bands, see
+/// `Channel::OrderbookGrouped`. Updates only contain the changed bands, with
+/// a size of zero for removed ones, and there is no checksum to verify.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GroupedOrderbookData {
+    /// Not part of the data but of the message, set when it is parsed
+    #[serde(skip_deserializing, default = "partial")]
+    pub action: OrderbookAction,
+    pub bids: Vec<(Decimal, Decimal)>,
+    pub asks: Vec<(Decimal, Decimal)>,
+}
+
+fn partial() -> OrderbookAction {
+    OrderbookAction::Partial
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum OrderbookAction {
     /// Initial snapshot of the orderbook
@@ -105,7 +269,7 @@ pub enum OrderbookAction {
 /// Represents the current state of the orderbook, guaranteed to be accurate
 /// up to the best 100 bids and best 100 asks since the latest update.
 /// Supports efficient insertions, updates, and deletions via a BTreeMap.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Orderbook {
     pub symbol: Symbol,
     pub bids: BTreeMap<Decimal, Decimal>,
@@ -121,24 +285,42 @@ impl Orderbook {
     }
 
     pub fn update(&mut self, data: &OrderbookData) {
-        match data.action {
+        self.apply(data.action, &data.bids, &data.asks);
+    }
+
+    /// Applies data of a grouped orderbook, see `Channel::OrderbookGrouped`.
+    /// Checksums don't apply to grouped books.
+    pub fn update_grouped(&mut self, data: &GroupedOrderbookData) {
+        self.apply(data.action, &data.bids, &data.asks);
+    }
+
+    fn apply(
+        &mut self,
+        action: OrderbookAction,
+        bids: &[(Decimal, Decimal)],
+        asks: &[(Decimal, Decimal)],
+    ) {
+        match action {
             OrderbookAction::Partial => {
-                for bid in &data.bids {
+                // A snapshot replaces the previous state of the book
+                self.bids.clear();
+                self.asks.clear();
+                for bid in bids {
                     self.bids.insert(bid.0, bid.1);
                 }
-                for ask in &data.asks {
+                for ask in asks {
                     self.asks.insert(ask.0, ask.1);
                 }
             }
             OrderbookAction::Update => {
-                for bid in &data.bids {
+                for bid in bids {
                     if bid.1 == dec!(0) {
                         self.bids.remove(&bid.0);
                     } else {
                         self.bids.insert(bid.0, bid.1);
                     }
                 }
-                for ask in &data.asks {
+                for ask in asks {
                     if ask.1 == dec!(0) {
                         self.asks.remove(&ask.0);
                     } else {
@@ -160,6 +342,12 @@ impl Orderbook {
     }
 
     pub fn verify_checksum(&self, checksum: Checksum) -> bool {
+        self.checksum() == checksum
+    }
+
+    /// Computes the CRC32 checksum of the best 100 bids and asks as specified
+    /// by FTX
+    pub fn checksum(&self) -> Checksum {
         let mut input: Vec<String> = Vec::new();
 
         let mut bids_iter = self.bids.iter().rev();
@@ -187,15 +375,12 @@ impl Orderbook {
 
         let mut hasher = Hasher::new();
         hasher.update(input);
-        let output = hasher.finalize();
-
-        // println!("Output: {}, Checksum: {}", output, checksum);
-        output == checksum
+        hasher.finalize()
     }
 
     /// Returns the price of the best bid
     pub fn bid_price(&self) -> Option<Decimal> {
-        self.bids.keys().rev().next().cloned()
+        self.bids.keys().next_back().cloned()
     }
 
     /// Returns the price of the best ask
@@ -209,10 +394,15 @@ impl Orderbook {
         Some((self.bid_price()? + self.ask_price()?) / dec!(2))
     }
 
+    /// Returns the difference between the best ask price and best bid price
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.ask_price()? - self.bid_price()?)
+    }
+
     /// Returns the price and quantity of the best bid
     /// (bid_price, bid_quantity)
     pub fn best_bid(&self) -> Option<(Decimal, Decimal)> {
-        let (price, quantity) = self.bids.iter().rev().next()?;
+        let (price, quantity) = self.bids.iter().next_back()?;
 
         Some((*price, *quantity))
     }
@@ -231,80 +421,188 @@ impl Orderbook {
         Some((self.best_bid()?, self.best_ask()?))
     }
 
+    /// Returns up to `levels` bids in best order, i.e. highest to lowest price
+    /// [(bid_price, bid_quantity), ...]
+    pub fn top_bids(&self, levels: usize) -> Vec<(Decimal, Decimal)> {
+        self.bids
+            .iter()
+            .rev()
+            .take(levels)
+            .map(|(price, quantity)| (*price, *quantity))
+            .collect()
+    }
+
+    /// Returns up to `levels` asks in best order, i.e. lowest to highest price
+    /// [(ask_price, ask_quantity), ...]
+    pub fn top_asks(&self, levels: usize) -> Vec<(Decimal, Decimal)> {
+        self.asks
+            .iter()
+            .take(levels)
+            .map(|(price, quantity)| (*price, *quantity))
+            .collect()
+    }
+
+    /// Returns the total quantity of the best `levels` price levels on `side`,
+    /// where `Side::Buy` refers to the bids and `Side::Sell` to the asks
+    pub fn depth(&self, side: Side, levels: usize) -> Decimal {
+        let levels = match side {
+            Side::Buy => self.top_bids(levels),
+            Side::Sell => self.top_asks(levels),
+        };
+
+        levels.iter().map(|(_, quantity)| quantity).sum()
+    }
+
     /// Returns the expected execution price of a market order given the current
     /// orders in the order book. Returns None if the order size exceeds the
     /// liquidity available on that side of the order book.
     pub fn quote(&self, side: Side, quantity: Decimal) -> Option<Decimal> {
-        // Step 1: Match with orders in the book
-        let mut bids_iter = self.bids.iter().rev();
-        let mut asks_iter = self.asks.iter();
+        self.quote_size(side, quantity)
+            .filter(|quote| quote.sufficient)
+            .map(|quote| quote.price)
+    }
 
-        let mut fills: Vec<(Decimal, Decimal)> = Vec::new(); // (price, quantity)
-        let mut remaining = quantity;
-
-        while remaining > dec!(0) {
-            let (price, quantity) = match side {
-                Side::Buy => asks_iter.next()?,
-                Side::Sell => bids_iter.next()?,
-            };
-
-            if *quantity <= remaining {
-                remaining -= quantity;
-                fills.push((*price, *quantity));
-            } else {
-                fills.push((*price, remaining));
-                remaining = dec!(0);
+    /// Walks the book to estimate the execution of a market order of
+    /// `quantity`, in the base currency. Returns None if there are no orders
+    /// on the opposite side of the book.
+    pub fn quote_size(&self, side: Side, quantity: Decimal) -> Option<Quote> {
+        self.walk(side, |_, size, _| quantity - size)
+    }
+
+    /// Walks the book to estimate the execution of a market order worth
+    /// `notional` in the quote currency, e.g. USD. Returns None if there are
+    /// no orders on the opposite side of the book.
+    pub fn quote_notional(&self, side: Side, notional: Decimal) -> Option<Quote> {
+        self.walk(side, |price, _, filled| (notional - filled) / price)
+    }
+
+    /// Matches a taker order on `side` against the levels of the opposite
+    /// side, best first. `remaining` returns the quantity still to fill at a
+    /// price, given the size and notional filled so far.
+    fn walk<F>(&self, side: Side, remaining: F) -> Option<Quote>
+    where
+        F: Fn(Decimal, Decimal, Decimal) -> Decimal,
+    {
+        let levels: Box<dyn Iterator<Item = (&Decimal, &Decimal)>> = match side {
+            Side::Buy => Box::new(self.asks.iter()),
+            Side::Sell => Box::new(self.bids.iter().rev()),
+        };
+        let mut levels = levels.peekable();
+        let best_price = *levels.peek()?.0;
+
+        let mut size = dec!(0);
+        let mut notional = dec!(0);
+        let mut worst_price = best_price;
+        for (price, available) in levels {
+            let filled = remaining(*price, size, notional).min(*available);
+            if filled <= dec!(0) {
+                break;
             }
+            size += filled;
+            notional += price * filled;
+            worst_price = *price;
         }
+        let sufficient = remaining(worst_price, size, notional) <= dec!(0);
 
-        // Step 2: Compute the weighted average
-        let mut dot_product = dec!(0);
-        for (fill_price, fill_quantity) in fills.iter() {
-            dot_product += fill_price * fill_quantity;
-        }
+        let price = if size > dec!(0) {
+            notional / size
+        } else {
+            best_price
+        };
+        let slippage = match side {
+            Side::Buy => price - best_price,
+            Side::Sell => best_price - price,
+        };
+
+        Some(Quote {
+            price,
+            size,
+            notional,
+            worst_price,
+            slippage_bps: slippage / best_price * dec!(10000),
+            sufficient,
+        })
+    }
 
-        Some(dot_product / quantity)
+    /// Returns the cumulative size and notional of the orders on `side`
+    /// within each of `bands`, given in basis points from the mid price.
+    /// `Side::Buy` refers to the bids and `Side::Sell` to the asks. Returns
+    /// None unless both sides of the book have orders.
+    pub fn depth_bands(&self, side: Side, bands: &[Decimal]) -> Option<Vec<DepthBand>> {
+        let mid_price = self.mid_price()?;
+
+        let bands = bands
+            .iter()
+            .map(|bps| {
+                let offset = mid_price * bps / dec!(10000);
+                let levels: Vec<(&Decimal, &Decimal)> = match side {
+                    Side::Buy => self.bids.range(mid_price - offset..).collect(),
+                    Side::Sell => self.asks.range(..=mid_price + offset).collect(),
+                };
+
+                DepthBand {
+                    bps: *bps,
+                    size: levels.iter().map(|(_, quantity)| **quantity).sum(),
+                    notional: levels
+                        .iter()
+                        .map(|(price, quantity)| **price * **quantity)
+                        .sum(),
+                }
+            })
+            .collect();
+
+        Some(bands)
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Fill {
-    pub id: Id,
-    pub market: Symbol,
-    pub future: Option<Symbol>,
-    pub base_currency: Option<Coin>,
-    pub quote_currency: Option<Coin>,
-    pub r#type: String, // e.g. "order"
-    pub side: Side,
+/// Estimated execution of a market order, returned by
+/// `Orderbook::quote_size` and `Orderbook::quote_notional`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Quote {
+    /// Average execution price
     pub price: Decimal,
+    /// Size filled, less than requested if the liquidity is insufficient
     pub size: Decimal,
-    pub order_id: Id,
-    pub trade_id: Id,
-    pub time: DateTime<Utc>,
-    pub fee: Decimal,
-    pub fee_rate: Decimal,
-    pub fee_currency: Coin,
-    pub liquidity: Liquidity,
+    /// Size times price of all fills, in the quote currency
+    pub notional: Decimal,
+    /// Price of the last level the order reaches
+    pub worst_price: Decimal,
+    /// Difference between the average and the best price in basis points,
+    /// positive if the average is worse
+    pub slippage_bps: Decimal,
+    /// Whether the book holds enough liquidity to fill the whole order
+    pub sufficient: bool,
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+/// Cumulative depth within a distance from the mid price, returned by
+/// `Orderbook::depth_bands`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DepthBand {
+    /// Distance from the mid price in basis points
+    pub bps: Decimal,
+    /// Total size of the orders within the band
+    pub size: Decimal,
+    /// Total size times price of the orders within the band
+    pub notional: Decimal,
+}
+
+/// Metadata of all markets, broadcast by FTX every 60 seconds
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub enum Liquidity {
-    Maker,
-    Taker,
+pub struct MarketsData {
+    pub data: HashMap<Symbol, Market>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Market {
-    name: Symbol,
-    enabled: bool,
-    price_increment: Decimal,
-    size_increment: Decimal,
+    pub name: Symbol,
+    pub enabled: bool,
+    pub price_increment: Decimal,
+    pub size_increment: Decimal,
     #[serde(rename = "type")]
     pub market_type: MarketType,
-    base_currency: Option<Coin>,
-    quote_currency: Option<Coin>,
-    underlying: Option<Coin>,
+    pub base_currency: Option<Coin>,
+    pub quote_currency: Option<Coin>,
+    pub underlying: Option<Coin>,
 }
@@ -0,0 +1,179 @@
+use super::{
+    Channel, Data, DataIntegrity, Orderbook, OrderbookAction, OrderbookData, Result, Symbol, Ws,
+    WsBuilder, WsPool,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+/// Read handle to the orderbooks maintained by an `OrderbookManager`. Cheap
+/// to clone and send to other threads, reads only block while the book of
+/// the same market is being updated.
+#[derive(Clone, Debug)]
+pub struct OrderbookHandle {
+    books: Arc<HashMap<Symbol, RwLock<Orderbook>>>,
+}
+
+impl OrderbookHandle {
+    /// Markets whose orderbooks are maintained
+    pub fn markets(&self) -> impl Iterator<Item = &Symbol> {
+        self.books.keys()
+    }
+
+    /// Locks the orderbook of `market` for reading. The book is empty until
+    /// the first snapshot is received, and again while it is resynchronized
+    /// after a checksum mismatch.
+    pub fn get(&self, market: &str) -> Option<RwLockReadGuard<'_, Orderbook>> {
+        Some(self.books.get(market)?.read().unwrap())
+    }
+
+    /// Returns a copy of the orderbook of `market`
+    pub fn snapshot(&self, market: &str) -> Option<Orderbook> {
+        Some(self.get(market)?.clone())
+    }
+}
+
+/// Maintains checksum-verified orderbooks of many markets, spread over one or
+/// more websocket connections by a `WsPool`. Books are read from other tasks
+/// or threads through an `OrderbookHandle`.
+///
+/// ```no_run
+/// # async fn example() -> ftx::ws::Result<()> {
+/// use ftx::ws::OrderbookManager;
+///
+/// let mut manager =
+///     OrderbookManager::connect(vec!["BTC-PERP".into(), "ETH-PERP".into()]).await?;
+///
+/// let handle = manager.handle();
+/// std::thread::spawn(move || loop {
+///     if let Some(book) = handle.get("BTC-PERP") {
+///         println!("{:?}", book.mid_price());
+///     }
+/// });
+///
+/// manager.run().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct OrderbookManager {
+    pool: WsPool,
+    handle: OrderbookHandle,
+    /// Time of the last update of each book matching its checksum
+    valid_at: HashMap<Symbol, DateTime<Utc>>,
+    integrity_hook: Option<IntegrityHook>,
+}
+
+/// Callback receiving the problems detected by an `OrderbookManager`
+pub type IntegrityHook = Box<dyn FnMut(&DataIntegrity) + Send>;
+
+impl OrderbookManager {
+    /// Connects to `Ws::ENDPOINT` and subscribes to the orderbooks of
+    /// `markets`
+    pub async fn connect(markets: Vec<Symbol>) -> Result<Self> {
+        Self::connect_with(markets, WsPool::CHANNELS_PER_CONNECTION, Ws::builder).await
+    }
+
+    /// Opens a connection built by `builder` for every `markets_per_connection`
+    /// markets and subscribes to their orderbooks
+    pub async fn connect_with<F>(
+        markets: Vec<Symbol>,
+        markets_per_connection: usize,
+        builder: F,
+    ) -> Result<Self>
+    where
+        F: Fn() -> WsBuilder + Send + Sync + 'static,
+    {
+        // Checksums are verified here, `Ws` would keep a second copy
+        let mut pool = WsPool::new(move || builder().verify_checksums(false));
+        pool.set_channels_per_connection(markets_per_connection);
+        pool.subscribe(markets.iter().cloned().map(Channel::Orderbook).collect())
+            .await?;
+
+        let books = markets
+            .into_iter()
+            .map(|market| (market.clone(), RwLock::new(Orderbook::new(market))))
+            .collect();
+
+        Ok(Self {
+            pool,
+            handle: OrderbookHandle {
+                books: Arc::new(books),
+            },
+            valid_at: HashMap::new(),
+            integrity_hook: None,
+        })
+    }
+
+    /// Set a callback called with every problem detected, e.g. to alert a
+    /// monitoring system. Affected books are resynchronized regardless.
+    pub fn set_integrity_hook<F: FnMut(&DataIntegrity) + Send + 'static>(&mut self, hook: F) {
+        self.integrity_hook = Some(Box::new(hook));
+    }
+
+    pub fn handle(&self) -> OrderbookHandle {
+        self.handle.clone()
+    }
+
+    /// Applies the next orderbook update received on any connection and
+    /// returns its market, or `None` once all connections are closed. A book
+    /// failing checksum verification is cleared, reported to the integrity
+    /// hook, and a new snapshot requested.
+    pub async fn next(&mut self) -> Result<Option<Symbol>> {
+        loop {
+            let (market, data) = match self.pool.next().await? {
+                Some((Some(market), Data::OrderbookData(data))) => (market, data),
+                Some(_) => continue,
+                None => return Ok(None),
+            };
+
+            if self.apply(&market, &data) {
+                return Ok(Some(market));
+            }
+
+            warn!("Orderbook checksum mismatch for {}, resubscribing", market);
+            if let Some(hook) = &mut self.integrity_hook {
+                hook(&DataIntegrity::OrderbookChecksumMismatch {
+                    last_valid: self.valid_at.get(&market).copied(),
+                    market: market.clone(),
+                });
+            }
+            self.pool.request_orderbook_snapshot(&market).await?;
+        }
+    }
+
+    /// Applies updates until all connections are closed
+    pub async fn run(&mut self) -> Result<()> {
+        while self.next().await?.is_some() {}
+
+        Ok(())
+    }
+
+    /// Closes all connections
+    pub async fn close(&mut self) -> Result<()> {
+        self.pool.close().await
+    }
+
+    /// Applies `data` to the book of `market`, returns whether its checksum
+    /// matches. Updates received before the first snapshot are ignored.
+    fn apply(&mut self, market: &Symbol, data: &OrderbookData) -> bool {
+        let mut book = match self.handle.books.get(market) {
+            Some(book) => book.write().unwrap(),
+            None => return true,
+        };
+
+        let synced = !book.bids.is_empty() || !book.asks.is_empty();
+        if data.action == OrderbookAction::Update && !synced {
+            return true;
+        }
+
+        book.update(data);
+        if book.verify_checksum(data.checksum) {
+            self.valid_at.insert(market.clone(), data.time);
+            return true;
+        }
+
+        book.bids.clear();
+        book.asks.clear();
+        false
+    }
+}
@@ -0,0 +1,245 @@
+use super::{Channel, Data, DataIntegrity, Error, Result, Symbol, Ws, WsBuilder};
+use futures_util::future::poll_fn;
+use futures_util::StreamExt;
+use std::task::Poll;
+
+type Builder = Box<dyn Fn() -> WsBuilder + Send + Sync>;
+
+/// Spreads subscriptions over as many websocket connections as needed to
+/// stay below a number of channels per connection, and merges their data
+/// into a single stream. Channels of a connection that fails beyond its
+/// own reconnection attempts are moved to the remaining connections.
+///
+/// ```no_run
+/// # async fn example() -> ftx::ws::Result<()> {
+/// use ftx::ws::{Channel, Ws, WsPool};
+///
+/// let mut pool = WsPool::new(Ws::builder);
+/// pool.subscribe(vec![
+///     Channel::Trades("BTC-PERP".into()),
+///     Channel::Trades("ETH-PERP".into()),
+/// ])
+/// .await?;
+///
+/// while let Some((market, data)) = pool.next().await? {
+///     println!("{:?}: {:?}", market, data);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct WsPool {
+    /// Configures every connection opened by the pool
+    builder: Builder,
+    connections: Vec<Ws>,
+    channels_per_connection: usize,
+    /// Connection polled first, rotated so that none is starved
+    next_connection: usize,
+}
+
+impl WsPool {
+    /// Default maximum number of channels subscribed to on a connection
+    pub const CHANNELS_PER_CONNECTION: usize = 25;
+
+    /// Creates a pool opening connections configured by `builder`, e.g. to
+    /// set credentials or the endpoint. No connection is opened until
+    /// channels are subscribed to.
+    pub fn new<F>(builder: F) -> Self
+    where
+        F: Fn() -> WsBuilder + Send + Sync + 'static,
+    {
+        Self {
+            builder: Box::new(builder),
+            connections: Vec::new(),
+            channels_per_connection: Self::CHANNELS_PER_CONNECTION,
+            next_connection: 0,
+        }
+    }
+
+    /// Set the maximum number of channels subscribed to on a connection,
+    /// applied to subsequent subscriptions
+    pub fn set_channels_per_connection(&mut self, channels: usize) {
+        self.channels_per_connection = channels.max(1);
+    }
+
+    /// Number of open connections
+    pub fn connections(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Channels subscribed to on all connections
+    pub fn channels(&self) -> Vec<Channel> {
+        self.connections
+            .iter()
+            .flat_map(|ws| ws.channels.iter().cloned())
+            .collect()
+    }
+
+    /// Subscribe to `channels`, filling the least busy connections first and
+    /// opening new ones when all are full
+    pub async fn subscribe(&mut self, channels: Vec<Channel>) -> Result<()> {
+        for channel in channels {
+            if self.connection_of(&channel).is_some() {
+                continue;
+            }
+            let index = self.assign(&channel).await?;
+            self.connections[index].subscribe(vec![channel]).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Unsubscribe from `channels` on the connections they are subscribed on.
+    /// Connections left without channels are closed.
+    pub async fn unsubscribe(&mut self, channels: Vec<Channel>) -> Result<()> {
+        for channel in channels {
+            let index = self
+                .connection_of(&channel)
+                .ok_or_else(|| Error::NotSubscribedToThisChannel(channel.clone()))?;
+            self.connections[index].unsubscribe(vec![channel]).await?;
+
+            if self.connections[index].channels.is_empty() {
+                self.connections.remove(index).close().await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Requests a fresh orderbook snapshot of `market` on the connection it
+    /// is subscribed on, see `Ws::request_orderbook_snapshot`
+    pub async fn request_orderbook_snapshot(&mut self, market: &str) -> Result<()> {
+        let channel = Channel::Orderbook(market.into());
+        let index = self
+            .connection_of(&channel)
+            .ok_or(Error::NotSubscribedToThisChannel(channel))?;
+        self.connections[index]
+            .request_orderbook_snapshot(market)
+            .await
+    }
+
+    /// Returns the next data received on any connection, along with the
+    /// market it belongs to. Returns `None` once all connections are closed.
+    pub async fn next(&mut self) -> Result<Option<(Option<Symbol>, Data)>> {
+        loop {
+            let (index, item) = match self.poll_connections().await {
+                Some(next) => next,
+                None => return Ok(None),
+            };
+
+            match item {
+                Ok((market, data)) => {
+                    if Self::is_subscribed(&self.connections[index], &market, &data) {
+                        return Ok(Some((market, data)));
+                    }
+                }
+                // The connection could not be re-established
+                Err(err @ Error::Tungstenite(_)) | Err(err @ Error::ConnectionStale) => {
+                    warn!("Websocket connection failed, rebalancing: {:?}", err);
+                    self.rebalance(index).await?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Closes all connections
+    pub async fn close(&mut self) -> Result<()> {
+        for ws in &mut self.connections {
+            ws.close().await?;
+        }
+
+        Ok(())
+    }
+
+    fn connection_of(&self, channel: &Channel) -> Option<usize> {
+        self.connections
+            .iter()
+            .position(|ws| ws.channels.contains(channel))
+    }
+
+    /// Returns the connection to subscribe to `channel` on, opening a new
+    /// one if all are full. Channels sharing a subscription with one already
+    /// subscribed to, e.g. trades and liquidations, use the same connection.
+    async fn assign(&mut self, channel: &Channel) -> Result<usize> {
+        let shared = self.connections.iter().position(|ws| {
+            ws.channels
+                .iter()
+                .any(|c| c.name_and_market() == channel.name_and_market())
+        });
+        if let Some(index) = shared {
+            return Ok(index);
+        }
+
+        let least_busy = self
+            .connections
+            .iter()
+            .enumerate()
+            .filter(|(_, ws)| ws.channels.len() < self.channels_per_connection)
+            .min_by_key(|(_, ws)| ws.channels.len())
+            .map(|(index, _)| index);
+        if let Some(index) = least_busy {
+            return Ok(index);
+        }
+
+        let ws = (self.builder)().connect().await?;
+        debug!("Opened websocket connection {}", self.connections.len());
+        self.connections.push(ws);
+        Ok(self.connections.len() - 1)
+    }
+
+    /// Drops the connection at `index` and subscribes to its channels on
+    /// the others
+    async fn rebalance(&mut self, index: usize) -> Result<()> {
+        let ws = self.connections.remove(index);
+        self.subscribe(ws.channels.clone()).await
+    }
+
+    /// Whether `data` belongs to a channel subscribed on `ws`. Filters data
+    /// of other connections' markets, e.g. sent by test servers.
+    fn is_subscribed(ws: &Ws, market: &Option<Symbol>, data: &Data) -> bool {
+        let name = match data {
+            Data::Trade(_) | Data::Liquidation(_) => "trades",
+            Data::OrderbookData(_)
+            | Data::Integrity(DataIntegrity::OrderbookChecksumMismatch { .. }) => "orderbook",
+            Data::Integrity(_) => "trades",
+            Data::GroupedOrderbook(_) => "orderbookGrouped",
+            Data::Ticker(_) => "ticker",
+            Data::Fill(_) => "fills",
+            Data::Order(_) => "orders",
+            Data::Markets(_) => "markets",
+            Data::SpotMarginRates(_) => "spotMarginRates",
+        };
+        let market = market.as_ref().map(Symbol::as_str).unwrap_or_default();
+
+        ws.channels
+            .iter()
+            .any(|channel| channel.name_and_market() == (name, market))
+    }
+
+    /// Waits for the next item of any connection, along with its index
+    async fn poll_connections(&mut self) -> Option<(usize, Result<(Option<Symbol>, Data)>)> {
+        let start = self.next_connection;
+        self.next_connection = (start + 1) % self.connections.len().max(1);
+        let connections = &mut self.connections;
+
+        poll_fn(|cx| {
+            let count = connections.len();
+            let mut open = 0;
+            for offset in 0..count {
+                let index = (start + offset) % count;
+                match connections[index].poll_next_unpin(cx) {
+                    Poll::Ready(Some(item)) => return Poll::Ready(Some((index, item))),
+                    Poll::Ready(None) => {}
+                    Poll::Pending => open += 1,
+                }
+            }
+
+            if open == 0 {
+                Poll::Ready(None)
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
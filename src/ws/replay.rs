@@ -0,0 +1,193 @@
+//! Records websocket sessions to newline-delimited JSON and replays them
+//! through the same `Data` API as `Ws`, e.g. for backtesting or
+//! deterministic tests.
+//!
+//! ```no_run
+//! # async fn example() -> ftx::ws::Result<()> {
+//! use ftx::ws::replay::{Recorder, Replay};
+//! use ftx::ws::{Channel, Ws};
+//!
+//! // Record
+//! let mut ws = Ws::builder()
+//!     .raw_message_hook(Recorder::create("session.ndjson")?.into_hook())
+//!     .connect()
+//!     .await?;
+//! ws.subscribe(vec![Channel::Trades("BTC-PERP".into())]).await?;
+//!
+//! // Replay at ten times the original speed
+//! let mut replay = Replay::open("session.ndjson")?;
+//! replay.set_speed(10.0);
+//! while let Some((market, data)) = replay.next().await? {
+//!     println!("{:?}: {:?}", market, data);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use super::{Data, Error, Response, ResponseData, Result, Symbol, Type};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+use tokio::time::{self, Duration, Instant};
+
+/// A single line of a recorded session
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+    /// Milliseconds since the Unix epoch when the message was received
+    pub time: i64,
+    /// Text of the message as sent by FTX
+    pub message: String,
+}
+
+/// Writes received messages as `Record`s, one per line
+pub struct Recorder<W: Write> {
+    writer: W,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Creates or truncates the file at `path`
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes `message` along with the current time
+    pub fn record(&mut self, message: &str) -> Result<()> {
+        let record = Record {
+            time: Utc::now().timestamp_millis(),
+            message: message.to_string(),
+        };
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send + 'static> Recorder<W> {
+    /// Returns a hook for `Ws::set_raw_message_hook` recording every message.
+    /// Write errors are logged, the session continues regardless.
+    pub fn into_hook(mut self) -> impl FnMut(&str) + Send + 'static {
+        move |message| {
+            if let Err(err) = self.record(message) {
+                warn!("Failed to record websocket message: {:?}", err);
+            }
+        }
+    }
+}
+
+/// Replays a recorded session, returning the same data as `Ws::next`
+pub struct Replay<R: BufRead> {
+    lines: Lines<R>,
+    buf: VecDeque<(Option<Symbol>, Data)>,
+    speed: f64,
+    /// Time of the first record and when it was replayed
+    start: Option<(i64, Instant)>,
+}
+
+impl Replay<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> Replay<R> {
+    /// Replays the records read from `reader` at their original speed
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            buf: VecDeque::new(),
+            speed: 1.0,
+            start: None,
+        }
+    }
+
+    /// Set the replay speed relative to the recording, e.g. `2.0` replays
+    /// twice as fast. `f64::INFINITY` replays without any delay.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    /// Returns the next data of the session, waiting until it is due, or
+    /// `None` at the end of the session
+    pub async fn next(&mut self) -> Result<Option<(Option<Symbol>, Data)>> {
+        loop {
+            if let Some(data) = self.buf.pop_front() {
+                return Ok(Some(data));
+            }
+
+            let line = match self.lines.next() {
+                Some(line) => line?,
+                None => return Ok(None),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Record = serde_json::from_str(&line)?;
+
+            self.wait_until_due(record.time).await;
+            self.handle_message(&record.message)?;
+        }
+    }
+
+    async fn wait_until_due(&mut self, time: i64) {
+        let (first, started) = *self.start.get_or_insert((time, Instant::now()));
+
+        if self.speed.is_finite() && self.speed > 0.0 {
+            let offset = (time - first).max(0) as f64 / 1000.0 / self.speed;
+            time::sleep_until(started + Duration::from_secs_f64(offset)).await;
+        }
+    }
+
+    /// Adds the data of a recorded message to the buffer
+    fn handle_message(&mut self, message: &str) -> Result<()> {
+        let response: Response = message.parse()?;
+
+        if response.r#type == Type::Error {
+            return Err(Error::Api {
+                code: response.code,
+                msg: response.msg.unwrap_or_default(),
+            });
+        }
+
+        let market = response.market;
+        match response.data {
+            Some(ResponseData::Trades(trades)) => {
+                for trade in trades {
+                    self.buf.push_back((market.clone(), Data::Trade(trade)));
+                }
+            }
+            Some(ResponseData::OrderbookData(orderbook)) => {
+                self.buf.push_back((market, Data::OrderbookData(orderbook)));
+            }
+            Some(ResponseData::GroupedOrderbook(orderbook)) => {
+                self.buf
+                    .push_back((market, Data::GroupedOrderbook(orderbook)));
+            }
+            Some(ResponseData::Fill(fill)) => self.buf.push_back((market, Data::Fill(fill))),
+            Some(ResponseData::Order(order)) => self.buf.push_back((market, Data::Order(order))),
+            Some(ResponseData::Markets(markets)) => {
+                self.buf.push_back((market, Data::Markets(markets)));
+            }
+            Some(ResponseData::Ticker(ticker)) => {
+                self.buf.push_back((market, Data::Ticker(ticker)));
+            }
+            None => {}
+        }
+
+        Ok(())
+    }
+}
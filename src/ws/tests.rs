@@ -1,8 +1,11 @@
 use super::*;
-use crate::rest::Rest;
+use crate::rest::{OrderStatus, Rest};
 use dotenv::dotenv;
 use rust_decimal_macros::dec;
 use std::env::var;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::WebSocketStream;
 
 async fn init_ws() -> Ws {
     dotenv().ok();
@@ -32,19 +35,19 @@ async fn subscribe_unsubscribe() {
 
     // Channels: BTC, ETH
     ws.subscribe(vec![
-        Channel::Trades("BTC-PERP".to_owned()),
-        Channel::Trades("ETH-PERP".to_owned()),
+        Channel::Trades("BTC-PERP".into()),
+        Channel::Trades("ETH-PERP".into()),
     ])
     .await
     .expect("Subscribe failed");
 
     // Channels: BTC
-    ws.unsubscribe(vec![Channel::Trades("ETH-PERP".to_owned())])
+    ws.unsubscribe(vec![Channel::Trades("ETH-PERP".into())])
         .await
         .expect("Unsubscribe failed");
 
     // Channels: BTC, LTC
-    ws.subscribe(vec![Channel::Trades("LTC-PERP".to_owned())])
+    ws.subscribe(vec![Channel::Trades("LTC-PERP".into())])
         .await
         .expect("Subscribe failed");
 
@@ -56,23 +59,151 @@ async fn subscribe_unsubscribe() {
 async fn trades() {
     let mut ws = init_ws().await;
 
-    ws.subscribe(vec![Channel::Trades("BTC-PERP".to_owned())])
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
         .await
         .expect("Subscription failed.");
 
     match ws.next().await.unwrap() {
-        Some(Data::Trade(..)) => {}
+        Some((_, Data::Trade(..))) => {}
         _ => panic!("Trade data expected."),
     }
 
     ws.unsubscribe_all().await.expect("Unsubscribe failed");
 }
 
+#[tokio::test]
+async fn ticker() {
+    let mut ws = init_ws().await;
+
+    ws.subscribe(vec![Channel::Ticker("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    match ws.next().await.unwrap() {
+        Some((_, Data::Ticker(..))) => {}
+        _ => panic!("Ticker data expected."),
+    }
+
+    ws.unsubscribe_all().await.expect("Unsubscribe failed");
+}
+
+#[tokio::test]
+async fn ticker_deserialization() {
+    // Sanitized message from FTX API.
+    let json = r#"{
+        "channel": "ticker",
+        "market": "BTC-PERP",
+        "type": "update",
+        "data": {
+            "bid": 37010.0,
+            "ask": 37011.0,
+            "bidSize": 1.2345,
+            "askSize": 0.0123,
+            "last": 37010.0,
+            "time": 1621740952.5079553
+        }
+    }"#;
+
+    match serde_json::from_str::<Response>(json).unwrap().data {
+        Some(ResponseData::Ticker(ticker)) => {
+            assert_eq!(ticker.bid, Some(dec!(37010)));
+            assert_eq!(ticker.ask_size, Some(dec!(0.0123)));
+        }
+        _ => panic!("Ticker data expected."),
+    }
+}
+
+#[tokio::test]
+async fn trade_decimal_precision() {
+    let json = r#"{
+        "channel": "trades",
+        "market": "SHIB-PERP",
+        "type": "update",
+        "data": [{
+            "id": 1,
+            "price": 0.1,
+            "size": 0.2,
+            "side": "buy",
+            "liquidation": false,
+            "time": "2021-05-23T05:24:24.315884+00:00"
+        }]
+    }"#;
+
+    match serde_json::from_str::<Response>(json).unwrap().data {
+        Some(ResponseData::Trades(trades)) => {
+            // Exact, unlike 0.1 + 0.2 with floats
+            assert_eq!(trades[0].price + trades[0].size, dec!(0.3));
+        }
+        _ => panic!("Trade data expected."),
+    }
+}
+
+#[tokio::test]
+async fn markets() {
+    let mut ws = init_ws().await;
+
+    ws.subscribe(vec![Channel::Markets])
+        .await
+        .expect("Subscription failed.");
+
+    match ws.next().await.unwrap() {
+        Some((_, Data::Markets(markets))) => assert!(markets.data.contains_key("BTC-PERP")),
+        _ => panic!("Markets data expected."),
+    }
+
+    ws.unsubscribe_all().await.expect("Unsubscribe failed");
+}
+
+#[tokio::test]
+async fn markets_deserialization() {
+    // Sanitized message from FTX API.
+    let json = r#"{
+        "channel": "markets",
+        "type": "partial",
+        "data": {
+            "data": {
+                "BTC-PERP": {
+                    "name": "BTC-PERP",
+                    "enabled": true,
+                    "priceIncrement": 1.0,
+                    "sizeIncrement": 0.0001,
+                    "type": "future",
+                    "baseCurrency": null,
+                    "quoteCurrency": null,
+                    "restricted": false,
+                    "underlying": "BTC"
+                },
+                "ETH/BTC": {
+                    "name": "ETH/BTC",
+                    "enabled": true,
+                    "priceIncrement": 2.5e-6,
+                    "sizeIncrement": 0.001,
+                    "type": "spot",
+                    "baseCurrency": "ETH",
+                    "quoteCurrency": "BTC",
+                    "restricted": false,
+                    "underlying": null
+                }
+            },
+            "action": "partial"
+        }
+    }"#;
+
+    match serde_json::from_str::<Response>(json).unwrap().data {
+        Some(ResponseData::Markets(markets)) => {
+            assert_eq!(markets.data.len(), 2);
+            assert_eq!(markets.data["BTC-PERP"].market_type, MarketType::Future);
+            assert_eq!(markets.data["ETH/BTC"].price_increment, dec!(0.0000025));
+        }
+        _ => panic!("Markets data expected."),
+    }
+}
+
 #[tokio::test]
 async fn order_book_update() {
     let mut ws = init_ws().await;
 
-    let symbol: Symbol = String::from("BTC-PERP");
+    let symbol = Symbol::from("BTC-PERP");
     ws.subscribe(vec![Channel::Orderbook(symbol.to_owned())])
         .await
         .expect("Subscription failed.");
@@ -81,7 +212,7 @@ async fn order_book_update() {
 
     // The initial snapshot of the order book
     match ws.next().await.unwrap() {
-        Some(Data::OrderbookData(data)) if data.action == OrderbookAction::Partial => {
+        Some((_, Data::OrderbookData(data))) if data.action == OrderbookAction::Partial => {
             orderbook.update(&data);
             assert!(orderbook.verify_checksum(data.checksum));
             // println!("{:#?}", orderbook);
@@ -92,7 +223,7 @@ async fn order_book_update() {
     // Update the order book 10 times
     for _i in 1..10 {
         match ws.next().await.unwrap() {
-            Some(Data::OrderbookData(data)) if data.action == OrderbookAction::Update => {
+            Some((_, Data::OrderbookData(data))) if data.action == OrderbookAction::Update => {
                 // Check that removed orders are in the orderbook
                 for bid in &data.bids {
                     if bid.1 == dec!(0) {
@@ -137,7 +268,7 @@ async fn order_book_update() {
 
 #[tokio::test]
 async fn order_book_helpers() {
-    let symbol: Symbol = String::from("SHIT-PERP");
+    let symbol = Symbol::from("SHIT-PERP");
     let mut ob = Orderbook::new(symbol);
 
     // All helpers should return None since there are no orders in the book
@@ -147,6 +278,9 @@ async fn order_book_helpers() {
     assert_eq!(ob.best_bid(), None);
     assert_eq!(ob.best_ask(), None);
     assert_eq!(ob.best_bid_and_ask(), None);
+    assert_eq!(ob.spread(), None);
+    assert!(ob.top_bids(10).is_empty());
+    assert_eq!(ob.depth(Side::Sell, 10), dec!(0));
     assert_eq!(ob.quote(Side::Buy, dec!(100)), None);
 
     // Asks
@@ -168,6 +302,21 @@ async fn order_book_helpers() {
         ob.best_bid_and_ask().unwrap(),
         ((dec!(4), dec!(5)), (dec!(5), dec!(20)))
     );
+    assert_eq!(ob.spread().unwrap(), dec!(1));
+    assert_eq!(
+        ob.top_bids(2),
+        vec![(dec!(4), dec!(5)), (dec!(3), dec!(10))]
+    );
+    assert_eq!(
+        ob.top_asks(5),
+        vec![
+            (dec!(5), dec!(20)),
+            (dec!(6), dec!(30)),
+            (dec!(7), dec!(40))
+        ]
+    );
+    assert_eq!(ob.depth(Side::Buy, 2), dec!(15));
+    assert_eq!(ob.depth(Side::Sell, 5), dec!(90));
 
     assert_eq!(ob.quote(Side::Buy, dec!(15)).unwrap(), dec!(5));
     assert_eq!(ob.quote(Side::Buy, dec!(20)).unwrap(), dec!(5));
@@ -200,6 +349,60 @@ async fn order_book_helpers() {
     assert_eq!(ob.quote(Side::Sell, dec!(100)), None);
 }
 
+#[test]
+fn order_book_depth() {
+    let mut ob = Orderbook::new("SHIT-PERP".into());
+    assert_eq!(ob.quote_size(Side::Buy, dec!(10)), None);
+    assert_eq!(ob.depth_bands(Side::Buy, &[dec!(100)]), None);
+
+    ob.asks.insert(dec!(5), dec!(20));
+    ob.asks.insert(dec!(6), dec!(30));
+    ob.asks.insert(dec!(8), dec!(40));
+    ob.bids.insert(dec!(4), dec!(5));
+    ob.bids.insert(dec!(3), dec!(10));
+
+    // 20 at $5, 5 at $6 = $5.2, 400 bps above the best ask
+    let quote = ob.quote_size(Side::Buy, dec!(25)).unwrap();
+    assert_eq!(quote.price, dec!(5.2));
+    assert_eq!(quote.size, dec!(25));
+    assert_eq!(quote.notional, dec!(130));
+    assert_eq!(quote.worst_price, dec!(6));
+    assert_eq!(quote.slippage_bps, dec!(400));
+    assert!(quote.sufficient);
+
+    // Exactly the whole side of the book
+    let quote = ob.quote_size(Side::Sell, dec!(15)).unwrap();
+    assert_eq!(quote.notional, dec!(50));
+    assert!(quote.sufficient);
+
+    // More than the book holds
+    let quote = ob.quote_size(Side::Sell, dec!(20)).unwrap();
+    assert_eq!(quote.size, dec!(15));
+    assert_eq!(quote.worst_price, dec!(3));
+    assert!(!quote.sufficient);
+
+    // $100 at $5, $180 at $6, $40 at $8
+    let quote = ob.quote_notional(Side::Buy, dec!(320)).unwrap();
+    assert_eq!(quote.size, dec!(55));
+    assert_eq!(quote.notional, dec!(320));
+    assert_eq!(quote.worst_price, dec!(8));
+    assert!(quote.sufficient);
+    assert!(!ob.quote_notional(Side::Sell, dec!(100)).unwrap().sufficient);
+
+    // Mid price $4.5, bands reaching $3.375 and $5.625, then $2.25 and $6.75
+    let bids = ob
+        .depth_bands(Side::Buy, &[dec!(2500), dec!(5000)])
+        .unwrap();
+    assert_eq!((bids[0].size, bids[0].notional), (dec!(5), dec!(20)));
+    assert_eq!((bids[1].size, bids[1].notional), (dec!(15), dec!(50)));
+    let asks = ob
+        .depth_bands(Side::Sell, &[dec!(2500), dec!(5000)])
+        .unwrap();
+    assert_eq!((asks[0].size, asks[0].notional), (dec!(20), dec!(100)));
+    assert_eq!(asks[1].bps, dec!(5000));
+    assert_eq!((asks[1].size, asks[1].notional), (dec!(50), dec!(280)));
+}
+
 #[tokio::test]
 async fn order_book_checksum() {
     // BTC-PERP: Whole number prices, decimal and fractional quantities
@@ -212,15 +415,15 @@ async fn order_book_checksum() {
     for symbol in symbols {
         let mut ws = init_ws().await;
 
-        ws.subscribe(vec![Channel::Orderbook(symbol.to_string())])
+        ws.subscribe(vec![Channel::Orderbook(symbol.into())])
             .await
             .expect("Subscription failed.");
 
-        let mut orderbook = Orderbook::new(symbol.to_string());
+        let mut orderbook = Orderbook::new(symbol.into());
 
         // Initial snapshot
         match ws.next().await.unwrap() {
-            Some(Data::OrderbookData(data)) if data.action == OrderbookAction::Partial => {
+            Some((_, Data::OrderbookData(data))) if data.action == OrderbookAction::Partial => {
                 orderbook.update(&data);
                 assert!(orderbook.verify_checksum(data.checksum));
                 // println!("{:#?}", orderbook);
@@ -230,7 +433,7 @@ async fn order_book_checksum() {
 
         // Orderbook update
         match ws.next().await.unwrap() {
-            Some(Data::OrderbookData(data)) if data.action == OrderbookAction::Update => {
+            Some((_, Data::OrderbookData(data))) if data.action == OrderbookAction::Update => {
                 orderbook.update(&data);
                 assert!(orderbook.verify_checksum(data.checksum));
             }
@@ -267,10 +470,1727 @@ async fn fills() {
         .await
         .expect("Could not place order for testing purposes");
     match ws.next().await.unwrap() {
-        Some(Data::Fill(..)) => {}
+        Some((_, Data::Fill(..))) => {}
         _ => panic!("Fill data expected."),
     }
     */
 
     ws.unsubscribe_all().await.expect("Unsubscribe failed");
 }
+
+#[tokio::test]
+async fn orders() {
+    let mut ws = init_ws().await;
+
+    ws.subscribe(vec![Channel::Orders])
+        .await
+        .expect("Subscription failed.");
+
+    ws.unsubscribe_all().await.expect("Unsubscribe failed");
+}
+
+#[tokio::test]
+async fn order_deserialization() {
+    // Sanitized message from FTX API.
+    let json = r#"{
+        "channel": "orders",
+        "type": "update",
+        "data": {
+            "id": 24852229,
+            "clientId": null,
+            "market": "XRP-PERP",
+            "type": "limit",
+            "side": "buy",
+            "size": 42353.0,
+            "price": 0.2977,
+            "reduceOnly": false,
+            "ioc": false,
+            "postOnly": false,
+            "status": "closed",
+            "filledSize": 0.0,
+            "remainingSize": 0.0,
+            "avgFillPrice": null,
+            "createdAt": "2019-03-05T09:56:55.728933+00:00"
+        }
+    }"#;
+
+    match serde_json::from_str::<Response>(json).unwrap().data {
+        Some(ResponseData::Order(order)) => {
+            assert_eq!(order.id, 24852229);
+            assert_eq!(order.status, OrderStatus::Closed);
+            assert_eq!(order.avg_fill_price, None);
+        }
+        _ => panic!("Order data expected."),
+    }
+}
+
+#[test]
+fn response_parsing() {
+    let messages = [
+        TRADES_UPDATE,
+        r#"{"channel": "orderbook", "market": "BTC-PERP", "type": "partial", "data": {"time": 1621740952.5079553, "checksum": 1, "bids": [[40000.0, 1.0]], "asks": [[40001.0, 2.0]], "action": "partial"}}"#,
+        r#"{"channel": "ticker", "market": "BTC-PERP", "type": "update", "data": {"bid": 37010.0, "ask": 37011.0, "bidSize": 1.2345, "askSize": 0.0123, "last": 37010.0, "time": 1621740952.5079553}}"#,
+        r#"{"channel": "trades", "market": "BTC-PERP", "type": "subscribed"}"#,
+        r#"{"type": "error", "code": 400, "msg": "Invalid \"market\""}"#,
+        r#"{"type": "pong"}"#,
+    ];
+
+    // Parses like deserializing with serde
+    for message in messages {
+        let parsed: Response = message.parse().unwrap();
+        let deserialized: Response = serde_json::from_str(message).unwrap();
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", deserialized));
+    }
+
+    let error: Response = messages[4].parse().unwrap();
+    assert_eq!(error.msg.as_deref(), Some(r#"Invalid "market""#));
+
+    // Data of the wrong shape for its channel is an error
+    let mismatch =
+        r#"{"channel": "trades", "market": "BTC-PERP", "type": "update", "data": {"bid": 1.0}}"#;
+    assert!(mismatch.parse::<Response>().is_err());
+
+    // Unknown channels fall back to trying each variant
+    let unknown = messages[1].replace(r#""channel": "orderbook""#, r#""channel": "unknown""#);
+    match unknown.parse::<Response>().unwrap().data {
+        Some(ResponseData::OrderbookData(orderbook)) => assert_eq!(orderbook.bids.len(), 1),
+        _ => panic!("Orderbook data expected."),
+    }
+}
+
+/// Accepts a websocket connection on `listener`
+async fn accept(listener: &TcpListener) -> WebSocketStream<TcpStream> {
+    let (socket, _) = listener.accept().await.unwrap();
+    tokio_tungstenite::accept_async(socket).await.unwrap()
+}
+
+/// Accepts a websocket connection on `listener`, consumes the login op and
+/// answers the ping verifying it
+async fn accept_login(listener: &TcpListener) -> WebSocketStream<TcpStream> {
+    let mut stream = accept(listener).await;
+    let login = stream.next().await.unwrap().unwrap().into_text().unwrap();
+    assert!(login.contains(r#""op":"login""#));
+    stream.next().await.unwrap().unwrap(); // ping
+    stream
+        .send(Message::Text(r#"{"type": "pong"}"#.to_string()))
+        .await
+        .unwrap();
+    stream
+}
+
+const SUBSCRIBED_TRADES: &str =
+    r#"{"type": "subscribed", "channel": "trades", "market": "BTC-PERP"}"#;
+const TRADES_UPDATE: &str = r#"{"channel": "trades", "market": "BTC-PERP", "type": "update", "data": [{"id": 1, "price": 40000.0, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"}, {"id": 2, "price": 40001.0, "size": 0.2, "side": "sell", "liquidation": true, "time": "2021-05-23T05:24:24.315884+00:00"}]}"#;
+
+#[tokio::test]
+async fn reconnect_resubscribes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        // First connection: confirm the subscription, then drop the socket
+        let mut stream = accept_login(&listener).await;
+        stream.next().await.unwrap().unwrap(); // subscribe
+        stream
+            .send(Message::Text(SUBSCRIBED_TRADES.to_string()))
+            .await
+            .unwrap();
+        drop(stream);
+
+        // Second connection: expect the replayed subscription
+        let mut stream = accept_login(&listener).await;
+        let subscribe = stream.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(subscribe.contains(r#""op":"subscribe""#));
+        assert!(subscribe.contains(r#""market":"BTC-PERP""#));
+        stream
+            .send(Message::Text(SUBSCRIBED_TRADES.to_string()))
+            .await
+            .unwrap();
+        stream
+            .send(Message::Text(TRADES_UPDATE.to_string()))
+            .await
+            .unwrap();
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .credentials("key", "secret")
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    match ws.next().await.unwrap() {
+        Some((market, Data::Trade(trade))) => {
+            assert_eq!(market.as_deref(), Some("BTC-PERP"));
+            assert_eq!(trade.price, dec!(40000));
+        }
+        _ => panic!("Trade data expected."),
+    }
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn reconnect_with_subaccount() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut logins = Vec::new();
+        let mut streams = Vec::new();
+        for _ in 0..2 {
+            let mut stream = accept(&listener).await;
+            let login = stream.next().await.unwrap().unwrap().into_text().unwrap();
+            let login: serde_json::Value = serde_json::from_str(&login).unwrap();
+            logins.push(login["args"]["subaccount"].clone());
+            stream.next().await.unwrap().unwrap(); // ping
+            stream
+                .send(Message::Text(r#"{"type": "pong"}"#.to_string()))
+                .await
+                .unwrap();
+            stream.next().await.unwrap().unwrap(); // subscribe
+            stream
+                .send(Message::Text(SUBSCRIBED_TRADES.to_string()))
+                .await
+                .unwrap();
+            streams.push(stream);
+        }
+        logins
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .credentials("key", "secret")
+        .subaccount("one")
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    ws.reconnect_with_subaccount(Some("two"))
+        .await
+        .expect("Reconnection failed.");
+    assert_eq!(ws.subaccount(), Some("two"));
+    assert_eq!(ws.channels, vec![Channel::Trades("BTC-PERP".into())]);
+
+    assert_eq!(server.await.unwrap(), vec![json!("one"), json!("two")]);
+}
+
+#[tokio::test]
+async fn stream() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        stream.next().await.unwrap().unwrap(); // subscribe
+        stream
+            .send(Message::Text(SUBSCRIBED_TRADES.to_string()))
+            .await
+            .unwrap();
+        stream
+            .send(Message::Text(TRADES_UPDATE.to_string()))
+            .await
+            .unwrap();
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    // Use stream combinators instead of `Ws::next`
+    let liquidations: Vec<Trade> = ws
+        .by_ref()
+        .take(2)
+        .filter_map(|data| async move {
+            match data {
+                Ok((_, Data::Trade(trade))) if trade.liquidation => Some(trade),
+                _ => None,
+            }
+        })
+        .collect()
+        .await;
+
+    assert_eq!(liquidations.len(), 1);
+    assert_eq!(liquidations[0].id, 2);
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn try_next() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+    let (send_trades, trades_requested) = tokio::sync::oneshot::channel();
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        stream.next().await.unwrap().unwrap(); // subscribe
+        stream
+            .send(Message::Text(SUBSCRIBED_TRADES.to_string()))
+            .await
+            .unwrap();
+        trades_requested.await.unwrap();
+        stream
+            .send(Message::Text(TRADES_UPDATE.to_string()))
+            .await
+            .unwrap();
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    // Nothing sent yet, returns immediately
+    assert!(ws.try_next().unwrap().is_none());
+
+    send_trades.send(()).unwrap();
+    let mut ids = Vec::new();
+    for _ in 0..100 {
+        match ws.try_next().unwrap() {
+            Some((_, Data::Trade(trade))) => ids.push(trade.id),
+            Some(_) => panic!("Trade data expected."),
+            None if ids.len() == 2 => break,
+            None => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    }
+    assert_eq!(ids, vec![1, 2]);
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn connect_public() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        // The first op must be the subscription, no login is sent
+        let mut stream = accept(&listener).await;
+        let subscribe = stream.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(subscribe.contains(r#""op":"subscribe""#));
+        stream
+            .send(Message::Text(SUBSCRIBED_TRADES.to_string()))
+            .await
+            .unwrap();
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn verify_checksums() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let mut expected = Orderbook::new("BTC-PERP".into());
+    expected.bids.insert(dec!(4), dec!(5));
+    expected.asks.insert(dec!(5), dec!(20));
+    let checksum = expected.checksum();
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        stream.next().await.unwrap().unwrap(); // subscribe
+        for message in [
+            r#"{"type": "subscribed", "channel": "orderbook", "market": "BTC-PERP"}"#.to_string(),
+            format!(
+                r#"{{"channel": "orderbook", "market": "BTC-PERP", "type": "partial", "data": {{"time": 1621740952.5079553, "checksum": {}, "bids": [[4.0, 5.0]], "asks": [[5.0, 20.0]], "action": "partial"}}}}"#,
+                checksum
+            ),
+            r#"{"channel": "orderbook", "market": "BTC-PERP", "type": "update", "data": {"time": 1621740953.5079553, "checksum": 0, "bids": [[4.0, 6.0]], "asks": [], "action": "update"}}"#.to_string(),
+        ] {
+            stream.send(Message::Text(message)).await.unwrap();
+        }
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.set_verify_checksums(true);
+    ws.subscribe(vec![Channel::Orderbook("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    match ws.next().await {
+        Ok(Some((_, Data::OrderbookData(data)))) if data.action == OrderbookAction::Partial => {}
+        _ => panic!("Order book snapshot data expected."),
+    }
+    assert_eq!(
+        ws.orderbook("BTC-PERP").unwrap().best_bid(),
+        Some((dec!(4), dec!(5)))
+    );
+
+    match ws.next().await {
+        Err(Error::ChecksumMismatch(symbol)) => assert_eq!(symbol, "BTC-PERP"),
+        _ => panic!("Checksum mismatch expected."),
+    }
+    assert!(ws.orderbook("BTC-PERP").is_none());
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn request_orderbook_snapshot() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        // Confirms each op
+        let mut ops = Vec::new();
+        while ops.len() < 4 {
+            let text = stream.next().await.unwrap().unwrap().into_text().unwrap();
+            if text.contains(r#""op":"ping""#) {
+                continue;
+            }
+            let channel = if text.contains("orderbook") {
+                "orderbook"
+            } else {
+                "trades"
+            };
+            let r#type = if text.contains("unsubscribe") {
+                "unsubscribed"
+            } else {
+                "subscribed"
+            };
+            ops.push(format!("{} {}", r#type, channel));
+            let response = format!(
+                r#"{{"type": "{}", "channel": "{}", "market": "BTC-PERP"}}"#,
+                r#type, channel
+            );
+            stream.send(Message::Text(response)).await.unwrap();
+        }
+        stream
+            .send(Message::Text(
+                r#"{"channel": "orderbook", "market": "BTC-PERP", "type": "partial", "data": {"time": 1621740952.5079553, "checksum": 0, "bids": [[40000.0, 1.0]], "asks": [], "action": "partial"}}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        (stream, ops)
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![
+        Channel::Orderbook("BTC-PERP".into()),
+        Channel::Trades("BTC-PERP".into()),
+    ])
+    .await
+    .expect("Subscription failed.");
+
+    match ws.request_orderbook_snapshot("ETH-PERP").await {
+        Err(Error::NotSubscribedToThisChannel(..)) => {}
+        _ => panic!("Not subscribed error expected."),
+    }
+    ws.request_orderbook_snapshot("BTC-PERP")
+        .await
+        .expect("Snapshot request failed.");
+    match ws.next().await.unwrap() {
+        Some((_, Data::OrderbookData(data))) => assert_eq!(data.action, OrderbookAction::Partial),
+        _ => panic!("Order book snapshot data expected."),
+    }
+    assert_eq!(ws.channels.len(), 2);
+
+    // The trades subscription is left alone
+    let (_stream, ops) = server.await.unwrap();
+    assert_eq!(
+        ops,
+        vec![
+            "subscribed orderbook",
+            "subscribed trades",
+            "unsubscribed orderbook",
+            "subscribed orderbook",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn grouped_orderbook() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        let subscribe = stream.next().await.unwrap().unwrap().into_text().unwrap();
+        for message in [
+            r#"{"type": "subscribed", "channel": "orderbookGrouped", "market": "BTC-PERP"}"#,
+            r#"{"channel": "orderbookGrouped", "market": "BTC-PERP", "type": "partial", "data": {"bids": [[40000.0, 3.5], [39500.0, 10.0]], "asks": [[40500.0, 2.0]]}}"#,
+            r#"{"channel": "orderbookGrouped", "market": "BTC-PERP", "type": "update", "data": {"bids": [[40000.0, 0.0]], "asks": [[40500.0, 1.0]]}}"#,
+        ] {
+            stream
+                .send(Message::Text(message.to_string()))
+                .await
+                .unwrap();
+        }
+        (stream, subscribe)
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::OrderbookGrouped {
+        market: "BTC-PERP".into(),
+        grouping: dec!(500),
+    }])
+    .await
+    .expect("Subscription failed.");
+
+    let mut book = Orderbook::new("BTC-PERP".into());
+    for action in [OrderbookAction::Partial, OrderbookAction::Update] {
+        match ws.next().await.unwrap() {
+            Some((_, Data::GroupedOrderbook(data))) => {
+                assert_eq!(data.action, action);
+                book.update_grouped(&data);
+            }
+            _ => panic!("Grouped orderbook data expected."),
+        }
+    }
+    assert_eq!(book.best_bid(), Some((dec!(39500), dec!(10))));
+    assert_eq!(book.best_ask(), Some((dec!(40500), dec!(1))));
+
+    let (_stream, subscribe) = server.await.unwrap();
+    let subscribe: serde_json::Value = serde_json::from_str(&subscribe).unwrap();
+    assert_eq!(subscribe["channel"], "orderbookGrouped");
+    assert_eq!(subscribe["grouping"], 500.0);
+}
+
+#[tokio::test]
+async fn unsupported_on_exchange() {
+    use crate::options::Exchange;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        let subscribe = stream.next().await.unwrap().unwrap().into_text().unwrap();
+        stream
+            .send(Message::Text(
+                r#"{"type": "subscribed", "channel": "trades", "market": "BTC/USD"}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        (stream, subscribe)
+    });
+
+    let mut ws = Ws::builder()
+        .exchange(Exchange::Us)
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    assert_eq!(ws.exchange(), Exchange::Us);
+
+    // No futures on ftx.us, nothing is subscribed to
+    match ws
+        .subscribe(vec![
+            Channel::Trades("BTC/USD".into()),
+            Channel::Orderbook("BTC-PERP".into()),
+        ])
+        .await
+    {
+        Err(Error::UnsupportedOnExchange(channel)) => {
+            assert_eq!(channel, Channel::Orderbook("BTC-PERP".into()))
+        }
+        _ => panic!("Unsupported channel error expected."),
+    }
+    assert!(ws.channels.is_empty());
+
+    ws.subscribe(vec![Channel::Trades("BTC/USD".into())])
+        .await
+        .expect("Subscription failed.");
+    let (_stream, subscribe) = server.await.unwrap();
+    assert!(subscribe.contains("BTC/USD"));
+}
+
+#[tokio::test]
+async fn subscription_confirmation() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        stream.next().await.unwrap().unwrap(); // subscribe
+                                               // Confirm a different market, followed by some data
+        for message in [
+            r#"{"type": "subscribed", "channel": "trades", "market": "ETH-PERP"}"#,
+            TRADES_UPDATE,
+        ] {
+            stream
+                .send(Message::Text(message.to_string()))
+                .await
+                .unwrap();
+        }
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.set_subscription_timeout(Duration::from_millis(200));
+
+    // The confirmation of another market must not be accepted
+    match ws.subscribe(vec![Channel::Trades("BTC-PERP".into())]).await {
+        Err(Error::MissingSubscriptionConfirmation(channel)) => {
+            assert_eq!(channel, Channel::Trades("BTC-PERP".into()))
+        }
+        _ => panic!("Missing subscription confirmation expected."),
+    }
+    // Unconfirmed channels are not tracked as subscribed
+    match ws
+        .unsubscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+    {
+        Err(Error::NotSubscribedToThisChannel(..)) => {}
+        _ => panic!("Not subscribed error expected."),
+    }
+
+    // Data received while waiting is still delivered
+    match ws.next().await.unwrap() {
+        Some((_, Data::Trade(trade))) => assert_eq!(trade.id, 1),
+        _ => panic!("Trade data expected."),
+    }
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn subscribe_each() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        // All ops arrive before any is confirmed
+        let mut ops = Vec::new();
+        while ops.len() < 3 {
+            let text = stream.next().await.unwrap().unwrap().into_text().unwrap();
+            if !text.contains(r#""op":"ping""#) {
+                ops.push(text);
+            }
+        }
+        // Leaves the orderbook unconfirmed
+        for message in [
+            r#"{"type": "subscribed", "channel": "ticker", "market": "SOL-PERP"}"#,
+            SUBSCRIBED_TRADES,
+        ] {
+            stream
+                .send(Message::Text(message.to_string()))
+                .await
+                .unwrap();
+        }
+        (stream, ops)
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .subscription_timeout(Duration::from_millis(200))
+        .connect()
+        .await
+        .expect("Connection failed.");
+
+    let results = ws
+        .subscribe_each(vec![
+            Channel::Trades("BTC-PERP".into()),
+            // Shares the op of the trades channel
+            Channel::Liquidations("BTC-PERP".into()),
+            Channel::Orderbook("ETH-PERP".into()),
+            Channel::Ticker("SOL-PERP".into()),
+        ])
+        .await
+        .expect("Subscription failed.");
+    let confirmed: Vec<_> = results
+        .iter()
+        .map(|(channel, result)| (channel.clone(), result.is_ok()))
+        .collect();
+    assert_eq!(
+        confirmed,
+        vec![
+            (Channel::Trades("BTC-PERP".into()), true),
+            (Channel::Liquidations("BTC-PERP".into()), true),
+            (Channel::Orderbook("ETH-PERP".into()), false),
+            (Channel::Ticker("SOL-PERP".into()), true),
+        ]
+    );
+    match &results[2].1 {
+        Err(Error::MissingSubscriptionConfirmation(channel)) => {
+            assert_eq!(channel, &Channel::Orderbook("ETH-PERP".into()))
+        }
+        _ => panic!("Missing subscription confirmation expected."),
+    }
+    assert_eq!(ws.channels.len(), 3);
+    assert!(!ws.channels.contains(&Channel::Orderbook("ETH-PERP".into())));
+
+    let (_stream, ops) = server.await.unwrap();
+    assert!(ops[0].contains(r#""channel":"trades""#));
+    assert!(ops[1].contains(r#""channel":"orderbook""#));
+    assert!(ops[2].contains(r#""channel":"ticker""#));
+}
+
+#[tokio::test]
+async fn api_error() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept_login(&listener).await;
+        stream.next().await.unwrap().unwrap(); // subscribe
+        stream
+            .send(Message::Text(
+                r#"{"type": "error", "code": 400, "msg": "Invalid login credentials"}"#.to_string(),
+            ))
+            .await
+            .unwrap();
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .credentials("key", "secret")
+        .connect()
+        .await
+        .expect("Connection failed.");
+
+    match ws.subscribe(vec![Channel::Fills]).await {
+        Err(Error::Api { code, msg }) => {
+            assert_eq!(code, Some(400));
+            assert_eq!(msg, "Invalid login credentials");
+        }
+        _ => panic!("API error expected."),
+    }
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn connection_stale() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    // The server accepts the connection but never responds
+    let server = tokio::spawn(async move { accept(&listener).await });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .auto_reconnect(false)
+        .stale_timeout(Duration::from_millis(200))
+        .connect()
+        .await
+        .expect("Connection failed.");
+
+    match ws.next().await {
+        Err(Error::ConnectionStale) => {}
+        _ => panic!("Stale connection expected."),
+    }
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn close() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        stream.next().await.unwrap().unwrap(); // subscribe
+        stream
+            .send(Message::Text(SUBSCRIBED_TRADES.to_string()))
+            .await
+            .unwrap();
+        // Still in flight when the client closes the connection
+        stream
+            .send(Message::Text(TRADES_UPDATE.to_string()))
+            .await
+            .unwrap();
+        // Skips pings
+        loop {
+            match stream.next().await {
+                Some(Ok(Message::Close(_))) => break,
+                Some(Ok(Message::Text(_))) => {}
+                _ => panic!("Close frame expected."),
+            }
+        }
+        // Replies to the close frame, then ends
+        assert!(stream.next().await.is_none());
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    ws.close().await.expect("Close failed.");
+    server.await.unwrap();
+
+    let mut ids = Vec::new();
+    while let Some((_, data)) = ws.next().await.unwrap() {
+        match data {
+            Data::Trade(trade) => ids.push(trade.id),
+            _ => panic!("Trade data expected."),
+        }
+    }
+    assert_eq!(ids, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn liquidations() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+    let (send_trades, trades_requested) = tokio::sync::oneshot::channel();
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        let subscribe = stream.next().await.unwrap().unwrap().into_text().unwrap();
+        assert!(subscribe.contains(r#""channel":"trades""#));
+        stream
+            .send(Message::Text(SUBSCRIBED_TRADES.to_string()))
+            .await
+            .unwrap();
+        stream
+            .send(Message::Text(TRADES_UPDATE.to_string()))
+            .await
+            .unwrap();
+        // No second subscribe op is expected for the shared subscription
+        trades_requested.await.unwrap();
+        stream
+            .send(Message::Text(TRADES_UPDATE.to_string()))
+            .await
+            .unwrap();
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Liquidations("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    // Only the liquidation of both trades
+    match ws.next().await.unwrap() {
+        Some((_, Data::Liquidation(trade))) => assert_eq!(trade.id, 2),
+        _ => panic!("Liquidation data expected."),
+    }
+
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+    send_trades.send(()).unwrap();
+
+    let mut received = Vec::new();
+    for _ in 0..3 {
+        match ws.next().await.unwrap() {
+            Some((_, Data::Trade(trade))) => received.push(("trade", trade.id)),
+            Some((_, Data::Liquidation(trade))) => received.push(("liquidation", trade.id)),
+            _ => panic!("Trade data expected."),
+        }
+    }
+    assert_eq!(
+        received,
+        vec![("trade", 1), ("liquidation", 2), ("trade", 2)]
+    );
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn raw_message_hook() {
+    use std::sync::{Arc, Mutex};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        stream.next().await.unwrap().unwrap(); // subscribe
+        for message in [SUBSCRIBED_TRADES, TRADES_UPDATE] {
+            stream
+                .send(Message::Text(message.to_string()))
+                .await
+                .unwrap();
+        }
+        stream
+    });
+
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .raw_message_hook({
+            let recorded = recorded.clone();
+            move |text| recorded.lock().unwrap().push(text.to_string())
+        })
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+    ws.next().await.unwrap();
+
+    server.await.unwrap();
+
+    let recorded = recorded.lock().unwrap();
+    assert!(recorded.contains(&SUBSCRIBED_TRADES.to_string()));
+    assert!(recorded.contains(&TRADES_UPDATE.to_string()));
+}
+
+#[tokio::test]
+async fn latency_stats() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept(&listener).await;
+        // The first ping is sent right away, possibly while subscribing
+        let (mut subscribed, mut ponged) = (false, false);
+        while !(subscribed && ponged) {
+            let text = stream.next().await.unwrap().unwrap().into_text().unwrap();
+            let response = if text.contains(r#""op":"ping""#) {
+                time::sleep(Duration::from_millis(20)).await;
+                ponged = true;
+                r#"{"type": "pong"}"#
+            } else {
+                subscribed = true;
+                SUBSCRIBED_TRADES
+            };
+            stream
+                .send(Message::Text(response.to_string()))
+                .await
+                .unwrap();
+        }
+        // Traded a second before it is sent
+        let time = Utc::now() - chrono::Duration::seconds(1);
+        let update = TRADES_UPDATE.replace("2021-05-23T05:24:24.315884+00:00", &time.to_rfc3339());
+        stream.send(Message::Text(update)).await.unwrap();
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .ping_interval(Duration::from_secs(60))
+        .connect()
+        .await
+        .expect("Connection failed.");
+    assert_eq!(ws.latency_stats(), LatencyStats::default());
+
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+    ws.next().await.unwrap();
+    server.await.unwrap();
+
+    let stats = ws.latency_stats();
+    let round_trip = stats.round_trip.expect("Round trip expected.");
+    assert_eq!(round_trip.samples, 1);
+    assert!(round_trip.p50 >= Duration::from_millis(20));
+    assert_eq!(round_trip.p50, round_trip.p95);
+    let trade_delay = stats.trade_delay.expect("Trade delay expected.");
+    assert_eq!(trade_delay.samples, 2);
+    assert!(trade_delay.p50 >= chrono::Duration::seconds(1));
+    assert!(trade_delay.p95 < chrono::Duration::seconds(5));
+}
+
+#[tokio::test]
+async fn record_replay() {
+    use replay::{Record, Recorder, Replay};
+
+    let mut recorder = Recorder::new(Vec::new());
+    for message in [SUBSCRIBED_TRADES, r#"{"type": "pong"}"#, TRADES_UPDATE] {
+        recorder.record(message).unwrap();
+    }
+    let recording = String::from_utf8(recorder.into_inner()).unwrap();
+    assert_eq!(recording.lines().count(), 3);
+
+    let mut replay = Replay::new(recording.as_bytes());
+    replay.set_speed(f64::INFINITY);
+    let mut ids = Vec::new();
+    while let Some((market, data)) = replay.next().await.unwrap() {
+        assert_eq!(market.as_deref(), Some("BTC-PERP"));
+        match data {
+            Data::Trade(trade) => ids.push(trade.id),
+            _ => panic!("Trade data expected."),
+        }
+    }
+    assert_eq!(ids, vec![1, 2]);
+
+    // Replayed at 100 times the recorded pace of one second
+    let recording: String = [0, 1000]
+        .iter()
+        .map(|time| {
+            let record = Record {
+                time: *time,
+                message: TRADES_UPDATE.to_string(),
+            };
+            serde_json::to_string(&record).unwrap() + "\n"
+        })
+        .collect();
+    let mut replay = Replay::new(recording.as_bytes());
+    replay.set_speed(100.0);
+    let started = std::time::Instant::now();
+    let mut count = 0;
+    while replay.next().await.unwrap().is_some() {
+        count += 1;
+    }
+    assert_eq!(count, 4);
+    assert!(started.elapsed() >= Duration::from_millis(10));
+}
+
+#[tokio::test]
+async fn mock_server() {
+    use crate::testing::MockWsServer;
+    use serde_json::json;
+
+    let server = MockWsServer::bind().await.unwrap();
+    let mut ws = Ws::builder()
+        .endpoint(&server.endpoint())
+        .credentials("key", "secret")
+        .connect()
+        .await
+        .expect("Connection failed.");
+
+    ws.subscribe(vec![
+        Channel::Trades("BTC-PERP".into()),
+        Channel::Orderbook("BTC-PERP".into()),
+        Channel::Fills,
+    ])
+    .await
+    .expect("Subscription failed.");
+    assert_eq!(server.subscriptions().len(), 3);
+    assert_eq!(server.received()[0]["op"], "login");
+
+    server.send_trades(
+        "BTC-PERP",
+        json!([{"id": 1, "price": 40000.0, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"}]),
+    );
+    match ws.next().await.unwrap() {
+        Some((Some(market), Data::Trade(trade))) => {
+            assert_eq!(market, "BTC-PERP");
+            assert_eq!(trade.id, 1);
+        }
+        _ => panic!("Trade data expected."),
+    }
+
+    server.send_orderbook(
+        "BTC-PERP",
+        json!({"action": "partial", "bids": [[40000.0, 1.0]], "asks": [[40001.0, 2.0]], "checksum": 0, "time": 1621747464.3}),
+    );
+    match ws.next().await.unwrap() {
+        Some((_, Data::OrderbookData(data))) => assert_eq!(data.bids.len(), 1),
+        _ => panic!("Orderbook data expected."),
+    }
+
+    ws.unsubscribe(vec![Channel::Fills])
+        .await
+        .expect("Unsubscribe failed");
+    assert_eq!(server.subscriptions().len(), 2);
+}
+
+#[test]
+fn candle_builder() {
+    use chrono::TimeZone;
+
+    let trade = |seconds: i64, price: rust_decimal::Decimal| Trade {
+        id: seconds as u64,
+        price,
+        size: dec!(2),
+        side: Side::Buy,
+        liquidation: false,
+        time: Utc.timestamp_opt(seconds, 500_000_000).unwrap(),
+    };
+
+    let mut builder = CandleBuilder::new(60);
+    assert!(builder.update(&trade(60, dec!(10))).is_empty());
+    assert!(builder.update(&trade(90, dec!(12))).is_empty());
+    assert!(builder.update(&trade(119, dec!(9))).is_empty());
+    assert_eq!(builder.current().unwrap().close, dec!(9));
+
+    // Skips to the fourth minute, leaving a gap
+    let candles = builder.update(&trade(245, dec!(11)));
+    assert_eq!(candles.len(), 3);
+    let first = candles[0];
+    assert_eq!(first.start_time.timestamp(), 60);
+    assert_eq!(
+        (first.open, first.high, first.low, first.close),
+        (dec!(10), dec!(12), dec!(9), dec!(9))
+    );
+    assert_eq!(first.volume, dec!(62));
+    for (candle, start) in candles[1..].iter().zip([120, 180].iter()) {
+        assert_eq!(candle.start_time.timestamp(), *start);
+        assert_eq!((candle.open, candle.close), (dec!(9), dec!(9)));
+        assert_eq!(candle.volume, dec!(0));
+    }
+
+    // Late trades are ignored
+    assert!(builder.update(&trade(100, dec!(1))).is_empty());
+    assert_eq!(builder.current().unwrap().low, dec!(11));
+
+    // Flushing completes candles without waiting for a trade
+    assert!(builder.flush(Utc.timestamp_opt(299, 0).unwrap()).is_empty());
+    let candles = builder.flush(Utc.timestamp_opt(370, 0).unwrap());
+    let starts: Vec<i64> = candles.iter().map(|c| c.start_time.timestamp()).collect();
+    assert_eq!(starts, vec![240, 300]);
+    let candles = builder.update(&trade(480, dec!(13)));
+    let starts: Vec<i64> = candles.iter().map(|c| c.start_time.timestamp()).collect();
+    assert_eq!(starts, vec![360, 420]);
+
+    // Without gap filling, only candles with trades are returned
+    let mut builder = CandleBuilder::new(86400);
+    builder.set_fill_gaps(false);
+    builder.update(&trade(0, dec!(1)));
+    let candles = builder.update(&trade(3 * 86400, dec!(2)));
+    assert_eq!(candles.len(), 1);
+    assert_eq!(candles[0].start_time.timestamp(), 0);
+}
+
+#[tokio::test]
+async fn orderbook_manager() {
+    use crate::testing::MockWsServer;
+    use std::sync::{Arc, Mutex};
+
+    let server = MockWsServer::bind().await.unwrap();
+    let endpoint = server.endpoint();
+    let mut manager =
+        OrderbookManager::connect_with(vec!["BTC-PERP".into(), "ETH-PERP".into()], 1, move || {
+            Ws::builder().endpoint(&endpoint)
+        })
+        .await
+        .expect("Connection failed.");
+    assert_eq!(server.subscriptions().len(), 2);
+    let issues = Arc::new(Mutex::new(Vec::new()));
+    let reported = issues.clone();
+    manager.set_integrity_hook(move |issue| reported.lock().unwrap().push(issue.clone()));
+
+    let handle = manager.handle();
+    assert_eq!(handle.markets().count(), 2);
+    assert!(handle.get("BTC-PERP").unwrap().bids.is_empty());
+
+    // Both connections receive every frame, each book is updated once
+    let mut btc = Orderbook::new("BTC-PERP".into());
+    send_orderbook(
+        &server,
+        &mut btc,
+        "partial",
+        &[(40000.0, 1.0)],
+        &[(40001.0, 2.0)],
+        0,
+    );
+    assert_eq!(manager.next().await.unwrap().unwrap(), "BTC-PERP");
+    send_orderbook(&server, &mut btc, "update", &[(40000.5, 3.0)], &[], 0);
+    assert_eq!(manager.next().await.unwrap().unwrap(), "BTC-PERP");
+    assert_eq!(
+        handle.get("BTC-PERP").unwrap().bid_price(),
+        Some(dec!(40000.5))
+    );
+
+    let mut eth = Orderbook::new("ETH-PERP".into());
+    send_orderbook(
+        &server,
+        &mut eth,
+        "partial",
+        &[(3000.0, 1.0)],
+        &[(3001.0, 2.0)],
+        0,
+    );
+    assert_eq!(manager.next().await.unwrap().unwrap(), "ETH-PERP");
+    assert_eq!(
+        handle.snapshot("ETH-PERP").unwrap().ask_price(),
+        Some(dec!(3001))
+    );
+
+    // A checksum mismatch clears the book and resubscribes
+    send_orderbook(&server, &mut btc, "update", &[(39999.0, 1.0)], &[], 1);
+    send_orderbook(&server, &mut eth, "update", &[], &[(3002.0, 1.0)], 0);
+    assert_eq!(manager.next().await.unwrap().unwrap(), "ETH-PERP");
+    assert!(handle.get("BTC-PERP").unwrap().bids.is_empty());
+    let ops: Vec<String> = server
+        .received()
+        .iter()
+        .filter(|op| op["market"] == "BTC-PERP")
+        .map(|op| op["op"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(ops, vec!["subscribe", "unsubscribe", "subscribe"]);
+    match issues.lock().unwrap().as_slice() {
+        [DataIntegrity::OrderbookChecksumMismatch { market, last_valid }] => {
+            assert_eq!(market, "BTC-PERP");
+            assert_eq!(last_valid.unwrap().timestamp(), 1621747464);
+        }
+        issues => panic!("Unexpected integrity problems {:?}", issues),
+    }
+
+    manager.close().await.unwrap();
+    assert_eq!(manager.next().await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn ws_pool() {
+    use crate::testing::MockWsServer;
+    use serde_json::json;
+
+    let server = MockWsServer::bind().await.unwrap();
+    let endpoint = server.endpoint();
+    let mut pool = WsPool::new(move || Ws::builder().endpoint(&endpoint).auto_reconnect(false));
+    pool.set_channels_per_connection(2);
+
+    pool.subscribe(vec![
+        Channel::Trades("BTC-PERP".into()),
+        Channel::Ticker("BTC-PERP".into()),
+        Channel::Trades("ETH-PERP".into()),
+    ])
+    .await
+    .expect("Subscription failed.");
+    assert_eq!(pool.connections(), 2);
+    // Shares the trades subscription, no new connection needed
+    pool.subscribe(vec![Channel::Liquidations("BTC-PERP".into())])
+        .await
+        .unwrap();
+    assert_eq!(pool.connections(), 2);
+    assert_eq!(pool.channels().len(), 4);
+
+    // Every connection receives the frame, it is returned once
+    let trade = json!([{"id": 1, "price": 3000.0, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"}]);
+    server.send_trades("ETH-PERP", trade.clone());
+    server.send_trades("BTC-PERP", trade);
+    let mut markets = Vec::new();
+    for _ in 0..2 {
+        match pool.next().await.unwrap() {
+            Some((Some(market), Data::Trade(_))) => markets.push(market),
+            _ => panic!("Trade data expected."),
+        }
+    }
+    markets.sort();
+    assert_eq!(markets, vec!["BTC-PERP", "ETH-PERP"]);
+
+    // Channels of a failed connection move to the others
+    server.disconnect_client(0);
+    // A frame sent before the failure may still arrive on the new connection
+    let _ = time::timeout(Duration::from_millis(500), pool.next()).await;
+    assert_eq!(pool.connections(), 2);
+    assert_eq!(pool.channels().len(), 4);
+    server.send_trades("BTC-PERP", json!([{"id": 2, "price": 3000.0, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"}]));
+    loop {
+        match pool.next().await.unwrap() {
+            Some((Some(market), Data::Trade(trade))) => {
+                assert_eq!(market, "BTC-PERP");
+                if trade.id == 2 {
+                    break;
+                }
+            }
+            _ => panic!("Trade data expected."),
+        }
+    }
+
+    pool.unsubscribe(vec![Channel::Trades("ETH-PERP".into())])
+        .await
+        .unwrap();
+    assert_eq!(pool.channels().len(), 3);
+    pool.close().await.unwrap();
+    assert!(pool.next().await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn buffer_policy() {
+    use crate::testing::MockWsServer;
+
+    let server = MockWsServer::bind().await.unwrap();
+    let mut ws = Ws::builder()
+        .endpoint(&server.endpoint())
+        .max_buffer_size(2)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .unwrap();
+
+    // Three trades in a single frame
+    let trades = r#"[
+        {"id": 1, "price": 1.0, "size": 1.0, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"},
+        {"id": 2, "price": 1.0, "size": 1.0, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"},
+        {"id": 3, "price": 1.0, "size": 1.0, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"}
+    ]"#;
+    let trades: serde_json::Value = serde_json::from_str(trades).unwrap();
+
+    for (policy, expected) in [
+        (BufferPolicy::DropOldest, vec![2, 3]),
+        (BufferPolicy::DropNewest, vec![1, 2]),
+    ] {
+        ws.set_buffer_policy(policy);
+        server.send_trades("BTC-PERP", trades.clone());
+
+        let mut ids = Vec::new();
+        while let Some((_, Data::Trade(trade))) = ws.next().await.unwrap() {
+            ids.push(trade.id);
+            if ws.buffer_len() == 0 {
+                break;
+            }
+        }
+        assert_eq!(ids, expected);
+    }
+
+    ws.set_buffer_policy(BufferPolicy::Error);
+    server.send_trades("BTC-PERP", trades);
+    match ws.next().await {
+        Err(Error::BufferFull) => {}
+        _ => panic!("Full buffer expected."),
+    }
+    assert_eq!(ws.buffer_len(), 2);
+}
+
+#[test]
+fn buffer_coalesce_orderbooks() {
+    use super::buffer::Buffer;
+
+    let data = |action: &str, bids: &str, asks: &str, checksum: u32| {
+        let json = format!(
+            r#"{{"action": "{}", "bids": {}, "asks": {}, "checksum": {}, "time": 1621747464.3}}"#,
+            action, bids, asks, checksum
+        );
+        Data::OrderbookData(serde_json::from_str(&json).unwrap())
+    };
+    let market = Some(Symbol::from("BTC-PERP"));
+
+    let mut buffer = Buffer::new();
+    buffer.max_size = Some(2);
+    buffer.policy = BufferPolicy::CoalesceOrderbooks;
+    buffer
+        .push((
+            market.clone(),
+            data("partial", "[[3, 1], [2, 1]]", "[[4, 1]]", 1),
+        ))
+        .unwrap();
+    buffer
+        .push((
+            None,
+            Data::Markets(serde_json::from_str(r#"{"data": {}}"#).unwrap()),
+        ))
+        .unwrap();
+    buffer
+        .push((
+            market.clone(),
+            data("update", "[[3, 0], [2.5, 2]]", "[[5, 1]]", 2),
+        ))
+        .unwrap();
+    buffer
+        .push((market.clone(), data("update", "[[2.5, 3]]", "[[4, 0]]", 3)))
+        .unwrap();
+    assert_eq!(buffer.len(), 2);
+
+    // Applying the coalesced snapshot equals applying all three
+    match buffer.pop_front() {
+        Some((_, Data::OrderbookData(data))) => {
+            assert_eq!(data.action, OrderbookAction::Partial);
+            assert_eq!(data.bids, vec![(dec!(2.5), dec!(3)), (dec!(2), dec!(1))]);
+            assert_eq!(data.asks, vec![(dec!(5), dec!(1))]);
+            assert_eq!(data.checksum, 3);
+        }
+        _ => panic!("Orderbook data expected."),
+    }
+
+    // Without orderbook data to merge into, the oldest data is dropped
+    buffer
+        .push((market.clone(), data("update", "[]", "[]", 4)))
+        .unwrap();
+    buffer
+        .push((Some("ETH-PERP".into()), data("update", "[]", "[]", 5)))
+        .unwrap();
+    assert_eq!(buffer.len(), 2);
+    assert!(matches!(buffer.pop_front(), Some((m, _)) if m == market));
+}
+
+#[tokio::test]
+async fn coalesce_orderbooks() {
+    use crate::testing::MockWsServer;
+    use serde_json::json;
+
+    let server = MockWsServer::bind().await.unwrap();
+    let mut ws = Ws::builder()
+        .endpoint(&server.endpoint())
+        .coalesce_orderbooks(true)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Orderbook("BTC-PERP".into())])
+        .await
+        .unwrap();
+
+    let data = |action: &str, bids: serde_json::Value, checksum: u32| json!({"action": action, "bids": bids, "asks": [[40001.0, 1.0]], "checksum": checksum, "time": 1621747464.3});
+    server.send_orderbook("BTC-PERP", data("partial", json!([[40000.0, 1.0]]), 1));
+    server.send_orderbook(
+        "BTC-PERP",
+        data("update", json!([[40000.0, 0.0], [39999.0, 2.0]]), 2),
+    );
+    server.send_orderbook("BTC-PERP", data("update", json!([[39998.0, 3.0]]), 3));
+    // Let the consumer fall behind
+    time::sleep(Duration::from_millis(100)).await;
+
+    match ws.next().await.unwrap() {
+        Some((_, Data::OrderbookData(data))) => {
+            assert_eq!(data.action, OrderbookAction::Partial);
+            assert_eq!(
+                data.bids,
+                vec![(dec!(39999), dec!(2)), (dec!(39998), dec!(3))]
+            );
+            assert_eq!(data.checksum, 3);
+        }
+        _ => panic!("Orderbook data expected."),
+    }
+    assert_eq!(ws.buffer_len(), 0);
+
+    // Updates arriving later are returned as usual
+    server.send_orderbook("BTC-PERP", data("update", json!([[39997.0, 1.0]]), 4));
+    match ws.next().await.unwrap() {
+        Some((_, Data::OrderbookData(data))) => assert_eq!(data.checksum, 4),
+        _ => panic!("Orderbook data expected."),
+    }
+}
+
+#[tokio::test]
+async fn proxy() {
+    use crate::testing::MockWsServer;
+    use serde_json::json;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    assert!(Proxy::new("https://proxy:3128").is_err());
+    assert!(Proxy::from_env("ws://127.0.0.1:8080/ws").is_none());
+
+    // HTTP proxy answering a single CONNECT request
+    let http_proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let http_addr = http_proxy.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut client, _) = http_proxy.accept().await.unwrap();
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            request.push(client.read_u8().await.unwrap());
+        }
+        let request = String::from_utf8(request).unwrap();
+        let target = request.split_whitespace().nth(1).unwrap().to_string();
+        assert!(request.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"));
+
+        let mut server = TcpStream::connect(target).await.unwrap();
+        client
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await
+            .unwrap();
+        tokio::io::copy_bidirectional(&mut client, &mut server)
+            .await
+            .ok();
+    });
+
+    // SOCKS5 proxy without authentication, answering a single request for
+    // an IPv4 address
+    let socks_proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let socks_addr = socks_proxy.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut client, _) = socks_proxy.accept().await.unwrap();
+        let mut greeting = [0; 3];
+        client.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, [5, 1, 0]);
+        client.write_all(&[5, 0]).await.unwrap();
+
+        let mut request = [0; 10];
+        client.read_exact(&mut request).await.unwrap();
+        assert_eq!(request[..4], [5, 1, 0, 1]);
+        let ip = std::net::Ipv4Addr::new(request[4], request[5], request[6], request[7]);
+        let port = u16::from_be_bytes([request[8], request[9]]);
+
+        let mut server = TcpStream::connect((ip, port)).await.unwrap();
+        client
+            .write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0])
+            .await
+            .unwrap();
+        tokio::io::copy_bidirectional(&mut client, &mut server)
+            .await
+            .ok();
+    });
+
+    for proxy in [
+        format!("http://user:pass@{}", http_addr),
+        format!("socks5://{}", socks_addr),
+    ] {
+        let server = MockWsServer::bind().await.unwrap();
+        let mut ws = Ws::builder()
+            .endpoint(&server.endpoint())
+            .proxy(Proxy::new(&proxy).unwrap())
+            .connect()
+            .await
+            .expect("Connection failed.");
+
+        ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+            .await
+            .expect("Subscription failed.");
+        server.send_trades(
+            "BTC-PERP",
+            json!([{"id": 1, "price": 40000.0, "size": 0.1, "side": "buy", "liquidation": false, "time": "2021-05-23T05:24:24.315884+00:00"}]),
+        );
+        match ws.next().await.unwrap() {
+            Some((_, Data::Trade(trade))) => assert_eq!(trade.id, 1),
+            _ => panic!("Trade data expected."),
+        }
+    }
+}
+
+#[tokio::test]
+async fn authentication_failed() {
+    use crate::testing::MockWsServer;
+
+    let server = MockWsServer::bind().await.unwrap();
+    server.set_reject_logins(true);
+    let result = Ws::builder()
+        .endpoint(&server.endpoint())
+        .credentials("key", "invalid")
+        .connect()
+        .await;
+    match result {
+        Err(Error::AuthenticationFailed(msg)) => assert_eq!(msg, "Invalid login credentials"),
+        _ => panic!("Authentication failure expected."),
+    }
+
+    server.set_reject_logins(false);
+    Ws::builder()
+        .endpoint(&server.endpoint())
+        .credentials("key", "secret")
+        .connect()
+        .await
+        .expect("Connection failed.");
+}
+
+#[tokio::test]
+async fn fast_orders() {
+    use crate::rest::{OrderRequest, OrderSide};
+    use crate::testing::{MockRest, MockWsServer};
+    use rust_decimal_macros::dec;
+    use serde_json::json;
+
+    let server = MockWsServer::bind().await.unwrap();
+    let rest_server = MockRest::with_fixtures().await.unwrap();
+    rest_server.respond_ok("GET", "/time", json!("2021-05-23T05:24:24.315884+00:00"));
+
+    // The REST connection is opened along with the websocket
+    let ws = Ws::builder()
+        .endpoint(&server.endpoint())
+        .credentials(MockRest::KEY, MockRest::SECRET)
+        .subaccount("bot")
+        .fast_orders(true)
+        .rest_endpoint(&rest_server.endpoint())
+        .connect()
+        .await
+        .expect("Connection failed.");
+    assert_eq!(rest_server.requests()[0].path, "/api/time");
+
+    let rest = ws.rest().expect("Fast order path expected.");
+    let order = OrderRequest::limit("BTC-PERP", OrderSide::Buy, dec!(40000), dec!(0.1));
+    rest.submit_order(&order).await.unwrap();
+    let requests = rest_server.requests();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[1].signature_valid);
+    assert_eq!(requests[1].headers["ftx-subaccount"], "bot");
+
+    // Public connections have no fast order path
+    let ws = Ws::builder()
+        .endpoint(&server.endpoint())
+        .fast_orders(true)
+        .rest_endpoint(&rest_server.endpoint())
+        .connect()
+        .await
+        .expect("Connection failed.");
+    assert!(ws.rest().is_none());
+    assert_eq!(rest_server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn trade_guard() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let endpoint = format!("ws://{}", listener.local_addr().unwrap());
+
+    let server = tokio::spawn(async move {
+        let mut stream = accept_login(&listener).await;
+        stream.next().await.unwrap().unwrap(); // subscribe
+        let gap = TRADES_UPDATE.replace(r#""id": 1,"#, r#""id": 10,"#);
+        let out_of_order = TRADES_UPDATE.replace(r#""id": 1,"#, r#""id": 5,"#);
+        // Sent again, e.g. after a reconnect
+        for message in [
+            SUBSCRIBED_TRADES,
+            TRADES_UPDATE,
+            TRADES_UPDATE,
+            &gap,
+            &out_of_order,
+        ] {
+            stream
+                .send(Message::Text(message.to_string()))
+                .await
+                .unwrap();
+        }
+        stream
+    });
+
+    let mut ws = Ws::builder()
+        .endpoint(&endpoint)
+        .credentials("key", "secret")
+        .trade_guard(TradeGuard::new().max_gap(5))
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Trades("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    let market = Symbol::from("BTC-PERP");
+    let mut received = Vec::new();
+    while received.len() < 10 {
+        match ws.next().await.unwrap() {
+            Some((_, Data::Trade(trade))) => received.push(Ok(trade.id)),
+            Some((_, Data::Integrity(issue))) => received.push(Err(issue)),
+            data => panic!("Unexpected data {:?}", data),
+        }
+    }
+    assert_eq!(
+        received,
+        vec![
+            Ok(1),
+            Ok(2),
+            Err(DataIntegrity::DuplicateTrade {
+                market: market.clone(),
+                id: 1
+            }),
+            Err(DataIntegrity::DuplicateTrade {
+                market: market.clone(),
+                id: 2
+            }),
+            Err(DataIntegrity::TradeGap {
+                market: market.clone(),
+                id: 10,
+                last_id: 2
+            }),
+            Ok(10),
+            Err(DataIntegrity::DuplicateTrade {
+                market: market.clone(),
+                id: 2
+            }),
+            Err(DataIntegrity::OutOfOrderTrade {
+                market: market.clone(),
+                id: 5,
+                last_id: 10
+            }),
+            Ok(5),
+            Err(DataIntegrity::DuplicateTrade { market, id: 2 }),
+        ]
+    );
+
+    server.await.unwrap();
+}
+
+/// Sends `bids` and `asks` with the checksum of the resulting book, offset by
+/// `corrupt`
+fn send_orderbook(
+    server: &crate::testing::MockWsServer,
+    book: &mut Orderbook,
+    action: &str,
+    bids: &[(f64, f64)],
+    asks: &[(f64, f64)],
+    corrupt: u32,
+) {
+    let mut data = serde_json::json!({
+        "action": action,
+        "bids": bids,
+        "asks": asks,
+        "checksum": 0,
+        "time": 1621747464.3,
+    });
+    book.update(&serde_json::from_value(data.clone()).unwrap());
+    data["checksum"] = serde_json::json!(book.checksum().wrapping_add(corrupt));
+    server.send_orderbook(&book.symbol, data);
+}
+
+#[tokio::test]
+async fn orderbook_integrity() {
+    use crate::testing::MockWsServer;
+
+    let server = MockWsServer::bind().await.unwrap();
+    let mut ws = Ws::builder()
+        .endpoint(&server.endpoint())
+        .verify_checksums(true)
+        .connect()
+        .await
+        .expect("Connection failed.");
+    ws.subscribe(vec![Channel::Orderbook("BTC-PERP".into())])
+        .await
+        .expect("Subscription failed.");
+
+    let mut book = Orderbook::new("BTC-PERP".into());
+    send_orderbook(
+        &server,
+        &mut book,
+        "partial",
+        &[(40000.0, 1.0)],
+        &[(40001.0, 2.0)],
+        0,
+    );
+    assert!(matches!(
+        ws.next().await.unwrap(),
+        Some((_, Data::OrderbookData(_)))
+    ));
+
+    // The error is followed by an event with the time of the last valid
+    // update
+    send_orderbook(&server, &mut book, "update", &[(39999.0, 1.0)], &[], 1);
+    assert!(matches!(
+        ws.next().await,
+        Err(Error::ChecksumMismatch(market)) if market == "BTC-PERP"
+    ));
+    match ws.next().await.unwrap() {
+        Some((
+            Some(market),
+            Data::Integrity(DataIntegrity::OrderbookChecksumMismatch { last_valid, .. }),
+        )) => {
+            assert_eq!(market, "BTC-PERP");
+            assert_eq!(last_valid.unwrap().timestamp(), 1621747464);
+        }
+        data => panic!("Integrity event expected, got {:?}", data),
+    }
+    assert!(ws.orderbook("BTC-PERP").is_none());
+}
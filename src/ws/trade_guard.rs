@@ -0,0 +1,132 @@
+use super::{Id, Symbol, Trade};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Number of most recent trade IDs remembered per market to detect
+/// duplicates
+const SEEN_TRADES: usize = 1000;
+
+/// Problem with the data of a market, see `Ws::set_trade_guard` and
+/// `Ws::set_verify_checksums`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum DataIntegrity {
+    /// The trade was already received, e.g. sent again after a reconnect,
+    /// and was dropped
+    DuplicateTrade { market: Symbol, id: Id },
+    /// The trade has a lower ID than the latest trade of the market but
+    /// wasn't received before. It is passed on.
+    OutOfOrderTrade { market: Symbol, id: Id, last_id: Id },
+    /// The ID of the trade is further from the latest trade of the market
+    /// than the allowed gap, so trades may have been missed. It is passed on.
+    TradeGap { market: Symbol, id: Id, last_id: Id },
+    /// The local orderbook doesn't match the checksum of an update, usually
+    /// because an update was missed. The book is discarded until the next
+    /// snapshot.
+    OrderbookChecksumMismatch {
+        market: Symbol,
+        /// Time of the last update that matched its checksum, `None` if
+        /// none did
+        last_valid: Option<DateTime<Utc>>,
+    },
+}
+
+impl DataIntegrity {
+    pub fn market(&self) -> &Symbol {
+        match self {
+            DataIntegrity::DuplicateTrade { market, .. }
+            | DataIntegrity::OutOfOrderTrade { market, .. }
+            | DataIntegrity::TradeGap { market, .. }
+            | DataIntegrity::OrderbookChecksumMismatch { market, .. } => market,
+        }
+    }
+}
+
+/// Checks that trade IDs increase per market and drops duplicates.
+///
+/// FTX trade IDs are shared by all markets, so the IDs of consecutive trades
+/// of a market usually skip some. Gaps are only reported when larger than
+/// `TradeGuard::max_gap`, which should be tuned to the activity of the
+/// exchange relative to the market.
+#[derive(Clone, Debug, Default)]
+pub struct TradeGuard {
+    max_gap: Option<Id>,
+    markets: HashMap<Symbol, SeenTrades>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct SeenTrades {
+    last_id: Option<Id>,
+    /// Most recent IDs in order of arrival, along with a set for lookups
+    order: VecDeque<Id>,
+    ids: HashSet<Id>,
+}
+
+impl TradeGuard {
+    /// Guard reporting duplicate and out of order trades, but no gaps
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report trades whose ID exceeds the latest one of the market by more
+    /// than `max_gap`
+    pub fn max_gap(mut self, max_gap: Id) -> Self {
+        self.max_gap = Some(max_gap);
+        self
+    }
+
+    /// Records a trade of `market`, returning the problem detected, if any.
+    /// The trade should be dropped if it is a `DataIntegrity::DuplicateTrade`.
+    pub fn check(&mut self, market: &Symbol, trade: &Trade) -> Option<DataIntegrity> {
+        let seen = self.markets.entry(market.clone()).or_default();
+        if seen.ids.contains(&trade.id) {
+            return Some(DataIntegrity::DuplicateTrade {
+                market: market.clone(),
+                id: trade.id,
+            });
+        }
+
+        if seen.order.len() == SEEN_TRADES {
+            if let Some(id) = seen.order.pop_front() {
+                seen.ids.remove(&id);
+            }
+        }
+        seen.order.push_back(trade.id);
+        seen.ids.insert(trade.id);
+
+        let last_id = match seen.last_id {
+            Some(last_id) => last_id,
+            None => {
+                seen.last_id = Some(trade.id);
+                return None;
+            }
+        };
+        if trade.id < last_id {
+            return Some(DataIntegrity::OutOfOrderTrade {
+                market: market.clone(),
+                id: trade.id,
+                last_id,
+            });
+        }
+
+        seen.last_id = Some(trade.id);
+        match self.max_gap {
+            Some(max_gap) if trade.id - last_id > max_gap => Some(DataIntegrity::TradeGap {
+                market: market.clone(),
+                id: trade.id,
+                last_id,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Forgets the trades of `market`, e.g. after unsubscribing
+    pub fn reset(&mut self, market: &str) {
+        self.markets.remove(market);
+    }
+}